@@ -0,0 +1,35 @@
+/* C API of dash2gps, built with
+ * `cargo rustc --profile ffi --lib --features ffi --crate-type cdylib`. */
+#ifndef DASH2GPS_H
+#define DASH2GPS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Called from worker threads after every frame read: user_data, frames read,
+ * position in the video and its length in seconds (negative when not known). */
+typedef void (*dash2gps_progress_fn)(void *user_data, uint32_t frames_read, double at_sec,
+                                     double duration_sec);
+
+/* Track of the video at `path` as a "gpx", "json" or "geojson" document, with
+ * the options of the command line in `args` (eg. "--interval", "5"). `progress`
+ * may be NULL. Returns NULL on error, see dash2gps_last_error(); free the
+ * document with dash2gps_free_string(). */
+char *dash2gps_convert(const char *path, const char *format, const char *const *args,
+                       size_t argc, dash2gps_progress_fn progress, void *user_data);
+
+/* Error of the last call on this thread that returned NULL, or NULL. Valid
+ * until the next call on this thread. */
+const char *dash2gps_last_error(void);
+
+void dash2gps_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,91 @@
+//! How well the overlay is being read, over the last frames of a run, to warn
+//! about wrong settings (eg. `--crop`) in the first seconds rather than after
+//! a long run with an empty result.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Frames the rates are taken over, the first warning can come after these
+const WINDOW: usize = 10;
+/// Share of frames with a readable overlay under which to warn
+const MIN_READ: f32 = 0.5;
+/// Mean OCR confidence (0-100) under which to warn
+const MIN_CONFIDENCE: f32 = 50.0;
+
+#[derive(Default)]
+pub struct Accuracy {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether the overlay was read and the OCR confidence, of the last frames
+    recent: VecDeque<(bool, Option<i32>)>,
+    warned: bool,
+}
+
+impl Accuracy {
+    /// Record a frame read, `confidence` is `None` when OCR failed. Returns a
+    /// warning the first time the last frames are read too poorly.
+    pub fn record(&self, read: bool, confidence: Option<i32>) -> Option<String> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.recent.len() == WINDOW {
+            state.recent.pop_front();
+        }
+        state.recent.push_back((read, confidence));
+        if state.warned || state.recent.len() < WINDOW {
+            return None;
+        }
+
+        let read = state.recent.iter().filter(|(read, _)| *read).count();
+        let confidences = state
+            .recent
+            .iter()
+            .map(|(_, c)| c.unwrap_or(0) as f32)
+            .collect::<Vec<_>>();
+        let confidence = confidences.iter().sum::<f32>() / confidences.len() as f32;
+        if (read as f32) < MIN_READ * WINDOW as f32 || confidence < MIN_CONFIDENCE {
+            state.warned = true;
+            return Some(format!(
+                "Warning: the overlay was read in {} of the last {} frames (mean OCR confidence {:.0}), check where the overlay is with `dash2gps sample <video> --with-crop` and set `--crop` (or find it with `--detect-overlay`) or `--ocr-engine`",
+                read, WINDOW, confidence
+            ));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn warns_once() {
+        let accuracy = Accuracy::default();
+        for _ in 0..WINDOW - 1 {
+            assert_eq!(accuracy.record(false, Some(20)), None);
+        }
+        let warning = accuracy.record(true, Some(90)).unwrap();
+        assert!(warning.contains("1 of the last 10 frames"), "{}", warning);
+        assert!(warning.contains("confidence 27"), "{}", warning);
+        assert_eq!(accuracy.record(false, None), None);
+    }
+
+    #[test]
+    fn quiet_when_read() {
+        let accuracy = Accuracy::default();
+        for i in 0..100 {
+            // a few misreads and failures
+            let failed = i % 7 == 0;
+            let warning = accuracy.record(!failed, (!failed).then_some(85));
+            assert_eq!(warning, None);
+        }
+
+        // unsure about every frame, though they parse
+        let accuracy = Accuracy::default();
+        let warnings = (0..WINDOW)
+            .filter_map(|_| accuracy.record(true, Some(30)))
+            .count();
+        assert_eq!(warnings, 1);
+    }
+}
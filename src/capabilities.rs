@@ -0,0 +1,181 @@
+//! What this build supports and which tools it found, for `dash2gps capabilities`,
+//! so orchestration can check a deployed binary can run a job before submitting it.
+
+use std::{fmt, process::Stdio};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    export::Format,
+    ffmpeg,
+    manifest::{tesseract_version, tool_version},
+    preprocess,
+    tessdata::{self, OcrModel},
+};
+
+/// Cargo features this binary was built with
+const FEATURES: [(&str, bool); 8] = [
+    ("crs", cfg!(feature = "crs")),
+    ("home-assistant", cfg!(feature = "home-assistant")),
+    ("osm", cfg!(feature = "osm")),
+    ("shapefile", cfg!(feature = "shapefile")),
+    ("smtp", cfg!(feature = "smtp")),
+    ("tesseract", cfg!(feature = "tesseract")),
+    ("webhook", cfg!(feature = "webhook")),
+    ("what3words", cfg!(feature = "what3words")),
+];
+
+#[derive(Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+    /// `--format` values
+    pub formats: Vec<String>,
+    /// Engines `--ocr-engine` can read frames with
+    pub ocr_engines: Vec<&'static str>,
+    /// `--ocr-model` models already downloaded, usable offline
+    pub ocr_models_cached: Vec<OcrModel>,
+    /// Where a track can be sent besides stdout
+    pub sinks: Vec<&'static str>,
+    /// How `--simd auto` prepares the overlay crops on this CPU
+    pub simd: &'static str,
+    pub ffmpeg_version: Option<String>,
+    /// `--hwaccel` methods of the ffmpeg found
+    pub hwaccels: Vec<String>,
+    pub tesseract_version: Option<String>,
+    /// Needed by `--upload`
+    pub rclone_version: Option<String>,
+    /// Needed by `--format gpkg`
+    pub sqlite3_version: Option<String>,
+    /// Needed by `--via-gpsbabel`
+    pub gpsbabel_version: Option<String>,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let mut sinks = vec!["upload"];
+        if cfg!(feature = "smtp") {
+            sinks.push("email");
+        }
+        if cfg!(feature = "home-assistant") {
+            sinks.push("home-assistant");
+        }
+        if cfg!(feature = "webhook") {
+            sinks.push("webhook");
+        }
+        let mut ocr_engines = vec!["glyphs"];
+        if cfg!(feature = "tesseract") {
+            ocr_engines.push("tesseract");
+        }
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            formats: Format::value_variants()
+                .iter()
+                .filter_map(|f| f.to_possible_value())
+                .map(|v| v.get_name().to_string())
+                .collect(),
+            ocr_engines,
+            ocr_models_cached: OcrModel::value_variants()
+                .iter()
+                .copied()
+                .filter(|&m| tessdata::is_cached(m))
+                .collect(),
+            sinks,
+            simd: preprocess::simd_path(),
+            ffmpeg_version: tool_version("ffmpeg", "-version"),
+            hwaccels: hwaccels(),
+            tesseract_version: tesseract_version(),
+            rclone_version: tool_version("rclone", "version"),
+            sqlite3_version: tool_version("sqlite3", "--version"),
+            gpsbabel_version: tool_version("gpsbabel", "-V"),
+        }
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let models = self
+            .ocr_models_cached
+            .iter()
+            .filter_map(|m| m.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect::<Vec<_>>();
+        let found = |version: &Option<String>| version.clone().unwrap_or("not found".into());
+
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Features: {}", list(&self.features))?;
+        writeln!(f, "Formats: {}", list(&self.formats))?;
+        writeln!(f, "OCR engines: {}", list(&self.ocr_engines))?;
+        writeln!(f, "OCR models cached: {}", list(&models))?;
+        writeln!(f, "Sinks: {}", list(&self.sinks))?;
+        writeln!(f, "SIMD: {}", self.simd)?;
+        writeln!(f, "ffmpeg: {}", found(&self.ffmpeg_version))?;
+        writeln!(f, "Hardware decoding: {}", list(&self.hwaccels))?;
+        writeln!(f, "Tesseract: {}", found(&self.tesseract_version))?;
+        writeln!(f, "rclone: {}", found(&self.rclone_version))?;
+        writeln!(f, "sqlite3: {}", found(&self.sqlite3_version))?;
+        write!(f, "gpsbabel: {}", found(&self.gpsbabel_version))
+    }
+}
+
+fn list(items: &[impl AsRef<str>]) -> String {
+    match items.is_empty() {
+        true => "none".to_string(),
+        false => items
+            .iter()
+            .map(|i| i.as_ref())
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+/// Methods listed by `ffmpeg -hwaccels`, none when ffmpeg is not found
+fn hwaccels() -> Vec<String> {
+    ffmpeg::command("ffmpeg")
+        .args(["-hide_banner", "-hwaccels"])
+        .stderr(Stdio::null())
+        .output()
+        .map(|o| parse_hwaccels(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
+
+/// Method names after the `Hardware acceleration methods:` heading
+fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|l| !l.ends_with(':'))
+        .skip(1)
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hwaccel_list() {
+        let output = "Hardware acceleration methods:\nvdpau\ncuda\nvaapi\n\n";
+        assert_eq!(parse_hwaccels(output), ["vdpau", "cuda", "vaapi"]);
+        assert!(parse_hwaccels("").is_empty());
+    }
+
+    #[test]
+    fn build_features() {
+        let capabilities = Capabilities::detect();
+        assert_eq!(
+            capabilities.features.contains(&"tesseract"),
+            capabilities.ocr_engines.contains(&"tesseract")
+        );
+        assert!(capabilities.formats.iter().any(|f| f == "gpx"));
+    }
+}
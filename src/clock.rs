@@ -0,0 +1,25 @@
+//! Time the outputs are stamped with, eg. the `created_at` of the manifest or
+//! the date of a Shapefile. With `--deterministic` it is fixed, so the same
+//! video and options give the same bytes: `SOURCE_DATE_EPOCH` when set, as
+//! reproducible builds do, the Unix epoch otherwise.
+
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::OnceCell;
+
+static FIXED: OnceCell<DateTime<Utc>> = OnceCell::new();
+
+/// Stamp the outputs of the rest of the run with a fixed time
+pub fn fix() {
+    FIXED.get_or_init(|| {
+        let epoch = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        Utc.timestamp_opt(epoch, 0).single().unwrap_or_default()
+    });
+}
+
+/// The current time, unless [`fix`]ed
+pub fn now() -> DateTime<Utc> {
+    FIXED.get().copied().unwrap_or_else(Utc::now)
+}
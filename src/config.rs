@@ -0,0 +1,498 @@
+//! `--config`: a TOML file setting any option of the command line, eg.
+//! `interval = 5`, and defining camera profiles in `[profiles.NAME]` tables.
+//! YAML and JSON files with the same keys are read too, eg. templated by fleet
+//! provisioning. `config.toml` in the [`dir`] is read when there is no
+//! `--config`. The options given on the command line or in the environment
+//! still win. `[[jobs]]` tables list the videos `dash2gps run` reads, each job
+//! with options of its own.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Arg, Command};
+use serde::Deserialize;
+
+use crate::profile::{self, Profile};
+
+/// Options read from a config file
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// File read, for errors
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Camera profiles, as in `profiles.json`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Jobs of `dash2gps run`
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+    /// Value of every option by its name, eg. `interval` or `ocr-var`
+    #[serde(flatten)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+/// A job of `dash2gps run`, eg. the videos of a vehicle
+#[derive(Clone, Debug, Deserialize)]
+pub struct Job {
+    pub name: String,
+    /// Videos, folders or patterns such as `/mnt/van12/*.MP4`, each read as
+    /// the input of the command line is
+    pub inputs: Vec<String>,
+    /// Options of the job, over those of the file, eg. `profile` or `output`
+    #[serde(flatten)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+/// Format of a config file
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    /// Format of the extension of `path`, of `text` otherwise
+    fn of(path: &Path, text: &str) -> Self {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            Some("json") => Format::Json,
+            _ if text.trim_start().starts_with('{') => Format::Json,
+            // `interval: 5` is no TOML
+            _ if text.parse::<toml::Table>().is_ok() => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+impl Config {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let config = Self::parse(&text, Format::of(path, &text))
+            .with_context(|| format!("read {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            ..config
+        })
+    }
+
+    fn parse(text: &str, format: Format) -> anyhow::Result<Self> {
+        let mut config = match format {
+            Format::Toml => toml::from_str::<Self>(text)?,
+            // an empty YAML document is no map
+            Format::Yaml if text.trim().is_empty() => Self::default(),
+            Format::Yaml => serde_yaml::from_str::<Self>(text)?,
+            Format::Json => serde_json::from_str::<Self>(text)?,
+        };
+        config.options = ids_of(std::mem::take(&mut config.options));
+        for job in &mut config.jobs {
+            job.options = ids_of(std::mem::take(&mut job.options));
+        }
+        Ok(config)
+    }
+
+    /// Options of `job` reading `input`, over those of the file. `{input}` in
+    /// its values is replaced by the name of the input, eg. for an `output`
+    /// per input.
+    pub fn job(&self, job: &Job, input: &str) -> Self {
+        let name = input_name(input);
+        let mut options = self.options.clone();
+        options.extend(job.options.iter().map(|(id, value)| {
+            let value = match value {
+                toml::Value::String(s) => toml::Value::String(s.replace("{input}", &name)),
+                value => value.clone(),
+            };
+            (id.clone(), value)
+        }));
+        Self {
+            path: self.path.clone(),
+            profiles: self.profiles.clone(),
+            jobs: Vec::new(),
+            options,
+        }
+    }
+
+    /// `profile` set in the file
+    pub fn profile(&self) -> Option<String> {
+        self.options
+            .get("profile")
+            .and_then(|p| p.as_str())
+            .map(String::from)
+    }
+
+    /// Id of every option set and its values, each as given on the command line
+    fn defaults(&self) -> anyhow::Result<Vec<(String, Vec<String>)>> {
+        self.options
+            .iter()
+            .map(|(id, value)| {
+                let values = match value {
+                    toml::Value::Array(values) => values.iter().map(scalar).collect(),
+                    value => scalar(value).map(|v| vec![v]),
+                };
+                Ok((id.clone(), values.with_context(|| format!("`{}`", id))?))
+            })
+            .collect()
+    }
+}
+
+/// Options named by their id, eg. `ocr_var` for `ocr-var`
+fn ids_of(options: BTreeMap<String, toml::Value>) -> BTreeMap<String, toml::Value> {
+    options
+        .into_iter()
+        .map(|(name, value)| (name.replace('-', "_"), value))
+        .collect()
+}
+
+/// Name of a video without its extension, of a folder, or of the folder of a
+/// pattern
+fn input_name(input: &str) -> String {
+    let path = Path::new(input);
+    let name = match path.file_name() {
+        Some(name) if name.to_string_lossy().contains(['*', '?']) => {
+            path.parent().and_then(Path::file_name)
+        }
+        _ if path.is_dir() => path.file_name(),
+        _ => path.file_stem(),
+    };
+    name.map_or_else(|| input.to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+fn scalar(value: &toml::Value) -> anyhow::Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Datetime(d) => Ok(d.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            anyhow::bail!("expected a value or a list of values")
+        }
+    }
+}
+
+/// `dash2gps` in `XDG_CONFIG_HOME`, `~/.config` otherwise
+pub fn dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dash2gps")
+}
+
+/// `config.toml` in the [`dir`], or `config.yaml`, `config.yml` or
+/// `config.json`, read by the command line when it exists
+pub fn default_file() -> PathBuf {
+    let dir = dir();
+    ["config.toml", "config.yaml", "config.yml", "config.json"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|file| file.exists())
+        .unwrap_or_else(|| dir.join("config.toml"))
+}
+
+/// Value of an option in command line arguments, eg. `--config`, for the
+/// options that must be known before the arguments are parsed.
+pub fn arg_value<I, T>(args: I, name: &str) -> Option<OsString>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut args = args.into_iter().map(Into::into);
+    let prefix = format!("{}=", name);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == name {
+            return args.next();
+        }
+        if let Some(value) = arg.to_str().and_then(|a| a.strip_prefix(&prefix)) {
+            return Some(value.into());
+        }
+    }
+    None
+}
+
+/// Make the options of `profile`, the profile of the file otherwise, then
+/// those of the file the defaults of `command` and of its subcommands.
+pub fn apply(
+    mut command: Command,
+    config: Option<&Config>,
+    profile: Option<String>,
+) -> anyhow::Result<Command> {
+    if let Some(name) = profile.or_else(|| config.and_then(Config::profile)) {
+        command = profile::apply(command, &name, config)?;
+    }
+    let Some(config) = config else {
+        return Ok(command);
+    };
+
+    let file = || format!("options of {}", config.path.display());
+    let defaults = config.defaults().with_context(file)?;
+    let mut known = Vec::new();
+    ids(&command, &mut known);
+    if let Some((id, _)) = defaults.iter().find(|(id, _)| !known.contains(id)) {
+        anyhow::bail!("unknown option `{}` in {}", id, config.path.display());
+    }
+    set_defaults(command, &defaults).with_context(file)
+}
+
+/// Whether `value` is valid for `arg`, parsed by an option alone like it,
+/// as clap asserts default values are
+fn check(arg: &Arg, value: &str) -> anyhow::Result<()> {
+    let long = arg
+        .get_long()
+        .map_or_else(|| arg.get_id().to_string(), String::from);
+    let mut alone = Arg::new(arg.get_id().clone())
+        .long(long.clone())
+        .action(arg.get_action().clone())
+        .value_parser(arg.get_value_parser().clone())
+        .value_delimiter(arg.get_value_delimiter());
+    if let Some(names) = arg.get_value_names() {
+        alone = alone.value_names(names.to_vec());
+    }
+    if let Some(range) = arg.get_num_args() {
+        alone = alone.num_args(range);
+    }
+    let args = match (arg.get_action().takes_values(), value) {
+        (true, _) => vec![format!("--{}={}", long, value)],
+        (false, "true") => vec![format!("--{}", long)],
+        (false, "false") => vec![],
+        (false, _) => anyhow::bail!("expected true or false, got `{}`", value),
+    };
+
+    match Command::new("check")
+        .arg(alone)
+        .try_get_matches_from(std::iter::once("check".to_string()).chain(args))
+    {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // the first line of the message, without its `error: ` prefix
+            let message = e.to_string();
+            let line = message.lines().next().unwrap_or_default();
+            anyhow::bail!("{}", line.trim_start_matches("error: "))
+        }
+    }
+}
+
+/// Id of the options of `command` and of its subcommands
+fn ids(command: &Command, ids: &mut Vec<String>) {
+    ids.extend(command.get_arguments().map(|a| a.get_id().to_string()));
+    for c in command.get_subcommands() {
+        self::ids(c, ids);
+    }
+}
+
+/// Make `values` the default of the option of each id, wherever `command`
+/// or one of its subcommands has it. Every value is checked as it would be
+/// on the command line: subcommands may have an option of the same id taking
+/// other values (eg. `--format`), so it is an error when no option takes it.
+pub fn set_defaults(
+    command: Command,
+    defaults: &[(String, Vec<String>)],
+) -> anyhow::Result<Command> {
+    let (mut taken, mut errors) = (Vec::new(), Vec::new());
+    let command = defaults_of(command, defaults, &mut taken, &mut errors);
+    match errors.into_iter().find(|(id, _)| !taken.contains(id)) {
+        Some((id, e)) => Err(e.context(format!("`{}`", id))),
+        None => Ok(command),
+    }
+}
+
+fn defaults_of(
+    mut command: Command,
+    defaults: &[(String, Vec<String>)],
+    taken: &mut Vec<String>,
+    errors: &mut Vec<(String, anyhow::Error)>,
+) -> Command {
+    for (id, values) in defaults {
+        let Some(arg) = command.get_arguments().find(|a| a.get_id() == id) else {
+            continue;
+        };
+        if let Err(e) = values.iter().try_for_each(|v| check(arg, v)) {
+            errors.push((id.clone(), e));
+            continue;
+        }
+        taken.push(id.clone());
+        let values = values.clone();
+        command = command.mut_arg(id, |a| a.default_values(values));
+    }
+    let subcommands = command
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in subcommands {
+        command = command.mut_subcommand(name, |c| defaults_of(c, defaults, taken, errors));
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{Args, FromArgMatches};
+
+    use super::*;
+    use crate::{parser::CoordinateFormat, pipeline::PipelineConfig};
+
+    fn command() -> Command {
+        PipelineConfig::augment_args(Command::new("dash2gps"))
+    }
+
+    fn read(text: &str) -> Config {
+        Config {
+            path: PathBuf::from("dash2gps.toml"),
+            ..Config::parse(text, Format::Toml).unwrap()
+        }
+    }
+
+    #[test]
+    fn option_values() {
+        assert_eq!(
+            arg_value(["dash2gps", "--profile", "viofo", "clip.mp4"], "--profile"),
+            Some("viofo".into())
+        );
+        assert_eq!(
+            arg_value(["dash2gps", "--config=cam.toml"], "--config"),
+            Some("cam.toml".into())
+        );
+        assert_eq!(
+            arg_value(["dash2gps", "--", "--profile"], "--profile"),
+            None
+        );
+    }
+
+    #[test]
+    fn options_of_file() {
+        let config = read(
+            r#"
+            interval = 5
+            ocr-ensemble = true
+            ocr_var = ["classify_bln_numeric_mode=1"]
+            profile = "dashy"
+
+            [profiles.dashy]
+            crop = "0,0,640,40"
+            coordinate_format = "ddm"
+            "#,
+        );
+        let parse = |args: &[&str]| {
+            let command = apply(command(), Some(&config), None).unwrap();
+            let matches = command
+                .try_get_matches_from(std::iter::once(&"dash2gps").chain(args))
+                .unwrap();
+            PipelineConfig::from_arg_matches(&matches).unwrap()
+        };
+
+        let opts = parse(&[]);
+        assert_eq!(opts.interval, 5);
+        assert!(opts.ocr_ensemble);
+        assert_eq!(opts.ocr_var[0].0, "classify_bln_numeric_mode");
+        assert_eq!(opts.coordinate_format, CoordinateFormat::Ddm);
+        assert_eq!(parse(&["--interval", "2"]).interval, 2);
+
+        let typo = read("intervall = 5");
+        let error = apply(command(), Some(&typo), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown option `intervall` in dash2gps.toml"
+        );
+        let invalid = read("interval = 0");
+        let error = apply(command(), Some(&invalid), None).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "options of dash2gps.toml: `interval`: invalid value '0' for '--interval <INTERVAL>': 0 is not in 1..=4294967295"
+        );
+    }
+
+    #[test]
+    fn jobs() {
+        let config = read(
+            r#"
+            interval = 5
+            format = "gpx"
+
+            [[jobs]]
+            name = "van-12"
+            inputs = ["/mnt/van12/front/*.MP4", "/mnt/van12/rear/*.MP4"]
+            profile = "viofo"
+            interval = 2
+            output = "/srv/tracks/van-12-{input}.gpx"
+
+            [[jobs]]
+            name = "car"
+            inputs = ["/mnt/car/2024_0501_081500_001.MP4"]
+            "#,
+        );
+        assert_eq!(config.jobs.len(), 2);
+
+        let van = config.job(&config.jobs[0], &config.jobs[0].inputs[1]);
+        assert!(van.jobs.is_empty());
+        assert_eq!(van.profile().as_deref(), Some("viofo"));
+        // `format` and `output` are options of the command line only
+        let parse = || {
+            let outputs = command()
+                .arg(Arg::new("format").long("format"))
+                .arg(Arg::new("output").long("output"));
+            let command = apply(outputs, Some(&van), None).unwrap();
+            let matches = command.try_get_matches_from(["dash2gps"]).unwrap();
+            PipelineConfig::from_arg_matches(&matches).unwrap()
+        };
+        assert_eq!(parse().interval, 2);
+        assert_eq!(parse().coordinate_format, CoordinateFormat::Decimal);
+        assert_eq!(
+            van.options["output"].as_str(),
+            Some("/srv/tracks/van-12-rear.gpx")
+        );
+        assert_eq!(van.options["format"].as_str(), Some("gpx"));
+
+        let car = config.job(&config.jobs[1], &config.jobs[1].inputs[0]);
+        assert_eq!(car.options["interval"].as_integer(), Some(5));
+        assert_eq!(
+            input_name(&config.jobs[1].inputs[0]),
+            "2024_0501_081500_001"
+        );
+    }
+
+    #[test]
+    fn yaml_and_json() {
+        let toml = "interval = 5\nocr-var = [\"classify_bln_numeric_mode=1\"]\n\n[profiles.dashy]\ncrop = \"0,0,640,40\"\n";
+        let yaml = "interval: 5\nocr-var:\n  - classify_bln_numeric_mode=1\nprofiles:\n  dashy:\n    crop: 0,0,640,40\n";
+        let json = r#"{"interval": 5, "ocr-var": ["classify_bln_numeric_mode=1"], "profiles": {"dashy": {"crop": "0,0,640,40"}}}"#;
+
+        let format = |name: &str, text: &str| Format::of(Path::new(name), text);
+        assert_eq!(format("dash2gps.yml", json), Format::Yaml);
+        assert_eq!(format("dash2gps.conf", toml), Format::Toml);
+        assert_eq!(format("dash2gps.conf", yaml), Format::Yaml);
+        assert_eq!(format("dash2gps.conf", json), Format::Json);
+
+        for (text, format) in [
+            (toml, Format::Toml),
+            (yaml, Format::Yaml),
+            (json, Format::Json),
+        ] {
+            let config = Config::parse(text, format).unwrap();
+            assert_eq!(
+                config.defaults().unwrap(),
+                [
+                    ("interval".to_string(), vec!["5".to_string()]),
+                    (
+                        "ocr_var".to_string(),
+                        vec!["classify_bln_numeric_mode=1".to_string()]
+                    ),
+                ],
+                "{:?}",
+                format
+            );
+            assert_eq!(config.profiles["dashy"].crop.as_deref(), Some("0,0,640,40"));
+        }
+        assert!(Config::parse("", Format::Yaml).unwrap().options.is_empty());
+    }
+}
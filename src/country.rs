@@ -0,0 +1,306 @@
+//! Country the footage was recorded in, from the first locations of the
+//! track, and the defaults of the cameras sold there: speed unit, order of
+//! day and month, and the time zone of the overlay clock.
+//!
+//! Countries are told apart by rough bounding boxes, checked smallest first,
+//! so a point near a border can be put in its neighbour. `--country` sets it
+//! and explicit options (`--speed-unit`, `--date-order`) always win.
+
+use serde::{Serialize, Serializer};
+
+use crate::{parser::DateOrder, speed::SpeedUnit, track::Track};
+
+/// Locations the country is decided on
+const FIRST_FIXES: usize = 10;
+
+#[derive(Debug, PartialEq)]
+pub struct Country {
+    /// ISO 3166-1 alpha-2
+    pub code: &'static str,
+    pub name: &'static str,
+    pub speed_unit: SpeedUnit,
+    pub date_order: DateOrder,
+    /// `(south, west, north, east)` boxes the country is in
+    bounds: &'static [(f32, f32, f32, f32)],
+    /// `(west, zone)` from west to east, the zone of the last one west of a
+    /// location is its time zone
+    time_zones: &'static [(f32, &'static str)],
+}
+
+impl Country {
+    fn contains(&self, (lat, lon): (f32, f32)) -> bool {
+        self.bounds
+            .iter()
+            .any(|&(s, w, n, e)| (s..=n).contains(&lat) && (w..=e).contains(&lon))
+    }
+
+    /// Time zone the overlay clock is likely set to at `(lat, lon)`
+    pub fn time_zone(&self, (_, lon): (f32, f32)) -> &'static str {
+        self.time_zones
+            .iter()
+            .rev()
+            .find(|(west, _)| lon >= *west)
+            .or(self.time_zones.first())
+            .map_or("UTC", |(_, zone)| zone)
+    }
+}
+
+impl Serialize for Country {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+const fn country(
+    code: &'static str,
+    name: &'static str,
+    speed_unit: SpeedUnit,
+    date_order: DateOrder,
+    bounds: &'static [(f32, f32, f32, f32)],
+    time_zones: &'static [(f32, &'static str)],
+) -> Country {
+    Country {
+        code,
+        name,
+        speed_unit,
+        date_order,
+        bounds,
+        time_zones,
+    }
+}
+
+use DateOrder::{Dmy, Mdy};
+use SpeedUnit::{Kmh, Mph};
+
+/// Smallest first, a location is in the first country containing it
+static COUNTRIES: &[Country] = &[
+    country(
+        "BE",
+        "Belgium",
+        Kmh,
+        Dmy,
+        &[(49.5, 2.5, 51.5, 6.4)],
+        &[(-180.0, "Europe/Brussels")],
+    ),
+    country(
+        "NL",
+        "the Netherlands",
+        Kmh,
+        Dmy,
+        &[(50.75, 3.3, 53.6, 7.2)],
+        &[(-180.0, "Europe/Amsterdam")],
+    ),
+    country(
+        "CH",
+        "Switzerland",
+        Kmh,
+        Dmy,
+        &[(45.8, 5.9, 47.8, 10.5)],
+        &[(-180.0, "Europe/Zurich")],
+    ),
+    country(
+        "AT",
+        "Austria",
+        Kmh,
+        Dmy,
+        &[(46.4, 9.5, 49.0, 17.2)],
+        &[(-180.0, "Europe/Vienna")],
+    ),
+    country(
+        "PT",
+        "Portugal",
+        Kmh,
+        Dmy,
+        &[(36.9, -9.6, 42.2, -6.2)],
+        &[(-180.0, "Europe/Lisbon")],
+    ),
+    // England and Wales, Scotland, then Northern Ireland
+    country(
+        "GB",
+        "the United Kingdom",
+        Mph,
+        Dmy,
+        &[
+            (49.9, -5.8, 55.9, 1.8),
+            (55.9, -7.7, 60.9, -0.7),
+            (54.0, -8.2, 55.35, -5.4),
+        ],
+        &[(-180.0, "Europe/London")],
+    ),
+    country(
+        "IE",
+        "Ireland",
+        Kmh,
+        Dmy,
+        &[(51.4, -10.7, 55.4, -5.9)],
+        &[(-180.0, "Europe/Dublin")],
+    ),
+    country(
+        "NZ",
+        "New Zealand",
+        Kmh,
+        Dmy,
+        &[(-47.3, 166.4, -34.4, 178.6)],
+        &[(-180.0, "Pacific/Auckland")],
+    ),
+    country(
+        "IT",
+        "Italy",
+        Kmh,
+        Dmy,
+        &[(36.6, 6.6, 47.1, 18.5)],
+        &[(-180.0, "Europe/Rome")],
+    ),
+    country(
+        "DE",
+        "Germany",
+        Kmh,
+        Dmy,
+        &[(47.3, 5.9, 55.1, 15.0)],
+        &[(-180.0, "Europe/Berlin")],
+    ),
+    country(
+        "ES",
+        "Spain",
+        Kmh,
+        Dmy,
+        &[(36.0, -9.3, 43.8, 3.3)],
+        &[(-180.0, "Europe/Madrid")],
+    ),
+    country(
+        "FR",
+        "France",
+        Kmh,
+        Dmy,
+        &[(42.3, -4.8, 51.1, 8.2)],
+        &[(-180.0, "Europe/Paris")],
+    ),
+    country(
+        "JP",
+        "Japan",
+        Kmh,
+        Dmy,
+        &[(24.0, 122.9, 45.6, 145.8)],
+        &[(-180.0, "Asia/Tokyo")],
+    ),
+    country(
+        "AU",
+        "Australia",
+        Kmh,
+        Dmy,
+        &[(-43.7, 112.9, -10.6, 153.7)],
+        &[
+            (-180.0, "Australia/Perth"),
+            (129.0, "Australia/Adelaide"),
+            (141.0, "Australia/Sydney"),
+        ],
+    ),
+    // the contiguous states, then Alaska
+    country(
+        "US",
+        "the United States",
+        Mph,
+        Mdy,
+        &[(24.5, -125.0, 49.4, -66.9), (51.2, -180.0, 71.4, -129.9)],
+        &[
+            (-180.0, "America/Anchorage"),
+            (-125.0, "America/Los_Angeles"),
+            (-114.0, "America/Denver"),
+            (-102.0, "America/Chicago"),
+            (-87.0, "America/New_York"),
+        ],
+    ),
+    country(
+        "CA",
+        "Canada",
+        Kmh,
+        Mdy,
+        &[(41.7, -141.0, 83.1, -52.6)],
+        &[
+            (-180.0, "America/Vancouver"),
+            (-120.0, "America/Edmonton"),
+            (-102.0, "America/Winnipeg"),
+            (-90.0, "America/Toronto"),
+            (-67.0, "America/Halifax"),
+        ],
+    ),
+];
+
+/// Country of an ISO 3166-1 alpha-2 code, for `--country`
+pub fn parse(code: &str) -> Result<&'static Country, String> {
+    COUNTRIES
+        .iter()
+        .find(|c| c.code.eq_ignore_ascii_case(code))
+        .ok_or_else(|| {
+            let codes = COUNTRIES.iter().map(|c| c.code).collect::<Vec<_>>();
+            format!("unknown country, one of {}", codes.join(", "))
+        })
+}
+
+/// Country most of the first locations of the track are in.
+pub fn detect(track: &Track) -> Option<&'static Country> {
+    let mut votes = vec![0; COUNTRIES.len()];
+    for p in track.points.iter().take(FIRST_FIXES) {
+        let at = p.coordinate.lat_lon();
+        if let Some(i) = COUNTRIES.iter().position(|c| c.contains(at)) {
+            votes[i] += 1;
+        }
+    }
+
+    let (i, &n) = votes.iter().enumerate().max_by_key(|&(_, n)| n)?;
+    (n > 0).then(|| &COUNTRIES[i])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Coordinate, track::TrackPoint};
+
+    fn at(lat: f32, lon: f32) -> Option<&'static str> {
+        COUNTRIES
+            .iter()
+            .find(|c| c.contains((lat, lon)))
+            .map(|c| c.code)
+    }
+
+    #[test]
+    fn located() {
+        assert_eq!(at(51.43, 0.32), Some("GB"));
+        assert_eq!(at(54.6, -5.93), Some("GB")); // Belfast
+        assert_eq!(at(53.35, -6.26), Some("IE")); // Dublin
+        assert_eq!(at(52.37, 4.9), Some("NL"));
+        assert_eq!(at(48.86, 2.35), Some("FR"));
+        assert_eq!(at(52.52, 13.4), Some("DE"));
+        assert_eq!(at(40.71, -74.0), Some("US"));
+        assert_eq!(at(-33.87, 151.21), Some("AU"));
+        assert_eq!(at(0.0, 0.0), None);
+
+        let us = parse("us").unwrap();
+        assert_eq!(us.time_zone((34.05, -118.24)), "America/Los_Angeles");
+        assert_eq!(us.time_zone((41.88, -87.63)), "America/Chicago");
+        assert_eq!(us.time_zone((40.71, -74.0)), "America/New_York");
+        assert!(parse("XX").is_err());
+    }
+
+    #[test]
+    fn most_of_the_first_fixes() {
+        let mut track = Track::from_frames(Vec::new(), 10);
+        // starting in Brussels
+        track.points = [(50.85, 4.35), (52.0, 4.9), (52.3, 4.9), (52.37, 4.9)]
+            .iter()
+            .enumerate()
+            .map(|(i, &(lat, lon))| TrackPoint {
+                frame_no: i as u32 + 1,
+                offset_sec: i as f64 * 10.0,
+                coordinate: Coordinate::Decimal { lat, lon },
+                accel_peak: None,
+                confidence: None,
+                readings: Default::default(),
+            })
+            .collect();
+        assert_eq!(detect(&track).map(|c| c.code), Some("NL"));
+
+        track.points.clear();
+        assert_eq!(detect(&track), None);
+    }
+}
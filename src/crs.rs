@@ -0,0 +1,365 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+
+/// Coordinate reference system points are written in.
+///
+/// Only WGS84 is available unless built with the `crs` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Crs {
+    /// EPSG:4326, plain latitude/longitude
+    #[default]
+    Wgs84,
+    /// EPSG:27700, Ordnance Survey National Grid
+    #[cfg(feature = "crs")]
+    BritishNationalGrid,
+    /// EPSG:3857, the projection used by web maps
+    #[cfg(feature = "crs")]
+    WebMercator,
+    /// EPSG:326xx (north) and EPSG:327xx (south)
+    #[cfg(feature = "crs")]
+    Utm { zone: u8, north: bool },
+}
+
+impl Crs {
+    pub fn epsg(&self) -> u32 {
+        match self {
+            Crs::Wgs84 => 4326,
+            #[cfg(feature = "crs")]
+            Crs::BritishNationalGrid => 27700,
+            #[cfg(feature = "crs")]
+            Crs::WebMercator => 3857,
+            #[cfg(feature = "crs")]
+            Crs::Utm { zone, north } => (if *north { 32600 } else { 32700 }) + *zone as u32,
+        }
+    }
+
+    pub fn is_projected(&self) -> bool {
+        *self != Crs::Wgs84
+    }
+
+    /// Transform a WGS84 position into `(x, y)` of this CRS.
+    ///
+    /// For projected systems this is `(easting, northing)` in meters, for
+    /// WGS84 it is `(lon, lat)`.
+    pub fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        match self {
+            Crs::Wgs84 => (lon, lat),
+            #[cfg(feature = "crs")]
+            Crs::BritishNationalGrid => {
+                let (lat, lon) = projection::wgs84_to_osgb36(lat, lon);
+                projection::transverse_mercator(lat, lon, &projection::NATIONAL_GRID)
+            }
+            #[cfg(feature = "crs")]
+            Crs::WebMercator => projection::web_mercator(lat, lon),
+            #[cfg(feature = "crs")]
+            Crs::Utm { zone, north } => {
+                projection::transverse_mercator(lat, lon, &projection::utm(*zone, *north))
+            }
+        }
+    }
+}
+
+impl FromStr for Crs {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s
+            .to_ascii_lowercase()
+            .strip_prefix("epsg:")
+            .and_then(|c| c.parse::<u32>().ok())
+            .ok_or_else(|| anyhow!("expected a CRS like `epsg:27700`, got `{}`", s))?;
+
+        match code {
+            4326 => Ok(Crs::Wgs84),
+            #[cfg(feature = "crs")]
+            27700 => Ok(Crs::BritishNationalGrid),
+            #[cfg(feature = "crs")]
+            3857 => Ok(Crs::WebMercator),
+            #[cfg(feature = "crs")]
+            32601..=32660 => Ok(Crs::Utm {
+                zone: (code - 32600) as u8,
+                north: true,
+            }),
+            #[cfg(feature = "crs")]
+            32701..=32760 => Ok(Crs::Utm {
+                zone: (code - 32700) as u8,
+                north: false,
+            }),
+            #[cfg(not(feature = "crs"))]
+            _ => Err(anyhow!(
+                "EPSG:{} is not available, dash2gps was built without the `crs` feature",
+                code
+            )),
+            #[cfg(feature = "crs")]
+            _ => Err(anyhow!("unsupported CRS EPSG:{}", code)),
+        }
+    }
+}
+
+impl Display for Crs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EPSG:{}", self.epsg())
+    }
+}
+
+impl serde::Serialize for Crs {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Ordnance Survey grid reference with 1m resolution (eg. `TQ 30047 79951`)
+/// from National Grid easting/northing.
+#[cfg(feature = "crs")]
+pub fn grid_reference(easting: f64, northing: f64) -> Option<String> {
+    if !(0.0..700_000.0).contains(&easting) || !(0.0..1_300_000.0).contains(&northing) {
+        return None;
+    }
+
+    let (e, n) = (easting as u32, northing as u32);
+    let (e100k, n100k) = (e / 100_000, n / 100_000);
+
+    // 500km squares then 100km squares, both 5x5 grids of letters skipping `I`
+    let first = (19 - n100k) - (19 - n100k) % 5 + (e100k + 10) / 5;
+    let second = (19 - n100k) * 5 % 25 + e100k % 5;
+    let letter = |i: u32| {
+        let i = if i > 7 { i + 1 } else { i };
+        char::from(b'A' + i as u8)
+    };
+
+    Some(format!(
+        "{}{} {:05} {:05}",
+        letter(first),
+        letter(second),
+        e % 100_000,
+        n % 100_000
+    ))
+}
+
+#[cfg(feature = "crs")]
+mod projection {
+    pub struct Ellipsoid {
+        a: f64,
+        b: f64,
+    }
+
+    const WGS84: Ellipsoid = Ellipsoid {
+        a: 6_378_137.0,
+        b: 6_356_752.314_245,
+    };
+
+    const AIRY_1830: Ellipsoid = Ellipsoid {
+        a: 6_377_563.396,
+        b: 6_356_256.909,
+    };
+
+    /// Parameters of a Transverse Mercator projection
+    pub struct TransverseMercator {
+        ellipsoid: Ellipsoid,
+        scale: f64,
+        /// true origin, degrees
+        lat0: f64,
+        lon0: f64,
+        /// false origin, meters
+        e0: f64,
+        n0: f64,
+    }
+
+    pub const NATIONAL_GRID: TransverseMercator = TransverseMercator {
+        ellipsoid: AIRY_1830,
+        scale: 0.999_601_271_7,
+        lat0: 49.0,
+        lon0: -2.0,
+        e0: 400_000.0,
+        n0: -100_000.0,
+    };
+
+    pub fn utm(zone: u8, north: bool) -> TransverseMercator {
+        TransverseMercator {
+            ellipsoid: WGS84,
+            scale: 0.9996,
+            lat0: 0.0,
+            lon0: zone as f64 * 6.0 - 183.0,
+            e0: 500_000.0,
+            n0: if north { 0.0 } else { 10_000_000.0 },
+        }
+    }
+
+    /// Returns `(easting, northing)`, formulae from the Ordnance Survey's
+    /// "A guide to coordinate systems in Great Britain"
+    pub fn transverse_mercator(lat: f64, lon: f64, p: &TransverseMercator) -> (f64, f64) {
+        let Ellipsoid { a, b } = p.ellipsoid;
+        let (phi, phi0) = (lat.to_radians(), p.lat0.to_radians());
+        let dl = (lon - p.lon0).to_radians();
+
+        let e2 = 1.0 - (b * b) / (a * a);
+        let n = (a - b) / (a + b);
+        let (sin, cos, tan) = (phi.sin(), phi.cos(), phi.tan());
+        let nu = a * p.scale / (1.0 - e2 * sin * sin).sqrt();
+        let rho = a * p.scale * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+        let eta2 = nu / rho - 1.0;
+
+        let (d, s) = (phi - phi0, phi + phi0);
+        let m = b
+            * p.scale
+            * ((1.0 + n + 1.25 * n.powi(2) + 1.25 * n.powi(3)) * d
+                - (3.0 * n + 3.0 * n.powi(2) + 21.0 / 8.0 * n.powi(3)) * d.sin() * s.cos()
+                + (15.0 / 8.0 * n.powi(2) + 15.0 / 8.0 * n.powi(3))
+                    * (2.0 * d).sin()
+                    * (2.0 * s).cos()
+                - 35.0 / 24.0 * n.powi(3) * (3.0 * d).sin() * (3.0 * s).cos());
+
+        let i = m + p.n0;
+        let ii = nu / 2.0 * sin * cos;
+        let iii = nu / 24.0 * sin * cos.powi(3) * (5.0 - tan.powi(2) + 9.0 * eta2);
+        let iiia = nu / 720.0 * sin * cos.powi(5) * (61.0 - 58.0 * tan.powi(2) + tan.powi(4));
+        let iv = nu * cos;
+        let v = nu / 6.0 * cos.powi(3) * (nu / rho - tan.powi(2));
+        let vi = nu / 120.0
+            * cos.powi(5)
+            * (5.0 - 18.0 * tan.powi(2) + tan.powi(4) + 14.0 * eta2 - 58.0 * tan.powi(2) * eta2);
+
+        let northing = i + ii * dl.powi(2) + iii * dl.powi(4) + iiia * dl.powi(6);
+        let easting = p.e0 + iv * dl + v * dl.powi(3) + vi * dl.powi(5);
+
+        (easting, northing)
+    }
+
+    pub fn web_mercator(lat: f64, lon: f64) -> (f64, f64) {
+        let r = WGS84.a;
+        let y = (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0)
+            .tan()
+            .ln();
+
+        (r * lon.to_radians(), r * y)
+    }
+
+    /// Move a WGS84 position onto the OSGB36 datum with a Helmert transform,
+    /// accurate to a few meters which is well below OCR'd DMS resolution.
+    pub fn wgs84_to_osgb36(lat: f64, lon: f64) -> (f64, f64) {
+        const TX: f64 = -446.448;
+        const TY: f64 = 125.157;
+        const TZ: f64 = -542.060;
+        const S: f64 = 20.4894e-6;
+        let arcsec = |v: f64| (v / 3600.0).to_radians();
+        let (rx, ry, rz) = (arcsec(-0.1502), arcsec(-0.2470), arcsec(-0.8421));
+
+        let (x, y, z) = to_cartesian(lat, lon, &WGS84);
+        let x2 = TX + (1.0 + S) * x - rz * y + ry * z;
+        let y2 = TY + rz * x + (1.0 + S) * y - rx * z;
+        let z2 = TZ - ry * x + rx * y + (1.0 + S) * z;
+
+        from_cartesian(x2, y2, z2, &AIRY_1830)
+    }
+
+    fn to_cartesian(lat: f64, lon: f64, e: &Ellipsoid) -> (f64, f64, f64) {
+        let (phi, lambda) = (lat.to_radians(), lon.to_radians());
+        let e2 = 1.0 - (e.b * e.b) / (e.a * e.a);
+        let nu = e.a / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+
+        (
+            nu * phi.cos() * lambda.cos(),
+            nu * phi.cos() * lambda.sin(),
+            (1.0 - e2) * nu * phi.sin(),
+        )
+    }
+
+    fn from_cartesian(x: f64, y: f64, z: f64, e: &Ellipsoid) -> (f64, f64) {
+        let e2 = 1.0 - (e.b * e.b) / (e.a * e.a);
+        let p = (x * x + y * y).sqrt();
+        let mut phi = z.atan2(p * (1.0 - e2));
+        for _ in 0..10 {
+            let nu = e.a / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+            phi = (z + e2 * nu * phi.sin()).atan2(p);
+        }
+
+        (phi.to_degrees(), y.atan2(x).to_degrees())
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn national_grid_worked_example() {
+            // OS guide worked example, already on the OSGB36 datum
+            let lat = 52.0 + 39.0 / 60.0 + 27.2531 / 3600.0;
+            let lon = 1.0 + 43.0 / 60.0 + 4.5177 / 3600.0;
+            let (e, n) = transverse_mercator(lat, lon, &NATIONAL_GRID);
+
+            assert!((e - 651_409.903).abs() < 0.01, "{}", e);
+            assert!((n - 313_177.270).abs() < 0.01, "{}", n);
+        }
+
+        #[test]
+        fn utm_central_meridian() {
+            let (e, n) = transverse_mercator(0.0, 3.0, &utm(31, true));
+            assert!((e - 500_000.0).abs() < 1e-6);
+            assert!(n.abs() < 1e-6);
+
+            let (_, n) = transverse_mercator(-0.000001, 3.0, &utm(31, false));
+            assert!((n - 10_000_000.0).abs() < 1.0);
+        }
+
+        #[test]
+        fn web_mercator_bounds() {
+            let (x, y) = web_mercator(0.0, 180.0);
+            assert!((x - 20_037_508.34).abs() < 0.01);
+            assert!(y.abs() < 1e-6);
+        }
+
+        #[test]
+        fn helmert_shift_is_small() {
+            // the datum shift in Great Britain is around 100m
+            let (lat, lon) = wgs84_to_osgb36(51.5, -0.12);
+            let d = crate::track::distance_m((51.5, -0.12), (lat as f32, lon as f32));
+            assert!(d > 50.0 && d < 150.0, "{}", d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("EPSG:4326".parse::<Crs>().unwrap(), Crs::Wgs84);
+        assert!("27700".parse::<Crs>().is_err());
+
+        #[cfg(feature = "crs")]
+        {
+            assert_eq!(
+                "epsg:27700".parse::<Crs>().unwrap(),
+                Crs::BritishNationalGrid
+            );
+            assert_eq!(
+                "epsg:32730".parse::<Crs>().unwrap(),
+                Crs::Utm {
+                    zone: 30,
+                    north: false
+                }
+            );
+            assert_eq!("epsg:32730".parse::<Crs>().unwrap().epsg(), 32730);
+        }
+    }
+
+    #[cfg(feature = "crs")]
+    #[test]
+    fn os_grid_reference() {
+        assert_eq!(
+            grid_reference(651_409.903, 313_177.270).unwrap(),
+            "TG 51409 13177"
+        );
+        assert_eq!(
+            grid_reference(530_047.0, 179_951.0).unwrap(),
+            "TQ 30047 79951"
+        );
+        assert_eq!(
+            grid_reference(216_600.0, 771_200.0).unwrap(),
+            "NN 16600 71200"
+        );
+        assert!(grid_reference(-1.0, 100.0).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Finding where a camera prints its overlay, to set `--crop` for cameras
+//! that do not print it in the strip at the bottom of the frame.
+//!
+//! Overlay text is drawn with sharp, high contrast edges at the same place in
+//! every frame, while the road behind it moves. The frames are split into
+//! cells, a cell is text when it has many sharp edges in every frame sampled,
+//! and the crop is the line of text cells with the most of them.
+
+use image::GrayImage;
+
+use crate::preprocess::Crop;
+
+/// Size of the cells, in pixels
+const CELL: u32 = 8;
+/// Change of gray level between neighbouring pixels counted as a sharp edge
+const EDGE: i16 = 64;
+/// Share of a cell's pixels on a sharp edge for it to be text
+const TEXT: f32 = 0.1;
+/// Text cells in a row of cells for it to be part of a line of text
+const LINE: usize = 3;
+/// Cells between two words of the same line
+const WORD_GAP: usize = 24;
+
+/// Rectangle around the overlay text in `frames`, all of the same size.
+/// `None` when no line of text is found.
+pub fn text_region(frames: &[GrayImage]) -> Option<Crop> {
+    let first = frames.first()?;
+    let (width, height) = first.dimensions();
+    let (columns, rows) = ((width / CELL) as usize, (height / CELL) as usize);
+
+    // share of sharp edges in each cell, the lowest of all frames
+    let mut cells = vec![1.0f32; columns * rows];
+    for frame in frames.iter().filter(|f| f.dimensions() == (width, height)) {
+        let mut edges = vec![0u32; columns * rows];
+        for (y, line) in frame.chunks_exact(width as usize).enumerate() {
+            let row = y / CELL as usize;
+            if row >= rows {
+                break;
+            }
+            for (x, pair) in line.windows(2).enumerate() {
+                let column = x / CELL as usize;
+                if column < columns && (pair[1] as i16 - pair[0] as i16).abs() >= EDGE {
+                    edges[row * columns + column] += 1;
+                }
+            }
+        }
+        for (c, e) in cells.iter_mut().zip(edges) {
+            *c = c.min(e as f32 / (CELL * CELL) as f32);
+        }
+    }
+    let text = |row: usize, column: usize| cells[row * columns + column] >= TEXT;
+
+    // consecutive rows of cells with text, the line with the most text cells
+    let counts = (0..rows)
+        .map(|r| (0..columns).filter(|&c| text(r, c)).count())
+        .collect::<Vec<_>>();
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut start = None;
+    for r in 0..=rows {
+        match (start, counts.get(r).is_some_and(|&n| n >= LINE)) {
+            (None, true) => start = Some(r),
+            (Some(s), false) => {
+                let cells = counts[s..r].iter().sum::<usize>();
+                if best.is_none_or(|(_, _, n)| n < cells) {
+                    best = Some((s, r, cells));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let (top, bottom, _) = best?;
+
+    // words of that line less than a gap apart, the longest run of them
+    let used = (0..columns)
+        .filter(|&c| (top..bottom).any(|r| text(r, c)))
+        .collect::<Vec<_>>();
+    let mut runs: Vec<(usize, usize, usize)> = Vec::new();
+    for c in used {
+        match runs.last_mut() {
+            Some((_, end, n)) if c - *end <= WORD_GAP => {
+                *end = c;
+                *n += 1;
+            }
+            _ => runs.push((c, c, 1)),
+        }
+    }
+    let (left, right, _) = runs.into_iter().max_by_key(|&(_, _, n)| n)?;
+
+    // a cell of margin around the text
+    let x = (left as u32 * CELL).saturating_sub(CELL);
+    let y = (top as u32 * CELL).saturating_sub(CELL);
+    Some(Crop {
+        x,
+        y,
+        width: ((right as u32 + 2) * CELL).min(width) - x,
+        height: ((bottom as u32 + 1) * CELL).min(height) - y,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_overlay() {
+        let frame = image::open("sample-frame.jpg")
+            .unwrap()
+            .resize_exact(1280, 720, image::imageops::FilterType::Triangle)
+            .to_luma8();
+        let crop = text_region(&[frame]).unwrap();
+        // the line of text at the bottom, not the logo at the top
+        assert!(crop.x <= 330 && crop.x + crop.width >= 1090, "{}", crop);
+        assert!(
+            crop.y >= 660 && crop.y <= 685 && crop.y + crop.height >= 710,
+            "{}",
+            crop
+        );
+        assert!(text_region(&[GrayImage::new(1280, 720)]).is_none());
+    }
+}
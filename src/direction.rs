@@ -0,0 +1,136 @@
+//! Tell footage of the front camera from the rear one of a dual-channel
+//! dashcam, so rear clips are not mistaken for the front when combining them.
+
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::track::{self, Track};
+
+/// Points closer than this are too noisy to take a bearing from
+const MIN_MOVE_M: f64 = 20.0;
+/// Fewest comparable points to decide on
+const MIN_SAMPLES: usize = 3;
+/// Share of the samples that must agree
+const MIN_AGREEMENT: f64 = 0.75;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facing {
+    Front,
+    Rear,
+}
+
+/// Compare the heading printed on the overlay with the direction the
+/// coordinates move in: cameras with their own compass print the way they
+/// face, which is opposite to the direction of travel on a rear camera.
+pub fn detect(track: &Track) -> Option<Facing> {
+    let (mut front, mut rear) = (0, 0);
+    for w in track.points.windows(2) {
+        let Some(heading) = w[1].readings.heading else {
+            continue;
+        };
+        let (a, b) = (w[0].coordinate.lat_lon(), w[1].coordinate.lat_lon());
+        if track::distance_m(a, b) < MIN_MOVE_M {
+            continue;
+        }
+
+        let diff = angle_between(heading as f64, bearing(a, b));
+        if diff < 45.0 {
+            front += 1;
+        } else if diff > 135.0 {
+            rear += 1;
+        }
+    }
+
+    let total = front + rear;
+    if total < MIN_SAMPLES {
+        return None;
+    }
+    if front as f64 >= total as f64 * MIN_AGREEMENT {
+        Some(Facing::Front)
+    } else if rear as f64 >= total as f64 * MIN_AGREEMENT {
+        Some(Facing::Rear)
+    } else {
+        None
+    }
+}
+
+/// Channel marker in the file name: BlackVue's `_NF`/`_NR` (and `E`, `P`, `M`
+/// event kinds), or the trailing `F`/`R` of Viofo and others (`..._0001R.MP4`).
+pub fn from_file_name(path: &Path) -> Option<Facing> {
+    static REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:_[NEPM]|\d)([FR])$").unwrap());
+
+    let stem = path.file_stem()?.to_str()?;
+    match &REGEX.captures(stem)?[1] {
+        "F" => Some(Facing::Front),
+        _ => Some(Facing::Rear),
+    }
+}
+
+/// Initial bearing from `a` to `b` in degrees clockwise from north
+pub fn bearing(a: (f32, f32), b: (f32, f32)) -> f64 {
+    let (lat1, lat2) = ((a.0 as f64).to_radians(), (b.0 as f64).to_radians());
+    let dlon = (b.1 as f64 - a.1 as f64).to_radians();
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Smallest angle between two bearings, 0 to 180
+fn angle_between(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{Coordinate, Overlay, Readings},
+        track::FrameResult,
+    };
+
+    /// Driving east, with the overlay printing `heading`
+    fn track(heading: f32) -> Track {
+        let frames = (0..5)
+            .map(|i| FrameResult {
+                frame_no: i + 1,
+                pts_sec: None,
+                overlay: Overlay::Fix(Coordinate::Decimal {
+                    lat: 51.43,
+                    lon: 0.32 + i as f32 * 0.001,
+                }),
+                readings: Readings {
+                    heading: Some(heading),
+                    ..Default::default()
+                },
+                error: None,
+                confidence: None,
+            })
+            .collect();
+
+        Track::from_frames(frames, 10)
+    }
+
+    #[test]
+    fn heading_against_movement() {
+        assert_eq!(detect(&track(92.0)), Some(Facing::Front));
+        assert_eq!(detect(&track(268.0)), Some(Facing::Rear));
+        assert_eq!(detect(&track(0.0)), None);
+    }
+
+    #[test]
+    fn file_names() {
+        let facing = |name: &str| from_file_name(Path::new(name));
+
+        assert_eq!(facing("20210606_124229_NF.mp4"), Some(Facing::Front));
+        assert_eq!(facing("20210606_124229_ER.mp4"), Some(Facing::Rear));
+        assert_eq!(facing("2023_0501_120000_0001R.MP4"), Some(Facing::Rear));
+        assert_eq!(facing("2021_0606_124229_001.MP4"), None);
+        assert_eq!(facing("GRMN0001.MP4"), None);
+    }
+}
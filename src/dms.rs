@@ -0,0 +1,164 @@
+//! Conversion between degrees, minutes and seconds and signed decimal degrees.
+//!
+//! The overlay prints whole seconds, or tenths, and EXIF stores them as
+//! rationals, so a value has to survive the trip both ways: seconds are kept
+//! below 60 and rounding carries into the minutes and degrees rather than
+//! printing `60"`.
+
+/// An angle in degrees, minutes and seconds. South and west are `negative`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dms {
+    pub negative: bool,
+    pub degrees: u32,
+    pub minutes: u32,
+    pub seconds: f64,
+}
+
+impl Dms {
+    /// Seconds rounded to `places` decimals, carried into the minutes and
+    /// degrees when they round up to 60.
+    pub fn rounded(&self, places: i32) -> Self {
+        let scale = 10f64.powi(places);
+        let mut seconds = (self.seconds * scale).round() / scale;
+        let (mut minutes, mut degrees) = (self.minutes, self.degrees);
+        if seconds >= 60.0 {
+            seconds = 0.0;
+            minutes += 1;
+        }
+        if minutes >= 60 {
+            minutes -= 60;
+            degrees += 1;
+        }
+        Self {
+            negative: self.negative && (degrees, minutes, seconds) != (0, 0, 0.0),
+            degrees,
+            minutes,
+            seconds,
+        }
+    }
+}
+
+/// Signed decimal degrees of `dms`.
+pub fn dms_to_decimal(dms: &Dms) -> f64 {
+    let v = dms.degrees as f64 + dms.minutes as f64 / 60.0 + dms.seconds / 3600.0;
+    if dms.negative {
+        -v
+    } else {
+        v
+    }
+}
+
+/// Degrees, minutes and seconds of finite signed decimal degrees, with
+/// `0 <= minutes < 60` and `0 <= seconds < 60`. Zero, even `-0.0`, is not
+/// negative.
+pub fn decimal_to_dms(decimal: f64) -> Dms {
+    let total = decimal.abs() * 3600.0;
+    let degrees = (total / 3600.0).floor();
+    let rest = (total - degrees * 3600.0).max(0.0);
+    let minutes = (rest / 60.0).floor().min(59.0);
+    let seconds = (rest - minutes * 60.0).max(0.0);
+    Dms {
+        negative: decimal < 0.0,
+        degrees: degrees as u32,
+        minutes: minutes as u32,
+        seconds,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// xorshift64*, enough spread for property tests without a dependency
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> f64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    fn dms(negative: bool, degrees: u32, minutes: u32, seconds: f64) -> Dms {
+        Dms {
+            negative,
+            degrees,
+            minutes,
+            seconds,
+        }
+    }
+
+    #[test]
+    fn known_values() {
+        let v = dms_to_decimal(&dms(false, 51, 25, 47.0));
+        assert!((v - 51.429_722_222).abs() < 1e-9, "{}", v);
+        assert_eq!(dms_to_decimal(&dms(true, 0, 30, 0.0)), -0.5);
+        assert_eq!(decimal_to_dms(-0.5), dms(true, 0, 30, 0.0));
+        assert_eq!(decimal_to_dms(180.0), dms(false, 180, 0, 0.0));
+        assert_eq!(decimal_to_dms(-90.0), dms(true, 90, 0, 0.0));
+    }
+
+    #[test]
+    fn zero_and_hemisphere() {
+        assert_eq!(decimal_to_dms(0.0), dms(false, 0, 0, 0.0));
+        assert_eq!(decimal_to_dms(-0.0), dms(false, 0, 0, 0.0));
+        assert_eq!(dms_to_decimal(&dms(true, 0, 0, 0.0)), 0.0);
+
+        // west of Greenwich by less than a degree keeps its sign
+        let west = decimal_to_dms(-0.0001);
+        assert!(west.negative);
+        assert_eq!((west.degrees, west.minutes), (0, 0));
+        assert!((west.seconds - 0.36).abs() < 1e-9);
+        assert!(dms_to_decimal(&west) < 0.0);
+
+        // rounded to nothing, not `-0°0'0"`
+        assert_eq!(decimal_to_dms(-1e-9).rounded(4), dms(false, 0, 0, 0.0));
+    }
+
+    #[test]
+    fn rounding_carries() {
+        let almost = decimal_to_dms(51.0 + 59.0 / 60.0 + 59.999_99 / 3600.0);
+        assert_eq!(almost.rounded(4), dms(false, 52, 0, 0.0));
+        assert_eq!(almost.rounded(5).minutes, 59);
+        let almost = decimal_to_dms(-(0.999_999_99));
+        assert_eq!(almost.rounded(2), dms(true, 1, 0, 0.0));
+    }
+
+    #[test]
+    fn every_whole_second_round_trips() {
+        for negative in [false, true] {
+            for s in 0..=180 * 3600 {
+                let whole = dms(negative && s > 0, s / 3600, s / 60 % 60, (s % 60) as f64);
+                let back = decimal_to_dms(dms_to_decimal(&whole)).rounded(0);
+                assert_eq!(back, whole);
+            }
+        }
+    }
+
+    #[test]
+    fn random_decimals_round_trip() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200_000 {
+            let v = (random.next() * 2.0 - 1.0) * 180.0;
+            let d = decimal_to_dms(v);
+            assert!(
+                d.minutes < 60 && (0.0..60.0).contains(&d.seconds),
+                "{:?}",
+                d
+            );
+            assert_eq!(d.negative, v < 0.0);
+            assert!((dms_to_decimal(&d) - v).abs() < 1e-9, "{} {:?}", v, d);
+
+            // EXIF keeps 4 decimals of seconds
+            let r = d.rounded(4);
+            assert!(
+                r.minutes < 60 && (0.0..60.0).contains(&r.seconds),
+                "{:?}",
+                r
+            );
+            assert!((dms_to_decimal(&r) - v).abs() <= 0.5e-4 / 3600.0 + 1e-12);
+        }
+    }
+}
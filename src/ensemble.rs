@@ -0,0 +1,148 @@
+//! Reconcile what several OCR engines read from the same frame, field by field
+//! (latitude, longitude, speed, heading), each engine's vote weighted by its
+//! confidence.
+
+use crate::parser::{Coordinate, Overlay, Readings};
+
+/// What one engine read from a frame
+pub struct Vote {
+    pub overlay: Overlay,
+    pub readings: Readings,
+    /// 0-100
+    pub confidence: i32,
+}
+
+impl Vote {
+    fn weight(&self) -> f32 {
+        // an unsure engine still breaks ties between the others
+        self.confidence.max(1) as f32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Fix,
+    NoFix,
+    OtherPage,
+}
+
+pub fn merge(votes: Vec<Vote>) -> (Overlay, Readings) {
+    let (time, ambiguous_date) = elect(votes.iter().map(|v| {
+        let time = v.readings.time.map(|t| (t, v.readings.ambiguous_date));
+        (time, v.weight())
+    }))
+    .unzip();
+    let readings = Readings {
+        heading: elect(votes.iter().map(|v| (v.readings.heading, v.weight()))),
+        time,
+        ambiguous_date: ambiguous_date.unwrap_or_default(),
+        ..elect(
+            votes
+                .iter()
+                .map(|v| (v.readings.speed.map(|_| v.readings), v.weight())),
+        )
+        .unwrap_or_default()
+    };
+
+    let kind = elect(votes.iter().map(|v| {
+        let kind = match v.overlay {
+            Overlay::Fix(_) => Some(Kind::Fix),
+            Overlay::NoFix => Some(Kind::NoFix),
+            Overlay::OtherPage => Some(Kind::OtherPage),
+            Overlay::Unreadable => None,
+        };
+        (kind, v.weight())
+    }));
+    let overlay = match kind {
+        Some(Kind::Fix) => merge_fix(votes),
+        Some(Kind::NoFix) => Overlay::NoFix,
+        Some(Kind::OtherPage) => Overlay::OtherPage,
+        None => Overlay::Unreadable,
+    };
+
+    (overlay, readings)
+}
+
+/// Elect the latitude and longitude separately, keeping the coordinate as read
+/// when one engine read both.
+fn merge_fix(votes: Vec<Vote>) -> Overlay {
+    let fixes = votes
+        .into_iter()
+        .filter_map(|v| {
+            let weight = v.weight();
+            match v.overlay {
+                Overlay::Fix(c) => Some((c, weight)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let lat = elect(fixes.iter().map(|(c, w)| (Some(c.lat_lon().0), *w)));
+    let lon = elect(fixes.iter().map(|(c, w)| (Some(c.lat_lon().1), *w)));
+    let (Some(lat), Some(lon)) = (lat, lon) else {
+        return Overlay::Unreadable;
+    };
+
+    match fixes.into_iter().find(|(c, _)| c.lat_lon() == (lat, lon)) {
+        Some((c, _)) => Overlay::Fix(c),
+        None => Overlay::Fix(Coordinate::Decimal { lat, lon }),
+    }
+}
+
+/// Value with the highest total weight, the first one read on a tie. `None`
+/// casts no vote.
+fn elect<T: PartialEq>(values: impl Iterator<Item = (Option<T>, f32)>) -> Option<T> {
+    let mut tally: Vec<(T, f32)> = Vec::new();
+    for (value, weight) in values {
+        let Some(value) = value else {
+            continue;
+        };
+        match tally.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, total)) => *total += weight,
+            None => tally.push((value, weight)),
+        }
+    }
+
+    tally
+        .into_iter()
+        .reduce(|best, t| if t.1 > best.1 { t } else { best })
+        .map(|(v, _)| v)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{self, Strictness};
+
+    fn vote(text: &str, confidence: i32) -> Vote {
+        Vote {
+            overlay: parser::parse_overlay_with(text, Strictness::Normal),
+            readings: parser::parse_readings(text),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn fields_from_the_most_confident() {
+        let (overlay, readings) = merge(vec![
+            // latitude seconds misread by the first, longitude by the second
+            vote("N51°25'41\" E0°19'21\" 50MPH", 70),
+            vote("N51°25'47\" E0°19'27\" 58MPH", 60),
+            vote("N51°25'47\" E0°19'21\"", 20),
+        ]);
+
+        let Overlay::Fix(c) = overlay else {
+            panic!("no fix");
+        };
+        assert_eq!(c.to_decimal(), "51.42972, 0.3225");
+        assert_eq!(readings.speed, Some(50.0));
+    }
+
+    #[test]
+    fn unreadable_casts_no_vote() {
+        let (overlay, _) = merge(vec![vote("junk", 90), vote("GPS: searching", 10)]);
+        assert!(matches!(overlay, Overlay::NoFix));
+
+        let (overlay, _) = merge(vec![vote("N51°25'47\" E0°19'21\"", 10), vote("", 90)]);
+        assert!(matches!(overlay, Overlay::Fix(Coordinate::DegreeMinSec(_))));
+    }
+}
@@ -0,0 +1,199 @@
+//! `--event-log`: one JSON object per line for every frame dispatched, OCR
+//! result, parse outcome and point dropped, to debug or analyse a run after
+//! the fact whatever was printed to the console.
+//!
+//! The log is process-wide so the workers and the track filters can record to
+//! it without it being passed around, and written a line at a time so an
+//! interrupted run still leaves every event up to then.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{LineWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use anyhow::Context;
+use chrono::{SecondsFormat, Utc};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+
+use crate::{
+    parser::Overlay,
+    sink::notify::Report,
+    track::{FrameError, Stage, Track},
+};
+
+static LOG: OnceCell<EventLog> = OnceCell::new();
+
+struct EventLog {
+    started: Instant,
+    /// `None` once writing failed
+    out: Mutex<Option<LineWriter<File>>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RunStarted {
+        version: &'static str,
+        command_line: Vec<String>,
+    },
+    /// ffmpeg wrote the frame and a worker picked it up
+    FrameDispatched {
+        frame_no: u32,
+    },
+    /// The frame could not be decoded or read
+    FrameFailed {
+        frame_no: u32,
+        stage: Stage,
+        message: &'a str,
+    },
+    OcrResult {
+        frame_no: u32,
+        text: &'a str,
+        confidence: i32,
+    },
+    Parsed {
+        frame_no: u32,
+        /// `fix`, `no_fix`, `other_page` or `unreadable`
+        outcome: &'static str,
+        lat: Option<f32>,
+        lon: Option<f32>,
+    },
+    /// A point left out of the outputs, eg. `outlier`, `trim_ends`, `max_points`
+    PointDropped {
+        frame_no: u32,
+        reason: &'a str,
+    },
+    RunFinished {
+        report: &'a Report,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    /// RFC 3339, in milliseconds
+    time: String,
+    /// Seconds since the log was opened
+    elapsed_sec: f64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Start logging to `path`, replacing it.
+pub fn open(path: &Path) -> anyhow::Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("create event log {}", path.display()))?;
+    let log = EventLog {
+        started: Instant::now(),
+        out: Mutex::new(Some(LineWriter::new(file))),
+    };
+    if LOG.set(log).is_err() {
+        anyhow::bail!("event log already open");
+    }
+
+    Ok(())
+}
+
+/// Whether `--event-log` is on, to skip work only done for the log
+pub fn enabled() -> bool {
+    LOG.get().is_some()
+}
+
+/// Append an event, if the log is open. Failing to write does not fail the
+/// run, the first error is printed and the log closed.
+pub fn record(event: Event) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+    let line = Line {
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        elapsed_sec: log.started.elapsed().as_secs_f64(),
+        event: &event,
+    };
+    let Ok(mut out) = log.out.lock() else {
+        return;
+    };
+    let Some(writer) = out.as_mut() else {
+        return;
+    };
+    let written = serde_json::to_writer(&mut *writer, &line)
+        .map_err(std::io::Error::from)
+        .and_then(|_| writer.write_all(b"\n"));
+    if let Err(e) = written {
+        eprintln!("Warning: event log closed: {}", e);
+        *out = None;
+    }
+}
+
+pub fn frame_failed(frame_no: u32, error: &FrameError) {
+    record(Event::FrameFailed {
+        frame_no,
+        stage: error.stage,
+        message: &error.message,
+    });
+}
+
+pub fn parsed(frame_no: u32, overlay: &Overlay) {
+    let (outcome, position) = match overlay {
+        Overlay::Fix(c) => ("fix", Some(c.lat_lon())),
+        Overlay::NoFix => ("no_fix", None),
+        Overlay::OtherPage => ("other_page", None),
+        Overlay::Unreadable => ("unreadable", None),
+    };
+    record(Event::Parsed {
+        frame_no,
+        outcome,
+        lat: position.map(|p| p.0),
+        lon: position.map(|p| p.1),
+    });
+}
+
+/// Run `filter` on `tracks`, recording the points it removes with `reason`.
+pub fn filter<T>(tracks: &mut [Track], reason: &str, filter: impl FnOnce(&mut [Track]) -> T) -> T {
+    if !enabled() {
+        return filter(tracks);
+    }
+    let frame_nos = |tracks: &[Track]| {
+        tracks
+            .iter()
+            .flat_map(|t| t.points.iter().map(|p| p.frame_no))
+            .collect::<Vec<_>>()
+    };
+    let before = frame_nos(tracks);
+    let result = filter(tracks);
+    let kept = frame_nos(tracks).into_iter().collect::<HashSet<_>>();
+    for frame_no in before.into_iter().filter(|f| !kept.contains(f)) {
+        record(Event::PointDropped { frame_no, reason });
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn event_line() {
+        let event = Event::Parsed {
+            frame_no: 3,
+            outcome: "fix",
+            lat: Some(51.5),
+            lon: Some(-0.25),
+        };
+        let line = Line {
+            time: "2024-05-01T10:00:00.000Z".to_string(),
+            elapsed_sec: 1.5,
+            event: &event,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&line).unwrap(),
+            r#"{"time":"2024-05-01T10:00:00.000Z","elapsed_sec":1.5,"event":"parsed","frame_no":3,"outcome":"fix","lat":51.5,"lon":-0.25}"#
+        );
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use super::TrackWriter;
+use crate::{
+    crs::Crs,
+    track::{Track, TrackPoint},
+};
+
+/// Build a FeatureCollection with a `LineString` of each trip followed by
+/// one `Point` feature per location. With several trips (`--trip-gap`) every
+/// feature has the number of its trip in `trip`.
+///
+/// Coordinates are written in `crs`. GeoJSON (RFC 7946) is WGS84 only, so a
+/// projected CRS is declared with the legacy `crs` member which QGIS and GDAL
+/// still honour.
+pub fn document(source: &str, trips: &[Track], crs: Crs) -> Value {
+    let mut features = Vec::new();
+    for (i, track) in trips.iter().enumerate() {
+        let trip = (trips.len() > 1).then_some(i + 1);
+        features.extend(trip_features(source, track, trip, crs));
+    }
+
+    let mut doc = json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    if crs.is_projected() {
+        doc["crs"] = json!({
+            "type": "name",
+            "properties": { "name": format!("urn:ogc:def:crs:EPSG::{}", crs.epsg()) },
+        });
+    }
+
+    doc
+}
+
+fn trip_features(source: &str, track: &Track, trip: Option<usize>, crs: Crs) -> Vec<Value> {
+    let positions = track
+        .points
+        .iter()
+        .map(|p| {
+            let (lat, lon) = p.coordinate.lat_lon();
+            let (x, y) = crs.project(lat as f64, lon as f64);
+            json!([x, y])
+        })
+        .collect::<Vec<_>>();
+    let properties = |mut properties: Value| {
+        if let Some(trip) = trip {
+            properties["trip"] = json!(trip);
+        }
+        tagged(properties, &track.meta)
+    };
+
+    let mut features = vec![json!({
+        "type": "Feature",
+        "geometry": { "type": "LineString", "coordinates": positions },
+        "properties": properties(json!({ "source": source, "camera": track.facing })),
+    })];
+    features.extend(track.points.iter().zip(&positions).map(|(p, position)| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position },
+            "properties": properties(point_properties(p)),
+        })
+    }));
+    // `--speed-limits` events, a layer of their own in GIS tools
+    #[cfg(feature = "osm")]
+    features.extend(track.speeding.iter().flatten().map(|e| {
+        let (x, y) = crs.project(e.lat as f64, e.lon as f64);
+        let mut event = json!(e);
+        event["layer"] = json!("speeding");
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [x, y] },
+            "properties": properties(event),
+        })
+    }));
+
+    features
+}
+
+/// Frame and offset of a point, with the speed, heading and clock printed on
+/// the overlay when it shows them
+fn point_properties(p: &TrackPoint) -> Value {
+    let mut properties = json!({ "frame_no": p.frame_no, "offset_sec": p.offset_sec });
+    if let Some(speed) = p.speed_kmh() {
+        properties["speed_kmh"] = json!(speed);
+    }
+    if let Some(heading) = p.readings.heading {
+        properties["heading"] = json!(heading);
+    }
+    if let Some(time) = p.readings.time {
+        properties["overlay_time"] = json!(super::format_clock(time));
+    }
+
+    properties
+}
+
+/// Add the `--meta` tags to the properties of a feature, without replacing
+/// the properties of the same name.
+fn tagged(mut properties: Value, meta: &BTreeMap<String, String>) -> Value {
+    if let Some(properties) = properties.as_object_mut() {
+        for (key, value) in meta {
+            properties
+                .entry(key.as_str())
+                .or_insert_with(|| json!(value));
+        }
+    }
+
+    properties
+}
+
+pub fn write(
+    out: impl std::io::Write,
+    source: &str,
+    trips: &[Track],
+    crs: Crs,
+) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(out, &document(source, trips, crs))?;
+
+    Ok(())
+}
+
+/// GeoJSON written as points arrive: one `Point` feature per line, the
+/// `LineString` of the track last.
+pub struct GeojsonWriter<W: std::io::Write> {
+    out: W,
+    source: String,
+    crs: Crs,
+    meta: BTreeMap<String, String>,
+    positions: Vec<Value>,
+    finished: bool,
+}
+
+impl<W: std::io::Write> GeojsonWriter<W> {
+    pub fn new(
+        mut out: W,
+        source: &str,
+        crs: Crs,
+        meta: BTreeMap<String, String>,
+    ) -> std::io::Result<Self> {
+        write!(out, r#"{{"type":"FeatureCollection","#)?;
+        if crs.is_projected() {
+            write!(
+                out,
+                r#""crs":{{"type":"name","properties":{{"name":"urn:ogc:def:crs:EPSG::{}"}}}},"#,
+                crs.epsg()
+            )?;
+        }
+        write!(out, r#""features":["#)?;
+
+        Ok(Self {
+            out,
+            source: source.to_string(),
+            crs,
+            meta,
+            positions: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn separator(&self) -> &'static str {
+        if self.positions.is_empty() {
+            "\n"
+        } else {
+            ",\n"
+        }
+    }
+}
+
+impl<W: std::io::Write> TrackWriter for GeojsonWriter<W> {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        let (lat, lon) = p.coordinate.lat_lon();
+        let (x, y) = self.crs.project(lat as f64, lon as f64);
+        let feature = json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [x, y] },
+            "properties": tagged(point_properties(p), &self.meta),
+        });
+        write!(self.out, "{}{}", self.separator(), feature)?;
+        self.positions.push(json!([x, y]));
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if !std::mem::replace(&mut self.finished, true) {
+            let line = json!({
+                "type": "Feature",
+                "geometry": { "type": "LineString", "coordinates": self.positions },
+                "properties": tagged(json!({ "source": self.source }), &self.meta),
+            });
+            writeln!(self.out, "{}{}\n]}}", self.separator(), line)?;
+        }
+        self.out.flush()
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: std::io::Write> Drop for GeojsonWriter<W> {
+    fn drop(&mut self) {
+        _ = self.finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{Coordinate, Readings};
+
+    #[test]
+    fn incremental_is_valid_when_dropped() {
+        let mut out = Vec::new();
+        {
+            let meta = BTreeMap::from([
+                ("vehicle".to_string(), "VAN12".to_string()),
+                ("frame_no".to_string(), "0".to_string()),
+            ]);
+            let mut writer =
+                GeojsonWriter::new(&mut out, "clip.mp4", Crs::default(), meta).unwrap();
+            for frame_no in 1..=2 {
+                writer
+                    .point(&TrackPoint {
+                        frame_no,
+                        offset_sec: frame_no as f64 * 10.0,
+                        coordinate: Coordinate::Decimal {
+                            lat: 51.43,
+                            lon: 0.32,
+                        },
+                        accel_peak: None,
+                        confidence: None,
+                        readings: Readings::default(),
+                    })
+                    .unwrap();
+            }
+        }
+
+        let doc: Value = serde_json::from_slice(&out).unwrap();
+        let features = doc["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!(features[1]["properties"]["frame_no"], 2);
+        assert_eq!(features[1]["properties"]["vehicle"], "VAN12");
+        assert_eq!(features[2]["properties"]["vehicle"], "VAN12");
+        assert_eq!(features[2]["geometry"]["type"], "LineString");
+        assert_eq!(features[2]["geometry"]["coordinates"][1][0], 0.32f32 as f64);
+    }
+
+    #[test]
+    fn trips_are_numbered() {
+        let trip = |frame_no| {
+            let mut track = Track::from_frames(Vec::new(), 10);
+            track.points.push(TrackPoint {
+                frame_no,
+                offset_sec: frame_no as f64 * 10.0,
+                coordinate: Coordinate::Decimal {
+                    lat: 51.43,
+                    lon: 0.32,
+                },
+                accel_peak: None,
+                confidence: None,
+                readings: Readings::default(),
+            });
+            track
+        };
+
+        let doc = document("clip.mp4", &[trip(1), trip(9)], Crs::default());
+        let features = doc["features"].as_array().unwrap();
+        assert_eq!(features.len(), 4);
+        assert_eq!(features[2]["geometry"]["type"], "LineString");
+        assert_eq!(features[2]["properties"]["trip"], 2);
+        assert_eq!(features[3]["properties"]["trip"], 2);
+
+        let doc = document("clip.mp4", &[trip(1)], Crs::default());
+        assert!(doc["features"][0]["properties"].get("trip").is_none());
+    }
+
+    #[test]
+    fn overlay_readings_are_properties() {
+        let mut readings = crate::parser::parse_readings("51KM/H HDG 90 12:42:29 06/06/2021");
+        readings.speed_unit = Some(crate::speed::SpeedUnit::Kmh);
+        let p = TrackPoint {
+            frame_no: 1,
+            offset_sec: 0.0,
+            coordinate: Coordinate::Decimal {
+                lat: 51.43,
+                lon: 0.32,
+            },
+            accel_peak: None,
+            confidence: None,
+            readings,
+        };
+        let properties = point_properties(&p);
+        assert_eq!(properties["speed_kmh"], 51.0);
+        assert_eq!(properties["heading"], 90.0);
+        assert_eq!(properties["overlay_time"], "2021-06-06T12:42:29");
+
+        let bare = point_properties(&TrackPoint {
+            readings: Readings::default(),
+            ..p
+        });
+        assert!(bare.get("speed_kmh").is_none());
+    }
+}
@@ -0,0 +1,304 @@
+//! GeoPackage with a `track` line layer and a `points` layer, built with the
+//! `sqlite3` command line tool. Coordinates are always WGS84 (EPSG:4326).
+
+use std::{
+    fmt::Write as _,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::Context;
+
+use crate::{manifest, track::Track};
+
+const SRS_ID: i32 = 4326;
+
+/// Columns of the `track` and `points` tables, which `--meta` tags cannot replace
+const COLUMNS: &[&str] = &[
+    "fid",
+    "geom",
+    "trip",
+    "source",
+    "camera",
+    "points",
+    "frame_no",
+    "offset_sec",
+    "speed_kmh",
+    "heading",
+    "accel_peak",
+];
+
+/// Fail early when sqlite3 is not installed, rather than once the video is read
+pub fn check() -> anyhow::Result<()> {
+    match manifest::tool_version("sqlite3", "--version") {
+        Some(_) => Ok(()),
+        None => anyhow::bail!(
+            "`--format gpkg` needs the sqlite3 command line tool, which was not found. Install it (eg. `apt install sqlite3` or `brew install sqlite`) or pick another `--format`"
+        ),
+    }
+}
+
+/// Write the GeoPackage of the trips, a row of the `track` layer each.
+pub fn write(mut out: impl Write, source: &str, trips: &[Track]) -> anyhow::Result<()> {
+    // a new file of its own, removed when dropped
+    let file = tempfile::Builder::new()
+        .prefix("dash2gps-")
+        .suffix(".gpkg")
+        .tempfile()
+        .context("create GeoPackage")?;
+    run_sqlite(file.path(), &script(source, trips))?;
+    let content = std::fs::read(file.path()).context("read GeoPackage")?;
+
+    out.write_all(&content)?;
+    Ok(())
+}
+
+fn run_sqlite(path: &std::path::Path, script: &str) -> anyhow::Result<()> {
+    let mut child = Command::new("sqlite3")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("start sqlite3 to write the GeoPackage, is it installed?")?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(script.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "unable to write the GeoPackage:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// SQL creating a GeoPackage 1.2 with the track and its points.
+fn script(source: &str, trips: &[Track]) -> String {
+    let positions = |track: &Track| {
+        track
+            .points
+            .iter()
+            .map(|p| {
+                let (lat, lon) = p.coordinate.lat_lon();
+                (lon as f64, lat as f64)
+            })
+            .collect::<Vec<_>>()
+    };
+    let bounds = trips
+        .iter()
+        .flat_map(positions)
+        .fold(None, |b: Option<[f64; 4]>, (x, y)| {
+            Some(match b {
+                None => [x, y, x, y],
+                Some([min_x, min_y, max_x, max_y]) => {
+                    [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+                }
+            })
+        });
+    let bounds = match bounds {
+        Some([min_x, min_y, max_x, max_y]) => format!("{min_x}, {min_y}, {max_x}, {max_y}"),
+        None => "NULL, NULL, NULL, NULL".to_string(),
+    };
+    let last_change = crate::clock::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+    // a text column per `--meta` tag on both tables, unless one is already named so
+    let meta = trips
+        .first()
+        .into_iter()
+        .flat_map(|t| &t.meta)
+        .filter(|(key, _)| !COLUMNS.iter().any(|c| c.eq_ignore_ascii_case(key)))
+        .collect::<Vec<_>>();
+    let meta_columns = meta
+        .iter()
+        .map(|(key, _)| format!(", {} TEXT", identifier(key)))
+        .collect::<String>();
+    let meta_names = meta
+        .iter()
+        .map(|(key, _)| format!(", {}", identifier(key)))
+        .collect::<String>();
+    let meta_values = meta
+        .iter()
+        .map(|(_, value)| format!(", {}", text(value)))
+        .collect::<String>();
+
+    let mut sql = String::from(
+        "PRAGMA application_id = 1196444487;
+PRAGMA user_version = 10200;
+BEGIN;
+CREATE TABLE gpkg_spatial_ref_sys (
+  srs_name TEXT NOT NULL, srs_id INTEGER PRIMARY KEY, organization TEXT NOT NULL,
+  organization_coordsys_id INTEGER NOT NULL, definition TEXT NOT NULL, description TEXT);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+  ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', NULL),
+  ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', NULL),
+  ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS[\"WGS 84\",DATUM[\"WGS_1984\",SPHEROID[\"WGS 84\",6378137,298.257223563,AUTHORITY[\"EPSG\",\"7030\"]],AUTHORITY[\"EPSG\",\"6326\"]],PRIMEM[\"Greenwich\",0,AUTHORITY[\"EPSG\",\"8901\"]],UNIT[\"degree\",0.0174532925199433,AUTHORITY[\"EPSG\",\"9122\"]],AUTHORITY[\"EPSG\",\"4326\"]]', NULL);
+CREATE TABLE gpkg_contents (
+  table_name TEXT NOT NULL PRIMARY KEY, data_type TEXT NOT NULL, identifier TEXT UNIQUE,
+  description TEXT DEFAULT '', last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+  min_x DOUBLE, min_y DOUBLE, max_x DOUBLE, max_y DOUBLE,
+  srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id));
+CREATE TABLE gpkg_geometry_columns (
+  table_name TEXT NOT NULL, column_name TEXT NOT NULL, geometry_type_name TEXT NOT NULL,
+  srs_id INTEGER NOT NULL, z TINYINT NOT NULL, m TINYINT NOT NULL,
+  PRIMARY KEY (table_name, column_name));
+",
+    );
+    _ = writeln!(
+        sql,
+        "CREATE TABLE track (
+  fid INTEGER PRIMARY KEY AUTOINCREMENT, geom LINESTRING,
+  trip INTEGER, source TEXT, camera TEXT, points INTEGER{meta_columns});
+CREATE TABLE points (
+  fid INTEGER PRIMARY KEY AUTOINCREMENT, geom POINT,
+  trip INTEGER, frame_no INTEGER, offset_sec REAL, speed_kmh REAL, heading REAL, accel_peak REAL{meta_columns});"
+    );
+    for (table, geometry) in [("track", "LINESTRING"), ("points", "POINT")] {
+        _ = writeln!(
+            sql,
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, last_change, min_x, min_y, max_x, max_y, srs_id) \
+             VALUES ('{table}', 'features', '{table}', '{last_change}', {bounds}, {SRS_ID});
+INSERT INTO gpkg_geometry_columns VALUES ('{table}', 'geom', '{geometry}', {SRS_ID}, 0, 0);"
+        );
+    }
+
+    for (trip, track) in (1..).zip(trips) {
+        let positions = positions(track);
+        let camera = track
+            .facing
+            .and_then(|f| serde_json::to_value(f).ok())
+            .and_then(|v| v.as_str().map(text))
+            .unwrap_or_else(|| "NULL".to_string());
+        _ = writeln!(
+            sql,
+            "INSERT INTO track (geom, trip, source, camera, points{}) VALUES ({}, {}, {}, {}, {}{});",
+            meta_names,
+            blob(&line_string(&positions)),
+            trip,
+            text(source),
+            camera,
+            positions.len(),
+            meta_values
+        );
+        for (p, &position) in track.points.iter().zip(&positions) {
+            _ = writeln!(
+                sql,
+                "INSERT INTO points (geom, trip, frame_no, offset_sec, speed_kmh, heading, accel_peak{}) \
+                 VALUES ({}, {}, {}, {}, {}, {}, {}{});",
+                meta_names,
+                blob(&point(position)),
+                trip,
+                p.frame_no,
+                p.offset_sec,
+                real(p.speed_kmh()),
+                real(p.readings.heading),
+                real(p.accel_peak),
+                meta_values
+            );
+        }
+    }
+    sql.push_str("COMMIT;\n");
+
+    sql
+}
+
+/// GeoPackage geometry: a `GP` header without envelope followed by
+/// little-endian WKB.
+fn geometry(wkb_type: u32, body: &[u8]) -> Vec<u8> {
+    let mut g = vec![b'G', b'P', 0, 0b0000_0001];
+    g.extend(SRS_ID.to_le_bytes());
+    g.push(1);
+    g.extend(wkb_type.to_le_bytes());
+    g.extend(body);
+    g
+}
+
+fn point((x, y): (f64, f64)) -> Vec<u8> {
+    geometry(1, &[x.to_le_bytes(), y.to_le_bytes()].concat())
+}
+
+fn line_string(positions: &[(f64, f64)]) -> Vec<u8> {
+    let mut body = (positions.len() as u32).to_le_bytes().to_vec();
+    for (x, y) in positions {
+        body.extend(x.to_le_bytes());
+        body.extend(y.to_le_bytes());
+    }
+    geometry(2, &body)
+}
+
+fn blob(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2 + 3);
+    s.push_str("X'");
+    for b in bytes {
+        _ = write!(s, "{b:02X}");
+    }
+    s.push('\'');
+    s
+}
+
+/// Quoted SQL identifier
+fn identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn text(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn real(v: Option<f32>) -> String {
+    v.map_or_else(|| "NULL".to_string(), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_geometry() {
+        let g = point((0.5, 51.0));
+        assert_eq!(&g[..4], b"GP\0\x01");
+        assert_eq!(&g[4..8], &4326i32.to_le_bytes());
+        // little-endian WKB point
+        assert_eq!(&g[8..13], &[1, 1, 0, 0, 0]);
+        assert_eq!(&g[13..21], &0.5f64.to_le_bytes());
+        assert_eq!(g.len(), 29);
+
+        assert_eq!(blob(&[0x47, 0x0a]), "X'470A'");
+        assert_eq!(text("it's"), "'it''s'");
+        assert_eq!(identifier(r#"a"b"#), r#""a""b""#);
+    }
+
+    #[test]
+    fn line_geometry() {
+        let g = line_string(&[(0.0, 51.0), (0.1, 51.1)]);
+        assert_eq!(&g[9..13], &2u32.to_le_bytes());
+        assert_eq!(&g[13..17], &2u32.to_le_bytes());
+        assert_eq!(g.len(), 17 + 2 * 16);
+    }
+
+    #[test]
+    fn written_side_by_side() {
+        if check().is_err() {
+            return;
+        }
+        let trips = [Track::from_frames(Vec::new(), 10)];
+        std::thread::scope(|s| {
+            let writers = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut out = Vec::new();
+                        write(&mut out, "clip.mp4", &trips).map(|_| out)
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                let gpkg = writer.join().unwrap().unwrap();
+                assert!(gpkg.starts_with(b"SQLite format 3\0"));
+            }
+        });
+    }
+}
@@ -0,0 +1,44 @@
+//! Formats gpsbabel writes (`--via-gpsbabel`), eg. `garmin_txt` or `ozi`, from
+//! the GPX output piped through it.
+
+use std::{io::Write, process::Stdio};
+
+use anyhow::Context;
+
+use crate::{ffmpeg, manifest};
+
+/// Fail early when gpsbabel is not installed, rather than once the video is read
+pub fn check() -> anyhow::Result<()> {
+    match manifest::tool_version("gpsbabel", "-V") {
+        Some(_) => Ok(()),
+        None => anyhow::bail!(
+            "`--via-gpsbabel` needs gpsbabel, which was not found. Install it (eg. `apt install gpsbabel` or `brew install gpsbabel`) or pick a `--format`"
+        ),
+    }
+}
+
+/// Convert a GPX document to `format`, with its options if any, eg. `garmin_txt,dist=m`.
+pub fn convert(gpx: &[u8], format: &str) -> anyhow::Result<Vec<u8>> {
+    let mut child = ffmpeg::command("gpsbabel")
+        .args(["-i", "gpx", "-f", "-", "-o", format, "-F", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("start gpsbabel, is it installed?")?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // written while the output is read, so a large track can't fill both pipes
+    let output = std::thread::scope(|s| {
+        s.spawn(move || stdin.write_all(gpx));
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gpsbabel could not write {}:\n{}",
+            format,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,462 @@
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use super::TrackWriter;
+use crate::track::{self, Track, TrackPoint};
+
+/// What the points become in the GPX document
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GpxMode {
+    /// Track points, in a new segment after every interval without GPS fix
+    #[default]
+    Track,
+    /// Route points of a single route, for devices that only import routes
+    Route,
+    /// Standalone waypoints named by their time in the video
+    Waypoints,
+}
+
+/// Namespace of the `--meta` tags in `<extensions>`
+const NAMESPACE: &str = "https://github.com/mustakimali/dash2gps";
+/// Garmin's namespace for the speed and course of a point, read by most
+/// tools as GPX 1.1 has neither
+const TRACK_POINT_EXTENSION: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v2";
+
+/// GPX 1.1 document of the trips, each a track or route, or waypoints. Points
+/// are timed from the start of the video when known, and those with a photo
+/// (`--photo-log`) link to it. The speed and heading printed on the overlay are
+/// in the Garmin `TrackPointExtension`.
+pub fn write(
+    out: impl Write,
+    name: &str,
+    mode: GpxMode,
+    trips: &[Track],
+    photos: &BTreeMap<u32, PathBuf>,
+) -> anyhow::Result<()> {
+    let trip_name = |i: usize| match trips.len() {
+        1 => name.to_string(),
+        _ => format!("{} trip {}", name, i + 1),
+    };
+    let meta = trips.first().map(|t| t.meta.clone()).unwrap_or_default();
+    let start = trips.first().and_then(|t| t.start);
+    let mut writer = GpxWriter::new(out, &trip_name(0), mode, photos.clone(), meta, start)?;
+    for (i, track) in trips.iter().enumerate() {
+        if i > 0 {
+            writer.next_trip(&trip_name(i))?;
+        }
+        for (i, segment) in track.segments().into_iter().enumerate() {
+            if i > 0 {
+                writer.gap()?;
+            }
+            for p in segment {
+                writer.point(p)?;
+            }
+        }
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+pub struct GpxWriter<W: Write> {
+    out: W,
+    mode: GpxMode,
+    photos: BTreeMap<u32, PathBuf>,
+    /// `<dash2gps:meta>` elements of the `--meta` tags
+    tags: String,
+    /// Recording start of the video, to time the points
+    start: Option<NaiveDateTime>,
+    /// Points in the current `<trkseg>`
+    in_segment: usize,
+    gap: bool,
+    finished: bool,
+}
+
+impl<W: Write> GpxWriter<W> {
+    pub fn new(
+        mut out: W,
+        name: &str,
+        mode: GpxMode,
+        photos: BTreeMap<u32, PathBuf>,
+        meta: BTreeMap<String, String>,
+        start: Option<NaiveDateTime>,
+    ) -> std::io::Result<Self> {
+        let tags = tags(&meta);
+        let mut namespaces = format!(r#" xmlns:gpxtpx="{}""#, TRACK_POINT_EXTENSION);
+        if !tags.is_empty() {
+            namespaces += &format!(r#" xmlns:dash2gps="{}""#, NAMESPACE);
+        }
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<gpx version="1.1" creator="dash2gps" xmlns="http://www.topografix.com/GPX/1/1"{}>"#,
+            namespaces
+        )?;
+        let mut writer = Self {
+            out,
+            mode,
+            photos,
+            tags,
+            start,
+            in_segment: 0,
+            gap: false,
+            finished: false,
+        };
+        writer.open(name)?;
+
+        Ok(writer)
+    }
+
+    /// End the track or route and start another for the next trip
+    pub fn next_trip(&mut self, name: &str) -> std::io::Result<()> {
+        self.close()?;
+        self.in_segment = 0;
+        self.gap = false;
+        self.open(name)
+    }
+
+    fn open(&mut self, name: &str) -> std::io::Result<()> {
+        match self.mode {
+            GpxMode::Track => writeln!(
+                self.out,
+                "  <trk>\n    <name>{}</name>{}\n    <trkseg>",
+                escape(name),
+                extensions(&self.tags)
+            ),
+            GpxMode::Route => writeln!(
+                self.out,
+                "  <rte>\n    <name>{}</name>{}",
+                escape(name),
+                extensions(&self.tags)
+            ),
+            GpxMode::Waypoints => Ok(()),
+        }
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        match self.mode {
+            GpxMode::Track => writeln!(self.out, "    </trkseg>\n  </trk>"),
+            GpxMode::Route => writeln!(self.out, "  </rte>"),
+            GpxMode::Waypoints => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> TrackWriter for GpxWriter<W> {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        // routes and waypoints have no segments
+        if std::mem::take(&mut self.gap) && self.in_segment > 0 && self.mode == GpxMode::Track {
+            writeln!(self.out, "    </trkseg>\n    <trkseg>")?;
+            self.in_segment = 0;
+        }
+        self.in_segment += 1;
+
+        let (tag, indent) = match self.mode {
+            GpxMode::Track => ("trkpt", "      "),
+            GpxMode::Route => ("rtept", "    "),
+            GpxMode::Waypoints => ("wpt", "  "),
+        };
+        let mut children = String::new();
+        if let Some(time) = p.time(self.start) {
+            children += &format!("<time>{}</time>", super::format_time(time));
+        }
+        if self.mode == GpxMode::Waypoints {
+            children += &format!("<name>{}</name>", track::format_offset(p.offset_sec));
+        }
+        if let Some(photo) = self.photos.get(&p.frame_no) {
+            children += &format!(
+                r#"<link href="{}"><type>image/jpeg</type></link>"#,
+                escape(&photo.to_string_lossy())
+            );
+        }
+        // a track or route carries the tags once, waypoints each
+        let mut tags = match self.mode {
+            GpxMode::Waypoints => self.tags.clone(),
+            _ => String::new(),
+        };
+        tags += &track_point_extension(p);
+        children += &extensions(&tags);
+
+        let (lat, lon) = p.coordinate.lat_lon();
+        if children.is_empty() {
+            writeln!(
+                self.out,
+                r#"{}<{} lat="{:.6}" lon="{:.6}"/>"#,
+                indent, tag, lat, lon
+            )
+        } else {
+            writeln!(
+                self.out,
+                r#"{}<{} lat="{:.6}" lon="{:.6}">{}</{}>"#,
+                indent, tag, lat, lon, children, tag
+            )
+        }
+    }
+
+    fn gap(&mut self) -> std::io::Result<()> {
+        self.gap = true;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if !std::mem::replace(&mut self.finished, true) {
+            self.close()?;
+            writeln!(self.out, "</gpx>")?;
+        }
+        self.out.flush()
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// One `<dash2gps:meta>` per tag
+fn tags(meta: &BTreeMap<String, String>) -> String {
+    meta.iter()
+        .map(|(key, value)| {
+            format!(
+                r#"<dash2gps:meta key="{}">{}</dash2gps:meta>"#,
+                escape(key),
+                escape(value)
+            )
+        })
+        .collect()
+}
+
+/// Speed in m/s and course of a point, empty when the overlay shows neither
+fn track_point_extension(p: &TrackPoint) -> String {
+    let mut values = String::new();
+    if let Some(speed) = p.speed_kmh() {
+        values += &format!("<gpxtpx:speed>{:.2}</gpxtpx:speed>", speed / 3.6);
+    }
+    if let Some(heading) = p.readings.heading {
+        values += &format!("<gpxtpx:course>{:.1}</gpxtpx:course>", heading);
+    }
+    match values.is_empty() {
+        true => values,
+        false => format!(
+            "<gpxtpx:TrackPointExtension>{}</gpxtpx:TrackPointExtension>",
+            values
+        ),
+    }
+}
+
+fn extensions(children: &str) -> String {
+    match children.is_empty() {
+        true => String::new(),
+        false => format!("<extensions>{}</extensions>", children),
+    }
+}
+
+impl<W: Write> Drop for GpxWriter<W> {
+    fn drop(&mut self) {
+        _ = self.finish();
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{parse_overlay, parse_readings},
+        speed::{self, SpeedUnit},
+        track::FrameResult,
+    };
+
+    #[test]
+    fn segments_split_at_no_fix() {
+        let frames = [
+            "N51°25 48” E0°19 20” 30MPH",
+            "GPS: searching",
+            "N51°25 50” E0°19 30” 30MPH",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| FrameResult {
+            frame_no: i as u32 + 1,
+            pts_sec: None,
+            overlay: parse_overlay(*text),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        })
+        .collect();
+        let track = Track::from_frames(frames, 10);
+
+        let mut out = Vec::new();
+        let photos = BTreeMap::from([(3, PathBuf::from("photos/frame_000000003.jpg"))]);
+        write(&mut out, "a & b.mp4", GpxMode::Track, &[track], &photos).unwrap();
+        let gpx = String::from_utf8(out).unwrap();
+
+        assert_eq!(gpx.matches("<trkseg>").count(), 2);
+        assert_eq!(gpx.matches("<trkpt").count(), 2);
+        assert!(gpx.contains(r#"<trkpt lat="51.430000" lon="0.322222"/>"#));
+        assert!(gpx.contains("<name>a &amp; b.mp4</name>"));
+        assert!(gpx.contains(r#"<link href="photos/frame_000000003.jpg">"#));
+    }
+
+    #[test]
+    fn route_and_waypoints() {
+        let frames = [
+            "N51°25 48” E0°19 20”",
+            "GPS: searching",
+            "N51°25 50” E0°19 30”",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| FrameResult {
+            frame_no: i as u32 + 1,
+            pts_sec: None,
+            overlay: parse_overlay(*text),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        })
+        .collect();
+        let track = Track::from_frames(frames, 10);
+        let photos = BTreeMap::from([(3, PathBuf::from("frame_000000003.jpg"))]);
+        let gpx = |mode| {
+            let mut out = Vec::new();
+            write(
+                &mut out,
+                "clip.mp4",
+                mode,
+                std::slice::from_ref(&track),
+                &photos,
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let route = gpx(GpxMode::Route);
+        assert!(route.contains("  <rte>\n    <name>clip.mp4</name>\n"));
+        assert_eq!(route.matches("<rtept").count(), 2);
+        assert!(!route.contains("trk"));
+        assert!(route.ends_with("  </rte>\n</gpx>\n"));
+
+        let waypoints = gpx(GpxMode::Waypoints);
+        assert!(!waypoints.contains("<trk>") && !waypoints.contains("<rte>"));
+        assert!(waypoints
+            .contains(r#"  <wpt lat="51.430000" lon="0.322222"><name>00:00:00</name></wpt>"#));
+        assert!(waypoints.contains(r#"<name>00:00:20</name><link href="frame_000000003.jpg">"#));
+    }
+
+    #[test]
+    fn meta_in_extensions() {
+        let frames = vec![FrameResult {
+            frame_no: 1,
+            pts_sec: None,
+            overlay: parse_overlay("N51°25 48” E0°19 20”"),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        }];
+        let mut track = Track::from_frames(frames, 10);
+        track.meta = BTreeMap::from([("driver".to_string(), "a&b".to_string())]);
+        let gpx = |mode| {
+            let mut out = Vec::new();
+            write(
+                &mut out,
+                "clip.mp4",
+                mode,
+                std::slice::from_ref(&track),
+                &Default::default(),
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let extensions =
+            r#"<extensions><dash2gps:meta key="driver">a&amp;b</dash2gps:meta></extensions>"#;
+
+        let trk = gpx(GpxMode::Track);
+        assert!(trk.contains(r#"xmlns:dash2gps="https://github.com/mustakimali/dash2gps""#));
+        assert!(trk.contains(&format!("<name>clip.mp4</name>{}\n", extensions)));
+        assert_eq!(trk.matches("<extensions>").count(), 1);
+
+        let waypoints = gpx(GpxMode::Waypoints);
+        assert!(waypoints.contains(&format!("<name>00:00:00</name>{}</wpt>", extensions)));
+    }
+
+    #[test]
+    fn points_timed_from_start() {
+        let frames = ["N51°25 48” E0°19 20”", "N51°25 50” E0°19 30”"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| FrameResult {
+                frame_no: i as u32 + 1,
+                pts_sec: Some(i as f64 * 10.5),
+                overlay: parse_overlay(*text),
+                readings: Default::default(),
+                error: None,
+                confidence: None,
+            })
+            .collect();
+        let mut track = Track::from_frames(frames, 10);
+        let gpx = |track: &Track| {
+            let mut out = Vec::new();
+            write(
+                &mut out,
+                "clip.mp4",
+                GpxMode::Track,
+                std::slice::from_ref(track),
+                &Default::default(),
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert!(!gpx(&track).contains("<time>"));
+
+        track.start = chrono::NaiveDate::from_ymd_opt(2021, 6, 6)
+            .unwrap()
+            .and_hms_opt(12, 42, 29);
+        let timed = gpx(&track);
+        assert!(timed.contains(
+            r#"<trkpt lat="51.430000" lon="0.322222"><time>2021-06-06T12:42:29Z</time></trkpt>"#
+        ));
+        assert!(timed.contains("<time>2021-06-06T12:42:39.500Z</time>"));
+    }
+
+    #[test]
+    fn speed_and_course() {
+        let text = "N51°25 48” E0°19 20” 36KM/H HDG 270";
+        let frames = vec![FrameResult {
+            frame_no: 1,
+            pts_sec: None,
+            overlay: parse_overlay(text),
+            readings: parse_readings(text),
+            error: None,
+            confidence: None,
+        }];
+        let mut track = Track::from_frames(frames, 10);
+        speed::resolve_units(&mut track, None, SpeedUnit::Kmh);
+        track.meta = BTreeMap::from([("driver".to_string(), "alice".to_string())]);
+        let mut out = Vec::new();
+        write(
+            &mut out,
+            "clip.mp4",
+            GpxMode::Waypoints,
+            std::slice::from_ref(&track),
+            &Default::default(),
+        )
+        .unwrap();
+        let gpx = String::from_utf8(out).unwrap();
+
+        assert!(gpx.contains(&format!(r#"xmlns:gpxtpx="{}""#, TRACK_POINT_EXTENSION)));
+        // a single `<extensions>` with the tags and the readings
+        assert!(gpx.contains(
+            r#"<extensions><dash2gps:meta key="driver">alice</dash2gps:meta><gpxtpx:TrackPointExtension><gpxtpx:speed>10.00</gpxtpx:speed><gpxtpx:course>270.0</gpxtpx:course></gpxtpx:TrackPointExtension></extensions></wpt>"#
+        ));
+    }
+}
@@ -0,0 +1,27 @@
+use super::tiles::Layer;
+use crate::timeline::Timeline;
+
+const TEMPLATE: &str = include_str!("report.html");
+pub const LEAFLET_URL: &str = "https://unpkg.com/leaflet@1.9.4/dist";
+
+/// Self-contained HTML page with the trips on a map and their stats. The page
+/// loads Leaflet from `leaflet_url` and the tiles of `layer` when opened.
+pub fn write(
+    mut out: impl std::io::Write,
+    timeline: &Timeline,
+    layer: &Layer,
+    leaflet_url: &str,
+) -> anyhow::Result<()> {
+    // keep `</script>` in a file name from closing the script block
+    let data = serde_json::to_string(timeline)?.replace("</", "<\\/");
+    let tiles = serde_json::to_string(layer)?.replace("</", "<\\/");
+    let title = format!("Trips on {}", timeline.date);
+    let page = TEMPLATE
+        .replace("{{title}}", &title)
+        .replace("{{leaflet}}", leaflet_url.trim_end_matches('/'))
+        .replace("{{tiles}}", &tiles)
+        .replace("{{data}}", &data);
+    out.write_all(page.as_bytes())?;
+
+    Ok(())
+}
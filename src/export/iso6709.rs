@@ -0,0 +1,41 @@
+use super::template::Formatter;
+
+/// ISO 6709 Annex H point string, eg. `+51.4300+000.3222/`.
+///
+/// Altitude is in meters and is tagged with the WGS84 CRS as the standard
+/// requires once a height is present.
+pub fn format(lat: f64, lon: f64, altitude: Option<f64>) -> String {
+    match altitude {
+        Some(alt) => format!("{:+08.4}{:+09.4}{:+.1}CRSWGS_84/", lat, lon, alt),
+        None => format!("{:+08.4}{:+09.4}/", lat, lon),
+    }
+}
+
+/// `{iso6709}` placeholder, also used by `--format iso6709`
+pub struct Iso6709;
+
+impl Formatter for Iso6709 {
+    fn placeholder(&self) -> &'static str {
+        "iso6709"
+    }
+
+    fn format(&self, lat: f64, lon: f64) -> anyhow::Result<String> {
+        Ok(format(lat, lon, None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_strings() {
+        assert_eq!(format(51.43, 0.3222, None), "+51.4300+000.3222/");
+        assert_eq!(format(-33.8651, 151.2099, None), "-33.8651+151.2099/");
+        assert_eq!(format(5.5, -74.04, None), "+05.5000-074.0400/");
+        assert_eq!(
+            format(40.6894, -74.0447, Some(12.0)),
+            "+40.6894-074.0447+12.0CRSWGS_84/"
+        );
+    }
+}
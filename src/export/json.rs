@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    crs::Crs,
+    direction::Facing,
+    track::{Errors, Gap, Stats, Track},
+};
+
+/// JSON Schema of the document written by `--format json`
+pub const SCHEMA: &str = include_str!("schema.json");
+
+#[derive(Serialize)]
+pub struct Document {
+    pub trips: Vec<Trip>,
+}
+
+#[derive(Serialize)]
+pub struct Trip {
+    pub source: String,
+    /// Projection of `x`/`y`, only present with `--crs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Facing>,
+    /// `--meta` tags
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, String>,
+    pub points: Vec<Point>,
+    pub no_fix: Vec<Gap>,
+    pub stats: Stats,
+    /// Only present with `--road-types`
+    #[cfg(feature = "osm")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roads: Option<crate::roads::RoadTypes>,
+    /// Only present with `--speed-limits`
+    #[cfg(feature = "osm")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speeding: Option<Vec<crate::speeding::Event>>,
+    pub errors: Errors,
+}
+
+#[derive(Serialize)]
+pub struct Point {
+    pub frame_no: u32,
+    pub offset_sec: f64,
+    pub lat: f32,
+    pub lon: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_kmh: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f32>,
+    /// Clock printed on the overlay, see [`super::format_clock`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlay_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accel_peak: Option<f32>,
+}
+
+impl Trip {
+    pub fn new(source: impl Into<String>, track: &Track, crs: Crs) -> Self {
+        Self {
+            source: source.into(),
+            crs: crs.is_projected().then(|| crs.to_string()),
+            camera: track.facing,
+            meta: track.meta.clone(),
+            points: track
+                .points
+                .iter()
+                .map(|p| {
+                    let (lat, lon) = p.coordinate.lat_lon();
+                    let projected = crs
+                        .is_projected()
+                        .then(|| crs.project(lat as f64, lon as f64));
+                    Point {
+                        frame_no: p.frame_no,
+                        offset_sec: p.offset_sec,
+                        lat,
+                        lon,
+                        x: projected.map(|p| p.0),
+                        y: projected.map(|p| p.1),
+                        speed_kmh: p.speed_kmh(),
+                        heading: p.readings.heading,
+                        overlay_time: p.readings.time.map(super::format_clock),
+                        accel_peak: p.accel_peak,
+                    }
+                })
+                .collect(),
+            no_fix: track.no_fix.clone(),
+            stats: track.stats(),
+            #[cfg(feature = "osm")]
+            roads: track.roads.clone(),
+            #[cfg(feature = "osm")]
+            speeding: track.speeding.clone(),
+            errors: track.errors.clone(),
+        }
+    }
+}
+
+pub fn write(out: impl std::io::Write, trips: Vec<Trip>) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(out, &Document { trips })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn schema_is_valid_json() {
+        let schema: serde_json::Value = serde_json::from_str(SCHEMA).unwrap();
+
+        assert_eq!(schema["required"][0], "trips");
+    }
+}
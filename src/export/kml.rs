@@ -0,0 +1,366 @@
+//! KML for Google Earth: each trip a `<gx:Track>` timing every point, or a
+//! plain line when the start of the video is not known, and optionally a
+//! `<gx:Tour>` flying along it. KMZ zips the document with the photos of the
+//! points, shown in the balloons of their placemarks.
+
+use std::{collections::BTreeMap, fmt::Write as _, io::Write, path::PathBuf};
+
+use anyhow::Context;
+
+use crate::{
+    direction,
+    track::{self, Track, TrackPoint},
+};
+
+/// Distance in metres the camera of the `<LookAt>` keeps from the point
+const LOOK_AT_RANGE: f64 = 500.0;
+/// Degrees from straight down the `<LookAt>` views the point from
+const LOOK_AT_TILT: f64 = 60.0;
+/// Seconds the tour takes from one point to the next
+const TOUR_STEP_SEC: f64 = 1.0;
+
+/// KML document of the trips. Points with a photo (`--photo-log`) get a
+/// placemark showing it, `tour` adds a flyover along the track.
+pub fn write(
+    mut out: impl Write,
+    source: &str,
+    trips: &[Track],
+    photos: &BTreeMap<u32, PathBuf>,
+    tour: bool,
+) -> anyhow::Result<()> {
+    let hrefs = photos
+        .iter()
+        .map(|(frame_no, path)| (*frame_no, path.to_string_lossy().to_string()))
+        .collect();
+    out.write_all(document(source, trips, &hrefs, tour).as_bytes())?;
+
+    Ok(())
+}
+
+/// KMZ archive of the KML document and the photos it shows, `doc.kml` first
+/// as Google Earth expects.
+pub fn write_kmz(
+    mut out: impl Write,
+    source: &str,
+    trips: &[Track],
+    photos: &BTreeMap<u32, PathBuf>,
+    tour: bool,
+) -> anyhow::Result<()> {
+    let mut hrefs = BTreeMap::new();
+    let mut files = Vec::new();
+    for (frame_no, path) in photos {
+        let name = format!("images/frame_{:09}.jpg", frame_no);
+        let content = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        hrefs.insert(*frame_no, name.clone());
+        files.push((name, content));
+    }
+    let kml = document(source, trips, &hrefs, tour);
+    files.insert(0, ("doc.kml".to_string(), kml.into_bytes()));
+    out.write_all(&zip(&files))?;
+
+    Ok(())
+}
+
+fn document(source: &str, trips: &[Track], photos: &BTreeMap<u32, String>, tour: bool) -> String {
+    let trip_name = |i: usize| match trips.len() {
+        1 => source.to_string(),
+        _ => format!("{} trip {}", source, i + 1),
+    };
+    let mut kml = String::new();
+    kml += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    kml += "<kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n";
+    _ = writeln!(kml, "  <Document>\n    <name>{}</name>", escape(source));
+    let points = trips.iter().flat_map(|t| &t.points).collect::<Vec<_>>();
+    if let Some(first) = points.first() {
+        _ = writeln!(kml, "    {}", look_at(first, heading(&points, 0)));
+    }
+
+    for (i, track) in trips.iter().enumerate() {
+        _ = writeln!(
+            kml,
+            "    <Placemark>\n      <name>{}</name>",
+            escape(&trip_name(i))
+        );
+        kml += &extended_data(&track.meta);
+        kml += &geometry(track);
+        kml += "    </Placemark>\n";
+    }
+    for p in points.iter().filter(|p| photos.contains_key(&p.frame_no)) {
+        let (lat, lon) = p.coordinate.lat_lon();
+        _ = writeln!(
+            kml,
+            "    <Placemark>\n      <name>{}</name>\n      <description><![CDATA[<img src=\"{}\" width=\"640\"/>]]></description>",
+            track::format_offset(p.offset_sec),
+            escape(&photos[&p.frame_no])
+        );
+        if let Some(time) = p.time(trips[0].start) {
+            _ = writeln!(
+                kml,
+                "      <TimeStamp><when>{}</when></TimeStamp>",
+                super::format_time(time)
+            );
+        }
+        _ = writeln!(
+            kml,
+            "      <Point><coordinates>{:.6},{:.6}</coordinates></Point>\n    </Placemark>",
+            lon, lat
+        );
+    }
+    if tour && !points.is_empty() {
+        kml += "    <gx:Tour>\n      <name>Flyover</name>\n      <gx:Playlist>\n";
+        for (i, p) in points.iter().enumerate() {
+            _ = writeln!(
+                kml,
+                "        <gx:FlyTo><gx:duration>{}</gx:duration><gx:flyToMode>smooth</gx:flyToMode>{}</gx:FlyTo>",
+                TOUR_STEP_SEC,
+                look_at(p, heading(&points, i))
+            );
+        }
+        kml += "      </gx:Playlist>\n    </gx:Tour>\n";
+    }
+    kml += "  </Document>\n</kml>\n";
+
+    kml
+}
+
+/// A `<gx:Track>` per segment when the points can be timed, a `<LineString>`
+/// per segment otherwise.
+fn geometry(track: &Track) -> String {
+    let segments = track.segments();
+    let timed = track.points.iter().all(|p| p.time(track.start).is_some());
+    let mut kml = String::new();
+    match timed {
+        true => {
+            kml += "      <gx:MultiTrack>\n        <gx:interpolate>0</gx:interpolate>\n";
+            for segment in segments {
+                kml += "        <gx:Track>\n";
+                for p in segment {
+                    if let Some(time) = p.time(track.start) {
+                        _ = writeln!(kml, "          <when>{}</when>", super::format_time(time));
+                    }
+                }
+                for p in segment {
+                    let (lat, lon) = p.coordinate.lat_lon();
+                    _ = writeln!(
+                        kml,
+                        "          <gx:coord>{:.6} {:.6} 0</gx:coord>",
+                        lon, lat
+                    );
+                }
+                kml += "        </gx:Track>\n";
+            }
+            kml += "      </gx:MultiTrack>\n";
+        }
+        false => {
+            kml += "      <MultiGeometry>\n";
+            for segment in segments {
+                let coordinates = segment
+                    .iter()
+                    .map(|p| {
+                        let (lat, lon) = p.coordinate.lat_lon();
+                        format!("{:.6},{:.6}", lon, lat)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                _ = writeln!(
+                    kml,
+                    "        <LineString><tessellate>1</tessellate><coordinates>{}</coordinates></LineString>",
+                    coordinates
+                );
+            }
+            kml += "      </MultiGeometry>\n";
+        }
+    }
+
+    kml
+}
+
+/// Heading at the `i`th point: the one printed on the overlay, or towards the
+/// next point (from the previous one at the end)
+fn heading(points: &[&TrackPoint], i: usize) -> f64 {
+    if let Some(heading) = points[i].readings.heading {
+        return heading as f64;
+    }
+    let (a, b) = match (i.checked_sub(1), points.get(i + 1)) {
+        (_, Some(next)) => (points[i], *next),
+        (Some(prev), None) => (points[prev], points[i]),
+        (None, None) => return 0.0,
+    };
+
+    direction::bearing(a.coordinate.lat_lon(), b.coordinate.lat_lon())
+}
+
+fn look_at(p: &TrackPoint, heading: f64) -> String {
+    let (lat, lon) = p.coordinate.lat_lon();
+    format!(
+        "<LookAt><longitude>{:.6}</longitude><latitude>{:.6}</latitude><heading>{:.1}</heading><tilt>{}</tilt><range>{}</range></LookAt>",
+        lon, lat, heading, LOOK_AT_TILT, LOOK_AT_RANGE
+    )
+}
+
+/// `<ExtendedData>` with one `<Data>` per `--meta` tag, empty without tags
+fn extended_data(meta: &BTreeMap<String, String>) -> String {
+    if meta.is_empty() {
+        return String::new();
+    }
+    let mut kml = String::from("      <ExtendedData>\n");
+    for (key, value) in meta {
+        _ = writeln!(
+            kml,
+            "        <Data name=\"{}\"><value>{}</value></Data>",
+            escape(key),
+            escape(value)
+        );
+    }
+    kml += "      </ExtendedData>\n";
+
+    kml
+}
+
+/// ZIP archive of the files, stored uncompressed: the photos are JPEG already
+fn zip(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    // 1980-01-01 00:00 in MS-DOS format, the earliest a ZIP can tell
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    for (name, content) in files {
+        let offset = out.len() as u32;
+        let crc = crc32fast::hash(content);
+        let fields = |v: &mut Vec<u8>| {
+            v.extend(20u16.to_le_bytes()); // version needed
+            v.extend(0u16.to_le_bytes()); // flags
+            v.extend(0u16.to_le_bytes()); // stored
+            v.extend(DOS_TIME.to_le_bytes());
+            v.extend(DOS_DATE.to_le_bytes());
+            v.extend(crc.to_le_bytes());
+            v.extend((content.len() as u32).to_le_bytes());
+            v.extend((content.len() as u32).to_le_bytes());
+            v.extend((name.len() as u16).to_le_bytes());
+            v.extend(0u16.to_le_bytes()); // extra field
+        };
+
+        out.extend(0x04034b50u32.to_le_bytes());
+        fields(&mut out);
+        out.extend(name.as_bytes());
+        out.extend(content);
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes()); // version made by
+        fields(&mut directory);
+        directory.extend(0u16.to_le_bytes()); // comment
+        directory.extend(0u16.to_le_bytes()); // disk
+        directory.extend(0u16.to_le_bytes()); // internal attributes
+        directory.extend(0u32.to_le_bytes()); // external attributes
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+
+    let directory_offset = out.len() as u32;
+    out.extend(&directory);
+    out.extend(0x06054b50u32.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // disk
+    out.extend(0u16.to_le_bytes()); // disk of the directory
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((files.len() as u16).to_le_bytes());
+    out.extend((directory.len() as u32).to_le_bytes());
+    out.extend(directory_offset.to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // comment
+
+    out
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::parse_overlay, track::FrameResult};
+
+    fn track() -> Track {
+        let frames = [
+            "N51°25 48” E0°19 20”",
+            "N51°25 50” E0°19 30”",
+            "GPS: searching",
+            "N51°25 52” E0°19 40”",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| FrameResult {
+            frame_no: i as u32 + 1,
+            pts_sec: None,
+            overlay: parse_overlay(*text),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        })
+        .collect();
+
+        Track::from_frames(frames, 10)
+    }
+
+    #[test]
+    fn timed_track_and_tour() {
+        let mut track = track();
+        track.start = chrono::NaiveDate::from_ymd_opt(2021, 6, 6)
+            .unwrap()
+            .and_hms_opt(12, 42, 29);
+        track.meta = BTreeMap::from([("driver".to_string(), "a&b".to_string())]);
+        let photos = BTreeMap::from([(2, PathBuf::from("photos/frame_000000002.jpg"))]);
+        let mut out = Vec::new();
+        write(&mut out, "clip.mp4", &[track], &photos, true).unwrap();
+        let kml = String::from_utf8(out).unwrap();
+
+        assert_eq!(kml.matches("<gx:Track>").count(), 2);
+        assert!(kml.contains("<when>2021-06-06T12:42:29Z</when>"));
+        assert!(kml.contains("<gx:coord>0.322222 51.430000 0</gx:coord>"));
+        assert!(kml.contains(r#"<Data name="driver"><value>a&amp;b</value></Data>"#));
+        assert!(kml.contains(r#"<img src="photos/frame_000000002.jpg" width="640"/>"#));
+        assert!(kml.contains("<TimeStamp><when>2021-06-06T12:42:39Z</when></TimeStamp>"));
+        assert_eq!(kml.matches("<gx:FlyTo>").count(), 3);
+        assert!(kml.ends_with("  </Document>\n</kml>\n"));
+    }
+
+    #[test]
+    fn line_without_start() {
+        let mut out = Vec::new();
+        write(&mut out, "clip.mp4", &[track()], &Default::default(), false).unwrap();
+        let kml = String::from_utf8(out).unwrap();
+
+        assert!(!kml.contains("<gx:Track>") && !kml.contains("<gx:Tour>"));
+        assert_eq!(kml.matches("<LineString>").count(), 2);
+        assert!(kml.contains("<coordinates>0.322222,51.430000 0.325000,"));
+        // looking north-east along the track from the first point
+        assert!(kml.contains(
+            "<LookAt><longitude>0.322222</longitude><latitude>51.430000</latitude><heading>"
+        ));
+    }
+
+    #[test]
+    fn kmz_bundles_photos() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-kmz-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let photo = dir.join("frame_000000002.jpg");
+        std::fs::write(&photo, b"jpeg").unwrap();
+        let mut out = Vec::new();
+        let photos = BTreeMap::from([(2, photo)]);
+        write_kmz(&mut out, "clip.mp4", &[track()], &photos, false).unwrap();
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(&out[..4], b"PK\x03\x04");
+        assert_eq!(&out[30..37], b"doc.kml");
+        let kml = String::from_utf8_lossy(&out);
+        assert!(kml.contains(r#"<img src="images/frame_000000002.jpg" width="640"/>"#));
+        assert!(kml.contains("images/frame_000000002.jpgjpeg"));
+        // end of central directory with both entries
+        let end = &out[out.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+    }
+}
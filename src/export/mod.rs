@@ -0,0 +1,138 @@
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    crs::Crs,
+    track::{Track, TrackPoint},
+};
+
+pub mod geojson;
+pub mod gpkg;
+pub mod gpsbabel;
+pub mod gpx;
+pub mod html;
+pub mod iso6709;
+pub mod json;
+pub mod kml;
+pub mod record;
+#[cfg(feature = "shapefile")]
+pub mod shapefile;
+pub mod subtitles;
+pub mod template;
+pub mod text;
+pub mod tiles;
+#[cfg(feature = "what3words")]
+pub mod what3words;
+
+/// Output format of the recovered track
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// One line per point using `--output-format`, printed as frames are processed
+    Text,
+    /// A single JSON document with every trip, see `dash2gps schema`
+    Json,
+    /// GeoJSON FeatureCollection with the track line and every point
+    Geojson,
+    /// One ISO 6709 string per point, eg. `+51.4300+000.3222/`
+    Iso6709,
+    /// GPX 1.1 track
+    Gpx,
+    /// GeoPackage with `track` and `points` layers, needs `sqlite3`
+    Gpkg,
+    /// KML for Google Earth, the time of every point in a `<gx:Track>`
+    Kml,
+    /// KML zipped with a frame of the video every `--photo-every` points
+    Kmz,
+    /// CSV with a header line, one record per point as found, see the README
+    Csv,
+    /// One JSON object per line with the fields of `--format csv`
+    Jsonl,
+    /// SubRip subtitles with the position, speed and a map link, for video players
+    Srt,
+    /// WebVTT subtitles, as `srt`
+    Vtt,
+}
+
+impl Format {
+    /// Formats that print points as soon as they are recognised
+    pub fn is_streaming(&self) -> bool {
+        matches!(
+            self,
+            Format::Text | Format::Iso6709 | Format::Csv | Format::Jsonl
+        )
+    }
+}
+
+/// Formats [`document`] writes
+pub const DOCUMENT_FORMATS: &[&str] = &["gpx", "json", "geojson"];
+
+/// Track of `source` as a `gpx`, `json` or `geojson` document, for the C and
+/// WebAssembly APIs
+pub fn document(track: &Track, source: &str, format: &str) -> anyhow::Result<String> {
+    let mut out = Vec::new();
+    let trips = std::slice::from_ref(track);
+    match format {
+        "gpx" => gpx::write(
+            &mut out,
+            source,
+            gpx::GpxMode::Track,
+            trips,
+            &Default::default(),
+        )?,
+        "json" => json::write(
+            &mut out,
+            vec![json::Trip::new(source, track, Crs::default())],
+        )?,
+        "geojson" => geojson::write(&mut out, source, trips, Crs::default())?,
+        _ => anyhow::bail!("unknown format `{}`, expected gpx, json or geojson", format),
+    }
+
+    Ok(String::from_utf8(out)?)
+}
+
+/// Time of a point in GPX and KML, in UTC as both expect. Cameras are
+/// usually set to local time, which is written as is.
+pub fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()
+}
+
+/// Clock printed on the overlay, without a zone as the camera's is not known
+pub fn format_clock(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Writes a track one point at a time, so whatever was written is a valid file.
+/// Dropping the writer finishes it.
+pub trait TrackWriter {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()>;
+
+    /// An interval without GPS fix, the next point starts a new segment
+    fn gap(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Close the document and flush, writing more points afterwards is an error
+    fn finish(&mut self) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()>;
+}
+
+impl<T: TrackWriter + ?Sized> TrackWriter for Box<T> {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        (**self).point(p)
+    }
+
+    fn gap(&mut self) -> std::io::Result<()> {
+        (**self).gap()
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        (**self).finish()
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+}
@@ -0,0 +1,196 @@
+//! One record per point with a stable schema, as CSV with a header line or as
+//! JSON Lines. Fields are only ever added at the end, so scripts can rely on
+//! their names and order.
+
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use super::TrackWriter;
+use crate::{stream::Newline, track::TrackPoint};
+
+/// Fields of a record, in order
+pub const FIELDS: &[&str] = &[
+    "timestamp",
+    "lat",
+    "lon",
+    "speed_kmh",
+    "heading",
+    "frame_no",
+    "offset_sec",
+    "ocr_confidence",
+];
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    /// Time of the point, in the format of [`super::format_time`], `None` when
+    /// neither the start of the video nor the overlay clock is known
+    pub timestamp: Option<String>,
+    pub lat: f32,
+    pub lon: f32,
+    /// Speed printed on the overlay, once its unit is known
+    pub speed_kmh: Option<f32>,
+    /// Heading printed on the overlay, degrees clockwise from north
+    pub heading: Option<f32>,
+    pub frame_no: u32,
+    /// Seconds since the start of the video
+    pub offset_sec: f64,
+    /// OCR confidence (0-100), `None` when not read by OCR
+    pub ocr_confidence: Option<i32>,
+}
+
+impl Record {
+    pub fn new(p: &TrackPoint, start: Option<NaiveDateTime>) -> Self {
+        let (lat, lon) = p.coordinate.lat_lon();
+        Self {
+            timestamp: p.time(start).map(super::format_time),
+            lat,
+            lon,
+            speed_kmh: p.speed_kmh(),
+            heading: p.readings.heading,
+            frame_no: p.frame_no,
+            offset_sec: p.offset_sec,
+            ocr_confidence: p.confidence,
+        }
+    }
+
+    /// Line of the CSV, the fields in the order of [`FIELDS`]
+    fn csv(&self) -> String {
+        let or_empty = |v: Option<String>| v.unwrap_or_default();
+        [
+            or_empty(self.timestamp.clone()),
+            format!("{:.6}", self.lat),
+            format!("{:.6}", self.lon),
+            or_empty(self.speed_kmh.map(|v| format!("{:.1}", v))),
+            or_empty(self.heading.map(|v| format!("{:.1}", v))),
+            self.frame_no.to_string(),
+            format!("{:.3}", self.offset_sec),
+            or_empty(self.ocr_confidence.map(|v| v.to_string())),
+        ]
+        .join(",")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Csv,
+    Jsonl,
+}
+
+/// Writes a record per point, the CSV header before the first
+pub struct RecordWriter<W: Write> {
+    out: W,
+    encoding: Encoding,
+    /// Start of the video, to time the points
+    start: Option<NaiveDateTime>,
+    newline: &'static str,
+    header: bool,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(out: W, encoding: Encoding, start: Option<NaiveDateTime>, newline: Newline) -> Self {
+        Self {
+            out,
+            encoding,
+            start,
+            newline: newline.as_str(),
+            header: encoding == Encoding::Csv,
+        }
+    }
+}
+
+impl<W: Write> TrackWriter for RecordWriter<W> {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        if std::mem::take(&mut self.header) {
+            write!(self.out, "{}{}", FIELDS.join(","), self.newline)?;
+        }
+        let record = Record::new(p, self.start);
+        let line = match self.encoding {
+            Encoding::Csv => record.csv(),
+            Encoding::Jsonl => serde_json::to_string(&record)?,
+        };
+        write!(self.out, "{}{}", line, self.newline)
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{parse_overlay, parse_readings},
+        speed, stream,
+        track::{FrameResult, Track},
+    };
+
+    #[test]
+    fn csv_and_jsonl() {
+        let text = "N51°25 48” E0°19 20” 30KM/H HDG 270";
+        let mut track = Track::from_frames(
+            vec![
+                FrameResult {
+                    frame_no: 1,
+                    pts_sec: None,
+                    overlay: parse_overlay(text),
+                    readings: parse_readings(text),
+                    error: None,
+                    confidence: Some(91),
+                },
+                FrameResult {
+                    frame_no: 2,
+                    pts_sec: None,
+                    overlay: parse_overlay("N51°25 50” E0°19 30”"),
+                    readings: Default::default(),
+                    error: None,
+                    confidence: None,
+                },
+            ],
+            10,
+        );
+        speed::resolve_units(&mut track, None, speed::SpeedUnit::Kmh);
+        let start = chrono::NaiveDate::from_ymd_opt(2021, 6, 6)
+            .unwrap()
+            .and_hms_opt(12, 42, 29);
+        let write = |encoding| {
+            let mut out = Vec::new();
+            stream::write_all(
+                RecordWriter::new(&mut out, encoding, start, Newline::Lf),
+                std::slice::from_ref(&track),
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let csv = write(Encoding::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "timestamp,lat,lon,speed_kmh,heading,frame_no,offset_sec,ocr_confidence"
+        );
+        assert_eq!(
+            lines[1],
+            "2021-06-06T12:42:29Z,51.430000,0.322222,30.0,270.0,1,0.000,91"
+        );
+        assert!(lines[2].starts_with("2021-06-06T12:42:39Z,51.43"));
+        assert!(lines[2].ends_with(",,,2,10.000,"));
+
+        let jsonl = write(Encoding::Jsonl);
+        let first: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(
+            first.as_object().unwrap().keys().collect::<Vec<_>>().len(),
+            FIELDS.len()
+        );
+        assert_eq!(first["timestamp"], "2021-06-06T12:42:29Z");
+        assert_eq!(first["ocr_confidence"], 91);
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(jsonl.lines().nth(1).unwrap().contains(r#""heading":null"#));
+    }
+}
@@ -0,0 +1,380 @@
+//! ESRI Shapefiles: `<base>_points` with one point per location and
+//! `<base>_track` with the track as a polyline, one part per segment. Each
+//! layer is a `.shp`, `.shx`, `.dbf` and `.prj`, coordinates are WGS84.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::track::Track;
+
+const POINT: i32 = 1;
+const POLYLINE: i32 = 3;
+
+const WGS84_PRJ: &str = r#"GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+
+/// Write both layers next to `base`, returns the paths of the `.shp` files.
+/// Each trip is a record of the track layer.
+pub fn write(base: &Path, source: &str, trips: &[Track]) -> anyhow::Result<Vec<PathBuf>> {
+    let xy = |p: &crate::track::TrackPoint| {
+        let (lat, lon) = p.coordinate.lat_lon();
+        (lon as f64, lat as f64)
+    };
+    let points_of = || {
+        (1..)
+            .zip(trips)
+            .flat_map(|(trip, t)| t.points.iter().map(move |p| (trip, p)))
+    };
+
+    let meta = meta_fields(trips.first().map(|t| &t.meta).unwrap_or(&BTreeMap::new()));
+    let points = Layer {
+        shape_type: POINT,
+        shapes: points_of().map(|(_, p)| vec![vec![xy(p)]]).collect(),
+        fields: vec![
+            Field::numeric("TRIP", 4, 0),
+            Field::numeric("FRAME_NO", 10, 0),
+            Field::numeric("OFFSET_SEC", 12, 2),
+            Field::numeric("SPEED_KMH", 8, 1),
+            Field::numeric("HEADING", 6, 1),
+            Field::numeric("ACCEL_PEAK", 8, 3),
+        ],
+        records: points_of()
+            .map(|(trip, p)| {
+                vec![
+                    Some(trip.to_string()),
+                    Some(p.frame_no.to_string()),
+                    Some(format!("{:.2}", p.offset_sec)),
+                    p.speed_kmh().map(|v| format!("{:.1}", v)),
+                    p.readings.heading.map(|v| format!("{:.1}", v)),
+                    p.accel_peak.map(|v| format!("{:.3}", v)),
+                ]
+            })
+            .collect(),
+    }
+    .tagged(&meta);
+
+    let line = Layer {
+        shape_type: POLYLINE,
+        shapes: trips
+            .iter()
+            .map(|t| {
+                t.segments()
+                    .into_iter()
+                    .map(|s| s.iter().map(xy).collect())
+                    .collect()
+            })
+            .collect(),
+        fields: vec![
+            Field::numeric("TRIP", 4, 0),
+            Field::character("SOURCE", 254),
+            Field::character("CAMERA", 8),
+            Field::numeric("POINTS", 10, 0),
+        ],
+        records: (1..)
+            .zip(trips)
+            .map(|(trip, t)| {
+                let camera = t
+                    .facing
+                    .and_then(|f| serde_json::to_value(f).ok())
+                    .and_then(|v| v.as_str().map(str::to_string));
+                vec![
+                    Some(trip.to_string()),
+                    Some(source.to_string()),
+                    camera,
+                    Some(t.points.len().to_string()),
+                ]
+            })
+            .collect(),
+    }
+    .tagged(&meta);
+
+    let mut written = Vec::new();
+    for (suffix, layer) in [("points", points), ("track", line)] {
+        let name = format!(
+            "{}_{}",
+            base.file_name().unwrap_or_default().to_string_lossy(),
+            suffix
+        );
+        let path = base.with_file_name(name);
+        let (shp, shx) = layer.shp_shx();
+        for (ext, content) in [
+            ("shp", shp),
+            ("shx", shx),
+            ("dbf", layer.dbf()),
+            ("prj", WGS84_PRJ.as_bytes().to_vec()),
+        ] {
+            let file = path.with_extension(ext);
+            std::fs::write(&file, content).with_context(|| format!("write {}", file.display()))?;
+        }
+        written.push(path.with_extension("shp"));
+    }
+
+    Ok(written)
+}
+
+/// A character field per `--meta` tag, with its value. dBase field names are
+/// at most 10 characters, tags that end up with the same name keep the first.
+fn meta_fields(meta: &BTreeMap<String, String>) -> Vec<(Field, String)> {
+    let mut fields: Vec<(Field, String)> = Vec::new();
+    for (key, value) in meta {
+        let name = key
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_uppercase(),
+                false => '_',
+            })
+            .take(10)
+            .collect::<String>();
+        if name.is_empty() || fields.iter().any(|(f, _)| f.name == name) {
+            continue;
+        }
+        let length = value.len().clamp(1, 254) as u8;
+        fields.push((Field::character(&name, length), value.clone()));
+    }
+
+    fields
+}
+
+struct Field {
+    name: String,
+    kind: u8,
+    length: u8,
+    decimals: u8,
+}
+
+impl Field {
+    fn numeric(name: &str, length: u8, decimals: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: b'N',
+            length,
+            decimals,
+        }
+    }
+
+    fn character(name: &str, length: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: b'C',
+            length,
+            decimals: 0,
+        }
+    }
+}
+
+/// Shapes are lists of parts, which are lists of `(x, y)`.
+struct Layer {
+    shape_type: i32,
+    shapes: Vec<Vec<Vec<(f64, f64)>>>,
+    fields: Vec<Field>,
+    /// One value per field, `None` is written as blank
+    records: Vec<Vec<Option<String>>>,
+}
+
+impl Layer {
+    /// Add the `--meta` fields to every record, leaving out those named like
+    /// a field of the layer.
+    fn tagged(mut self, meta: &[(Field, String)]) -> Self {
+        for (field, value) in meta {
+            if self.fields.iter().any(|f| f.name == field.name) {
+                continue;
+            }
+            self.fields
+                .push(Field::character(&field.name, field.length));
+            for record in &mut self.records {
+                record.push(Some(value.clone()));
+            }
+        }
+
+        self
+    }
+
+    fn shp_shx(&self) -> (Vec<u8>, Vec<u8>) {
+        let contents = self
+            .shapes
+            .iter()
+            .map(|parts| self.content(parts))
+            .collect::<Vec<_>>();
+        let bounds = bounds(self.shapes.iter().flatten().flatten());
+
+        let shp_len = 100 + contents.iter().map(|c| 8 + c.len()).sum::<usize>();
+        let mut shp = self.header(shp_len, bounds);
+        let mut shx = self.header(100 + 8 * contents.len(), bounds);
+        for (i, content) in contents.iter().enumerate() {
+            shx.extend(((shp.len() / 2) as i32).to_be_bytes());
+            shx.extend(((content.len() / 2) as i32).to_be_bytes());
+            shp.extend((i as i32 + 1).to_be_bytes());
+            shp.extend(((content.len() / 2) as i32).to_be_bytes());
+            shp.extend(content);
+        }
+
+        (shp, shx)
+    }
+
+    /// The 100 bytes header shared by `.shp` and `.shx`, lengths are in
+    /// 16-bit words.
+    fn header(&self, len: usize, bounds: [f64; 4]) -> Vec<u8> {
+        let mut h = Vec::with_capacity(len);
+        h.extend(9994i32.to_be_bytes());
+        h.extend([0; 20]);
+        h.extend(((len / 2) as i32).to_be_bytes());
+        h.extend(1000i32.to_le_bytes());
+        h.extend(self.shape_type.to_le_bytes());
+        for v in bounds {
+            h.extend(v.to_le_bytes());
+        }
+        // z and m ranges
+        h.extend([0; 32]);
+        h
+    }
+
+    fn content(&self, parts: &[Vec<(f64, f64)>]) -> Vec<u8> {
+        if parts.is_empty() {
+            // null shape
+            return 0i32.to_le_bytes().to_vec();
+        }
+        let mut c = self.shape_type.to_le_bytes().to_vec();
+        if self.shape_type == POINT {
+            let (x, y) = parts[0][0];
+            c.extend(x.to_le_bytes());
+            c.extend(y.to_le_bytes());
+            return c;
+        }
+
+        for v in bounds(parts.iter().flatten()) {
+            c.extend(v.to_le_bytes());
+        }
+        c.extend((parts.len() as i32).to_le_bytes());
+        c.extend((parts.iter().map(Vec::len).sum::<usize>() as i32).to_le_bytes());
+        let mut start = 0;
+        for part in parts {
+            c.extend((start as i32).to_le_bytes());
+            start += part.len();
+        }
+        for (x, y) in parts.iter().flatten() {
+            c.extend(x.to_le_bytes());
+            c.extend(y.to_le_bytes());
+        }
+        c
+    }
+
+    /// dBase III table with the attributes
+    fn dbf(&self) -> Vec<u8> {
+        let header_len = 32 + 32 * self.fields.len() + 1;
+        let record_len = 1 + self.fields.iter().map(|f| f.length as usize).sum::<usize>();
+        let today = crate::clock::now().date_naive();
+
+        let mut d = vec![
+            0x03,
+            (chrono::Datelike::year(&today) - 1900) as u8,
+            chrono::Datelike::month(&today) as u8,
+            chrono::Datelike::day(&today) as u8,
+        ];
+        d.extend((self.records.len() as u32).to_le_bytes());
+        d.extend((header_len as u16).to_le_bytes());
+        d.extend((record_len as u16).to_le_bytes());
+        d.extend([0; 20]);
+        for f in &self.fields {
+            let mut name = [0u8; 11];
+            name[..f.name.len()].copy_from_slice(f.name.as_bytes());
+            d.extend(name);
+            d.push(f.kind);
+            d.extend([0; 4]);
+            d.push(f.length);
+            d.push(f.decimals);
+            d.extend([0; 14]);
+        }
+        d.push(0x0d);
+
+        for record in &self.records {
+            d.push(b' ');
+            for (f, value) in self.fields.iter().zip(record) {
+                let width = f.length as usize;
+                let value = value.as_deref().unwrap_or("");
+                // dBase is single byte, anything else would shift the columns
+                let value = value
+                    .chars()
+                    .map(|c| if c.is_ascii() { c } else { '?' })
+                    .take(width)
+                    .collect::<String>();
+                let cell = match f.kind {
+                    b'N' => format!("{:>width$}", value),
+                    _ => format!("{:<width$}", value),
+                };
+                d.extend(cell.as_bytes());
+            }
+        }
+        d.push(0x1a);
+
+        d
+    }
+}
+
+fn bounds<'a>(points: impl Iterator<Item = &'a (f64, f64)>) -> [f64; 4] {
+    points
+        .fold(None, |b: Option<[f64; 4]>, &(x, y)| {
+            Some(match b {
+                None => [x, y, x, y],
+                Some([min_x, min_y, max_x, max_y]) => {
+                    [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+                }
+            })
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn polyline_layer() {
+        let layer = Layer {
+            shape_type: POLYLINE,
+            shapes: vec![vec![vec![(0.0, 51.0), (0.1, 51.1)], vec![(0.2, 51.2)]]],
+            fields: vec![
+                Field::character("SOURCE", 8),
+                Field::numeric("POINTS", 4, 0),
+            ],
+            records: vec![vec![Some("clip.mp4".into()), Some("3".into())]],
+        };
+
+        let (shp, shx) = layer.shp_shx();
+        // header, record header, type + box + counts + 2 part starts + 3 points
+        let content_len = 4 + 32 + 8 + 8 + 3 * 16;
+        assert_eq!(shp.len(), 100 + 8 + content_len);
+        assert_eq!(&shp[24..28], &((shp.len() / 2) as i32).to_be_bytes());
+        assert_eq!(&shp[32..36], &3i32.to_le_bytes());
+        assert_eq!(&shp[36..44], &0.0f64.to_le_bytes());
+        assert_eq!(&shp[60..68], &51.2f64.to_le_bytes());
+        assert_eq!(shx.len(), 108);
+        assert_eq!(&shx[100..104], &50i32.to_be_bytes());
+
+        let dbf = layer.dbf();
+        assert_eq!(&dbf[4..8], &1u32.to_le_bytes());
+        let header_len = 32 + 2 * 32 + 1;
+        assert_eq!(&dbf[header_len..], b" clip.mp4   3\x1a");
+    }
+
+    #[test]
+    fn meta_fields_on_every_record() {
+        let layer = Layer {
+            shape_type: POINT,
+            shapes: vec![vec![vec![(0.0, 51.0)]], vec![vec![(0.1, 51.1)]]],
+            fields: vec![Field::numeric("FRAME_NO", 2, 0)],
+            records: vec![vec![Some("1".into())], vec![Some("2".into())]],
+        };
+        let meta = [
+            (Field::character("VEHICLE", 5), "VAN12".to_string()),
+            (Field::character("FRAME_NO", 1), "x".to_string()),
+        ];
+
+        let dbf = layer.tagged(&meta).dbf();
+        assert_eq!(&dbf[32 + 32..32 + 32 + 7], b"VEHICLE");
+        let header_len = 32 + 2 * 32 + 1;
+        assert_eq!(&dbf[header_len..], b"  1VAN12  2VAN12\x1a");
+    }
+}
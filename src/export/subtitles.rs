@@ -0,0 +1,166 @@
+//! SubRip and WebVTT subtitles showing the position, speed and a map link while
+//! the video plays, eg. in VLC which loads `clip.srt` next to `clip.mp4`.
+
+use std::io::Write;
+
+use crate::track::{Track, TrackPoint};
+
+const SHORTLINK_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_~";
+/// Zoom of the map links, streets are readable
+const LINK_ZOOM: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Srt,
+    Vtt,
+}
+
+/// A cue per point, shown until the next point or for the usual interval
+/// between points when the next one is further.
+pub fn write(mut out: impl Write, trips: &[Track], kind: Kind) -> anyhow::Result<()> {
+    let points = trips.iter().flat_map(|t| &t.points).collect::<Vec<_>>();
+    let step = points
+        .windows(2)
+        .map(|w| w[1].offset_sec - w[0].offset_sec)
+        .filter(|d| *d > 0.0)
+        .fold(None, |min: Option<f64>, d| {
+            Some(min.map_or(d, |m| m.min(d)))
+        })
+        .unwrap_or(1.0);
+
+    if kind == Kind::Vtt {
+        write!(out, "WEBVTT\n\n")?;
+    }
+    for (i, p) in points.iter().enumerate() {
+        let end = points
+            .get(i + 1)
+            .map_or(f64::MAX, |next| next.offset_sec)
+            .min(p.offset_sec + step);
+        if kind == Kind::Srt {
+            writeln!(out, "{}", i + 1)?;
+        }
+        writeln!(
+            out,
+            "{} --> {}",
+            timestamp(p.offset_sec, kind),
+            timestamp(end, kind)
+        )?;
+        write!(out, "{}\n\n", cue(p))?;
+    }
+
+    Ok(out.flush()?)
+}
+
+fn cue(p: &TrackPoint) -> String {
+    let (lat, lon) = p.coordinate.lat_lon();
+    let mut position = format!("{:.5}, {:.5}", lat, lon);
+    if let Some(speed) = p.speed_kmh() {
+        position.push_str(&format!("  {:.0} km/h", speed));
+    }
+    format!("{}\n{}", position, map_link(lat as f64, lon as f64))
+}
+
+/// `HH:MM:SS,mmm`, with a `.` before the milliseconds in WebVTT
+fn timestamp(offset_sec: f64, kind: Kind) -> String {
+    let ms = (offset_sec.max(0.0) * 1000.0).round() as u64;
+    let separator = match kind {
+        Kind::Srt => ',',
+        Kind::Vtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+/// OpenStreetMap short link with a marker, eg. `https://osm.org/go/0EEOmYmV?m`
+fn map_link(lat: f64, lon: f64) -> String {
+    format!("https://osm.org/go/{}?m", shortlink(lat, lon, LINK_ZOOM))
+}
+
+/// The code of an OpenStreetMap short link: the bits of the x and y of the
+/// position interleaved, 6 at a time, with a `-` per zoom level in between.
+fn shortlink(lat: f64, lon: f64, zoom: u32) -> String {
+    let x = ((lon + 180.0) * (1u64 << 32) as f64 / 360.0).round() as u64 as u32;
+    let y = ((lat + 90.0) * (1u64 << 32) as f64 / 180.0).round() as u64 as u32;
+    let code = (0..32).rev().fold(0u64, |code, bit| {
+        let code = code << 1 | (x >> bit & 1) as u64;
+        code << 1 | (y >> bit & 1) as u64
+    });
+    let digits = (zoom + 8).div_ceil(3).min(10);
+    let mut link = (0..digits)
+        .map(|i| SHORTLINK_CHARS[(code >> (58 - 6 * i) & 63) as usize] as char)
+        .collect::<String>();
+    link.extend(std::iter::repeat_n('-', ((zoom + 8) % 3) as usize));
+    link
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{parse_overlay, parse_readings},
+        speed::{self, SpeedUnit},
+        track::FrameResult,
+    };
+
+    fn track() -> Track {
+        let frames = [
+            "N51°25 48” E0°19 20” 30KM/H",
+            "N51°25 49” E0°19 25”",
+            "",
+            "N51°25 50” E0°19 30”",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| FrameResult {
+            frame_no: i as u32 + 1,
+            pts_sec: None,
+            overlay: parse_overlay(text),
+            readings: parse_readings(text),
+            error: None,
+            confidence: None,
+        })
+        .collect();
+        let mut track = Track::from_frames(frames, 10);
+        speed::resolve_units(&mut track, None, SpeedUnit::Kmh);
+        track
+    }
+
+    #[test]
+    fn srt() {
+        let mut out = Vec::new();
+        write(&mut out, &[track()], Kind::Srt).unwrap();
+        let srt = String::from_utf8(out).unwrap();
+        assert!(srt.starts_with(
+            "1\n00:00:00,000 --> 00:00:10,000\n51.43000, 0.32222  30 km/h\nhttps://osm.org/go/0EEOmYmV?m\n\n"
+        ));
+        // the frame before was not read, the cue is as long as the others
+        assert!(srt.contains("\n\n3\n00:00:30,000 --> 00:00:40,000\n51.43056, 0.32500\n"));
+    }
+
+    #[test]
+    fn vtt() {
+        let mut out = Vec::new();
+        write(&mut out, &[track()], Kind::Vtt).unwrap();
+        let vtt = String::from_utf8(out).unwrap();
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:10.000\n"));
+        assert_eq!(timestamp(3725.5, Kind::Vtt), "01:02:05.500");
+    }
+
+    #[test]
+    fn shortlinks() {
+        assert_eq!(shortlink(0.0, 0.0, 0), "wAA--");
+        assert_eq!(shortlink(51.5, -0.1, 16).len(), 8);
+        // links to nearby places share a prefix
+        assert_eq!(
+            shortlink(51.43, 0.3222, 10)[..3],
+            shortlink(51.431, 0.3223, 16)[..3]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{crs::Crs, parser::Coordinate};
+
+/// Renders a `{placeholder}` of the `--output-format` template for a point.
+pub trait Formatter: Send + Sync {
+    /// Name used in the template, without braces
+    fn placeholder(&self) -> &'static str;
+
+    fn format(&self, lat: f64, lon: f64) -> anyhow::Result<String>;
+}
+
+/// Placeholders of the `--output-format` template, with the feature they need
+/// and whether it is built in
+const PLACEHOLDERS: &[(&str, Option<(&str, bool)>)] = &[
+    ("lat", None),
+    ("lon", None),
+    ("x", None),
+    ("y", None),
+    ("iso6709", None),
+    ("osgr", Some(("crs", cfg!(feature = "crs")))),
+    ("w3w", Some(("what3words", cfg!(feature = "what3words")))),
+];
+
+/// Check every `{placeholder}` of an `--output-format` template is known and
+/// available in this build.
+pub fn parse(template: &str) -> Result<String, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        let name = match rest[start..]
+            .strip_prefix('{')
+            .and_then(|r| r.split_once('}'))
+        {
+            Some((name, after)) if !name.contains('{') => {
+                rest = after;
+                name
+            }
+            _ => return Err(format!("unmatched brace at `{}`", &rest[start..])),
+        };
+        match PLACEHOLDERS.iter().find(|(p, _)| *p == name) {
+            None => {
+                let known = PLACEHOLDERS
+                    .iter()
+                    .map(|(p, _)| format!("{{{}}}", p))
+                    .collect::<Vec<_>>();
+                return Err(format!(
+                    "unknown placeholder {{{}}}, one of {}",
+                    name,
+                    known.join(", ")
+                ));
+            }
+            Some((_, Some((feature, false)))) => {
+                return Err(format!(
+                    "{{{}}} needs building with `--features {}`",
+                    name, feature
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(template.to_string())
+}
+
+/// The `--output-format` template with the formatters it uses.
+///
+/// `{lat}`/`{lon}` are always WGS84, `{x}`/`{y}` are in the `--crs` projection.
+pub struct Template {
+    template: String,
+    formatters: Vec<Box<dyn Formatter>>,
+}
+
+impl Template {
+    pub fn new(template: impl Into<String>, crs: Crs) -> Self {
+        let mut t = Self {
+            template: template.into(),
+            formatters: Vec::new(),
+        };
+        t.register(Projected { crs, axis: Axis::X });
+        t.register(Projected { crs, axis: Axis::Y });
+        t.register(super::iso6709::Iso6709);
+        #[cfg(feature = "crs")]
+        t.register(GridReference);
+
+        t
+    }
+
+    /// Add a formatter, it is only kept when the template references it
+    pub fn register(&mut self, formatter: impl Formatter + 'static) {
+        if self
+            .template
+            .contains(&format!("{{{}}}", formatter.placeholder()))
+        {
+            self.formatters.push(Box::new(formatter));
+        }
+    }
+
+    pub fn render(&self, coordinate: &Coordinate) -> String {
+        let mut out = coordinate.to_decimal_with_format(&self.template);
+        let (lat, lon) = coordinate.lat_lon();
+
+        for f in &self.formatters {
+            let value = f.format(lat as f64, lon as f64).unwrap_or_else(|e| {
+                eprintln!("Error: {} ({{{}}})", e, f.placeholder());
+                String::new()
+            });
+            out = out.replace(&format!("{{{}}}", f.placeholder()), &value);
+        }
+
+        out
+    }
+}
+
+enum Axis {
+    X,
+    Y,
+}
+
+struct Projected {
+    crs: Crs,
+    axis: Axis,
+}
+
+impl Formatter for Projected {
+    fn placeholder(&self) -> &'static str {
+        match self.axis {
+            Axis::X => "x",
+            Axis::Y => "y",
+        }
+    }
+
+    fn format(&self, lat: f64, lon: f64) -> anyhow::Result<String> {
+        let (x, y) = self.crs.project(lat, lon);
+        let v = match self.axis {
+            Axis::X => x,
+            Axis::Y => y,
+        };
+
+        // projected coordinates are in meters, a millimeter is plenty
+        Ok(if self.crs.is_projected() {
+            format!("{:.3}", v)
+        } else {
+            v.to_string()
+        })
+    }
+}
+
+/// Ordnance Survey grid reference, eg. `TQ 30047 79951`
+#[cfg(feature = "crs")]
+struct GridReference;
+
+#[cfg(feature = "crs")]
+impl Formatter for GridReference {
+    fn placeholder(&self) -> &'static str {
+        "osgr"
+    }
+
+    fn format(&self, lat: f64, lon: f64) -> anyhow::Result<String> {
+        let (e, n) = Crs::BritishNationalGrid.project(lat, lon);
+
+        crate::crs::grid_reference(e, n)
+            .ok_or_else(|| anyhow::anyhow!("{},{} is outside the National Grid", lat, lon))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placeholders_checked() {
+        assert_eq!(parse("{lat},{lon}").as_deref(), Ok("{lat},{lon}"));
+        assert!(parse("{x} {y} {iso6709}").is_ok());
+        assert!(parse("no placeholders").is_ok());
+        assert!(parse("{lat},{lng}")
+            .unwrap_err()
+            .contains("unknown placeholder {lng}"));
+        assert!(parse("{lat").unwrap_err().contains("unmatched"));
+        assert!(parse("lat}").unwrap_err().contains("unmatched"));
+        assert!(parse("{{lat}").is_err());
+        assert_eq!(parse("{osgr}").is_ok(), cfg!(feature = "crs"));
+    }
+}
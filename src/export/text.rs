@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use super::{template::Template, TrackWriter};
+use crate::{stream::Newline, track::TrackPoint};
+
+/// One line per point rendered with `--output-format`
+pub struct TextWriter<W: Write> {
+    out: W,
+    template: Template,
+    newline: &'static str,
+}
+
+impl<W: Write> TextWriter<W> {
+    pub fn new(out: W, template: Template, newline: Newline) -> Self {
+        Self {
+            out,
+            template,
+            newline: newline.as_str(),
+        }
+    }
+}
+
+impl<W: Write> TrackWriter for TextWriter<W> {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        write!(
+            self.out,
+            "{}{}",
+            self.template.render(&p.coordinate),
+            self.newline
+        )
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,321 @@
+//! Tiles of the map of the HTML report: OpenStreetMap, a self-hosted tile
+//! server, or a raster MBTiles file whose tiles around the trips are embedded
+//! in the page, so it can be made and viewed without internet access.
+
+use std::{
+    collections::BTreeMap,
+    f64::consts::PI,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+pub const OSM_URL: &str = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+/// Tiles embedded at most, the deepest zoom levels are left out past it
+const MAX_TILES: u64 = 1500;
+const MAX_ZOOM: u8 = 19;
+
+/// `--tiles`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tiles {
+    /// Tile URL template, eg. `http://tiles.local/{z}/{x}/{y}.png`
+    Url(String),
+    /// Raster MBTiles file
+    MbTiles(PathBuf),
+}
+
+impl FromStr for Tiles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains("{z}") && s.contains("{x}") && s.contains("{y}") {
+            return Ok(Tiles::Url(s.to_string()));
+        }
+        match Path::new(s).extension().and_then(|e| e.to_str()) {
+            Some("mbtiles") => Ok(Tiles::MbTiles(PathBuf::from(s))),
+            Some("pmtiles") => Err(
+                "PMTiles can't be embedded, serve them, eg. with `pmtiles serve`, and pass the tile URL"
+                    .to_string(),
+            ),
+            _ => Err(format!(
+                "expected a tile URL with {{z}}, {{x}} and {{y}} or an .mbtiles file, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// Tile layer of the page
+#[derive(Debug, Serialize)]
+pub struct Layer {
+    /// URL template, `None` when the tiles are embedded
+    pub url: Option<String>,
+    /// Data URI of every embedded tile by `z/x/y`
+    pub tiles: BTreeMap<String, String>,
+    /// Deepest zoom with tiles, the map is scaled up past it
+    pub max_zoom: u8,
+    pub attribution: String,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            url: Some(OSM_URL.to_string()),
+            tiles: BTreeMap::new(),
+            max_zoom: MAX_ZOOM,
+            attribution: "&copy; OpenStreetMap contributors".to_string(),
+        }
+    }
+}
+
+impl Layer {
+    /// Layer of `--tiles`, with the tiles around `points` (`[lat, lon]`) for an
+    /// MBTiles file.
+    pub fn new(tiles: Option<&Tiles>, points: &[(f32, f32)]) -> anyhow::Result<Self> {
+        match tiles {
+            None => Ok(Self::default()),
+            Some(Tiles::Url(url)) => Ok(Self {
+                url: Some(url.clone()),
+                attribution: String::new(),
+                ..Self::default()
+            }),
+            Some(Tiles::MbTiles(path)) => {
+                embed(path, points).with_context(|| format!("read tiles from {}", path.display()))
+            }
+        }
+    }
+}
+
+fn embed(path: &Path, points: &[(f32, f32)]) -> anyhow::Result<Layer> {
+    let metadata = sqlite(
+        path,
+        "SELECT name, value FROM metadata WHERE name IN ('format', 'minzoom', 'maxzoom', 'attribution');",
+    )?
+    .lines()
+    .filter_map(|l| l.split_once('|'))
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect::<BTreeMap<_, _>>();
+    let mime = match metadata.get("format").map_or("png", String::as_str) {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        other => anyhow::bail!(
+            "{} tiles are not supported, only raster png, jpg or webp",
+            other
+        ),
+    };
+    let zoom = |name, default| {
+        metadata
+            .get(name)
+            .and_then(|z| z.parse::<u8>().ok())
+            .unwrap_or(default)
+            .min(MAX_ZOOM)
+    };
+    let ranges = ranges(points, zoom("minzoom", 0), zoom("maxzoom", MAX_ZOOM));
+    let Some(max_zoom) = ranges.last().map(|r| r.zoom) else {
+        anyhow::bail!("no zoom levels");
+    };
+
+    let filter = ranges
+        .iter()
+        .map(|r| {
+            // MBTiles rows count from the south
+            let flip = |y: u32| (1u32 << r.zoom) - 1 - y;
+            format!(
+                "(zoom_level = {} AND tile_column BETWEEN {} AND {} AND tile_row BETWEEN {} AND {})",
+                r.zoom,
+                r.x.0,
+                r.x.1,
+                flip(r.y.1),
+                flip(r.y.0)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let rows = sqlite(
+        path,
+        &format!(
+            "SELECT zoom_level, tile_column, tile_row, hex(tile_data) FROM tiles WHERE {};",
+            filter
+        ),
+    )?;
+    let mut tiles = BTreeMap::new();
+    for row in rows.lines() {
+        let fields = row.split('|').collect::<Vec<_>>();
+        let [z, x, row, data] = fields[..] else {
+            continue;
+        };
+        let z = z.parse::<u8>()?;
+        let y = (1u32 << z) - 1 - row.parse::<u32>()?;
+        tiles.insert(
+            format!("{}/{}/{}", z, x, y),
+            format!("data:{};base64,{}", mime, base64(&unhex(data)?)),
+        );
+    }
+    eprintln!(
+        "Embedded {} map tiles, up to zoom {}",
+        tiles.len(),
+        max_zoom
+    );
+
+    Ok(Layer {
+        url: None,
+        tiles,
+        max_zoom,
+        attribution: metadata.get("attribution").cloned().unwrap_or_default(),
+    })
+}
+
+fn sqlite(path: &Path, query: &str) -> anyhow::Result<String> {
+    let output = Command::new("sqlite3")
+        .arg("-readonly")
+        .arg(path)
+        .arg(query)
+        .output()
+        .context("start sqlite3 to read the MBTiles, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Tiles covering some points at a zoom level, inclusive
+#[derive(Debug, PartialEq)]
+struct Range {
+    zoom: u8,
+    x: (u32, u32),
+    y: (u32, u32),
+}
+
+impl Range {
+    fn count(&self) -> u64 {
+        (self.x.1 - self.x.0 + 1) as u64 * (self.y.1 - self.y.0 + 1) as u64
+    }
+}
+
+/// Tiles around the points, with a tile of margin, from `min_zoom` to the
+/// deepest zoom that keeps them under [`MAX_TILES`]. The whole world without points.
+fn ranges(points: &[(f32, f32)], min_zoom: u8, max_zoom: u8) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    let mut count = 0;
+    for zoom in min_zoom..=max_zoom {
+        let last = (1u32 << zoom) - 1;
+        let range = match points.is_empty() {
+            true => Range {
+                zoom,
+                x: (0, last),
+                y: (0, last),
+            },
+            false => {
+                let tiles = points.iter().map(|&(lat, lon)| tile(lat, lon, zoom));
+                let (xs, ys): (Vec<_>, Vec<_>) = tiles.unzip();
+                let min = |v: &[u32]| v.iter().min().map_or(0, |m| m.saturating_sub(1));
+                let max = |v: &[u32]| v.iter().max().map_or(0, |m| (m + 1).min(last));
+                Range {
+                    zoom,
+                    x: (min(&xs), max(&xs)),
+                    y: (min(&ys), max(&ys)),
+                }
+            }
+        };
+        count += range.count();
+        if count > MAX_TILES && !ranges.is_empty() {
+            break;
+        }
+        ranges.push(range);
+    }
+    ranges
+}
+
+/// Web Mercator tile of a position
+fn tile(lat: f32, lon: f32, zoom: u8) -> (u32, u32) {
+    let n = (1u64 << zoom) as f64;
+    let lat = (lat as f64).clamp(-85.0511, 85.0511).to_radians();
+    let x = (lon as f64 + 180.0) / 360.0 * n;
+    let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * n;
+    let last = n as u32 - 1;
+    ((x as u32).min(last), (y.max(0.0) as u32).min(last))
+}
+
+fn unhex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .context("invalid tile data")
+        })
+        .collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "http://tiles.local/{z}/{x}/{y}.png".parse(),
+            Ok(Tiles::Url("http://tiles.local/{z}/{x}/{y}.png".to_string()))
+        );
+        assert_eq!(
+            "uk.mbtiles".parse(),
+            Ok(Tiles::MbTiles(PathBuf::from("uk.mbtiles")))
+        );
+        assert!("uk.pmtiles".parse::<Tiles>().unwrap_err().contains("serve"));
+        assert!("http://tiles.local".parse::<Tiles>().is_err());
+    }
+
+    #[test]
+    fn tiles_around_points() {
+        assert_eq!(tile(51.43, 0.3222, 0), (0, 0));
+        assert_eq!(tile(51.43, 0.3222, 10), (512, 340));
+
+        let ranges = ranges(&[(51.43, 0.3222), (51.5, 0.2)], 0, 19);
+        assert_eq!(
+            ranges[1],
+            Range {
+                zoom: 1,
+                x: (0, 1),
+                y: (0, 1)
+            }
+        );
+        assert!(ranges.iter().map(Range::count).sum::<u64>() <= MAX_TILES);
+        assert!(ranges.len() > 12, "{}", ranges.len());
+
+        let world = super::ranges(&[], 0, 19);
+        assert_eq!(world.last().unwrap().zoom, 5);
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(unhex("89504E47").unwrap(), b"\x89PNG");
+        assert!(unhex("8").is_err());
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+}
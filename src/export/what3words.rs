@@ -0,0 +1,46 @@
+use anyhow::Context;
+
+use super::template::Formatter;
+use crate::sink::network::NetworkSink;
+
+const API: &str = "https://api.what3words.com/v3/convert-to-3wa";
+
+/// Looks up the what3words address of a point, eg. `///filled.count.soap`
+pub struct What3Words {
+    key: String,
+    network: NetworkSink,
+}
+
+impl What3Words {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            network: NetworkSink::default(),
+        }
+    }
+}
+
+impl Formatter for What3Words {
+    fn placeholder(&self) -> &'static str {
+        "w3w"
+    }
+
+    fn format(&self, lat: f64, lon: f64) -> anyhow::Result<String> {
+        let response: serde_json::Value = self
+            .network
+            .call(|| {
+                Ok(ureq::get(API)
+                    .query("coordinates", &format!("{},{}", lat, lon))
+                    .query("key", &self.key)
+                    .call()?)
+            })
+            .context("call what3words API")?
+            .into_json()
+            .context("parse what3words response")?;
+
+        response["words"]
+            .as_str()
+            .map(|w| format!("///{}", w))
+            .ok_or_else(|| anyhow::anyhow!("what3words returned no address: {}", response))
+    }
+}
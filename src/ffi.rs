@@ -0,0 +1,234 @@
+//! C API (`--features ffi`), for bindings from Python, C# and other languages.
+//! Declared in `include/dash2gps.h`. Build the library with
+//! `cargo rustc --profile ffi --lib --features ffi --crate-type cdylib`, whose
+//! panics unwind so they are returned as errors.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_void, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use crate::{
+    export::{self, DOCUMENT_FORMATS},
+    pipeline::{Observer, PipelineConfig},
+    progress::Snapshot,
+};
+
+/// Called after every frame read: `user_data`, frames read, position in the
+/// video and its length in seconds (negative when not known).
+pub type ProgressFn = extern "C" fn(*mut c_void, u32, f64, f64);
+
+thread_local! {
+    /// Error of the last call on this thread that failed
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Track of the video at `path` as a `gpx`, `json` or `geojson` document,
+/// with the options of the command line in `args` (`argc` strings, eg.
+/// `"--interval"`, `"5"`). `progress` may be null. Returns null on error, see
+/// [`dash2gps_last_error`]; free the document with [`dash2gps_free_string`].
+///
+/// # Safety
+///
+/// `path` and `format` must be NUL-terminated strings, `args` an array of
+/// `argc` of them (or null when `argc` is 0). `progress` is called from worker
+/// threads, with `user_data` as given.
+#[no_mangle]
+pub unsafe extern "C" fn dash2gps_convert(
+    path: *const c_char,
+    format: *const c_char,
+    args: *const *const c_char,
+    argc: usize,
+    progress: Option<ProgressFn>,
+    user_data: *mut c_void,
+) -> *mut c_char {
+    document(|| {
+        let path = string(path, "path")?;
+        let format = string(format, "format")?;
+        if !DOCUMENT_FORMATS.contains(&format.as_str()) {
+            anyhow::bail!("unknown format `{}`, expected gpx, json or geojson", format);
+        }
+        let args = match argc {
+            0 => Vec::new(),
+            _ if args.is_null() => anyhow::bail!("args is null"),
+            _ => (0..argc)
+                .map(|i| string(*args.add(i), "argument"))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        };
+        let config = PipelineConfig::from_args(&args)?;
+        let mut pipeline = crate::Pipeline::new(config);
+        if let Some(callback) = progress {
+            pipeline = pipeline.with_observer(Progress {
+                callback,
+                user_data,
+            });
+        }
+
+        let track = crate::convert_with(&pipeline, Path::new(&path))?;
+        export::document(&track, &path, &format)
+    })
+}
+
+/// Document made by `make`, or null with the error set. A panic is an error
+/// too, rather than unwind into the caller.
+fn document(make: impl FnOnce() -> anyhow::Result<String>) -> *mut c_char {
+    let result = panic::catch_unwind(AssertUnwindSafe(make)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        Err(anyhow::anyhow!("dash2gps panicked: {}", message))
+    });
+
+    match result.and_then(|doc| Ok(CString::new(doc)?)) {
+        Ok(doc) => doc.into_raw(),
+        Err(e) => {
+            set_error(format!("{:#}", e));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Error of the last call on this thread that returned null, or null. Valid
+/// until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn dash2gps_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |e| e.as_ptr()))
+}
+
+/// Free a document returned by [`dash2gps_convert`].
+///
+/// # Safety
+///
+/// `s` must come from [`dash2gps_convert`], or be null, and not be used after.
+#[no_mangle]
+pub unsafe extern "C" fn dash2gps_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+unsafe fn string(s: *const c_char, name: &str) -> anyhow::Result<String> {
+    if s.is_null() {
+        anyhow::bail!("{} is null", name);
+    }
+    Ok(CStr::from_ptr(s).to_str()?.to_string())
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("no NUL left");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Passes the progress to the C callback
+struct Progress {
+    callback: ProgressFn,
+    user_data: *mut c_void,
+}
+
+// SAFETY: the caller is told the callback is called from worker threads
+unsafe impl Send for Progress {}
+unsafe impl Sync for Progress {}
+
+impl Observer for Progress {
+    fn on_progress(&self, progress: &Snapshot) {
+        (self.callback)(
+            self.user_data,
+            progress.read as u32,
+            progress.at_sec,
+            progress.duration_sec.unwrap_or(-1.0),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gpx_of_gps_log() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("clip.nmea"),
+            "$GPRMC,124229.00,A,5125.8000,N,00019.3333,E,44.3,90.0,060621,,,A*5A\n",
+        )
+        .unwrap();
+        let path = CString::new(dir.join("clip.mov").to_string_lossy().as_ref()).unwrap();
+        let args = [c"--interval", c"1"].map(|a| a.as_ptr());
+
+        unsafe {
+            let doc = dash2gps_convert(
+                path.as_ptr(),
+                c"gpx".as_ptr(),
+                args.as_ptr(),
+                args.len(),
+                None,
+                std::ptr::null_mut(),
+            );
+            assert!(!doc.is_null());
+            let gpx = CStr::from_ptr(doc).to_str().unwrap().to_string();
+            dash2gps_free_string(doc);
+            assert!(gpx.contains(r#"<trkpt lat="51.430000""#), "{}", gpx);
+
+            let none = dash2gps_convert(
+                path.as_ptr(),
+                c"kml".as_ptr(),
+                std::ptr::null(),
+                0,
+                None,
+                std::ptr::null_mut(),
+            );
+            assert!(none.is_null());
+            let error = CStr::from_ptr(dash2gps_last_error()).to_str().unwrap();
+            assert!(error.contains("unknown format `kml`"), "{}", error);
+        }
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_model_is_an_error() {
+        // unless a model is next to the test binary or in the current folder
+        if crate::pipeline::local_data_dir().unwrap().is_some() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("dash2gps-ffi-model-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("clip.mov"), b"").unwrap();
+        let path = CString::new(dir.join("clip.mov").to_string_lossy().as_ref()).unwrap();
+        let args = [c"--ocr-engine", c"tesseract"].map(|a| a.as_ptr());
+
+        unsafe {
+            let doc = dash2gps_convert(
+                path.as_ptr(),
+                c"gpx".as_ptr(),
+                args.as_ptr(),
+                args.len(),
+                None,
+                std::ptr::null_mut(),
+            );
+            assert!(doc.is_null());
+            let error = CStr::from_ptr(dash2gps_last_error()).to_str().unwrap();
+            let expected = match cfg!(feature = "tesseract") {
+                true => "eng.traineddata",
+                false => "built without Tesseract",
+            };
+            assert!(error.contains(expected), "{}", error);
+        }
+        _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn panic_is_an_error() {
+        let doc = document(|| panic!("frame {} out of range", 3));
+        assert!(doc.is_null());
+        let error = unsafe { CStr::from_ptr(dash2gps_last_error()) };
+        assert_eq!(
+            error.to_str().unwrap(),
+            "dash2gps panicked: frame 3 out of range"
+        );
+    }
+}
@@ -0,0 +1,591 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cancel::CancellationToken,
+    preprocess::{Crop, FRAME_HEIGHT, FRAME_WIDTH},
+};
+
+/// `ffmpeg` or `ffprobe` found by [`locate`]
+pub fn command(tool: &str) -> Command {
+    Command::new(locate(tool))
+}
+
+/// The tool in `PATH`, or where package managers install it: Homebrew and
+/// MacPorts on macOS, winget on Windows since `PATH` only picks that up in
+/// terminals opened after the install. Falls back to the bare name so the error
+/// reads as the tool not being found.
+pub fn locate(tool: &str) -> PathBuf {
+    let file = format!("{}{}", tool, std::env::consts::EXE_SUFFIX);
+    let in_path = std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).collect::<Vec<_>>())
+        .unwrap_or_default();
+    in_path
+        .into_iter()
+        .chain(install_dirs())
+        .map(|dir| dir.join(&file))
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| PathBuf::from(tool))
+}
+
+fn install_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(target_os = "macos") {
+        // apps started from Finder or launchd do not get the shell's PATH
+        dirs.extend(
+            ["/opt/homebrew/bin", "/usr/local/bin", "/opt/local/bin"]
+                .into_iter()
+                .map(PathBuf::from),
+        );
+    }
+    if cfg!(windows) {
+        if let Some(local) = std::env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+            let winget = local.join("Microsoft").join("WinGet");
+            dirs.push(winget.join("Links"));
+            // Gyan.FFmpeg_<source>/ffmpeg-<version>-full_build/bin
+            let packages = winget.join("Packages").read_dir().into_iter().flatten();
+            for package in packages.flatten() {
+                if package
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("Gyan.FFmpeg")
+                {
+                    let builds = package.path().read_dir().into_iter().flatten();
+                    dirs.extend(builds.flatten().map(|b| b.path().join("bin")));
+                }
+            }
+        }
+    }
+
+    dirs
+}
+
+/// How to get ffmpeg, for errors when it cannot be started
+pub fn install_hint() -> &'static str {
+    if cfg!(windows) {
+        "Is ffmpeg installed? Install it with `winget install Gyan.FFmpeg`"
+    } else if cfg!(target_os = "macos") {
+        "Is ffmpeg installed? Install it with `brew install ffmpeg`"
+    } else {
+        "Is ffmpeg installed and in PATH?"
+    }
+}
+
+/// Outcome of a frame extraction run.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Extraction {
+    /// Number of frames written to the output folder
+    pub frames: usize,
+    /// Whether ffmpeg exited cleanly
+    pub clean_exit: bool,
+    /// Last lines ffmpeg printed to stderr, useful when the file was damaged
+    pub stderr_tail: String,
+    /// Presentation time in seconds of each extracted frame, by frame number
+    pub pts: HashMap<u32, f64>,
+}
+
+impl Extraction {
+    /// Seconds of footage covered by the extracted frames.
+    pub fn covered_sec(&self, interval_sec: u32) -> u64 {
+        self.frames as u64 * interval_sec as u64
+    }
+
+    /// Add the frames of the extraction of a later chunk.
+    pub fn extend(&mut self, chunk: Extraction) {
+        self.frames += chunk.frames;
+        self.clean_exit &= chunk.clean_exit;
+        if !chunk.stderr_tail.is_empty() {
+            self.stderr_tail = chunk.stderr_tail;
+        }
+        self.pts.extend(chunk.pts);
+    }
+}
+
+/// Start of each chunk (in seconds) a video of `duration_sec` is read in,
+/// with chunks of at least `chunk_sec` rounded up to whole intervals. Empty
+/// when the video fits in one chunk or its duration is unknown.
+pub fn chunks(duration_sec: Option<f64>, chunk_sec: f64, interval_sec: u32) -> Vec<u64> {
+    let interval = interval_sec.max(1) as u64;
+    let chunk = (chunk_sec.ceil() as u64).div_ceil(interval).max(1) * interval;
+    match duration_sec {
+        Some(duration) if duration > chunk as f64 => (0..)
+            .map(|i| i * chunk)
+            .take_while(|&start| (start as f64) < duration)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub struct ExtractOptions {
+    pub interval_sec: u32,
+    pub threads: u8,
+    /// Stop after this many frames
+    pub max_frames: Option<u32>,
+    /// Sample by timestamp rather than with the `fps` filter, which assumes a
+    /// constant frame rate and skews times on variable frame rate footage
+    pub vfr: bool,
+    /// ffmpeg `-hwaccel` method to decode with, eg. `videotoolbox`
+    pub hwaccel: Option<String>,
+    /// Write only this part of the 1280x720 frames, so decoding them for
+    /// OCR does not go through the whole picture
+    pub crop: Option<Crop>,
+    /// Seconds into the video to start at, a multiple of the interval
+    pub start_sec: u64,
+    /// Seconds of video to read, to the end by default
+    pub duration_sec: Option<u64>,
+    /// Seconds into every interval to sample at, to keep to the same page of
+    /// an overlay showing pages in turn
+    pub offset_sec: f64,
+}
+
+/// Fail early with a hint when the video cannot be read. On macOS a terminal
+/// not allowed to access removable volumes cannot open videos on an SD card,
+/// which ffmpeg only reports as `Operation not permitted`.
+pub fn check_readable(input: &Path) -> anyhow::Result<()> {
+    match std::fs::File::open(input) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && cfg!(target_os = "macos") => {
+            anyhow::bail!(
+                "unable to read {}: {}. Allow your terminal to access Removable Volumes in System Settings > Privacy & Security > Files and Folders, or copy the video off the card",
+                input.display(),
+                e
+            )
+        }
+        Err(e) => Err(e).with_context(|| format!("unable to read {}", input.display())),
+    }
+}
+
+/// Extract one frame every `interval_sec` into `out_dir`.
+///
+/// Dashcam files interrupted by power loss often have a broken index or a
+/// truncated tail, so ffmpeg is asked to ignore decode errors and drop corrupt
+/// packets. A non-zero exit is only treated as fatal when no frame at all could
+/// be recovered. ffmpeg is stopped once `cancel` is, keeping the frames
+/// written so far.
+pub fn extract_frames(
+    input: &Path,
+    out_dir: &Path,
+    options: &ExtractOptions,
+    cancel: &CancellationToken,
+) -> anyhow::Result<Extraction> {
+    let input = input
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("unable to parse input path"))?;
+    let mut cmd = command("ffmpeg");
+    if let Some(method) = &options.hwaccel {
+        cmd.args(["-hwaccel", method]);
+    }
+    if options.start_sec > 0 {
+        cmd.args(["-ss", &options.start_sec.to_string()]);
+    }
+    if let Some(duration) = options.duration_sec {
+        cmd.args(["-t", &duration.to_string()]);
+    }
+    // frames are numbered from the start of the video
+    let skipped = (options.start_sec / options.interval_sec.max(1) as u64) as u32;
+    cmd.args(["-err_detect", "ignore_err"])
+        .args(["-fflags", "+genpts+discardcorrupt"])
+        .args(["-i", input])
+        .arg("-an")
+        .args(["-vf", &sample_filter(options)])
+        .args(["-threads", &options.threads.to_string()]);
+    if options.vfr {
+        cmd.args(["-vsync", "vfr"]);
+    }
+    if let Some(max) = options.max_frames {
+        cmd.args(["-frames:v", &max.to_string()]);
+    }
+    let mut ffmpeg = cmd
+        .args(["-start_number", &(skipped + 1).to_string()])
+        .arg("f%09d.jpg")
+        .current_dir(out_dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("start ffmpeg to extract frames. {}", install_hint()))?;
+    // read while waiting, so a long log can't fill the pipe
+    let mut log = ffmpeg.stderr.take().expect("stderr is piped");
+    let reader = std::thread::spawn(move || {
+        let mut stderr = Vec::new();
+        _ = log.read_to_end(&mut stderr);
+        stderr
+    });
+    let status = loop {
+        if let Some(status) = ffmpeg.try_wait()? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            _ = ffmpeg.kill();
+            break ffmpeg.wait()?;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    let stderr = reader.join().unwrap_or_default();
+
+    let stderr = String::from_utf8_lossy(&stderr);
+    let pts = parse_showinfo(&stderr)
+        .into_iter()
+        .map(|(n, t)| {
+            (
+                n + skipped,
+                t + options.start_sec as f64 + options.offset_sec,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    // frames already read may have been removed while extracting a chunk
+    let frames = count_frames(out_dir)?.max(pts.len());
+    let stderr_tail = tail(
+        &stderr
+            .lines()
+            .filter(|l| !l.contains("showinfo"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        10,
+    );
+
+    if !status.success() && frames == 0 && !cancel.is_cancelled() {
+        if status.code().is_none() && stderr_tail.is_empty() && cfg!(target_os = "macos") {
+            // Gatekeeper kills unsigned binaries downloaded with a browser
+            anyhow::bail!(
+                "ffmpeg was stopped before it started, macOS may have blocked {0} as downloaded from the internet. Install ffmpeg with `brew install ffmpeg`, or allow it with `xattr -d com.apple.quarantine {0}`",
+                locate("ffmpeg").display()
+            );
+        }
+        anyhow::bail!("ffmpeg process exited with error:\n{}", stderr_tail);
+    }
+
+    Ok(Extraction {
+        frames,
+        clean_exit: status.success(),
+        stderr_tail,
+        pts,
+    })
+}
+
+/// Extract the frames at `(frame_no, offset_sec)` into `out_dir`, named as
+/// [`extract_frames`] would have, eg. to read failed frames again.
+pub fn extract_frames_at(
+    input: &Path,
+    out_dir: &Path,
+    frames: &[(u32, f64)],
+) -> anyhow::Result<Extraction> {
+    let mut extraction = Extraction {
+        frames: 0,
+        clean_exit: true,
+        stderr_tail: String::new(),
+        pts: HashMap::new(),
+    };
+    for &(frame_no, offset_sec) in frames {
+        let result = command("ffmpeg")
+            .args(["-err_detect", "ignore_err"])
+            .args(["-ss", &offset_sec.to_string()])
+            .arg("-i")
+            .arg(input)
+            .args(["-an", "-frames:v", "1"])
+            .args(["-s", "1280x720"])
+            .args(["-f", "image2", "-y"])
+            .arg(format!("f{:09}.jpg", frame_no))
+            .current_dir(out_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| format!("start ffmpeg to extract frames. {}", install_hint()))?;
+
+        if result.status.success() {
+            extraction.frames += 1;
+            extraction.pts.insert(frame_no, offset_sec);
+        } else {
+            extraction.clean_exit = false;
+            extraction.stderr_tail = tail(&String::from_utf8_lossy(&result.stderr), 10);
+        }
+    }
+
+    Ok(extraction)
+}
+
+/// Filter graph picking one frame per interval, followed by `showinfo` to
+/// report the timestamp of each picked frame.
+fn sample_filter(options: &ExtractOptions) -> String {
+    // drop the start so the samples fall `offset_sec` into every interval
+    let shift = match options.offset_sec > 0.0 {
+        true => format!("trim=start={},setpts=PTS-STARTPTS,", options.offset_sec),
+        false => String::new(),
+    };
+    let sample = if options.vfr {
+        // the first frame of every interval, using the frames' own timestamps
+        format!(
+            "{1}select='isnan(prev_selected_t)+gt(floor(t/{0}),floor(prev_selected_t/{0}))',showinfo",
+            options.interval_sec, shift
+        )
+    } else {
+        format!("{}fps=1/{},showinfo", shift, options.interval_sec)
+    };
+    let scale = format!("scale={}:{}", FRAME_WIDTH, FRAME_HEIGHT);
+    match options.crop {
+        Some(c) => format!(
+            "{},{},crop={}:{}:{}:{}",
+            sample, scale, c.width, c.height, c.x, c.y
+        ),
+        None => format!("{},{}", sample, scale),
+    }
+}
+
+/// Frame rates of the first video stream
+pub struct FrameRate {
+    /// The container's nominal rate (`r_frame_rate`)
+    pub nominal: f64,
+    /// The actual average rate (`avg_frame_rate`)
+    pub average: f64,
+}
+
+impl FrameRate {
+    /// Average rate more than 1% away from the nominal one
+    pub fn is_variable(&self) -> bool {
+        self.nominal > 0.0 && ((self.nominal - self.average) / self.nominal).abs() > 0.01
+    }
+}
+
+pub fn probe_frame_rate(input: &Path) -> Option<FrameRate> {
+    let output = command("ffprobe")
+        .args(["-v", "error"])
+        .args(["-select_streams", "v:0"])
+        .args(["-show_entries", "stream=r_frame_rate,avg_frame_rate"])
+        .args(["-of", "default=noprint_wrappers=1"])
+        .arg(input)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    parse_frame_rate(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_frame_rate(probe: &str) -> Option<FrameRate> {
+    let rate = |key: &str| {
+        let value = probe.lines().find_map(|l| l.strip_prefix(key))?;
+        let (num, den) = value.trim().split_once('/')?;
+        let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+        (den > 0.0).then(|| num / den)
+    };
+
+    Some(FrameRate {
+        nominal: rate("r_frame_rate=")?,
+        average: rate("avg_frame_rate=")?,
+    })
+}
+
+/// Duration of the input in seconds as reported by the container.
+///
+/// Returns `None` when the file is too damaged for ffprobe to tell.
+pub fn probe_duration(input: &Path) -> Option<f64> {
+    let output = command("ffprobe")
+        .args(["-v", "error"])
+        .args(["-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(input)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Recording start as a unix timestamp, from the container's `creation_time`.
+pub fn probe_creation_time(input: &Path) -> Option<i64> {
+    let output = command("ffprobe")
+        .args(["-v", "error"])
+        .args(["-show_entries", "format_tags=creation_time"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(input)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    chrono::DateTime::parse_from_rfc3339(String::from_utf8_lossy(&output.stdout).trim())
+        .ok()
+        .map(|t| t.timestamp())
+}
+
+/// Timestamps printed by the `showinfo` filter, keyed by 1-based frame number
+/// to match the `f%09d.jpg` names.
+fn parse_showinfo(stderr: &str) -> HashMap<u32, f64> {
+    static REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"showinfo.*\bn:\s*(\d+).*\bpts_time:\s*(-?[\d.]+)").unwrap());
+
+    stderr
+        .lines()
+        .filter_map(|l| {
+            let cap = REGEX.captures(l)?;
+            Some((cap[1].parse::<u32>().ok()? + 1, cap[2].parse().ok()?))
+        })
+        .collect()
+}
+
+fn count_frames(dir: &Path) -> anyhow::Result<usize> {
+    Ok(dir
+        .read_dir()
+        .context("read frames folder")?
+        .flatten()
+        .filter(|f| f.file_name().to_string_lossy().ends_with(".jpg"))
+        .count())
+}
+
+fn tail(s: &str, lines: usize) -> String {
+    let all = s.lines().collect::<Vec<_>>();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// Index of an extracted frame from its file name (`f000000012.jpg` -> 12).
+pub fn frame_no(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_str()?.strip_prefix('f')?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filter_crops_after_scaling() {
+        let options = ExtractOptions {
+            interval_sec: 10,
+            threads: 1,
+            max_frames: None,
+            vfr: false,
+            hwaccel: None,
+            crop: None,
+            start_sec: 0,
+            duration_sec: None,
+            offset_sec: 0.0,
+        };
+        assert_eq!(sample_filter(&options), "fps=1/10,showinfo,scale=1280:720");
+        let options = ExtractOptions {
+            crop: Some(Crop::bottom(FRAME_WIDTH, FRAME_HEIGHT)),
+            ..options
+        };
+        assert_eq!(
+            sample_filter(&options),
+            "fps=1/10,showinfo,scale=1280:720,crop=1280:50:0:670"
+        );
+    }
+
+    #[test]
+    fn filter_shifts_sampling() {
+        let options = ExtractOptions {
+            interval_sec: 2,
+            threads: 1,
+            max_frames: None,
+            vfr: false,
+            hwaccel: None,
+            crop: None,
+            start_sec: 0,
+            duration_sec: None,
+            offset_sec: 0.5,
+        };
+        assert_eq!(
+            sample_filter(&options),
+            "trim=start=0.5,setpts=PTS-STARTPTS,fps=1/2,showinfo,scale=1280:720"
+        );
+        let options = ExtractOptions {
+            vfr: true,
+            ..options
+        };
+        assert!(sample_filter(&options).starts_with("trim=start=0.5,setpts=PTS-STARTPTS,select="));
+    }
+
+    #[test]
+    fn extracts_frames() {
+        // CI installs ffmpeg on every platform
+        if !locate("ffmpeg").is_file() {
+            return;
+        }
+        // a space to check the paths are passed as is
+        let dir = std::env::temp_dir().join(format!("dash2gps test {}", std::process::id()));
+        let frames = dir.join("frames");
+        std::fs::create_dir_all(&frames).unwrap();
+        let input = dir.join("clip.mp4");
+        let status = command("ffmpeg")
+            .args(["-v", "error", "-f", "lavfi", "-i"])
+            .arg("testsrc=duration=3:size=320x240:rate=10")
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let options = ExtractOptions {
+            interval_sec: 1,
+            threads: 1,
+            max_frames: None,
+            vfr: false,
+            hwaccel: None,
+            crop: None,
+            start_sec: 0,
+            duration_sec: None,
+            offset_sec: 0.0,
+        };
+        let extraction = extract_frames(&input, &frames, &options, &CancellationToken::new());
+        // the second half, numbered from the start
+        let second = frames.join("second");
+        std::fs::create_dir_all(&second).unwrap();
+        let options = ExtractOptions {
+            start_sec: 2,
+            duration_sec: Some(2),
+            ..options
+        };
+        let chunk = extract_frames(&input, &second, &options, &CancellationToken::new());
+        _ = std::fs::remove_dir_all(&dir);
+
+        let extraction = extraction.unwrap();
+        assert!(extraction.clean_exit);
+        // one per second, some ffmpeg versions add one at the end
+        assert!((3..=4).contains(&extraction.frames));
+
+        let chunk = chunk.unwrap();
+        assert!(chunk.pts.contains_key(&3), "{:?}", chunk.pts);
+        assert!(!chunk.pts.contains_key(&1));
+        assert!((chunk.pts[&3] - 2.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn chunked() {
+        assert_eq!(chunks(Some(3600.0), 1800.0, 10), [0, 1800]);
+        assert_eq!(chunks(Some(3601.0), 1800.0, 10), [0, 1800, 3600]);
+        // whole intervals
+        assert_eq!(chunks(Some(100.0), 25.0, 10), [0, 30, 60, 90]);
+        assert!(chunks(Some(1800.0), 1800.0, 10).is_empty());
+        assert!(chunks(None, 1800.0, 10).is_empty());
+    }
+
+    #[test]
+    fn showinfo_timestamps() {
+        let stderr = "frame=    2 fps=0.0 q=2.0 size=N/A
+[Parsed_showinfo_1 @ 0x5583] n:   0 pts:      0 pts_time:0       duration:      1 fmt:yuvj420p
+[Parsed_showinfo_1 @ 0x5583] n:   1 pts:  10010 pts_time:10.01   duration:      1 fmt:yuvj420p
+[Parsed_showinfo_1 @ 0x5583] color_range:pc color_space:bt470bg
+";
+        let pts = parse_showinfo(stderr);
+
+        assert_eq!(pts.len(), 2);
+        assert_eq!(pts[&1], 0.0);
+        assert_eq!(pts[&2], 10.01);
+    }
+
+    #[test]
+    fn variable_frame_rate() {
+        let rate = parse_frame_rate("r_frame_rate=30/1\navg_frame_rate=2997/100\n").unwrap();
+        assert!(!rate.is_variable());
+
+        let rate = parse_frame_rate("r_frame_rate=30/1\navg_frame_rate=8310/300\n").unwrap();
+        assert!(rate.is_variable());
+
+        assert!(parse_frame_rate("r_frame_rate=0/0\navg_frame_rate=0/0\n").is_none());
+    }
+}
@@ -0,0 +1,110 @@
+//! Frames extracted by an earlier run, kept with `--reuse-frames` so that
+//! iterating on OCR settings does not pay for running ffmpeg every time.
+//!
+//! Frames are kept in the cache folder under the SHA-256 of the video, the
+//! interval and the sample offset. A folder is only reused once its extraction finished, which is
+//! recorded in `extraction.json` next to the frames. That file is
+//! [`versioned`]: one written by a newer release is extracted again.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::{ffmpeg::Extraction, manifest, tessdata, versioned};
+
+const DONE: &str = "extraction.json";
+/// Version of the layout of `extraction.json`, 1 before it was stamped
+const VERSION: u32 = 2;
+
+pub struct FrameCache {
+    pub dir: PathBuf,
+}
+
+impl FrameCache {
+    pub fn new(input: &Path, interval_sec: u32, offset_sec: f64) -> anyhow::Result<Self> {
+        let hash = manifest::sha256_file(input)?;
+        Ok(Self::at(
+            &tessdata::cache_dir().join("frames"),
+            &hash,
+            interval_sec,
+            offset_sec,
+        ))
+    }
+
+    fn at(root: &Path, hash: &str, interval_sec: u32, offset_sec: f64) -> Self {
+        let name = match offset_sec > 0.0 {
+            true => format!("{}-{}s+{}s", hash, interval_sec, offset_sec),
+            false => format!("{}-{}s", hash, interval_sec),
+        };
+        Self {
+            dir: root.join(name),
+        }
+    }
+
+    /// The extraction of an earlier run, when it finished.
+    pub fn load(&self) -> Option<Extraction> {
+        let json = std::fs::read(self.dir.join(DONE)).ok()?;
+        // version 2 only added the stamp
+        versioned::from_slice(&json, VERSION, |_, value| Some(value))
+    }
+
+    /// Empty the folder for a new extraction.
+    pub fn clear(&self) -> anyhow::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir).context("clear frame cache")?;
+        }
+        std::fs::create_dir_all(&self.dir).context("create frame cache folder")
+    }
+
+    /// Mark the frames in the folder as complete.
+    pub fn save(&self, extraction: &Extraction) -> anyhow::Result<()> {
+        let json = versioned::to_vec(VERSION, extraction)?;
+        std::fs::write(self.dir.join(DONE), json).context("save frame cache")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reused_once_saved() {
+        let root =
+            std::env::temp_dir().join(format!("dash2gps-frame-cache-{}", std::process::id()));
+        let cache = FrameCache::at(&root, "abc", 10, 0.0);
+        assert!(cache.dir.ends_with("abc-10s"));
+        assert_ne!(cache.dir, FrameCache::at(&root, "abc", 5, 0.0).dir);
+        assert!(FrameCache::at(&root, "abc", 10, 0.5)
+            .dir
+            .ends_with("abc-10s+0.5s"));
+
+        cache.clear().unwrap();
+        assert!(cache.load().is_none());
+        let extraction = Extraction {
+            frames: 2,
+            clean_exit: true,
+            stderr_tail: String::new(),
+            pts: [(1, 0.0), (2, 10.0)].into_iter().collect(),
+        };
+        cache.save(&extraction).unwrap();
+        let loaded = cache.load().unwrap();
+        assert_eq!(loaded.frames, 2);
+        assert_eq!(loaded.pts.get(&2), Some(&10.0));
+
+        // written before the stamp, or by a newer release
+        let done = cache.dir.join(DONE);
+        std::fs::write(
+            &done,
+            r#"{"frames":1,"clean_exit":true,"stderr_tail":"","pts":{"1":0.0}}"#,
+        )
+        .unwrap();
+        assert_eq!(cache.load().unwrap().frames, 1);
+        std::fs::write(&done, r#"{"frames":1,"version":3}"#).unwrap();
+        assert!(cache.load().is_none());
+
+        // a new extraction starts from an empty folder
+        cache.clear().unwrap();
+        assert!(cache.load().is_none());
+        _ = std::fs::remove_dir_all(root);
+    }
+}
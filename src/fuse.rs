@@ -0,0 +1,188 @@
+//! Combine the recovered track with an external GPS log (`--fuse`).
+
+use crate::{
+    import::{self, TimedFix},
+    parser::{Coordinate, Overlay, Readings},
+    track::{self, FrameResult, Track},
+};
+
+/// External fixes further apart than this are not interpolated
+const MAX_GAP_SEC: f64 = 10.0;
+
+pub struct Alignment {
+    /// Unix timestamp the video starts at according to the external log
+    pub video_start: f64,
+    /// Mean distance between the recovered and external points at that offset
+    pub mean_deviation_m: f64,
+    /// Recovered points that overlap with the external log
+    pub matched: usize,
+}
+
+pub struct Fused {
+    pub track: Track,
+    pub alignment: Alignment,
+    pub from_external: usize,
+    pub from_video: usize,
+}
+
+/// Find when the video starts relative to the external log by sliding the
+/// recovered points along it one second at a time and keeping the offset with
+/// the smallest mean distance. At least half of the points must overlap.
+pub fn align(track: &Track, external: &[TimedFix]) -> Option<Alignment> {
+    let (first, last) = (external.first()?, external.last()?);
+    let span = track.points.last()?.offset_sec;
+    let min_matched = track.points.len().div_ceil(2);
+
+    let mut best: Option<Alignment> = None;
+    let mut video_start = (first.timestamp - span).floor();
+    while video_start <= last.timestamp {
+        let (sum, matched) = track
+            .points
+            .iter()
+            .filter_map(|p| {
+                let at = video_start + p.offset_sec;
+                let external = import::position_at(external, at, MAX_GAP_SEC)?;
+                Some(track::distance_m(p.coordinate.lat_lon(), external))
+            })
+            .fold((0.0, 0), |(sum, n), d| (sum + d, n + 1));
+
+        if matched >= min_matched {
+            let mean_deviation_m = sum / matched as f64;
+            if best
+                .as_ref()
+                .is_none_or(|b| mean_deviation_m < b.mean_deviation_m)
+            {
+                best = Some(Alignment {
+                    video_start,
+                    mean_deviation_m,
+                    matched,
+                });
+            }
+        }
+        video_start += 1.0;
+    }
+
+    best
+}
+
+/// Use the external log wherever it covers a sampled frame and the recovered
+/// points everywhere else.
+pub fn fuse(track: Track, external: &[TimedFix], interval_sec: u32) -> anyhow::Result<Fused> {
+    let alignment = align(&track, external).ok_or_else(|| {
+        anyhow::anyhow!("the GPS log does not overlap with the locations found in the video")
+    })?;
+
+    let frames = track
+        .frames
+        .max(track.points.last().map_or(0, |p| p.frame_no as usize));
+    let no_fix = track.no_fix;
+    // what was lost reading the video, not what the log could not fill in
+    let errors = track.errors;
+    let mut points = track.points.into_iter().peekable();
+    let (mut from_external, mut from_video) = (0, 0);
+
+    let mut results = Vec::with_capacity(frames);
+    for frame_no in 1..=frames as u32 {
+        let ocr = points.next_if(|p| p.frame_no == frame_no);
+        let offset_sec = ocr
+            .as_ref()
+            .map_or((frame_no - 1) as f64 * interval_sec as f64, |p| {
+                p.offset_sec
+            });
+        let (ocr, readings) = ocr.map_or((None, Readings::default()), |p| {
+            (Some(p.coordinate), p.readings)
+        });
+        let external =
+            import::position_at(external, alignment.video_start + offset_sec, MAX_GAP_SEC);
+
+        let overlay = match (external, ocr) {
+            (Some((lat, lon)), _) => {
+                from_external += 1;
+                Overlay::Fix(Coordinate::Decimal { lat, lon })
+            }
+            (None, Some(c)) => {
+                from_video += 1;
+                Overlay::Fix(c)
+            }
+            (None, None)
+                if no_fix
+                    .iter()
+                    .any(|g| (g.start_sec..g.end_sec).contains(&offset_sec)) =>
+            {
+                Overlay::NoFix
+            }
+            (None, None) => Overlay::Unreadable,
+        };
+        results.push(FrameResult {
+            frame_no,
+            pts_sec: Some(offset_sec),
+            overlay,
+            readings,
+            error: None,
+            confidence: None,
+        });
+    }
+
+    let mut track = Track::from_frames(results, interval_sec);
+    track.errors = errors;
+    Ok(Fused {
+        track,
+        alignment,
+        from_external,
+        from_video,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse_overlay;
+
+    #[test]
+    fn finds_offset_and_fills_gaps() {
+        // external log starts 100s before the video, one fix per second heading east
+        let start = 1_600_000_000.0;
+        let external = (0..400)
+            .map(|i| TimedFix {
+                timestamp: start + i as f64,
+                lat: 51.43,
+                lon: 0.3 + i as f32 * 0.0001,
+            })
+            .collect::<Vec<_>>();
+        let fix = |frame_no: u32| {
+            let lon = 0.3 + (100 + (frame_no - 1) * 10) as f32 * 0.0001;
+            FrameResult {
+                frame_no,
+                pts_sec: None,
+                overlay: Overlay::Fix(Coordinate::Decimal { lat: 51.43, lon }),
+                readings: Default::default(),
+                error: None,
+                confidence: None,
+            }
+        };
+        let track = Track::from_frames(
+            vec![
+                fix(1),
+                fix(2),
+                FrameResult {
+                    frame_no: 3,
+                    pts_sec: None,
+                    overlay: parse_overlay("unreadable"),
+                    readings: Default::default(),
+                    error: None,
+                    confidence: None,
+                },
+                fix(4),
+            ],
+            10,
+        );
+
+        let fused = fuse(track, &external, 10).unwrap();
+
+        assert_eq!(fused.alignment.video_start, start + 100.0);
+        assert!(fused.alignment.mean_deviation_m < 1.0);
+        assert_eq!(fused.from_external, 4);
+        assert_eq!(fused.track.points.len(), 4);
+        assert_eq!(fused.track.unreadable, 0);
+    }
+}
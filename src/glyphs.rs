@@ -0,0 +1,716 @@
+//! Built-in recognizer for the overlay font of common dashcams (Nextbase and
+//! lookalikes): every glyph is matched against a template. Reads the overlay
+//! without a Tesseract model, and frames Tesseract could not read.
+
+use std::path::Path;
+
+use anyhow::Context;
+use image::GrayImage;
+use once_cell::sync::Lazy;
+
+/// Rows of the text line the templates are drawn in
+const LINE_HEIGHT: usize = 18;
+/// Glyphs are compared scaled to this grid
+const GRID_W: usize = 6;
+const GRID_H: usize = 9;
+/// Blobs matching no template better than this are skipped as noise
+const MAX_COST: f32 = 0.5;
+
+/// `(glyph, first row in the text line, bitmap)`, taken from the overlay of a
+/// 1280x720 frame.
+const TEMPLATES: &[(char, usize, &str)] = &[
+    (
+        '0',
+        0,
+        "
+        ..######..
+        .#######..
+        .##...###.
+        ##.....##.
+        ##.....###
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##.....##.
+        ##.....##.
+        ###...###.
+        .#######..
+        ..#####...",
+    ),
+    (
+        '1',
+        0,
+        "
+        ..#.
+        ####
+        ####
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..##
+        ..#.",
+    ),
+    (
+        '2',
+        0,
+        "
+        ..######..
+        .########.
+        ###....##.
+        ##......##
+        ##......##
+        .......###
+        ......###.
+        .....###..
+        .....##...
+        ....###...
+        ...###....
+        ..###.....
+        ..##......
+        .###......
+        ###.......
+        ###.......
+        ##########
+        #########.",
+    ),
+    (
+        '3',
+        0,
+        "
+        ..#####...
+        .#######..
+        ####..###.
+        ##.....##.
+        .......##.
+        .......##.
+        ......###.
+        .....###..
+        ..#####...
+        ...#####..
+        ......###.
+        .......##.
+        ........##
+        ........##
+        ##.....##.
+        ###....##.
+        .#######..
+        ..######..",
+    ),
+    (
+        '4',
+        0,
+        "
+        ......#...
+        .....###..
+        .....###..
+        ....####..
+        ....####..
+        ...#####..
+        ..###.##..
+        ..##..##..
+        .###..##..
+        .##...##..
+        ###...##..
+        ###..###..
+        ##########
+        ##########
+        ......##..
+        ......##..
+        ......##..
+        ......#...",
+    ),
+    (
+        '5',
+        0,
+        "
+        .########.
+        #########.
+        ###.......
+        ##........
+        ##........
+        ##........
+        #######...
+        ########..
+        ###...###.
+        .#.....##.
+        .......###
+        ........##
+        ........##
+        .......###
+        ##.....##.
+        ###...###.
+        .#######..
+        ..#####...",
+    ),
+    (
+        '6',
+        0,
+        "
+        ..######..
+        .########.
+        .##....##.
+        ###....##.
+        ##........
+        ##........
+        ##..##....
+        ########..
+        ####.####.
+        ##.....##.
+        ##.....###
+        ##......##
+        ##......##
+        ##......##
+        ##.....##.
+        ###...###.
+        .#######..
+        ...####...",
+    ),
+    (
+        '7',
+        0,
+        "
+        ##########
+        ##########
+        ......###.
+        ......##..
+        ......##..
+        .....##...
+        ....###...
+        ....##....
+        ....##....
+        ...###....
+        ...##.....
+        ...##.....
+        ...##.....
+        ..###.....
+        ..##......
+        ..##......
+        ..##......
+        ...#......",
+    ),
+    (
+        '8',
+        0,
+        "
+        ..######..
+        .########.
+        ###....###
+        ##......##
+        ##......##
+        ###....###
+        .########.
+        ..######..
+        .########.
+        ###....###
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ###....###
+        .########.
+        ..######..",
+    ),
+    (
+        '9',
+        0,
+        "
+        ..######..
+        .########.
+        ###...###.
+        ##.....##.
+        ##......##
+        ##......##
+        ##......##
+        ##.....###
+        ##....####
+        .#########
+        ..########
+        .......###
+        .......##.
+        .......##.
+        ##....###.
+        ###..###..
+        .#######..
+        ..####....",
+    ),
+    (
+        'N',
+        0,
+        "
+        #........#
+        ##......##
+        ##......##
+        ###.....##
+        ####....##
+        ####....##
+        ##.##...##
+        ##.##...##
+        ##..##..##
+        ##..##..##
+        ##...##.##
+        ##...##.##
+        ##....####
+        ##....####
+        ##.....###
+        ##.....###
+        ##......##
+        ##......##",
+    ),
+    (
+        'S',
+        0,
+        "
+        ..######..
+        .########.
+        ###....###
+        ##......##
+        ##........
+        ##........
+        ###.......
+        .#######..
+        ..#######.
+        .......###
+        ........##
+        ........##
+        ........##
+        ##......##
+        ##......##
+        ###....###
+        .########.
+        ..######..",
+    ),
+    (
+        'E',
+        0,
+        "
+        .########.
+        ##########
+        ###.......
+        ##........
+        ##........
+        ##........
+        ##........
+        ##........
+        ########..
+        ########..
+        ###.......
+        ##........
+        ##........
+        ##........
+        ##........
+        ##........
+        #########.
+        ##########",
+    ),
+    (
+        'W',
+        0,
+        "
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##..##..##
+        ##..##..##
+        ##.####.##
+        ##.####.##
+        ##.####.##
+        ####..####
+        ####..####
+        ####..####
+        ###....###
+        ###....###
+        ###....###
+        ##......##",
+    ),
+    (
+        'M',
+        0,
+        "
+        ##......##
+        ###.....##
+        ###....###
+        ###....###
+        ####..####
+        ####..####
+        ####..####
+        ####..####
+        ####..#.##
+        ##.#.##.##
+        ##.####.##
+        ##.####.##
+        ##.###..##
+        ##.###..##
+        ##..##..##
+        ##..##..##
+        ##......##
+        #.......##",
+    ),
+    (
+        'P',
+        0,
+        "
+        #######..
+        ########.
+        ##....##.
+        ##.....##
+        ##.....##
+        ##.....##
+        ##....###
+        ##....##.
+        ########.
+        #######..
+        ###......
+        ##.......
+        ##.......
+        ##.......
+        ##.......
+        ##.......
+        ##.......
+        ##.......",
+    ),
+    (
+        'H',
+        0,
+        "
+        .#......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ###....###
+        ##########
+        ##########
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##
+        ##......##",
+    ),
+    (
+        '°',
+        0,
+        "
+        #####.
+        #...##
+        #...##
+        #...##
+        #####.
+        .###..",
+    ),
+    (
+        '\'',
+        0,
+        "
+        ..##
+        ..##
+        .###
+        .##.
+        .#..
+        .#..
+        .#..
+        #...",
+    ),
+    (
+        ':',
+        2,
+        "
+        .#.
+        ###
+        ###
+        .##
+        ...
+        ...
+        ...
+        ...
+        ...
+        ...
+        ###
+        ###
+        ###",
+    ),
+    (
+        '/',
+        0,
+        "
+        ........#
+        .......#.
+        .......#.
+        ......##.
+        ......#..
+        .....##..
+        .....#...
+        .....#...
+        ....##...
+        ....#....
+        ...##....
+        ...#.....
+        ...#.....
+        ..#......
+        ..#......
+        .........
+        .#.......
+        ##.......",
+    ),
+];
+
+static GLYPHS: Lazy<Vec<(char, Glyph)>> = Lazy::new(|| {
+    TEMPLATES
+        .iter()
+        .map(|(c, row, art)| {
+            let rows = art
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(|l| l.bytes().map(|b| b == b'#').collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let ink = |x: u32, y: u32| rows[y as usize][x as usize];
+            let bounds = Bounds {
+                x0: 0,
+                x1: rows[0].len() as u32,
+                y0: 0,
+                y1: rows.len() as u32,
+            };
+            let line = (-(*row as f32), LINE_HEIGHT as f32);
+
+            (*c, Glyph::new(ink, &bounds, line))
+        })
+        .collect()
+});
+
+/// Build the glyph templates ahead of the first frame.
+pub fn warm_up() {
+    Lazy::force(&GLYPHS);
+}
+
+/// Read the text of an overlay crop prepared for OCR (dark text on white),
+/// with the confidence (0-100) of the glyph matches.
+pub fn read(crop: &Path) -> anyhow::Result<(String, i32)> {
+    let image = image::open(crop).context("open crop")?.to_luma8();
+
+    Ok(recognize(&image))
+}
+
+pub fn recognize(image: &GrayImage) -> (String, i32) {
+    let ink = |x: u32, y: u32| image.get_pixel(x, y)[0] < 128;
+    let blobs = find_blobs(image.width(), image.height(), ink);
+
+    // the line is where most glyphs are as tall as each other, past the
+    // shorter marks (`°`, `'`) and any taller junk
+    let mut heights = blobs.iter().map(Bounds::height).collect::<Vec<_>>();
+    heights.sort_unstable();
+    let Some(&median) = heights.get(heights.len() / 2) else {
+        return (String::new(), 0);
+    };
+    let mut line = blobs
+        .iter()
+        .filter(|b| b.height().abs_diff(median) * 5 <= median)
+        .map(|b| (b.y0, b.y1))
+        .collect::<Vec<_>>();
+    line.sort_unstable();
+    let (top, bottom) = line[line.len() / 2];
+    let line_height = (bottom - top) as f32;
+
+    let mut text = String::new();
+    let mut costs = Vec::new();
+    let mut previous: Option<&Bounds> = None;
+    for blob in &blobs {
+        if blob.height() as f32 > line_height * 1.3
+            || blob.y1 <= top
+            || blob.y0 >= bottom
+            || blob.height() * blob.width() < 4
+        {
+            continue;
+        }
+        let Some((c, cost)) = best_match(&Glyph::new(ink, blob, (top as f32, line_height))) else {
+            continue;
+        };
+        costs.push(cost);
+
+        let gap = previous.map_or(0.0, |p| (blob.x0 - p.x1) as f32);
+        if c == '\'' && text.ends_with('\'') && gap < line_height / 3.0 {
+            text.pop();
+            text.push('"');
+        } else {
+            if gap > line_height * 0.6 && !text.is_empty() {
+                text.push(' ');
+            }
+            text.push(c);
+        }
+        previous = Some(blob);
+    }
+
+    let confidence = match costs.len() {
+        0 => 0,
+        n => {
+            (100.0 * (1.0 - costs.iter().fold(0.0, |sum, c| sum + c) / n as f32 / MAX_COST)) as i32
+        }
+    };
+    (text, confidence)
+}
+
+fn best_match(glyph: &Glyph) -> Option<(char, f32)> {
+    GLYPHS
+        .iter()
+        .map(|(c, template)| (*c, template.cost(glyph)))
+        .filter(|(_, cost)| *cost <= MAX_COST)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Box of the pixels of one glyph, end exclusive
+struct Bounds {
+    x0: u32,
+    x1: u32,
+    y0: u32,
+    y1: u32,
+}
+
+impl Bounds {
+    fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+}
+
+/// Split the image into runs of columns with ink, each trimmed to its rows with ink.
+fn find_blobs(width: u32, height: u32, ink: impl Fn(u32, u32) -> bool) -> Vec<Bounds> {
+    let column = |x: u32| (0..height).any(|y| ink(x, y));
+    let row = |y: u32, x0: u32, x1: u32| (x0..x1).any(|x| ink(x, y));
+
+    let mut blobs = Vec::new();
+    let mut x = 0;
+    while x < width {
+        if !column(x) {
+            x += 1;
+            continue;
+        }
+        let x0 = x;
+        while x < width && column(x) {
+            x += 1;
+        }
+        let y0 = (0..height).find(|&y| row(y, x0, x)).unwrap_or(0);
+        let y1 = (0..height).rev().find(|&y| row(y, x0, x)).unwrap_or(0) + 1;
+        blobs.push(Bounds { x0, x1: x, y0, y1 });
+    }
+
+    blobs
+}
+
+/// A glyph scaled to the grid, with its place in the text line
+struct Glyph {
+    grid: [f32; GRID_W * GRID_H],
+    /// Edges and width as a share of the line height
+    top: f32,
+    bottom: f32,
+    width: f32,
+}
+
+impl Glyph {
+    /// `line` is the top row and the height of the text line.
+    fn new(ink: impl Fn(u32, u32) -> bool, bounds: &Bounds, line: (f32, f32)) -> Self {
+        let (w, h) = (bounds.width() as usize, bounds.height() as usize);
+        let mut grid = [0.0; GRID_W * GRID_H];
+        for gy in 0..GRID_H {
+            let (ya, yb) = span(gy, GRID_H, h);
+            for gx in 0..GRID_W {
+                let (xa, xb) = span(gx, GRID_W, w);
+                let filled = (ya..yb)
+                    .flat_map(|y| (xa..xb).map(move |x| (x, y)))
+                    .filter(|&(x, y)| ink(bounds.x0 + x as u32, bounds.y0 + y as u32))
+                    .count();
+                grid[gy * GRID_W + gx] = filled as f32 / ((yb - ya) * (xb - xa)) as f32;
+            }
+        }
+
+        let (line_top, line_height) = line;
+        Self {
+            grid,
+            top: (bounds.y0 as f32 - line_top) / line_height,
+            bottom: (bounds.y1 as f32 - line_top) / line_height,
+            width: w as f32 / line_height,
+        }
+    }
+
+    fn cost(&self, other: &Glyph) -> f32 {
+        let shape = self
+            .grid
+            .iter()
+            .zip(&other.grid)
+            .fold(0.0, |sum, (a, b)| sum + (a - b).abs())
+            / self.grid.len() as f32;
+
+        shape
+            + (self.top - other.top).abs()
+            + (self.bottom - other.bottom).abs()
+            + (self.width - other.width).abs()
+    }
+}
+
+/// Pixels `[start, end)` covered by cell `i` of `cells` over `len` pixels, at least one
+fn span(i: usize, cells: usize, len: usize) -> (usize, usize) {
+    let start = (i * len / cells).min(len - 1);
+    let end = ((i + 1) * len / cells).max(start + 1);
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod test {
+    use image::imageops::FilterType;
+
+    use super::*;
+    use crate::parser::{self, Coordinate, Overlay};
+
+    #[test]
+    fn sample_frame() {
+        // prepared like the crops given to Tesseract
+        let frame =
+            image::open("sample-frame.jpg")
+                .unwrap()
+                .resize_exact(1280, 720, FilterType::Triangle);
+        let mut crop = frame.crop_imm(0, 670, 1280, 50).grayscale();
+        crop.invert();
+        let crop = crop.adjust_contrast(-500.0).brighten(50).to_luma8();
+
+        let (text, confidence) = recognize(&crop);
+        assert!(confidence > 50, "{}", confidence);
+        assert_eq!(text, "N51°25'47\" E0°19'21\" 50MPH 12:42:30 06/06/2021");
+        assert!(matches!(
+            parser::parse_overlay_with(text.as_str(), parser::Strictness::Strict),
+            Overlay::Fix(Coordinate::DegreeMinSec(_))
+        ));
+    }
+
+    #[test]
+    fn templates_match_themselves() {
+        for (c, glyph) in GLYPHS.iter() {
+            assert_eq!(best_match(glyph).map(|(c, _)| c), Some(*c));
+        }
+    }
+}
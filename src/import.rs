@@ -0,0 +1,404 @@
+//! Readers for GPS tracks recorded by other tools.
+
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    parser::{Coordinate, Readings},
+    speed::SpeedUnit,
+    track::{Errors, Gap, Track, TrackPoint},
+};
+
+/// A timestamped position from another GPS log.
+#[derive(Clone, Debug)]
+pub struct TimedFix {
+    /// Unix timestamp in seconds
+    pub timestamp: f64,
+    pub lat: f32,
+    pub lon: f32,
+}
+
+/// Read a GPX, NMEA or FIT log, sorted by time. Points without a timestamp are skipped.
+pub fn read_log(path: &Path) -> anyhow::Result<Vec<TimedFix>> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let mut fixes = match extension.as_str() {
+        "fit" => crate::sidecar::fit::parse(&std::fs::read(path).context("read GPS log")?)?
+            .into_iter()
+            .filter_map(|r| {
+                let (lat, lon) = r.lat_lon?;
+                Some(TimedFix {
+                    timestamp: r.timestamp as f64,
+                    lat,
+                    lon,
+                })
+            })
+            .collect(),
+        "nmea" | "gps" | "txt" => std::fs::read_to_string(path)
+            .context("read GPS log")?
+            .lines()
+            .filter_map(crate::sidecar::nmea::parse_rmc)
+            .filter_map(|(time, lat_lon)| {
+                let (lat, lon) = lat_lon?;
+                Some(TimedFix {
+                    timestamp: time.timestamp_millis() as f64 / 1000.0,
+                    lat,
+                    lon,
+                })
+            })
+            .collect(),
+        _ => parse_gpx(&std::fs::read_to_string(path).context("read GPS log")?),
+    };
+    fixes.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+    Ok(fixes)
+}
+
+/// `(offset_sec, lat, lon)` of a point of a track read back
+type Point = (f64, f32, f32);
+
+/// Read a track written by an earlier run or another tool: CSV, `--format json`
+/// or any GPS log [`read_log`] understands. Points of a CSV file without a time
+/// column are taken to be `interval_sec` apart.
+pub fn read_track(path: &Path, interval_sec: u32) -> anyhow::Result<Track> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let read = || std::fs::read_to_string(path).context("read track");
+
+    let mut start_time = None;
+    let (points, no_fix) = match extension.as_str() {
+        "csv" => (parse_csv(&read()?, interval_sec)?, Vec::new()),
+        "json" => parse_json(&read()?)?,
+        _ => {
+            let fixes = read_log(path)?;
+            let start = fixes.first().map_or(0.0, |f| f.timestamp);
+            start_time = fixes
+                .first()
+                .and_then(|f| NaiveDateTime::from_timestamp_millis((f.timestamp * 1000.0) as i64));
+            let points = fixes
+                .iter()
+                .map(|f| (f.timestamp - start, f.lat, f.lon))
+                .collect();
+            (points, Vec::new())
+        }
+    };
+
+    Ok(Track {
+        frames: points.len(),
+        points: points
+            .into_iter()
+            .enumerate()
+            .map(|(i, (offset_sec, lat, lon))| TrackPoint {
+                frame_no: i as u32 + 1,
+                offset_sec,
+                coordinate: Coordinate::Decimal { lat, lon },
+                accel_peak: None,
+                confidence: None,
+                readings: Default::default(),
+            })
+            .collect(),
+        no_fix,
+        unreadable: 0,
+        other_pages: 0,
+        errors: Default::default(),
+        facing: None,
+        coordinate_format: None,
+        meta: Default::default(),
+        start: start_time,
+        #[cfg(feature = "osm")]
+        roads: None,
+        #[cfg(feature = "osm")]
+        speeding: None,
+    })
+}
+
+/// Points of every row of a CSV file. Either `lat,lon` lines as
+/// printed by the default `--output-format`, or a header naming the `lat`/`latitude`
+/// and `lon`/`lng`/`longitude` columns and optionally `offset_sec` or `time`
+/// (RFC 3339 or unix seconds).
+pub fn parse_csv(content: &str, interval_sec: u32) -> anyhow::Result<Vec<Point>> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty()).peekable();
+    let first = lines.peek().copied().unwrap_or_default();
+
+    let is_header = first.split(',').any(|c| c.trim().parse::<f64>().is_err());
+    let (lat, lon, offset, time) = if is_header {
+        let header = lines
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|c| c.trim().trim_matches('"').to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+
+        (
+            column(&["lat", "latitude"]).context("no latitude column in CSV header")?,
+            column(&["lon", "lng", "long", "longitude"])
+                .context("no longitude column in CSV header")?,
+            column(&["offset_sec", "offset"]),
+            column(&["time", "timestamp"]),
+        )
+    } else {
+        (0, 1, None, None)
+    };
+
+    let mut start = None;
+    let mut points = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let cells = line
+            .split(',')
+            .map(|c| c.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+        let cell = |i: usize| cells.get(i).copied().unwrap_or_default();
+        let (Ok(lat), Ok(lon)) = (cell(lat).parse(), cell(lon).parse()) else {
+            continue;
+        };
+
+        let offset_sec = match (offset, time) {
+            (Some(c), _) => cell(c).parse().ok(),
+            (_, Some(c)) => parse_time(cell(c)).map(|t| t - *start.get_or_insert(t)),
+            _ => None,
+        };
+        points.push((
+            offset_sec.unwrap_or(i as f64 * interval_sec as f64),
+            lat,
+            lon,
+        ));
+    }
+
+    Ok(points)
+}
+
+fn parse_time(s: &str) -> Option<f64> {
+    s.parse().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.timestamp_millis() as f64 / 1000.0)
+    })
+}
+
+/// Points and no-fix intervals of every trip of a `--format json` document.
+fn parse_json(content: &str) -> anyhow::Result<(Vec<Point>, Vec<Gap>)> {
+    let document: serde_json::Value = serde_json::from_str(content).context("parse JSON")?;
+    let trips = document["trips"]
+        .as_array()
+        .context("not a dash2gps JSON document")?;
+
+    let mut points = Vec::new();
+    let mut no_fix = Vec::new();
+    for trip in trips {
+        for p in trip["points"].as_array().into_iter().flatten() {
+            let (Some(offset), Some(lat), Some(lon)) = (
+                p["offset_sec"].as_f64(),
+                p["lat"].as_f64(),
+                p["lon"].as_f64(),
+            ) else {
+                continue;
+            };
+            points.push((offset, lat as f32, lon as f32));
+        }
+        no_fix
+            .extend(serde_json::from_value::<Vec<Gap>>(trip["no_fix"].clone()).unwrap_or_default());
+    }
+
+    Ok((points, no_fix))
+}
+
+/// Read back the first trip of a `--format json` document as it was written,
+/// keeping frame numbers and failed frames for `--retry-failures`.
+pub fn read_report(path: &Path) -> anyhow::Result<Track> {
+    let content = std::fs::read_to_string(path).context("read report")?;
+    let document: serde_json::Value = serde_json::from_str(&content).context("parse JSON")?;
+    let trip = &document["trips"][0];
+    if !trip.is_object() {
+        anyhow::bail!("not a dash2gps JSON document");
+    }
+
+    let points = trip["points"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|p| {
+            Some(TrackPoint {
+                frame_no: p["frame_no"].as_u64()? as u32,
+                offset_sec: p["offset_sec"].as_f64()?,
+                coordinate: Coordinate::Decimal {
+                    lat: p["lat"].as_f64()? as f32,
+                    lon: p["lon"].as_f64()? as f32,
+                },
+                accel_peak: p["accel_peak"].as_f64().map(|a| a as f32),
+                confidence: None,
+                readings: Readings {
+                    speed: p["speed_kmh"].as_f64().map(|s| s as f32),
+                    speed_unit: p["speed_kmh"].is_number().then_some(SpeedUnit::Kmh),
+                    ..Default::default()
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    let errors = serde_json::from_value::<Errors>(trip["errors"].clone())
+        .context("the report has no failed frames, write it with this version")?;
+
+    Ok(Track {
+        frames: trip["stats"]["frames"]
+            .as_u64()
+            .map_or(points.len(), |f| f as usize),
+        points,
+        no_fix: serde_json::from_value(trip["no_fix"].clone()).unwrap_or_default(),
+        unreadable: errors.frames.len(),
+        other_pages: 0,
+        errors,
+        facing: serde_json::from_value(trip["camera"].clone()).ok(),
+        coordinate_format: None,
+        meta: serde_json::from_value(trip["meta"].clone()).unwrap_or_default(),
+        start: None,
+        #[cfg(feature = "osm")]
+        roads: None,
+        #[cfg(feature = "osm")]
+        speeding: None,
+    })
+}
+
+/// Track, route and waypoints of a GPX document that carry a `<time>`.
+pub fn parse_gpx(content: &str) -> Vec<TimedFix> {
+    static POINT: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?s)<(?:trkpt|rtept|wpt)\b([^>]*)>(.*?)</(?:trkpt|rtept|wpt)>").unwrap()
+    });
+    static LAT: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\blat\s*=\s*["']([^"']+)["']"#).unwrap());
+    static LON: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\blon\s*=\s*["']([^"']+)["']"#).unwrap());
+    static TIME: Lazy<Regex> = Lazy::new(|| Regex::new(r"<time>\s*([^<\s]+)\s*</time>").unwrap());
+
+    POINT
+        .captures_iter(content)
+        .filter_map(|cap| {
+            let attributes = cap.get(1)?.as_str();
+            let body = cap.get(2)?.as_str();
+            let time = chrono::DateTime::parse_from_rfc3339(&TIME.captures(body)?[1]).ok()?;
+
+            Some(TimedFix {
+                timestamp: time.timestamp_millis() as f64 / 1000.0,
+                lat: LAT.captures(attributes)?[1].parse().ok()?,
+                lon: LON.captures(attributes)?[1].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Position at `timestamp`, interpolated between the surrounding fixes.
+///
+/// `None` outside the log or when the surrounding fixes are more than
+/// `max_gap_sec` apart.
+pub fn position_at(fixes: &[TimedFix], timestamp: f64, max_gap_sec: f64) -> Option<(f32, f32)> {
+    let i = fixes.partition_point(|f| f.timestamp < timestamp);
+    let after = fixes.get(i)?;
+    if after.timestamp == timestamp {
+        return Some((after.lat, after.lon));
+    }
+    let before = fixes.get(i.checked_sub(1)?)?;
+    if after.timestamp - before.timestamp > max_gap_sec {
+        return None;
+    }
+
+    let t = ((timestamp - before.timestamp) / (after.timestamp - before.timestamp)) as f32;
+    Some((
+        before.lat + (after.lat - before.lat) * t,
+        before.lon + (after.lon - before.lon) * t,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="phone">
+  <trk><trkseg>
+    <trkpt lat="51.4300" lon="0.3222"><ele>12</ele><time>2021-06-06T12:42:29Z</time></trkpt>
+    <trkpt lon='0.3250' lat='51.4290'>
+      <time>2021-06-06T12:42:39.500Z</time>
+    </trkpt>
+    <trkpt lat="51.4280" lon="0.3270"></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    #[test]
+    fn gpx_points() {
+        let fixes = parse_gpx(GPX);
+
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(fixes[0].lat, 51.43);
+        assert_eq!(fixes[1].lon, 0.325);
+        assert_eq!(fixes[1].timestamp - fixes[0].timestamp, 10.5);
+    }
+
+    #[test]
+    fn csv_rows() {
+        let points = parse_csv("51.43,0.3222\n51.429,0.325\n", 10).unwrap();
+        assert_eq!(points, vec![(0.0, 51.43, 0.3222), (10.0, 51.429, 0.325)]);
+
+        let csv = "frame,time,latitude,longitude
+1,2021-06-06T12:42:29Z,51.43,0.3222
+2,2021-06-06T12:42:39.5Z,51.429,0.325
+3,2021-06-06T12:42:49Z,,
+";
+        let points = parse_csv(csv, 10).unwrap();
+        assert_eq!(points, vec![(0.0, 51.43, 0.3222), (10.5, 51.429, 0.325)]);
+
+        assert!(parse_csv("x,y\n1,2\n", 10).is_err());
+    }
+
+    #[test]
+    fn interpolate() {
+        let fixes = parse_gpx(GPX);
+        let start = fixes[0].timestamp;
+
+        let (lat, _) = position_at(&fixes, start + 5.25, 60.0).unwrap();
+        assert!((lat - 51.4295).abs() < 1e-5);
+        assert!(position_at(&fixes, start + 5.25, 5.0).is_none());
+        assert!(position_at(&fixes, start - 1.0, 60.0).is_none());
+        assert!(position_at(&fixes, start + 11.0, 60.0).is_none());
+    }
+
+    #[test]
+    fn report_round_trip() {
+        use crate::{crs::Crs, export::json, parser::parse_overlay, track::FrameResult};
+
+        let frame = |frame_no, text| FrameResult {
+            frame_no,
+            pts_sec: None,
+            overlay: parse_overlay(text),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        };
+        let track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "garbage"),
+                frame(3, "N51°25 58” E0°19 20”"),
+            ],
+            10,
+        );
+        let path = std::env::temp_dir().join("dash2gps-report-round-trip.json");
+        let file = std::fs::File::create(&path).unwrap();
+        json::write(
+            file,
+            vec![json::Trip::new("clip.mp4", &track, Crs::default())],
+        )
+        .unwrap();
+
+        let read = read_report(&path).unwrap();
+        assert_eq!(read.points[1].frame_no, 3);
+        assert_eq!(read.frames, 3);
+        assert_eq!(read.errors, track.errors);
+        std::fs::remove_file(path).unwrap();
+    }
+}
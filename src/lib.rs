@@ -0,0 +1,96 @@
+//! Recover the GPS track of dashcam footage printing the location on the
+//! video, see [`pipeline::Pipeline`]. The `dash2gps` command line is built on
+//! this library, [`convert`] reads a video in one call.
+//!
+//! # Thread safety
+//!
+//! [`Pipeline`], [`PipelineConfig`], [`Track`] and
+//! [`CancellationToken`](cancel::CancellationToken) are `Send` and `Sync`: a
+//! pipeline can be shared between threads, eg. a GUI reading videos in the
+//! background, and reads each video with threads of its own. Calls block
+//! until the video is read, cancel it from another thread with the token of
+//! [`Pipeline::cancellation_token`]. [`Observer`](pipeline::Observer)s are
+//! called from the worker threads.
+
+use std::path::Path;
+
+pub use pipeline::{GpsSample, Pipeline, PipelineConfig};
+pub use track::Track;
+
+pub mod accuracy;
+pub mod cancel;
+pub mod capabilities;
+pub mod clock;
+pub mod config;
+pub mod country;
+pub mod crs;
+pub mod detect;
+pub mod direction;
+pub mod dms;
+pub mod ensemble;
+pub mod event_log;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod ffmpeg;
+pub mod frame_cache;
+pub mod fuse;
+pub mod glyphs;
+pub mod import;
+pub mod manifest;
+pub mod montage;
+pub mod parser;
+pub mod photo_log;
+pub mod pipeline;
+pub mod preprocess;
+pub mod profile;
+pub mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "osm")]
+pub mod roads;
+pub mod sidecar;
+pub mod simplify;
+pub mod sink;
+pub mod speed;
+#[cfg(feature = "osm")]
+pub mod speeding;
+pub mod spool;
+pub mod stream;
+pub mod tessdata;
+pub mod timeline;
+pub mod track;
+pub mod versioned;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watcher;
+
+/// Track of a video, from the GPS log next to it or by OCR, with the speeds
+/// and dates read as usual in the country it was recorded in and the points
+/// timed from the recording start when known. Blocks until it is read.
+pub fn convert(path: impl AsRef<Path>, config: PipelineConfig) -> anyhow::Result<Track> {
+    convert_with(&Pipeline::new(config), path.as_ref())
+}
+
+/// [`convert`] with a pipeline set up by the caller
+pub(crate) fn convert_with(pipeline: &Pipeline, path: &Path) -> anyhow::Result<Track> {
+    let mut track = pipeline.track(path)?;
+    pipeline::localize(&mut track, pipeline.config());
+    track.start = timeline::clip_start(path, pipeline.config().file_name_time.as_ref());
+
+    Ok(track)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_between_threads() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Pipeline>();
+        send_sync::<PipelineConfig>();
+        send_sync::<Track>();
+        send_sync::<cancel::CancellationToken>();
+    }
+}
@@ -0,0 +1,213 @@
+//! A frame next to the map of the track with where the car was at that time
+//! (`--montage-at`), for incident reports.
+//!
+//! The map is drawn from the track alone, north up and to scale, without a
+//! basemap so that it needs no network.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use image::{Rgb, RgbImage};
+
+use crate::{
+    ffmpeg,
+    track::{self, Track},
+};
+
+/// Pixels around the track on the map
+const MARGIN: f64 = 40.0;
+const BACKGROUND: Rgb<u8> = Rgb([245, 245, 240]);
+const TRACK: Rgb<u8> = Rgb([40, 90, 200]);
+const START: Rgb<u8> = Rgb([30, 160, 60]);
+const MARKER: Rgb<u8> = Rgb([220, 30, 30]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+/// Save a montage for each time (seconds in the video) into `dir` as
+/// `montage_HH-MM-SS.png`, returns their paths.
+pub fn write(
+    input: &Path,
+    dir: &Path,
+    track: &Track,
+    times: &[f64],
+) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    let frames = times
+        .iter()
+        .enumerate()
+        .map(|(i, &at)| (i as u32 + 1, at))
+        .collect::<Vec<_>>();
+    ffmpeg::extract_frames_at(input, dir, &frames)?;
+
+    let mut montages = Vec::new();
+    for (frame_no, at) in frames {
+        let extracted = dir.join(format!("f{:09}.jpg", frame_no));
+        let still = image::open(&extracted);
+        _ = std::fs::remove_file(&extracted);
+        let Ok(still) = still else {
+            eprintln!(
+                "Warning: no frame at {}, past the end of the video?",
+                track::format_offset(at)
+            );
+            continue;
+        };
+
+        let position = position_at(track, at);
+        if position.is_none() {
+            eprintln!(
+                "Warning: no location at {}, its map has no marker",
+                track::format_offset(at)
+            );
+        }
+        let still = still.to_rgb8();
+        let map = map(track, position, still.height());
+        let mut montage = RgbImage::new(still.width() + map.width(), still.height());
+        image::imageops::replace(&mut montage, &still, 0, 0);
+        image::imageops::replace(&mut montage, &map, still.width() as i64, 0);
+
+        let path = dir.join(format!(
+            "montage_{}.png",
+            track::format_offset(at).replace(':', "-")
+        ));
+        montage
+            .save(&path)
+            .with_context(|| format!("write {}", path.display()))?;
+        montages.push(path);
+    }
+
+    Ok(montages)
+}
+
+/// Location at `sec` in the video, between the points around it. `None`
+/// outside the track and in intervals without GPS fix.
+pub fn position_at(track: &Track, sec: f64) -> Option<(f32, f32)> {
+    track.segments().into_iter().find_map(|segment| {
+        if let [p] = segment {
+            return (p.offset_sec == sec).then(|| p.coordinate.lat_lon());
+        }
+        segment.windows(2).find_map(|w| {
+            let (a, b) = (&w[0], &w[1]);
+            if !(a.offset_sec..=b.offset_sec).contains(&sec) {
+                return None;
+            }
+            let t = ((sec - a.offset_sec) / (b.offset_sec - a.offset_sec).max(f64::EPSILON)) as f32;
+            let ((a_lat, a_lon), (b_lat, b_lon)) = (a.coordinate.lat_lon(), b.coordinate.lat_lon());
+            Some((a_lat + (b_lat - a_lat) * t, a_lon + (b_lon - a_lon) * t))
+        })
+    })
+}
+
+/// `size` pixels square map of the track, with a marker at `position`.
+fn map(track: &Track, position: Option<(f32, f32)>, size: u32) -> RgbImage {
+    let mut map = RgbImage::from_pixel(size, size, BACKGROUND);
+    let points = track
+        .points
+        .iter()
+        .map(|p| p.coordinate.lat_lon())
+        .chain(position)
+        .map(|(lat, lon)| (lat as f64, lon as f64))
+        .collect::<Vec<_>>();
+    if points.is_empty() {
+        return map;
+    }
+
+    // equirectangular around the middle of the track, fit in the margin
+    let bound = |f: fn(&(f64, f64)) -> f64| {
+        let values = points.iter().map(f);
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        (min, values.fold(f64::NEG_INFINITY, f64::max))
+    };
+    let (south, north) = bound(|p| p.0);
+    let (west, east) = bound(|p| p.1);
+    let aspect = ((south + north) / 2.0).to_radians().cos();
+    let span = ((east - west) * aspect).max(north - south).max(1e-6);
+    let scale = (size as f64 - 2.0 * MARGIN).max(1.0) / span;
+    let center = size as f64 / 2.0;
+    let xy = |(lat, lon): (f32, f32)| {
+        (
+            center + (lon as f64 - (west + east) / 2.0) * aspect * scale,
+            center - (lat as f64 - (south + north) / 2.0) * scale,
+        )
+    };
+
+    for segment in track.segments() {
+        for w in segment.windows(2) {
+            let (a, b) = (xy(w[0].coordinate.lat_lon()), xy(w[1].coordinate.lat_lon()));
+            let steps = ((b.0 - a.0).hypot(b.1 - a.1) * 2.0).ceil().max(1.0) as u32;
+            for i in 0..=steps {
+                let t = i as f64 / steps as f64;
+                disc(
+                    &mut map,
+                    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t),
+                    2.0,
+                    TRACK,
+                );
+            }
+        }
+    }
+    if let Some(first) = track.points.first() {
+        disc(&mut map, xy(first.coordinate.lat_lon()), 6.0, START);
+    }
+    if let Some(position) = position {
+        disc(&mut map, xy(position), 13.0, WHITE);
+        disc(&mut map, xy(position), 10.0, MARKER);
+    }
+
+    map
+}
+
+fn disc(image: &mut RgbImage, (x, y): (f64, f64), radius: f64, color: Rgb<u8>) {
+    let (width, height) = image.dimensions();
+    let from = |v: f64| (v - radius).floor().max(0.0) as u32;
+    for py in from(y)..((y + radius).ceil().max(0.0) as u32).min(height) {
+        for px in from(x)..((x + radius).ceil().max(0.0) as u32).min(width) {
+            if (px as f64 + 0.5 - x).hypot(py as f64 + 0.5 - y) <= radius {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parser::Coordinate, track::TrackPoint};
+
+    fn track(points: &[(f64, f32, f32)]) -> Track {
+        let mut track = Track::from_frames(Vec::new(), 10);
+        track.points = points
+            .iter()
+            .enumerate()
+            .map(|(i, &(offset_sec, lat, lon))| TrackPoint {
+                frame_no: i as u32 + 1,
+                offset_sec,
+                coordinate: Coordinate::Decimal { lat, lon },
+                accel_peak: None,
+                confidence: None,
+                readings: Default::default(),
+            })
+            .collect();
+        track
+    }
+
+    #[test]
+    fn position_between_points() {
+        let track = track(&[(0.0, 51.0, 0.0), (10.0, 51.1, 0.2), (20.0, 51.1, 0.4)]);
+        let (lat, lon) = position_at(&track, 5.0).unwrap();
+        assert!((lat - 51.05).abs() < 1e-5 && (lon - 0.1).abs() < 1e-5);
+        assert_eq!(position_at(&track, 20.0), Some((51.1, 0.4)));
+        assert_eq!(position_at(&track, 25.0), None);
+    }
+
+    #[test]
+    fn marker_on_the_map() {
+        // west to east, the marker half way
+        let track = track(&[(0.0, 51.0, 0.0), (10.0, 51.0, 0.1)]);
+        let map = map(&track, position_at(&track, 5.0), 400);
+        assert_eq!(map.dimensions(), (400, 400));
+        assert_eq!(*map.get_pixel(200, 200), MARKER);
+        // the start on the left edge of the margin, the track on to the right
+        assert_eq!(*map.get_pixel(40, 200), START);
+        assert_eq!(*map.get_pixel(340, 200), TRACK);
+        assert_eq!(*map.get_pixel(200, 100), BACKGROUND);
+    }
+}
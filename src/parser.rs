@@ -1,81 +1,506 @@
 use std::str::FromStr;
 
 use anyhow::anyhow;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::ValueEnum;
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use serde::Serialize;
 
+use crate::{
+    dms::{dms_to_decimal, Dms},
+    speed::SpeedUnit,
+};
+
+/// Order of day and month in dates such as `06/07/2021`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DateOrder {
+    /// Day first, eg. in Europe and Australia
+    Dmy,
+    /// Month first, eg. in the US
+    Mdy,
+}
+
+#[allow(dead_code)]
 pub fn parse_coordinate_from_lines(lines: impl Into<String>) -> Vec<Coordinate> {
     lines
         .into()
         .split('\n')
         .flat_map(CoordinateDms::try_parse)
         .map(Coordinate::DegreeMinSec)
+        .filter(|c| !c.is_null())
         .collect::<Vec<_>>()
 }
 
+/// What the overlay of a single frame says about the position.
+#[derive(Clone)]
+pub enum Overlay {
+    Fix(Coordinate),
+    /// The camera explicitly shows it has no GPS fix (eg. `GPS: searching`)
+    NoFix,
+    /// Read, but a page of the overlay without the position, eg. the firmware
+    /// or number plate some cameras show in turn with it
+    OtherPage,
+    Unreadable,
+}
+
+/// How permissive coordinate parsing is, trading junk points for missed ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Also accept a missing or misread `°` and fix more OCR confusions (`l`, `I`, `B`, ...),
+    /// for noisy footage such as night drives
+    Loose,
+    #[default]
+    Normal,
+    /// Only the exact overlay layout with values in range, no OCR corrections
+    Strict,
+}
+
+/// How the overlay prints the position
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateFormat {
+    /// Try each format on every frame
+    #[default]
+    Auto,
+    /// Degrees, minutes and seconds, eg. `N51°25 48” E0°19 20”`
+    Dms,
+    /// Degrees and decimal minutes, eg. `N51°25.800' E0°19.333'` or
+    /// `N51 25.80 E000 19.33`
+    Ddm,
+    /// Decimal degrees, eg. `N51.43000 E0.32222` or `51.43000, 0.32222`
+    Decimal,
+}
+
+/// The format most positions of a video were read in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormatDetection {
+    pub format: CoordinateFormat,
+    /// Share of the positions read in `format`
+    pub share: f64,
+    /// Set with `--coordinate-format` rather than detected
+    pub pinned: bool,
+}
+
+/// Which format the positions of `overlays` were read in. `None` without any.
+pub fn detect_format<'a>(
+    overlays: impl Iterator<Item = &'a Overlay>,
+    setting: CoordinateFormat,
+) -> Option<FormatDetection> {
+    let mut counts = [
+        (CoordinateFormat::Dms, 0),
+        (CoordinateFormat::Ddm, 0),
+        (CoordinateFormat::Decimal, 0),
+    ];
+    for overlay in overlays {
+        if let Overlay::Fix(c) = overlay {
+            if let Some((_, count)) = counts.iter_mut().find(|(f, _)| *f == c.format()) {
+                *count += 1;
+            }
+        }
+    }
+    let total = counts.iter().map(|(_, count)| count).sum::<usize>();
+    if total == 0 {
+        return None;
+    }
+    // the first format on a tie
+    let (format, count) = counts.into_iter().rev().max_by_key(|(_, count)| *count)?;
+
+    Some(FormatDetection {
+        format,
+        share: count as f64 / total as f64,
+        pinned: setting != CoordinateFormat::Auto,
+    })
+}
+
+#[allow(dead_code)]
+pub fn parse_overlay(lines: impl Into<String>) -> Overlay {
+    parse_overlay_with(lines, Strictness::Normal)
+}
+
+pub fn parse_overlay_with(lines: impl Into<String>, strictness: Strictness) -> Overlay {
+    parse_overlay_as(lines, strictness, CoordinateFormat::Auto)
+}
+
+pub fn parse_overlay_as(
+    lines: impl Into<String>,
+    strictness: Strictness,
+    format: CoordinateFormat,
+) -> Overlay {
+    let lines: String = lines.into();
+    let mut no_fix = false;
+
+    for line in lines.split('\n') {
+        match parse_position(line, strictness, format) {
+            Some(c) if c.is_null() => no_fix = true,
+            Some(c) => return Overlay::Fix(c),
+            None => no_fix |= is_no_fix_marker(line),
+        }
+    }
+
+    if no_fix {
+        Overlay::NoFix
+    } else if is_other_page(&lines) {
+        Overlay::OtherPage
+    } else {
+        Overlay::Unreadable
+    }
+}
+
+/// A firmware version or number plate with nothing like a position or speed,
+/// as on the pages some cameras show in turn with the position. Anything else
+/// without a position is taken for a misread.
+fn is_other_page(text: &str) -> bool {
+    static POSITION: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"°|\b[NSEW] ?\d|\d ?[NSEW]\b|\d\.\d{3,}").unwrap());
+    static FIRMWARE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\b(fw|firmware|ver|version)\b|\bv\d+(\.\d+)+\b").unwrap());
+    // eg. `AB12 CDE` or `ABC-1234`
+    static PLATE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\b[A-Z0-9]{2,4}[ -]?[A-Z0-9]{3,4}\b").unwrap());
+    if POSITION.is_match(text) || parse_speed(text).is_some() {
+        return false;
+    }
+    let is_plate = |s: &str| {
+        s.chars().filter(char::is_ascii_uppercase).count() >= 2
+            && s.chars().filter(char::is_ascii_digit).count() >= 2
+    };
+
+    FIRMWARE.is_match(text) || PLATE.find_iter(text).any(|m| is_plate(m.as_str()))
+}
+
+/// Position printed on `line` in `format`. With `Auto` decimal minutes are
+/// tried first, as they would be misread as seconds.
+fn parse_position(
+    line: &str,
+    strictness: Strictness,
+    format: CoordinateFormat,
+) -> Option<Coordinate> {
+    let dms = || {
+        CoordinateDms::try_parse_with(line, strictness)
+            .ok()
+            .map(Coordinate::DegreeMinSec)
+    };
+    let ddm = || {
+        parse_ddm(&ocr_corrections(line, strictness))
+            .map(|(lat, lon)| Coordinate::DegreeDecimalMin { lat, lon })
+    };
+    let decimal = || {
+        parse_decimal(&ocr_corrections(line, strictness))
+            .map(|(lat, lon)| Coordinate::Decimal { lat, lon })
+    };
+
+    match format {
+        CoordinateFormat::Auto => ddm().or_else(decimal).or_else(dms),
+        CoordinateFormat::Dms => dms(),
+        CoordinateFormat::Ddm => ddm(),
+        CoordinateFormat::Decimal => decimal(),
+    }
+}
+
+/// Degrees and decimal minutes, eg. `N51°25.800' E0°19.333'`, or without
+/// the degree sign as Garmin prints them, eg. `N51 25.80 E000 19.33`
+fn parse_ddm(line: &str) -> Option<(f32, f32)> {
+    static REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"([NS]) ?(\d{1,2})(?: ?° ?| +)(\d{1,2}\.\d+)\D*?([EW]) ?(\d{1,3})(?: ?° ?| +)(\d{1,2}\.\d+)",
+        )
+        .unwrap()
+    });
+    let cap = REGEX.captures(line)?;
+    let part = |degrees: usize, minutes: usize| {
+        let minutes = cap[minutes].parse::<f32>().ok().filter(|m| *m < 60.0)?;
+        Some(cap[degrees].parse::<f32>().ok()? + minutes / 60.0)
+    };
+
+    signed(&cap[1], part(2, 3)?, &cap[4], part(5, 6)?)
+}
+
+/// Decimal degrees after or before the hemisphere, eg. `N51.43000 E0.32222`
+/// or `51.4300 N 0.3222 E`, or signed, eg. `51.43000, -0.32222`,
+/// `-33.8651 151.2099` or `LAT -33.8651 LON 151.2099`
+fn parse_decimal(line: &str) -> Option<(f32, f32)> {
+    static PREFIX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"([NS]) ?(\d{1,2}\.\d{3,})°?\D*?([EW]) ?(\d{1,3}\.\d{3,})").unwrap()
+    });
+    static SUFFIX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(\d{1,2}\.\d{3,})°? ?([NS])\D*?(\d{1,3}\.\d{3,})°? ?([EW])").unwrap()
+    });
+    static SIGNED: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?:^|[^\d.])(-?\d{1,2}\.\d{4,})°?(?: *, *| +)(-?\d{1,3}\.\d{4,})").unwrap()
+    });
+    static LABELLED: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)\blat\w*[:= ]*(-?\d{1,2}\.\d{3,})°?\D*?\bl(?:[o0]n|ng)\w*[:= ]*(-?\d{1,3}\.\d{3,})",
+        )
+        .unwrap()
+    });
+
+    if let Some(cap) = PREFIX.captures(line) {
+        return signed(&cap[1], cap[2].parse().ok()?, &cap[3], cap[4].parse().ok()?);
+    }
+    if let Some(cap) = SUFFIX.captures(line) {
+        return signed(&cap[2], cap[1].parse().ok()?, &cap[4], cap[3].parse().ok()?);
+    }
+    let cap = LABELLED.captures(line).or_else(|| SIGNED.captures(line))?;
+    let (lat, lon) = (cap[1].parse::<f32>().ok()?, cap[2].parse::<f32>().ok()?);
+
+    (lat.abs() <= 90.0 && lon.abs() <= 180.0).then_some((lat, lon))
+}
+
+/// Latitude and longitude signed by their hemisphere, `None` out of range
+fn signed(lat_direction: &str, lat: f32, lon_direction: &str, lon: f32) -> Option<(f32, f32)> {
+    if lat > 90.0 || lon > 180.0 {
+        return None;
+    }
+    let lat = if lat_direction == "S" { -lat } else { lat };
+    let lon = if lon_direction == "W" { -lon } else { lon };
+
+    Some((lat, lon))
+}
+
+/// Fix the letters OCR commonly reads for digits, more of them with `loose`
+fn ocr_corrections(line: &str, strictness: Strictness) -> String {
+    match strictness {
+        Strictness::Loose => line
+            .replace(['O', 'Q', 'D'], "0") // O/Q/D -> 0
+            .replace(['l', 'I'], "1")
+            .replace('B', "8")
+            .replace('Z', "2")
+            .replace('G', "6"),
+        Strictness::Normal => line.replace(['O', 'Q'], "0"), // O/Q -> 0
+        Strictness::Strict => line.to_string(),
+    }
+}
+
+/// Values some cameras print next to the position.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Readings {
+    /// Heading in degrees clockwise from north
+    pub heading: Option<f32>,
+    /// Speed as printed, in `speed_unit`
+    pub speed: Option<f32>,
+    /// Unit printed next to the speed, see [`crate::speed::resolve_units`]
+    pub speed_unit: Option<SpeedUnit>,
+    /// Date and time printed on the overlay, by the camera's clock
+    pub time: Option<NaiveDateTime>,
+    /// The date of `time` reads as day or month first (eg. `06/07/2021`) and
+    /// was read day first, see [`Readings::order_date`]
+    pub ambiguous_date: bool,
+}
+
+impl Readings {
+    /// Read an ambiguous date in `order`.
+    pub fn order_date(&mut self, order: DateOrder) {
+        if !std::mem::take(&mut self.ambiguous_date) || order == DateOrder::Dmy {
+            return;
+        }
+        if let Some(time) = self.time {
+            let date = time.date();
+            self.time = NaiveDate::from_ymd_opt(date.year(), date.day(), date.month())
+                .map(|d| d.and_time(time.time()));
+        }
+    }
+}
+
+pub fn parse_readings(text: &str) -> Readings {
+    let (speed, speed_unit) = parse_speed(text).unzip();
+    let (time, ambiguous_date) = parse_time(text).unzip();
+    Readings {
+        heading: parse_heading(text),
+        speed,
+        speed_unit: speed_unit.flatten(),
+        time,
+        ambiguous_date: ambiguous_date.unwrap_or_default(),
+    }
+}
+
+/// Date and time printed as `12:42:29 06/06/2021` (day first, or month first
+/// when that is no date) or `2021/06/06 12:42:29`, and whether the date could
+/// be read either way.
+fn parse_time(text: &str) -> Option<(NaiveDateTime, bool)> {
+    static TIME: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b(\d{1,2}):(\d{2}):(\d{2})\b").unwrap());
+    static DATE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"\b(?:(\d{2})[/.-](\d{2})[/.-](\d{4})|(\d{4})[/.-](\d{2})[/.-](\d{2}))\b")
+            .unwrap()
+    });
+
+    let number = |cap: &Captures, i| cap.get(i)?.as_str().parse::<u32>().ok();
+    let cap = TIME.captures(text)?;
+    let time = NaiveTime::from_hms_opt(number(&cap, 1)?, number(&cap, 2)?, number(&cap, 3)?)?;
+
+    let cap = DATE.captures(text)?;
+    let (date, ambiguous) = match number(&cap, 4) {
+        Some(year) => (
+            NaiveDate::from_ymd_opt(year as i32, number(&cap, 5)?, number(&cap, 6)?)?,
+            false,
+        ),
+        None => {
+            let (a, b, year) = (number(&cap, 1)?, number(&cap, 2)?, number(&cap, 3)? as i32);
+            let day_first = NaiveDate::from_ymd_opt(year, b, a);
+            let month_first = NaiveDate::from_ymd_opt(year, a, b);
+            (
+                day_first.or(month_first)?,
+                day_first.is_some() && month_first.is_some() && a != b,
+            )
+        }
+    };
+
+    Some((date.and_time(time), ambiguous))
+}
+
+/// Speed printed as `51MPH`, `82 km/h` or `SPD 82`.
+fn parse_speed(text: &str) -> Option<(f32, Option<SpeedUnit>)> {
+    static REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?i)\b(\d{1,3}(?:\.\d)?)\s*(mph|km/?h|kph)\b|\b(?:spd|speed)\W{0,2}(\d{1,3}(?:\.\d)?)")
+            .unwrap()
+    });
+
+    let cap = REGEX.captures(text)?;
+    let unit = cap
+        .get(2)
+        .map(|u| match u.as_str().to_ascii_lowercase().as_str() {
+            "mph" => SpeedUnit::Mph,
+            _ => SpeedUnit::Kmh,
+        });
+    let speed = cap.get(1).or_else(|| cap.get(3))?.as_str().parse().ok()?;
+
+    Some((speed, unit))
+}
+
+/// Heading printed as `HDG 123`, `Heading: 123°` or `123° SE`.
+fn parse_heading(text: &str) -> Option<f32> {
+    static REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?i:\b(?:hdg|heading|course|dir))\W{0,2}(\d{1,3}(?:\.\d+)?)|\b(\d{1,3}(?:\.\d+)?)\s*°\s*(?:NE|NW|SE|SW|N|E|S|W)\b")
+            .unwrap()
+    });
+
+    let cap = REGEX.captures(text)?;
+    let heading: f32 = cap.get(1).or_else(|| cap.get(2))?.as_str().parse().ok()?;
+    (heading < 360.0).then_some(heading)
+}
+
+/// Overlay states cameras print while waiting for satellites
+/// (eg. `GPS: searching`, `N--°--'--"` or `--.------`).
+fn is_no_fix_marker(line: &str) -> bool {
+    static REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)(gps\W*(search|no\W*fix|no\W*signal|lost)|no\W*gps|-{2,}\s*°|-{2,}\.-{2,})",
+        )
+        .unwrap()
+    });
+
+    REGEX.is_match(line)
+}
+
+#[derive(Clone)]
 pub enum Coordinate {
     DegreeMinSec(CoordinateDms),
+    /// Signed degrees and decimal minutes, as degrees
+    DegreeDecimalMin {
+        lat: f32,
+        lon: f32,
+    },
+    /// Signed decimal degrees, as read from GPS logs
+    Decimal {
+        lat: f32,
+        lon: f32,
+    },
 }
 
 impl Coordinate {
-    #[allow(dead_code)]
     pub fn to_decimal(&self) -> String {
         self.to_decimal_with_format("{lat}, {lon}")
     }
 
     pub fn to_decimal_with_format(&self, format: impl Into<String>) -> String {
+        let (lat, lon) = self.lat_lon();
+        let f: String = format.into();
+
+        f.replace("{lat}", &lat.to_string())
+            .replace("{lon}", &lon.to_string())
+    }
+
+    /// Signed decimal latitude and longitude
+    pub fn lat_lon(&self) -> (f32, f32) {
         match self {
             Coordinate::DegreeMinSec(dms) => {
                 let (lat, lon) = Self::get_lat_lon_for_dms(dms);
 
-                let f: String = format.into();
-
-                f.replace(
-                    "{lat}",
-                    &match dms.lat_direction {
+                (
+                    match dms.lat_direction {
                         DirectionLat::North => lat,
                         DirectionLat::South => -lat,
-                    }
-                    .to_string(),
-                )
-                .replace(
-                    "{lon}",
-                    &match dms.lon_direction {
+                    },
+                    match dms.lon_direction {
                         DirectionLon::East => lon,
                         DirectionLon::West => -lon,
-                    }
-                    .to_string(),
+                    },
                 )
             }
+            Coordinate::DegreeDecimalMin { lat, lon } | Coordinate::Decimal { lat, lon } => {
+                (*lat, *lon)
+            }
         }
     }
 
+    /// The format this was read in, `Decimal` for positions of GPS logs
+    pub fn format(&self) -> CoordinateFormat {
+        match self {
+            Coordinate::DegreeMinSec(_) => CoordinateFormat::Dms,
+            Coordinate::DegreeDecimalMin { .. } => CoordinateFormat::Ddm,
+            Coordinate::Decimal { .. } => CoordinateFormat::Decimal,
+        }
+    }
+
+    /// `0°0'0"` is what some cameras print before they get a fix
+    pub fn is_null(&self) -> bool {
+        self.lat_lon() == (0.0, 0.0)
+    }
+
     fn get_lat_lon_for_dms(dms: &CoordinateDms) -> (f32, f32) {
-        let lat =
-            dms.lat_degree as f32 + (dms.lat_min as f32 / 60.0) + (dms.lat_sec as f32 / 3600.0);
-        let lon =
-            dms.lon_degree as f32 + (dms.lon_min as f32 / 60.0) + (dms.lon_sec as f32 / 3600.0);
-        (lat, lon)
+        // digits only, never negative
+        let decimal = |degrees: u8, minutes: u8, seconds: f32| {
+            dms_to_decimal(&Dms {
+                negative: false,
+                degrees: degrees.into(),
+                minutes: minutes.into(),
+                seconds: seconds.into(),
+            }) as f32
+        };
+        (
+            decimal(dms.lat_degree, dms.lat_min, dms.lat_sec),
+            decimal(dms.lon_degree, dms.lon_min, dms.lon_sec),
+        )
     }
 }
 
+/// Digits of a position as printed, the hemisphere giving the sign. Every
+/// one read is in range: latitude up to 90°, longitude up to 180°, minutes and
+/// seconds below 60.
+#[derive(Clone)]
 pub struct CoordinateDms {
     lat_direction: DirectionLat,
-    lat_degree: i8,
-    lat_min: i8,
-    lat_sec: i8,
+    lat_degree: u8,
+    lat_min: u8,
+    /// Some cameras print tenths, eg. `48.5”`
+    lat_sec: f32,
 
     lon_direction: DirectionLon,
-    lon_degree: i8,
-    lon_min: i8,
-    lon_sec: i8,
+    lon_degree: u8,
+    lon_min: u8,
+    lon_sec: f32,
 }
 
+#[derive(Clone)]
 pub enum DirectionLat {
     North,
     South,
 }
 
+#[derive(Clone)]
 pub enum DirectionLon {
     East,
     West,
@@ -107,16 +532,30 @@ impl FromStr for DirectionLon {
 
 impl CoordinateDms {
     pub fn try_parse(input: &str) -> anyhow::Result<Self> {
+        Self::try_parse_with(input, Strictness::Normal)
+    }
+
+    pub fn try_parse_with(input: &str, strictness: Strictness) -> anyhow::Result<Self> {
         static REGEX: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"(?m)([N|S])[^\d]*(\d*)[^°]*°[^\d]*(\d*)[^\d]*(\d*).*([E|W])[^\d]*(\d*)[^°]*°[^\d]*(\d*)[^\d]*(\d*)").unwrap()
+            Regex::new(r"(?m)([N|S])[^\d]*(\d*)[^°]*°[^\d]*(\d*)[^\d]*(\d*(?:\.\d+)?).*([E|W])[^\d]*(\d*)[^°]*°[^\d]*(\d*)[^\d]*(\d*(?:\.\d+)?)").unwrap()
+        });
+        // degrees, minutes and seconds separated by anything, `°` or not
+        static LOOSE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"([NS])\D*(\d{1,2})\D+(\d{1,2})\D+(\d{1,2}(?:\.\d+)?).*?([EW])\D*(\d{1,3})\D+(\d{1,2})\D+(\d{1,2}(?:\.\d+)?)").unwrap()
+        });
+        static STRICT: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r#"\b([NS]) ?(\d{1,2}) ?° ?(\d{1,2})['’ ] ?(\d{1,2}(?:\.\d+)?) ?["”] +([EW]) ?(\d{1,3}) ?° ?(\d{1,2})['’ ] ?(\d{1,2}(?:\.\d+)?) ?["”]"#).unwrap()
         });
 
-        let input_s = input
-            .replace(['O'  ,'Q'], "0") // O/Q -> 0
-        ;
+        let regex = match strictness {
+            Strictness::Loose => &LOOSE,
+            Strictness::Normal => &REGEX,
+            Strictness::Strict => &STRICT,
+        };
+        let input_s = ocr_corrections(input, strictness);
 
-        match REGEX.captures_iter(&input_s).next() {
-            Some(cap) => Ok(Self {
+        let dms = match regex.captures_iter(&input_s).next() {
+            Some(cap) => Self {
                 lat_direction: Self::from_capture_as_str(&cap, 1)?.parse::<_>()?,
                 lat_degree: Self::from_capture_as_str(&cap, 2)?.parse::<_>()?,
                 lat_min: Self::from_capture_as_str(&cap, 3)?.parse::<_>()?,
@@ -125,9 +564,27 @@ impl CoordinateDms {
                 lon_degree: Self::from_capture_as_str(&cap, 6)?.parse::<_>()?,
                 lon_min: Self::from_capture_as_str(&cap, 7)?.parse::<_>()?,
                 lon_sec: Self::from_capture_as_str(&cap, 8)?.parse::<_>()?,
-            }),
-            _ => Err(anyhow!("failed")),
+            },
+            _ => return Err(anyhow!("failed")),
+        };
+
+        if !dms.is_in_range() {
+            return Err(anyhow!("coordinate out of range"));
         }
+
+        Ok(dms)
+    }
+
+    fn is_in_range(&self) -> bool {
+        let (lat, lon) = Coordinate::get_lat_lon_for_dms(self);
+
+        lat <= 90.0
+            && lon <= 180.0
+            && self.lat_min < 60
+            && self.lon_min < 60
+            && [self.lat_sec, self.lon_sec]
+                .iter()
+                .all(|s| (0.0..60.0).contains(s))
     }
 
     fn from_capture_as_str<'c>(cap: &'c Captures, index: usize) -> anyhow::Result<&'c str> {
@@ -141,6 +598,217 @@ impl CoordinateDms {
 mod test {
     use super::*;
 
+    #[test]
+    fn strictness() {
+        let parse = |line: &str, strictness| {
+            CoordinateDms::try_parse_with(line, strictness)
+                .ok()
+                .map(|c| Coordinate::DegreeMinSec(c).to_decimal())
+        };
+        let expected = Some("51.43, 0.32222223".to_string());
+
+        let clean = "N51°25 48” E0°19 20” 51MPH";
+        assert_eq!(parse(clean, Strictness::Strict), expected);
+        assert_eq!(parse(clean, Strictness::Loose), expected);
+
+        // `°` read as `*`, `1` read as `l`
+        let noisy = "N5l*25 48” E0*19 20” 51MPH";
+        assert_eq!(parse(noisy, Strictness::Loose), expected);
+        assert!(parse(noisy, Strictness::Normal).is_none());
+        assert!(parse(noisy, Strictness::Strict).is_none());
+
+        for out_of_range in [
+            "N51°25 98” E0°19 20” 51MPH",
+            "N51°65 48” E0°19 20” 51MPH",
+            "N91°25 48” E0°19 20” 51MPH",
+            "N90°00 01” E0°19 20” 51MPH",
+            "N51°25 48” E181°19 20” 51MPH",
+            "N51°25 48” E0°19 60.5” 51MPH",
+        ] {
+            for strictness in [Strictness::Loose, Strictness::Normal, Strictness::Strict] {
+                assert!(
+                    parse(out_of_range, strictness).is_none(),
+                    "{}",
+                    out_of_range
+                );
+            }
+        }
+
+        // tenths of seconds
+        let tenths = "N51°25 48.5” W0°19 20.25” 51MPH";
+        let expected = Some("51.430138, -0.32229167".to_string());
+        assert_eq!(parse(tenths, Strictness::Strict), expected);
+        assert_eq!(parse(tenths, Strictness::Normal), expected);
+        assert_eq!(parse(tenths, Strictness::Loose), expected);
+    }
+
+    #[test]
+    fn coordinate_formats() {
+        let read = |line: &str, format| match parse_overlay_as(line, Strictness::Normal, format) {
+            Overlay::Fix(c) => Some((c.format(), c.to_decimal())),
+            _ => None,
+        };
+        let auto = CoordinateFormat::Auto;
+
+        assert_eq!(
+            read("N51°25 48” E0°19 20” 51MPH", auto),
+            Some((CoordinateFormat::Dms, "51.43, 0.32222223".to_string()))
+        );
+        assert_eq!(
+            read("N51°25.800' W0°19.500' 51MPH", auto),
+            Some((CoordinateFormat::Ddm, "51.43, -0.325".to_string()))
+        );
+        assert_eq!(
+            read("S33.86882 E151.20930 60KM/H", auto),
+            Some((CoordinateFormat::Decimal, "-33.86882, 151.2093".to_string()))
+        );
+        assert_eq!(
+            read("51.43000N 0.32500W", auto),
+            Some((CoordinateFormat::Decimal, "51.43, -0.325".to_string()))
+        );
+        assert_eq!(
+            read("2024/05/01 08:15:00 51.4300, -0.3250", auto),
+            Some((CoordinateFormat::Decimal, "51.43, -0.325".to_string()))
+        );
+        assert_eq!(
+            read("51.4301 N 0.3222 E 44KM/H", auto),
+            Some((CoordinateFormat::Decimal, "51.4301, 0.3222".to_string()))
+        );
+        assert_eq!(
+            read("-33.8651, 151.2099", auto),
+            Some((CoordinateFormat::Decimal, "-33.8651, 151.2099".to_string()))
+        );
+        assert_eq!(
+            read("-33.8651 151.2099 60KM/H", auto),
+            Some((CoordinateFormat::Decimal, "-33.8651, 151.2099".to_string()))
+        );
+        assert_eq!(
+            read("LAT:-33.8651 LON:151.2099", auto),
+            Some((CoordinateFormat::Decimal, "-33.8651, 151.2099".to_string()))
+        );
+        assert_eq!(
+            read("Lat 51.430 Lng -0.325", CoordinateFormat::Decimal),
+            Some((CoordinateFormat::Decimal, "51.43, -0.325".to_string()))
+        );
+        assert_eq!(read("-95.1234, 151.2099", auto), None);
+        // minutes out of range
+        assert_eq!(read("N51°65.800' W0°19.500'", CoordinateFormat::Ddm), None);
+        assert_eq!(read("N51 65.80 W000 19.50", auto), None);
+
+        // pinned, other formats are not read
+        assert!(read("N51°25.800' W0°19.500'", CoordinateFormat::Dms).is_none());
+        assert!(read("N51°25 48” E0°19 20”", CoordinateFormat::Decimal).is_none());
+    }
+
+    #[test]
+    fn garmin_overlays() {
+        let read = |text: &str| match parse_overlay(text) {
+            Overlay::Fix(c) => Some((c.format(), c.to_decimal())),
+            _ => None,
+        };
+        let ddm = |position: &str| Some((CoordinateFormat::Ddm, position.to_string()));
+
+        assert_eq!(read("N51 25.80 E000 19.50"), ddm("51.43, 0.325"));
+        assert_eq!(read("N51 25.80 W000 19.50 31 MPH"), ddm("51.43, -0.325"));
+        assert_eq!(
+            read("06/06/2021 12:42:29 S33 52.106 E151 12.558 47 KM/H"),
+            ddm("-33.868435, 151.2093")
+        );
+        assert_eq!(read("N 51 25.800 E 000 19.500"), ddm("51.43, 0.325"));
+        // `0` of the degrees read as `O`
+        assert_eq!(read("N51 25.80 WOOO 19.50"), ddm("51.43, -0.325"));
+    }
+
+    #[test]
+    fn other_pages() {
+        let other = |text: &str| matches!(parse_overlay(text), Overlay::OtherPage);
+
+        assert!(other("FW V2.1.3 2024/05/01 08:15:00"));
+        assert!(other("AB12 CDE"));
+        assert!(other("ABC-1234 12:42:29"));
+        // misreads of the position page
+        assert!(!other("garbage"));
+        assert!(!other("51MPH AB12 CDE"));
+        assert!(!other("N5l*25 48 AB12 CDE"));
+        assert!(matches!(
+            parse_overlay("N51°25 48” E0°19 20” AB12 CDE"),
+            Overlay::Fix(_)
+        ));
+    }
+
+    #[test]
+    fn format_detection() {
+        let overlays = [
+            parse_overlay("N51°25.800' W0°19.500'"),
+            parse_overlay("N51°25.810' W0°19.500'"),
+            parse_overlay("N51°25 48” E0°19 20”"),
+            parse_overlay("unreadable"),
+        ];
+        let detection = detect_format(overlays.iter(), CoordinateFormat::Auto).unwrap();
+        assert_eq!(detection.format, CoordinateFormat::Ddm);
+        assert!((detection.share - 2.0 / 3.0).abs() < 1e-9);
+        assert!(!detection.pinned);
+
+        assert!(detect_format(overlays[3..].iter(), CoordinateFormat::Auto).is_none());
+    }
+
+    #[test]
+    fn speed() {
+        assert_eq!(
+            parse_speed("N51°25 48” E0°19 20” 51MPH 12:42:29"),
+            Some((51.0, Some(SpeedUnit::Mph)))
+        );
+        assert_eq!(parse_speed("82 km/h"), Some((82.0, Some(SpeedUnit::Kmh))));
+        assert_eq!(parse_speed("SPD 82"), Some((82.0, None)));
+        assert_eq!(parse_speed("N51°25 48” E0°19 20”"), None);
+    }
+
+    #[test]
+    fn heading() {
+        assert_eq!(
+            parse_heading("N51°25 48” E0°19 20” 51MPH HDG 123"),
+            Some(123.0)
+        );
+        assert_eq!(parse_heading("Heading: 45.5°"), Some(45.5));
+        assert_eq!(parse_heading("51MPH 270° W 12:42:29"), Some(270.0));
+    }
+
+    #[test]
+    fn time() {
+        let at = |s: &str| {
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|t| (t, false))
+        };
+        assert_eq!(
+            parse_time("N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021"),
+            at("2021-06-06 12:42:29")
+        );
+        assert_eq!(
+            parse_time("2024/05/01 08:15:00 N51°25 48” E0°19 20”"),
+            at("2024-05-01 08:15:00")
+        );
+        // month first, as some US cameras print it
+        assert_eq!(parse_time("17:38:46 11/24/2020"), at("2020-11-24 17:38:46"));
+        // misread
+        assert_eq!(parse_time("17:3§926 22/11/2020"), None);
+        assert_eq!(parse_time("25:38:46 24/11/2020"), None);
+        assert_eq!(parse_time("17:38:46"), None);
+
+        let mut readings = parse_readings("17:38:46 06/07/2020");
+        assert!(readings.ambiguous_date);
+        readings.order_date(DateOrder::Mdy);
+        assert_eq!(
+            readings.time.map(|t| t.to_string()),
+            Some("2020-06-07 17:38:46".to_string())
+        );
+        // only once
+        readings.order_date(DateOrder::Mdy);
+        assert_eq!(readings.time.unwrap().month(), 6);
+        assert_eq!(parse_heading("N51°25 48” E0°19 20” 51MPH"), None);
+        assert_eq!(parse_heading("HDG 400"), None);
+    }
+
     const INPUT_LINES: &str = r#"N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021
         N51°25 45” E0° 19 30” 48MPH 12:42:39 06/06/2021
         N51°25 40” E0° 19 40” 55MPH 12:42:49 06/06/2021
@@ -209,7 +877,14 @@ mod test {
     fn coordinate_dms() {
         let result = CoordinateDms::try_parse("N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021");
 
-        assert!(matches!(result, Ok(_)));
+        assert!(result.is_ok());
+
+        // Sydney, past the 127° an `i8` holds
+        let sydney = CoordinateDms::try_parse_with("S33°51 31” E151°12 51”", Strictness::Strict)
+            .map(Coordinate::DegreeMinSec)
+            .unwrap();
+        let (lat, lon) = sydney.lat_lon();
+        assert!((lat + 33.858_61).abs() < 1e-5 && (lon - 151.214_17).abs() < 1e-5);
     }
 
     #[test]
@@ -221,4 +896,28 @@ mod test {
 
         assert_eq!(parsed.len(), 40); // ~60 (target)
     }
+
+    #[test]
+    fn overlay_no_fix() {
+        for line in [
+            "GPS: searching 12:42:29 06/06/2021",
+            "N--°--'--\" E--°--'--\" 0MPH 12:42:29 06/06/2021",
+            "--.------ --.------ 0MPH",
+            "N0°0'0\" E0°0'0\" 0MPH 12:42:29 06/06/2021",
+        ] {
+            assert!(matches!(parse_overlay(line), Overlay::NoFix), "{}", line);
+        }
+    }
+
+    #[test]
+    fn overlay_fix_and_unreadable() {
+        assert!(matches!(
+            parse_overlay("N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021"),
+            Overlay::Fix(_)
+        ));
+        assert!(matches!(
+            parse_overlay("= _ 51°31\" 53\" E0°8' 54”. OMPIWSE 35:36\" 24/11/2028"),
+            Overlay::Unreadable
+        ));
+    }
 }
@@ -0,0 +1,119 @@
+//! Save a geotagged frame every few points (`--photo-log`), linked from the
+//! GPX points they were taken at.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{dms::decimal_to_dms, ffmpeg, track::Track};
+
+/// Extract the frame of every `every`th point into `dir` as
+/// `frame_<frame_no>.jpg` with its location in the EXIF GPS tags. Returns
+/// the photos written by frame number.
+pub fn write(
+    input: &Path,
+    dir: &Path,
+    track: &Track,
+    every: usize,
+) -> anyhow::Result<BTreeMap<u32, PathBuf>> {
+    std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    let points = track
+        .points
+        .iter()
+        .step_by(every.max(1))
+        .collect::<Vec<_>>();
+    let frames = points
+        .iter()
+        .map(|p| (p.frame_no, p.offset_sec))
+        .collect::<Vec<_>>();
+    ffmpeg::extract_frames_at(input, dir, &frames)?;
+
+    let mut photos = BTreeMap::new();
+    for p in points {
+        let extracted = dir.join(format!("f{:09}.jpg", p.frame_no));
+        let Ok(jpeg) = std::fs::read(&extracted) else {
+            continue;
+        };
+        let (lat, lon) = p.coordinate.lat_lon();
+        let photo = dir.join(format!("frame_{:09}.jpg", p.frame_no));
+        std::fs::write(&photo, geotag(&jpeg, lat as f64, lon as f64))
+            .with_context(|| format!("write {}", photo.display()))?;
+        _ = std::fs::remove_file(&extracted);
+        photos.insert(p.frame_no, photo);
+    }
+
+    Ok(photos)
+}
+
+/// Insert an EXIF segment with the GPS latitude and longitude right after the
+/// start of image marker.
+fn geotag(jpeg: &[u8], lat: f64, lon: f64) -> Vec<u8> {
+    // big-endian TIFF: IFD0 pointing to the GPS IFD at 26, its rationals at 92
+    let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+    tiff.extend(1u16.to_be_bytes());
+    entry(&mut tiff, 0x8825, 4, 1, &26u32.to_be_bytes());
+    tiff.extend(0u32.to_be_bytes());
+
+    tiff.extend(5u16.to_be_bytes());
+    entry(&mut tiff, 0x0000, 1, 4, &[2, 3, 0, 0]);
+    let lat_ref = if lat < 0.0 { b"S\0\0\0" } else { b"N\0\0\0" };
+    entry(&mut tiff, 0x0001, 2, 2, lat_ref);
+    entry(&mut tiff, 0x0002, 5, 3, &92u32.to_be_bytes());
+    let lon_ref = if lon < 0.0 { b"W\0\0\0" } else { b"E\0\0\0" };
+    entry(&mut tiff, 0x0003, 2, 2, lon_ref);
+    entry(&mut tiff, 0x0004, 5, 3, &116u32.to_be_bytes());
+    tiff.extend(0u32.to_be_bytes());
+    for v in [lat, lon] {
+        let dms = decimal_to_dms(v).rounded(4);
+        let sec = (dms.seconds * 10_000.0).round() as u32;
+        for (n, d) in [(dms.degrees, 1u32), (dms.minutes, 1), (sec, 10_000)] {
+            tiff.extend(n.to_be_bytes());
+            tiff.extend(d.to_be_bytes());
+        }
+    }
+
+    let mut app1 = vec![0xff, 0xe1];
+    app1.extend(((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    app1.extend(b"Exif\0\0");
+    app1.extend(tiff);
+
+    let (soi, rest) = jpeg.split_at(2.min(jpeg.len()));
+    [soi, &app1, rest].concat()
+}
+
+/// IFD entry with its value (or offset to it) padded to 4 bytes
+fn entry(out: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: &[u8]) {
+    out.extend(tag.to_be_bytes());
+    out.extend(kind.to_be_bytes());
+    out.extend(count.to_be_bytes());
+    out.extend(value);
+    out.resize(out.len() + 4 - value.len(), 0);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gps_exif() {
+        let tagged = geotag(b"\xff\xd8\xff\xd9", 51.4297, -0.3225);
+        assert_eq!(&tagged[..4], b"\xff\xd8\xff\xe1");
+        assert_eq!(&tagged[tagged.len() - 2..], b"\xff\xd9");
+
+        let tiff = &tagged[12..tagged.len() - 2];
+        let len = u16::from_be_bytes([tagged[4], tagged[5]]) as usize;
+        assert_eq!(len, 8 + tiff.len());
+        let rational = |at: usize| {
+            let n = u32::from_be_bytes(tiff[at..at + 4].try_into().unwrap());
+            let d = u32::from_be_bytes(tiff[at + 4..at + 8].try_into().unwrap());
+            n as f64 / d as f64
+        };
+        let lat = rational(92) + rational(100) / 60.0 + rational(108) / 3600.0;
+        assert!((lat - 51.4297).abs() < 1e-6);
+        // longitude reference
+        assert_eq!(tiff[26 + 2 + 3 * 12 + 8], b'W');
+    }
+}
@@ -0,0 +1,1844 @@
+//! Reading the track of a video: from the GPS log next to it, or by running
+//! OCR on the overlay of sampled frames.
+//!
+//! ```no_run
+//! use dash2gps::pipeline::{Pipeline, PipelineConfig};
+//!
+//! let pipeline = Pipeline::new(PipelineConfig {
+//!     interval: 5,
+//!     ..Default::default()
+//! });
+//! let mut samples = pipeline.process("footage.mov".as_ref())?;
+//! for sample in &mut samples {
+//!     println!("{} {},{}", sample.offset_sec, sample.lat, sample.lon);
+//! }
+//! let track = samples.finish()?;
+//! # anyhow::Ok(())
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{NaiveDateTime, Utc};
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use image::ImageOutputFormat;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+#[cfg(feature = "tesseract")]
+use tesseract::Tesseract;
+
+use crate::{
+    accuracy::Accuracy,
+    cancel::CancellationToken,
+    config::{self, Config},
+    country, ensemble,
+    event_log::{self, Event},
+    export::TrackWriter,
+    ffmpeg,
+    frame_cache::FrameCache,
+    glyphs,
+    parser::{self, DateOrder},
+    preprocess::{
+        self, Binarize, Channel, Color, Crop, Filter, FrameDecoder, Gray, OverlayPosition, Prepare,
+        Simd, OVERLAY_HEIGHT,
+    },
+    progress::{Heartbeat, Progress, Snapshot},
+    sidecar::{self, Source},
+    speed::{self, SpeedUnit},
+    stream::Live,
+    tessdata::{self, OcrModel, Traineddata},
+    timeline,
+    track::{self, FrameError, FrameResult, Stage, Track, TrackPoint},
+    watcher::FsWatcher,
+};
+
+/// Reads videos with a [`PipelineConfig`]. The OCR model is loaded on the
+/// first video read by OCR and kept for the next ones.
+pub struct Pipeline {
+    config: PipelineConfig,
+    recognizer: OnceCell<Recognizer>,
+    hooks: Hooks,
+}
+
+impl Pipeline {
+    pub fn new(config: PipelineConfig) -> Self {
+        Self {
+            config,
+            recognizer: OnceCell::new(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Stop reading when `cancel` is, the frames read so far make the track.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.hooks.cancel = cancel;
+        self
+    }
+
+    /// Report the progress of every video read to `observer`.
+    pub fn with_observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.hooks.observer = Some(Arc::new(observer));
+        self
+    }
+
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// Token stopping the videos being read
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.hooks.cancel.clone()
+    }
+
+    /// Track of a video, with implausible jumps dropped.
+    pub fn track(&self, path: &Path) -> anyhow::Result<Track> {
+        match find_sidecar(&self.config, path)? {
+            Some(sidecar) => read_log(&sidecar, path, &self.config, &self.hooks, None),
+            None => {
+                // points are only reported as found to an observer
+                let live = self.hooks.observer.as_ref().map(|_| self.live(None));
+                ocr_track(
+                    &self.config,
+                    self.recognizer()?,
+                    path,
+                    live,
+                    None,
+                    None,
+                    &self.hooks,
+                )?
+                .ok_or_else(|| anyhow::anyhow!("no track recovered"))
+            }
+        }
+    }
+
+    /// Read a video in the background, yielding its points in order as they
+    /// are found. Points are yielded before implausible jumps are dropped,
+    /// [`Samples::finish`] returns the track without them.
+    pub fn process(&self, path: &Path) -> anyhow::Result<Samples> {
+        let sidecar = find_sidecar(&self.config, path)?;
+        let recognizer = match sidecar {
+            Some(_) => None,
+            None => Some(self.recognizer()?.clone()),
+        };
+        let (sender, receiver) = unbounded();
+        let live = recognizer.as_ref().map(|_| self.live(Some(sender.clone())));
+        let config = self.config.clone();
+        let hooks = self.hooks.clone();
+        let path = path.to_path_buf();
+        let reading = std::thread::spawn(move || match (sidecar, recognizer) {
+            (Some(sidecar), _) => read_log(&sidecar, &path, &config, &hooks, Some(sender)),
+            (None, recognizer) => {
+                let recognizer = recognizer.expect("made without a GPS log");
+                ocr_track(&config, &recognizer, &path, live, None, None, &hooks)?
+                    .ok_or_else(|| anyhow::anyhow!("no track recovered"))
+            }
+        });
+
+        Ok(Samples { receiver, reading })
+    }
+
+    fn recognizer(&self) -> anyhow::Result<&Recognizer> {
+        self.recognizer.get_or_try_init(|| {
+            self.hooks.stage(PipelineStage::LoadingModel);
+            recognizer_for(&self.config)
+        })
+    }
+
+    /// Points found while the video is read, for `sender` and the observer
+    fn live(&self, sender: Option<Sender<GpsSample>>) -> Live {
+        Live::new(
+            Box::new(SampleSender {
+                sender,
+                observer: self.hooks.observer.clone(),
+            }),
+            self.config.interval,
+            self.config.sample_offset.unwrap_or_default(),
+            1,
+        )
+    }
+}
+
+/// Track of a GPS log, its points sent to `sender` and the observer.
+fn read_log(
+    log: &Path,
+    video: &Path,
+    config: &PipelineConfig,
+    hooks: &Hooks,
+    sender: Option<Sender<GpsSample>>,
+) -> anyhow::Result<Track> {
+    hooks.stage(PipelineStage::ReadingLog);
+    let track = sidecar::read(log, video, config.interval)?;
+    let mut points = SampleSender {
+        sender,
+        observer: hooks.observer.clone(),
+    };
+    for p in &track.points {
+        points.point(p)?;
+    }
+
+    Ok(track)
+}
+
+/// Steps of reading a video, see [`Observer::on_stage_change`]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStage {
+    /// Loading the OCR model, before the first video read by OCR
+    LoadingModel,
+    /// Reading the GPS log next to the video instead of running OCR
+    ReadingLog,
+    /// ffmpeg extracts frames while the workers read them
+    ReadingFrames,
+    /// ffmpeg is done, the workers read the frames left
+    Draining,
+    /// The track is made from the frames read
+    BuildingTrack,
+}
+
+/// Told how reading a video goes, eg. to show it in a GUI. Called from the
+/// worker threads, so implementations should return quickly.
+pub trait Observer: Send + Sync {
+    fn on_stage_change(&self, _stage: PipelineStage) {}
+
+    /// After every frame read by OCR
+    fn on_progress(&self, _progress: &Snapshot) {}
+
+    /// A point was found, in order of the frames
+    fn on_point(&self, _sample: &GpsSample) {}
+}
+
+/// Stops and reports on the reading of a video
+#[derive(Clone, Default)]
+pub struct Hooks {
+    pub cancel: CancellationToken,
+    pub observer: Option<Arc<dyn Observer>>,
+}
+
+impl Hooks {
+    pub fn new(cancel: CancellationToken) -> Self {
+        Self {
+            cancel,
+            observer: None,
+        }
+    }
+
+    fn stage(&self, stage: PipelineStage) {
+        if let Some(observer) = &self.observer {
+            observer.on_stage_change(stage);
+        }
+    }
+}
+
+/// A position read from a video
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GpsSample {
+    pub frame_no: u32,
+    /// Seconds since the start of the video
+    pub offset_sec: f64,
+    pub lat: f32,
+    pub lon: f32,
+    /// Speed printed on the overlay, once its unit is known
+    pub speed_kmh: Option<f32>,
+    /// Heading printed on the overlay, degrees clockwise from north
+    pub heading: Option<f32>,
+    /// Clock printed on the overlay
+    pub time: Option<NaiveDateTime>,
+    /// OCR confidence (0-100), `None` when read from a GPS log
+    pub confidence: Option<i32>,
+}
+
+impl From<&TrackPoint> for GpsSample {
+    fn from(p: &TrackPoint) -> Self {
+        let (lat, lon) = p.coordinate.lat_lon();
+        Self {
+            frame_no: p.frame_no,
+            offset_sec: p.offset_sec,
+            lat,
+            lon,
+            speed_kmh: p.speed_kmh(),
+            heading: p.readings.heading,
+            time: p.readings.time,
+            confidence: p.confidence,
+        }
+    }
+}
+
+/// Points of a video being read, see [`Pipeline::process`]
+pub struct Samples {
+    receiver: Receiver<GpsSample>,
+    reading: JoinHandle<anyhow::Result<Track>>,
+}
+
+impl Samples {
+    /// Wait for the video to be read, returns its track.
+    pub fn finish(self) -> anyhow::Result<Track> {
+        drop(self.receiver);
+        self.reading
+            .join()
+            .map_err(|_| anyhow::anyhow!("reading the video panicked"))?
+    }
+}
+
+impl Iterator for Samples {
+    type Item = GpsSample;
+
+    fn next(&mut self) -> Option<GpsSample> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Passes the points of [`Live`] to [`Samples`] and the observer
+struct SampleSender {
+    sender: Option<Sender<GpsSample>>,
+    observer: Option<Arc<dyn Observer>>,
+}
+
+impl TrackWriter for SampleSender {
+    fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+        let sample = GpsSample::from(p);
+        if let Some(observer) = &self.observer {
+            observer.on_point(&sample);
+        }
+        if let Some(sender) = &self.sender {
+            // the samples may be dropped before the end
+            _ = sender.send(sample);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// How the track of a video is recovered
+#[derive(clap::Args, Clone, Debug, Serialize)]
+pub struct PipelineConfig {
+    /// Defaults suiting a camera: `nextbase`, `viofo`, `blackvue`, `garmin` or `generic`, or
+    /// a profile of `~/.config/dash2gps/profiles.json`. The options given still win
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// TOML, YAML or JSON file setting any option, eg. `interval = 5`, and defining
+    /// profiles in `[profiles.NAME]` tables. By default `~/.config/dash2gps/config.toml`
+    /// when it exists
+    #[arg(long, value_name = "PATH")]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// Find locations at interval in the video
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    pub interval: u32,
+
+    /// Sample this far into every interval, eg. `0.5s`, for cameras showing the position and
+    /// other pages (firmware, number plate) in turn: shift it until every frame hits the
+    /// position page
+    #[arg(long, value_name = "DURATION", value_parser = track::parse_duration)]
+    pub sample_offset: Option<f64>,
+
+    /// Threads of every stage, unless set by `--ffmpeg-threads`, `--preprocess-workers` or
+    /// `--ocr-workers`. By default they are based on the number of CPUs
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+    pub threads: Option<u8>,
+
+    /// Threads ffmpeg decodes the video with, by default half the CPUs
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(u8).range(1..))]
+    pub ffmpeg_threads: Option<u8>,
+
+    /// Workers cropping the overlay out of the frames, by default a quarter of the CPUs
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(u8).range(1..))]
+    pub preprocess_workers: Option<u8>,
+
+    /// Workers reading the overlay crops, by default one per CPU
+    #[arg(long, value_name = "NUM", value_parser = clap::value_parser!(u8).range(1..))]
+    pub ocr_workers: Option<u8>,
+
+    /// Decode the video with this ffmpeg hardware acceleration, eg. `videotoolbox` on macOS,
+    /// `cuda`, `vaapi` or `auto`
+    #[arg(long, value_name = "METHOD")]
+    pub hwaccel: Option<String>,
+
+    /// Read videos longer than this in chunks of this length, eg. `00:30:00`: the frames of a
+    /// chunk are extracted once those of the previous one are read and removed, which bounds
+    /// the space they take. Not with `--reuse-frames`
+    #[arg(long, value_name = "DURATION", value_parser = track::parse_offset)]
+    pub chunk: Option<f64>,
+
+    /// Print a status line (frames queued and read, share parsed, position in the video) every
+    /// this many seconds while the video is read, `0` for none
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub status_every: u64,
+
+    /// Keep the extracted frames in the cache folder, keyed on the hash of the video and the
+    /// interval, and reuse them instead of running ffmpeg when they are already there
+    #[arg(long)]
+    pub reuse_frames: bool,
+
+    /// Write the same bytes for the same video and options, eg. to compare the outputs of two
+    /// versions: one worker per stage so points are found in order, a temporary folder named
+    /// after the video and outputs dated `SOURCE_DATE_EPOCH` (1970-01-01 when unset) rather
+    /// than now. Slower
+    #[arg(long)]
+    pub deterministic: bool,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub prepare: PrepareConfig,
+
+    /// Where to read locations from
+    #[arg(long, value_enum, default_value_t = Source::Auto)]
+    pub source: Source,
+
+    /// Unit of the speed printed on the overlay, by default the unit printed next to it
+    /// (the one of the `--country` when there is none). Checked against the distance between
+    /// points
+    #[arg(long, value_enum)]
+    pub speed_unit: Option<SpeedUnit>,
+
+    /// Order of day and month in overlay dates such as `06/07/2021`, by default the one of the
+    /// `--country`
+    #[arg(long, value_enum)]
+    pub date_order: Option<DateOrder>,
+
+    /// Country the footage was recorded in (ISO code, eg. `GB`), which sets the defaults of
+    /// `--speed-unit` and `--date-order`. Found from the first locations by default
+    #[arg(long, value_name = "CODE", value_parser = country::parse)]
+    pub country: Option<&'static country::Country>,
+
+    /// Fastest the vehicle goes, eg. `200kmh` or `120mph` (km/h without a unit): a point
+    /// reached faster from the last one kept is dropped as a misread, and counted in the
+    /// summary
+    #[arg(long, value_name = "SPEED", default_value = "300kmh", value_parser = speed::parse_speed)]
+    pub max_speed: f64,
+
+    /// How permissive reading coordinates from the overlay is: `loose` recovers more points
+    /// from noisy footage, `strict` lets no junk through
+    #[arg(long, value_enum, default_value_t = parser::Strictness::Normal)]
+    pub parser_strictness: parser::Strictness,
+
+    /// How the overlay prints the position, to pin it when auto-detection picks the wrong one.
+    /// The format read is printed in the summary
+    #[arg(long, value_enum, default_value_t = parser::CoordinateFormat::Auto)]
+    pub coordinate_format: parser::CoordinateFormat,
+
+    /// Where the recording start is in the video file names, as a regex with `year`, `month`,
+    /// `day`, `hour`, `minute` and `second` groups, eg.
+    /// `(?P<year>\d{2})(?P<month>\d{2})(?P<day>\d{2})_(?P<hour>\d{2})(?P<minute>\d{2})(?P<second>\d{2})`.
+    /// By default names such as `2021_0606_124229_001.MP4` are read, the time the container
+    /// records otherwise
+    #[arg(long, value_name = "REGEX")]
+    pub file_name_time: Option<timeline::FileNameTime>,
+
+    /// How the overlay is read: `auto` uses Tesseract and the built-in recognizer of common
+    /// dashcam fonts for frames Tesseract cannot read, or when no model is found
+    #[arg(long, value_enum, default_value_t = OcrEngine::Auto)]
+    pub ocr_engine: OcrEngine,
+
+    /// Run both Tesseract and the built-in recognizer on every frame and vote on each field
+    /// (latitude, longitude, speed, heading), weighted by the confidence of each
+    #[arg(long)]
+    pub ocr_ensemble: bool,
+
+    /// Give up on a frame Tesseract is still reading after this long, eg. `10s`, rather than
+    /// have it hold up a worker: the frame is counted as an OCR error, and read with the
+    /// built-in recognizer with `--ocr-engine auto`
+    #[arg(long, value_name = "DURATION", value_parser = track::parse_duration)]
+    pub ocr_timeout: Option<f64>,
+
+    /// Read a frame that timed out once more, made plain black and white
+    #[arg(long, requires = "ocr_timeout")]
+    pub ocr_timeout_retry: bool,
+
+    /// Set a Tesseract variable, eg. `--ocr-var classify_bln_numeric_mode=1`. Can be repeated
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_key_value)]
+    pub ocr_var: Vec<(String, String)>,
+
+    /// Use the `fast` or `best` English model published by the Tesseract project, downloaded on
+    /// first use, instead of the `.traineddata` next to the executable or in the current folder
+    #[arg(long, value_enum)]
+    pub ocr_model: Option<OcrModel>,
+
+    /// Use this `.traineddata`, eg. a model trained on dashcam overlay fonts
+    #[arg(long, value_name = "PATH", conflicts_with = "ocr_model")]
+    pub ocr_model_file: Option<PathBuf>,
+}
+
+impl PipelineConfig {
+    /// Options given as on the command line, eg. `["--interval", "5"]`, with
+    /// the defaults of the `--config` file and `--profile` given if any
+    pub fn from_args<I, T>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        #[derive(Parser)]
+        #[command(name = "dash2gps")]
+        struct Options {
+            #[command(flatten)]
+            config: PipelineConfig,
+        }
+        let args = std::iter::once("dash2gps".into())
+            .chain(args.into_iter().map(Into::into))
+            .collect::<Vec<std::ffi::OsString>>();
+        let file = match config::arg_value(&args, "--config") {
+            Some(path) => Some(Config::read(Path::new(&path))?),
+            None => None,
+        };
+        let profile = config::arg_value(&args, "--profile").map(|p| p.to_string_lossy().into());
+        let command = config::apply(Options::command(), file.as_ref(), profile)?;
+        let matches = command.try_get_matches_from(args)?;
+        Ok(Options::from_arg_matches(&matches)?.config)
+    }
+}
+
+impl Default for PipelineConfig {
+    /// The defaults of the command line options
+    fn default() -> Self {
+        Self::from_args(std::iter::empty::<&str>()).expect("defaults parse")
+    }
+}
+
+// How the overlay crops are prepared for OCR
+#[derive(clap::Args, Clone, Debug, Serialize)]
+pub struct PrepareConfig {
+    /// Where the overlay is in the 1280x720 frames, as `WIDTHxHEIGHT+X+Y` or `X,Y,WIDTH,HEIGHT`,
+    /// by default the bottom 50 pixels. `--detect-overlay` finds it
+    #[arg(long, value_name = "GEOMETRY")]
+    pub crop: Option<Crop>,
+
+    /// Read the overlay from a full-width strip at the `top` or `bottom` of the frame, eg. for
+    /// Viofo cameras printing it at the top. Not with `--crop`
+    #[arg(long, value_enum, conflicts_with = "crop")]
+    pub overlay_position: Option<OverlayPosition>,
+
+    /// Height of that strip in the 1280x720 frames the video is scaled to, 50 by default: the
+    /// overlay takes as many pixels whatever the resolution, eg. 100 at 1440p is 50 here
+    #[arg(long, value_name = "PIXELS", conflicts_with = "crop", value_parser = clap::value_parser!(u32).range(1..=720))]
+    pub overlay_height: Option<u32>,
+
+    /// Prepare the overlay crops in one vectorized pass, `off` runs each step separately
+    #[arg(long, value_enum, default_value_t = Simd::Auto)]
+    pub simd: Simd,
+
+    /// Extra filters for the overlay crops, comma separated: `unsharp[:AMOUNT]` sharpens text
+    /// blurred by vibration, eg. `--preprocess unsharp:1.5`
+    #[arg(long, value_name = "FILTERS", value_delimiter = ',')]
+    pub preprocess: Vec<Filter>,
+
+    /// Color of the overlay text as `RRGGBB`, eg. `ffff00` for yellow: pixels near it are read
+    /// as text, where the brightness washes colored text out
+    #[arg(long, value_name = "RRGGBB", conflicts_with = "channel")]
+    pub overlay_color: Option<Color>,
+
+    /// How far from `--overlay-color` a pixel is still text, as the distance between their RGB
+    /// values (0-441)
+    #[arg(long, value_name = "DISTANCE", default_value_t = 60, value_parser = clap::value_parser!(u16).range(1..=441))]
+    pub color_tolerance: u16,
+
+    /// Read the overlay from one color channel rather than the brightness, eg. `red` for
+    /// yellow text
+    #[arg(long, value_enum)]
+    pub channel: Option<Channel>,
+
+    /// How the crops are made black text on white for OCR
+    #[arg(long, value_enum, default_value_t = Binarize::Contrast)]
+    pub binarize: Binarize,
+}
+
+impl PrepareConfig {
+    /// `--crop`, or the strip of `--overlay-position` and `--overlay-height`
+    pub fn crop(&self) -> Option<Crop> {
+        match (self.overlay_position, self.overlay_height) {
+            (None, None) => self.crop,
+            (position, height) => Some(Crop::strip(
+                position.unwrap_or(OverlayPosition::Bottom),
+                height.unwrap_or(OVERLAY_HEIGHT),
+            )),
+        }
+    }
+
+    /// How to prepare the crops of the overlay at `crop`
+    pub fn prepare(&self, crop: Option<Crop>) -> Prepare {
+        let gray = match (self.overlay_color, self.channel) {
+            (Some(color), _) => Gray::Key {
+                color,
+                tolerance: self.color_tolerance,
+            },
+            (None, Some(channel)) => Gray::Channel(channel),
+            (None, None) => Gray::Luma,
+        };
+        Prepare {
+            crop,
+            simd: self.simd,
+            gray,
+            filters: self.preprocess.clone(),
+            binarize: self.binarize,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrEngine {
+    /// Tesseract, falling back to the built-in recognizer
+    Auto,
+    /// Only Tesseract
+    Tesseract,
+    /// Only the built-in recognizer, needs no Tesseract model
+    Glyphs,
+}
+
+/// The OCR engine picked for `--ocr-engine`
+#[derive(Clone)]
+pub enum Recognizer {
+    Tesseract {
+        data: Traineddata,
+        vars: Vec<(String, String)>,
+        /// Try the built-in recognizer on frames Tesseract cannot read
+        fallback: bool,
+        /// Run both on every frame, see [`ensemble`]
+        ensemble: bool,
+    },
+    Glyphs,
+}
+
+impl Recognizer {
+    /// Load what reading the first frame would, so it is done while ffmpeg
+    /// starts rather than after. Errors show again on the frames.
+    fn warm_up(&self) {
+        match self {
+            Recognizer::Tesseract {
+                data,
+                vars,
+                fallback,
+                ensemble,
+            } => {
+                _ = warm_up_ocr(data, vars);
+                if *fallback || *ensemble {
+                    glyphs::warm_up();
+                }
+            }
+            Recognizer::Glyphs => glyphs::warm_up(),
+        }
+    }
+}
+
+/// GPS log to read instead of running OCR, as allowed by `--source`.
+pub fn find_sidecar(opts: &PipelineConfig, input: &Path) -> anyhow::Result<Option<PathBuf>> {
+    Ok(match opts.source {
+        Source::Ocr => None,
+        Source::Auto => sidecar::find(input),
+        Source::Sidecar => Some(
+            sidecar::find(input)
+                .ok_or_else(|| anyhow::anyhow!("no GPS log found next to {}", input.display()))?,
+        ),
+    })
+}
+
+/// Recover the track by running OCR on sampled frames, printing points with
+/// `live` as they are found.
+///
+/// Returns `None` in `--preview` mode (the number of frames and the folder for
+/// the crops), where only a per-frame report is printed. With `only` just the
+/// frames `(frame_no, offset_sec)` are read. Once the token of `hooks` is
+/// cancelled, the frames extracted so far make the track.
+pub fn ocr_track(
+    opts: &PipelineConfig,
+    recognizer: &Recognizer,
+    input: &Path,
+    live: Option<Live>,
+    preview: Option<(u32, &Path)>,
+    only: Option<&[(u32, f64)]>,
+    hooks: &Hooks,
+) -> anyhow::Result<Option<Track>> {
+    let cancel = &hooks.cancel;
+    if opts.sample_offset.unwrap_or_default() >= opts.interval as f64 {
+        anyhow::bail!("`--sample-offset` must be shorter than `--interval`");
+    }
+    let mut workers = Vec::new();
+    let workspace = match opts.deterministic {
+        // frame paths end up in the errors reported
+        true => Workspace::for_input(input)?,
+        false => Workspace::new()?,
+    };
+
+    let (sender, receiver) = unbounded();
+
+    let cache = match (opts.reuse_frames, only, preview) {
+        (true, None, None) => Some(FrameCache::new(
+            input,
+            opts.interval,
+            opts.sample_offset.unwrap_or_default(),
+        )?),
+        _ => None,
+    };
+    let cached = cache.as_ref().and_then(|c| c.load());
+    let frame_path = match &cache {
+        Some(cache) => {
+            if cached.is_none() {
+                cache.clear()?;
+            }
+            cache.dir.clone()
+        }
+        None => workspace.new_folder("frames")?,
+    };
+    let resize_path = workspace.new_folder("frames-resize")?;
+    let chunks = match (opts.chunk, only, preview, &cache) {
+        (Some(chunk), None, None, None) => {
+            ffmpeg::chunks(ffmpeg::probe_duration(input), chunk, opts.interval)
+        }
+        _ => Vec::new(),
+    };
+
+    let mut watcher = FsWatcher::new(frame_path.clone(), sender)?;
+    watcher.start()?;
+    // ffmpeg writes just the overlay, but frames kept for `--reuse-frames` may
+    // be read again with another `--crop`
+    let (region, crop) = match (&cache, only) {
+        (None, None) => {
+            let (region, crop) = Crop::extracted(opts.prepare.crop());
+            (Some(region), Some(crop))
+        }
+        _ => (None, opts.prepare.crop()),
+    };
+
+    let preview_dir = match preview {
+        Some((_, dir)) => {
+            std::fs::create_dir_all(dir).context("create preview folder")?;
+            Some(dir.to_path_buf())
+        }
+        None => None,
+    };
+    let ctx = Arc::new(WorkerContext {
+        tmp_path: resize_path,
+        recognizer: recognizer.clone(),
+        live: live.filter(|_| preview_dir.is_none()),
+        preview_dir,
+        interval: opts.interval,
+        strictness: opts.parser_strictness,
+        coordinate_format: opts.coordinate_format,
+        sample_offset: opts.sample_offset.unwrap_or_default(),
+        prepare: opts.prepare.prepare(crop),
+        accuracy: Accuracy::default(),
+        progress: Progress::default(),
+        remove_read: !chunks.is_empty(),
+        ocr_timeout: opts
+            .ocr_timeout
+            .map(|t| (Duration::from_secs_f64(t), opts.ocr_timeout_retry)),
+        done: AtomicBool::new(false),
+        observer: hooks.observer.clone(),
+        duration_sec: hooks
+            .observer
+            .as_ref()
+            .and_then(|_| ffmpeg::probe_duration(input)),
+    });
+    let concurrency = Concurrency::new(opts);
+    let (prepared, prepared_receiver) = unbounded();
+    let preprocessing = (0..concurrency.preprocess)
+        .map(|_| preprocess_worker(receiver.clone(), prepared.clone(), ctx.clone()))
+        .collect::<Vec<_>>();
+    // the OCR workers stop once the preprocess workers drop their senders
+    drop(prepared);
+    for _ in 0..concurrency.ocr {
+        workers.push(ocr_worker(prepared_receiver.clone(), ctx.clone()));
+    }
+    // `--preview` already describes every frame
+    let heartbeat = (opts.status_every > 0 && preview.is_none()).then(|| {
+        let ctx = ctx.clone();
+        Heartbeat::start(Duration::from_secs(opts.status_every), move || {
+            ctx.progress.line(ctx.interval)
+        })
+    });
+
+    let rate = match (only, &cached) {
+        (None, None) => ffmpeg::probe_frame_rate(input),
+        _ => None,
+    };
+    let vfr = match rate {
+        Some(rate) if rate.is_variable() => {
+            eprintln!(
+                "Warning: variable frame rate video (nominal {:.2} fps, average {:.2} fps), sampling frames by timestamp",
+                rate.nominal, rate.average
+            );
+            true
+        }
+        _ => false,
+    };
+    let options = ffmpeg::ExtractOptions {
+        interval_sec: opts.interval,
+        threads: concurrency.ffmpeg,
+        max_frames: preview.map(|(n, _)| n),
+        vfr,
+        hwaccel: opts.hwaccel.clone(),
+        crop: region,
+        start_sec: 0,
+        duration_sec: None,
+        offset_sec: opts.sample_offset.unwrap_or_default(),
+    };
+    hooks.stage(PipelineStage::ReadingFrames);
+    let extraction = match (cached, only) {
+        (Some(extraction), _) => {
+            eprintln!(
+                "Reusing {} frames extracted earlier, from {}",
+                extraction.frames,
+                frame_path.display()
+            );
+            extraction
+        }
+        (None, Some(frames)) => ffmpeg::extract_frames_at(input, &frame_path, frames)
+            .context("extract frame using ffmpeg")?,
+        (None, None) if !chunks.is_empty() => {
+            extract_in_chunks(input, &frame_path, &watcher, &chunks, options, cancel)?
+        }
+        (None, None) => {
+            let extraction = ffmpeg::extract_frames(input, &frame_path, &options, cancel)
+                .context("extract frame using ffmpeg")?;
+            // frames of an interrupted run are not all there
+            if let Some(cache) = cache.filter(|_| !cancel.is_cancelled()) {
+                if let Err(e) = cache.save(&extraction) {
+                    eprintln!("Warning: frames not kept for --reuse-frames: {:#}", e);
+                }
+            }
+            extraction
+        }
+    };
+    // sends every frame of a reused extraction
+    watcher.finish()?;
+    hooks.stage(PipelineStage::Draining);
+
+    ctx.done.store(true, Ordering::Relaxed);
+    for w in preprocessing {
+        _ = w.join();
+    }
+
+    let mut results = workers
+        .into_iter()
+        .filter_map(|w| w.join().ok())
+        .flatten()
+        .collect::<Vec<_>>();
+    drop(heartbeat);
+    if let Some(live) = &ctx.live {
+        live.finish().context("print the points")?;
+    }
+    if cancel.is_cancelled() {
+        eprintln!("Interrupted, using the frames read so far");
+    }
+    hooks.stage(PipelineStage::BuildingTrack);
+
+    for r in &mut results {
+        r.pts_sec = extraction.pts.get(&r.frame_no).copied().or_else(|| {
+            // with an offset the nominal positions are off the interval
+            opts.sample_offset
+                .map(|offset| track::nominal_offset(r.frame_no, opts.interval, offset))
+        });
+    }
+
+    let track = build_track(results, opts);
+    if preview.is_some() {
+        print_summary(&track);
+        return Ok(None);
+    }
+
+    if only.is_none() {
+        report_readable(input, &extraction, opts.interval);
+    }
+
+    Ok(Some(track))
+}
+
+/// Extract the frames of each chunk starting at `chunks`, once the workers
+/// have read (and removed) those of the previous one.
+fn extract_in_chunks(
+    input: &Path,
+    frame_path: &Path,
+    watcher: &FsWatcher,
+    chunks: &[u64],
+    options: ffmpeg::ExtractOptions,
+    cancel: &CancellationToken,
+) -> anyhow::Result<ffmpeg::Extraction> {
+    let mut extraction = ffmpeg::Extraction::default();
+    for (i, &start) in chunks.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let end = chunks.get(i + 1).copied();
+        let chunk = ffmpeg::extract_frames(
+            input,
+            frame_path,
+            &ffmpeg::ExtractOptions {
+                start_sec: start,
+                duration_sec: end.map(|end| end - start),
+                hwaccel: options.hwaccel.clone(),
+                ..options
+            },
+            cancel,
+        )
+        .context("extract frame using ffmpeg")?;
+        extraction.extend(chunk);
+        watcher.finish()?;
+
+        // the frames are removed once read
+        while frame_path
+            .read_dir()
+            .context("read frames folder")?
+            .flatten()
+            .any(|f| f.file_name().to_string_lossy().ends_with(".jpg"))
+        {
+            std::thread::sleep(Duration::from_millis(250));
+        }
+        eprintln!(
+            "Read chunk {} of {} (up to {})",
+            i + 1,
+            chunks.len(),
+            end.map_or_else(
+                || "the end".to_string(),
+                |end| track::format_offset(end as f64)
+            )
+        );
+    }
+
+    Ok(extraction)
+}
+
+/// Resolve `--ocr-engine`, checking the Tesseract model and variables up front.
+pub fn recognizer_for(opts: &PipelineConfig) -> anyhow::Result<Recognizer> {
+    let fallback = match opts.ocr_engine {
+        OcrEngine::Glyphs if opts.ocr_ensemble => {
+            anyhow::bail!("`--ocr-ensemble` needs Tesseract, not `--ocr-engine glyphs`")
+        }
+        OcrEngine::Glyphs => return Ok(Recognizer::Glyphs),
+        OcrEngine::Tesseract => false,
+        OcrEngine::Auto => true,
+    };
+    if !cfg!(feature = "tesseract") {
+        if fallback && !opts.ocr_ensemble {
+            return Ok(Recognizer::Glyphs);
+        }
+        anyhow::bail!("built without Tesseract, use `--ocr-engine glyphs`");
+    }
+
+    let model_given = opts.ocr_model.is_some() || opts.ocr_model_file.is_some();
+    if fallback && !opts.ocr_ensemble && !model_given && local_data_dir()?.is_none() {
+        eprintln!("Tesseract model not found, reading the overlay with the built-in recognizer");
+        return Ok(Recognizer::Glyphs);
+    }
+
+    let data = traineddata(opts)?;
+    // fail on a misspelled `--ocr-var` now rather than on every frame
+    #[cfg(feature = "tesseract")]
+    ocr_engine(&data, &opts.ocr_var)?;
+
+    Ok(Recognizer::Tesseract {
+        data,
+        vars: opts.ocr_var.clone(),
+        fallback,
+        ensemble: opts.ocr_ensemble,
+    })
+}
+
+/// Model to use: `--ocr-model-file`, `--ocr-model` or the English model found locally.
+fn traineddata(opts: &PipelineConfig) -> anyhow::Result<Traineddata> {
+    if let Some(file) = &opts.ocr_model_file {
+        return Traineddata::from_file(file);
+    }
+
+    let dir = match opts.ocr_model {
+        Some(model) => tessdata::model_dir(model)?.to_string_lossy().to_string(),
+        None => find_data_dir()?,
+    };
+    Ok(Traineddata {
+        dir,
+        lang: "eng".to_string(),
+    })
+}
+
+/// Folder with the English model, an error telling how to download it otherwise
+fn find_data_dir() -> anyhow::Result<String> {
+    if let Some(dir) = local_data_dir()? {
+        return Ok(dir);
+    }
+
+    let exe = std::env::current_exe()?;
+    let exe_path = exe.parent().unwrap_or(Path::new("/"));
+    anyhow::bail!("train data was not found. Please download training data for english language using:\ncurl -o \"{}/eng.traineddata\" https://raw.githubusercontent.com/tesseract-ocr/tessdata_best/main/eng.traineddata", exe_path.to_string_lossy())
+}
+
+/// Folder next to the executable, or the current folder, when it has a `.traineddata`
+pub(crate) fn local_data_dir() -> anyhow::Result<Option<String>> {
+    fn has_train_data(input: &Path) -> anyhow::Result<bool> {
+        for file in input.read_dir()?.flatten() {
+            if file.file_name().to_string_lossy().ends_with(".traineddata") {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    let exe = std::env::current_exe()?;
+    let exe_path = exe.parent().unwrap_or(Path::new("/"));
+    if has_train_data(exe_path)? {
+        return Ok(Some(exe_path.to_string_lossy().to_string()));
+    }
+
+    // current dir
+    let current_dir = std::env::current_dir()?;
+    if has_train_data(&current_dir)? {
+        return Ok(Some(current_dir.to_string_lossy().to_string()));
+    }
+
+    Ok(None)
+}
+
+/// Threads of each stage of reading a video.
+struct Concurrency {
+    ffmpeg: u8,
+    preprocess: u8,
+    ocr: u8,
+}
+
+impl Concurrency {
+    fn new(opts: &PipelineConfig) -> Self {
+        let cpus = std::thread::available_parallelism().map_or(4, |n| n.get().min(255) as u8);
+        let or_share =
+            |set: Option<u8>, divisor: u8| set.or(opts.threads).unwrap_or(cpus / divisor).max(1);
+        match opts.deterministic {
+            // the frames are read one after the other, in order
+            true => Self {
+                ffmpeg: or_share(opts.ffmpeg_threads, 2),
+                preprocess: 1,
+                ocr: 1,
+            },
+            false => Self {
+                ffmpeg: or_share(opts.ffmpeg_threads, 2),
+                preprocess: or_share(opts.preprocess_workers, 4),
+                ocr: or_share(opts.ocr_workers, 1),
+            },
+        }
+    }
+}
+
+struct WorkerContext {
+    tmp_path: PathBuf,
+    recognizer: Recognizer,
+    /// Write points as they are found, for streaming formats and `--incremental`
+    live: Option<Live>,
+    /// `--preview`: save the crops here and describe every frame
+    preview_dir: Option<PathBuf>,
+    interval: u32,
+    strictness: parser::Strictness,
+    coordinate_format: parser::CoordinateFormat,
+    /// `--sample-offset`
+    sample_offset: f64,
+    prepare: Prepare,
+    /// How well the last frames were read
+    accuracy: Accuracy,
+    /// Counters of the status line
+    progress: Progress,
+    /// Remove frames once read, to bound the space taken by long videos read
+    /// in chunks
+    remove_read: bool,
+    /// `--ocr-timeout` and `--ocr-timeout-retry`
+    ocr_timeout: Option<(Duration, bool)>,
+    /// Set once ffmpeg has written every frame, the preprocess workers stop
+    /// when the queue is empty
+    done: AtomicBool,
+    observer: Option<Arc<dyn Observer>>,
+    /// Length of the video, for the observer
+    duration_sec: Option<f64>,
+}
+
+/// A frame cropped by a preprocess worker, for the OCR workers
+struct Prepared {
+    frame_no: u32,
+    source: PathBuf,
+    crop: Result<PathBuf, FrameError>,
+}
+
+fn frame_error(stage: Stage, e: anyhow::Error, source: &Path) -> FrameError {
+    FrameError {
+        stage,
+        message: format!("{} ({})", e, source.to_string_lossy()),
+    }
+}
+
+/// Crop the overlay of the frames ffmpeg writes, until it is done.
+fn preprocess_worker(
+    receiver: Receiver<PathBuf>,
+    prepared: Sender<Prepared>,
+    ctx: Arc<WorkerContext>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut decoder = FrameDecoder::default();
+        loop {
+            // keep draining frames still queued when ffmpeg finishes
+            let Ok(source) = receiver.recv_timeout(Duration::from_millis(250)) else {
+                if ctx.done.load(Ordering::Relaxed) {
+                    break;
+                }
+                continue;
+            };
+            let Some(frame_no) = ffmpeg::frame_no(&source) else {
+                continue;
+            };
+            ctx.progress.queued();
+            event_log::record(Event::FrameDispatched { frame_no });
+
+            let crop = preprocess(&mut decoder, &source, &ctx.tmp_path, &ctx.prepare)
+                .map_err(|e| frame_error(Stage::ImageDecode, e, &source));
+            if ctx.remove_read {
+                _ = std::fs::remove_file(&source);
+            }
+            let frame = Prepared {
+                frame_no,
+                source,
+                crop,
+            };
+            if prepared.send(frame).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Read the cropped frames, until every preprocess worker stopped.
+fn ocr_worker(
+    receiver: Receiver<Prepared>,
+    ctx: Arc<WorkerContext>,
+) -> std::thread::JoinHandle<Vec<FrameResult>> {
+    std::thread::spawn(move || {
+        let mut watchdog = match (&ctx.recognizer, ctx.ocr_timeout) {
+            (Recognizer::Tesseract { data, vars, .. }, Some((timeout, retry))) => {
+                let (data, vars) = (data.clone(), vars.clone());
+                let warm_up = (data.clone(), vars.clone());
+                Some(Watchdog::new(
+                    timeout,
+                    retry,
+                    move || _ = warm_up_ocr(&warm_up.0, &warm_up.1),
+                    move |crop| ocr(crop, &data, &vars),
+                ))
+            }
+            _ => None,
+        };
+        // the watchdog loads Tesseract on a thread of its own
+        if watchdog.is_none() {
+            ctx.recognizer.warm_up();
+        }
+        let mut results = Vec::new();
+        for Prepared {
+            frame_no,
+            source,
+            crop,
+        } in receiver
+        {
+            let detected = crop.and_then(|crop| {
+                let (text, overlay, readings, confidence) = read_frame(
+                    &crop,
+                    &ctx.recognizer,
+                    watchdog.as_mut(),
+                    ctx.strictness,
+                    ctx.coordinate_format,
+                )
+                .map_err(|e| frame_error(Stage::Ocr, e, &source))?;
+                Ok((text, crop, overlay, readings, confidence))
+            });
+            let confidence = detected.as_ref().ok().map(|d| d.4);
+            match &detected {
+                Ok((text, _, _, _, confidence)) => event_log::record(Event::OcrResult {
+                    frame_no,
+                    text,
+                    confidence: *confidence,
+                }),
+                Err(e) => event_log::frame_failed(frame_no, e),
+            }
+            // errors are summed up at the end rather than scrolling past, but
+            // `--preview` describes every frame
+            let (detected, overlay, readings, error) = match detected {
+                Ok((text, crop, overlay, readings, _)) => {
+                    (Some((text, crop)), overlay, readings, None)
+                }
+                Err(e) => {
+                    if ctx.preview_dir.is_some() {
+                        println!("frame {}\n  error:  {}", frame_no, e.message);
+                    }
+                    (
+                        None,
+                        parser::Overlay::Unreadable,
+                        Default::default(),
+                        Some(e),
+                    )
+                }
+            };
+
+            if detected.is_some() {
+                event_log::parsed(frame_no, &overlay);
+            }
+            let read = !matches!(overlay, parser::Overlay::Unreadable);
+            ctx.progress.read(frame_no, read);
+            if let Some(observer) = &ctx.observer {
+                observer.on_progress(&ctx.progress.snapshot(ctx.interval, ctx.duration_sec));
+            }
+            if let Some(warning) = ctx.accuracy.record(read, confidence) {
+                if ctx.preview_dir.is_none() {
+                    eprintln!("{}", warning);
+                }
+            }
+            if let Some(live) = &ctx.live {
+                live.frame(frame_no, &overlay, &readings, confidence);
+            }
+            if let (Some((text, crop)), Some(dir)) = (&detected, &ctx.preview_dir) {
+                let offset_sec = track::nominal_offset(frame_no, ctx.interval, ctx.sample_offset);
+                print_preview(frame_no, text, &overlay, crop, dir, offset_sec);
+            }
+            if let (Some((_, crop)), true) = (&detected, ctx.remove_read) {
+                _ = std::fs::remove_file(crop);
+            }
+
+            results.push(FrameResult {
+                frame_no,
+                pts_sec: None,
+                overlay,
+                readings,
+                error,
+                confidence,
+            });
+        }
+
+        results
+    })
+}
+
+fn print_preview(
+    frame_no: u32,
+    text: &str,
+    overlay: &parser::Overlay,
+    crop: &Path,
+    preview_dir: &Path,
+    offset_sec: f64,
+) {
+    let saved = preview_dir.join(format!("f{:09}.png", frame_no));
+    let crop = match std::fs::copy(crop, &saved) {
+        Ok(_) => saved.to_string_lossy().to_string(),
+        Err(e) => format!("not saved ({})", e),
+    };
+    let result = match overlay {
+        parser::Overlay::Fix(c) => c.to_decimal(),
+        parser::Overlay::NoFix => "no GPS fix".to_string(),
+        parser::Overlay::OtherPage => "another overlay page, without the position".to_string(),
+        parser::Overlay::Unreadable => "unreadable".to_string(),
+    };
+
+    println!(
+        "frame {} @ {}\n  ocr:    {}\n  result: {}\n  crop:   {}",
+        frame_no,
+        track::format_offset(offset_sec),
+        text.trim().replace('\n', " | "),
+        result,
+        crop
+    );
+}
+
+/// Track of the frames read, without the points too far off the others
+pub fn build_track(results: Vec<FrameResult>, opts: &PipelineConfig) -> Track {
+    let coordinate_format =
+        parser::detect_format(results.iter().map(|r| &r.overlay), opts.coordinate_format);
+    let mut track = Track::from_frames(results, opts.interval);
+    track.coordinate_format = coordinate_format;
+    drop_outliers(&mut track, opts);
+
+    track
+}
+
+/// Drop implausible jumps, faster than `--max-speed`, recording them in the
+/// `--event-log`.
+pub fn drop_outliers(track: &mut Track, opts: &PipelineConfig) {
+    event_log::filter(std::slice::from_mut(track), "outlier", |t| {
+        t[0].drop_outliers(opts.max_speed)
+    });
+}
+
+/// Read dates and bare speeds as usual in the country of the footage, unless
+/// set explicitly.
+pub fn localize(track: &mut Track, opts: &PipelineConfig) {
+    let country = opts.country.or_else(|| country::detect(track));
+    let date_order = opts
+        .date_order
+        .or(country.map(|c| c.date_order))
+        .unwrap_or(DateOrder::Dmy);
+    let speed_unit = country.map_or(SpeedUnit::Kmh, |c| c.speed_unit);
+    if let (Some(c), Some(first)) = (country, track.points.first()) {
+        eprintln!(
+            "Recorded in {} ({}): speeds without a unit in {}, dates {} first, overlay clock likely on {} time",
+            c.name,
+            c.code,
+            opts.speed_unit.unwrap_or(speed_unit).label(),
+            match date_order {
+                DateOrder::Dmy => "day",
+                DateOrder::Mdy => "month",
+            },
+            c.time_zone(first.coordinate.lat_lon())
+        );
+    }
+
+    for p in &mut track.points {
+        p.readings.order_date(date_order);
+    }
+    speed::resolve_units(track, opts.speed_unit, speed_unit);
+}
+
+/// Print how much of the video was read, returns the lines printed.
+pub fn print_summary(track: &Track) -> Vec<String> {
+    let mut processed = format!(
+        "Processed {} frames: {} with location, {} without GPS fix, {} unreadable",
+        track.frames,
+        track.points.len(),
+        track.no_fix_frames(),
+        track.unreadable
+    );
+    if track.other_pages > 0 {
+        processed.push_str(&format!(", {} other overlay pages", track.other_pages));
+    }
+    let mut lines = vec![processed];
+
+    if !track.no_fix.is_empty() {
+        let intervals = track
+            .no_fix
+            .iter()
+            .map(|g| {
+                format!(
+                    "{}-{}",
+                    track::format_offset(g.start_sec),
+                    track::format_offset(g.end_sec)
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!("No GPS fix: {}", intervals.join(", ")));
+    }
+
+    if let Some(detection) = &track.coordinate_format {
+        lines.push(coordinate_format_line(detection));
+    }
+
+    let errors = &track.errors;
+    if !errors.is_empty() {
+        lines.push(format!(
+            "Errors: {} image decode, {} OCR, {} parse, {} outliers dropped",
+            errors.image_decode, errors.ocr, errors.parse, errors.outliers
+        ));
+    }
+    if let Some(first) = &errors.first {
+        lines.push(format!("First error: {}", first));
+    }
+
+    for line in &lines {
+        eprintln!("{}", line);
+    }
+    lines
+}
+
+/// eg. `Coordinate format: ddm, detected in 98% of positions`
+fn coordinate_format_line(detection: &parser::FormatDetection) -> String {
+    let format = detection
+        .format
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default();
+    if detection.pinned {
+        return format!("Coordinate format: {} (--coordinate-format)", format);
+    }
+
+    let mut line = format!(
+        "Coordinate format: {}, detected in {:.0}% of positions",
+        format,
+        detection.share * 100.0
+    );
+    // some frames matched another format, likely misread
+    if detection.share < 0.9 {
+        line.push_str(", pin it with `--coordinate-format` if that is wrong");
+    }
+    line
+}
+
+/// Tell the user how much of a damaged file could be recovered.
+fn report_readable(input: &Path, extraction: &ffmpeg::Extraction, interval_sec: u32) {
+    let covered = extraction.covered_sec(interval_sec);
+    let duration = ffmpeg::probe_duration(input);
+
+    // the last sample may legitimately fall short of the end by up to one interval
+    let truncated = duration.is_some_and(|d| d > (covered + interval_sec as u64) as f64);
+    if extraction.clean_exit && !truncated {
+        return;
+    }
+
+    match duration {
+        Some(d) if d > 0.0 => eprintln!(
+            "Warning: input is damaged, recovered {} frames covering ~{}s of {:.0}s ({:.0}%)",
+            extraction.frames,
+            covered,
+            d,
+            (covered as f64 / d * 100.0).min(100.0)
+        ),
+        _ => eprintln!(
+            "Warning: input is damaged, recovered {} frames covering ~{}s",
+            extraction.frames, covered
+        ),
+    }
+
+    if !extraction.clean_exit {
+        eprintln!("ffmpeg reported:\n{}", extraction.stderr_tail);
+    }
+}
+
+/// Crop the overlay strip and prepare it for OCR, returns the path of the crop.
+fn preprocess(
+    decoder: &mut FrameDecoder,
+    source: &Path,
+    tmp_path: &Path,
+    prepare: &Prepare,
+) -> anyhow::Result<PathBuf> {
+    let out_name = tmp_path.join(format!(
+        "{}-edit.jpg",
+        source.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    {
+        let mut f = std::fs::File::create(&out_name).context("open file")?;
+
+        decoder
+            .overlay_crop(source, prepare)?
+            .write_to(&mut f, ImageOutputFormat::Png)
+            .context("update image")?;
+    }
+
+    Ok(out_name)
+}
+
+/// Text of the overlay crop, what it says and the OCR confidence (0-100).
+/// With the fallback, frames Tesseract fails on, reads without coordinates or
+/// is unsure about are tried with the built-in recognizer, keeping its reading
+/// when it has the coordinates. With the ensemble both are run and vote on
+/// every field. Tesseract is run by the `watchdog` when there is one.
+fn read_frame(
+    crop: &Path,
+    recognizer: &Recognizer,
+    mut watchdog: Option<&mut Watchdog>,
+    strictness: parser::Strictness,
+    format: parser::CoordinateFormat,
+) -> anyhow::Result<(String, parser::Overlay, parser::Readings, i32)> {
+    let parse = |(text, confidence): (String, i32)| {
+        let overlay = parser::parse_overlay_as(text.as_str(), strictness, format);
+        let readings = parser::parse_readings(&text);
+        (text, overlay, readings, confidence)
+    };
+    let Recognizer::Tesseract {
+        data,
+        vars,
+        fallback,
+        ensemble,
+    } = recognizer
+    else {
+        return glyphs::read(crop).map(parse);
+    };
+    let mut ocr = |crop: &Path| match watchdog.as_mut() {
+        Some(watchdog) => watchdog.read(crop),
+        None => ocr(crop, data, vars),
+    };
+
+    if *ensemble {
+        let (mut texts, mut votes) = (Vec::new(), Vec::new());
+        let mut error = None;
+        for read in [ocr(crop), glyphs::read(crop)] {
+            match read {
+                Ok(read) => {
+                    let (text, overlay, readings, confidence) = parse(read);
+                    texts.push(text.trim().to_string());
+                    votes.push(ensemble::Vote {
+                        overlay,
+                        readings,
+                        confidence,
+                    });
+                }
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        if let (Some(e), true) = (error, votes.is_empty()) {
+            return Err(e);
+        }
+        let confidence = votes.iter().map(|v| v.confidence).sum::<i32>() / votes.len() as i32;
+        let (overlay, readings) = ensemble::merge(votes);
+        return Ok((texts.join("\n"), overlay, readings, confidence));
+    }
+
+    let tesseract = ocr(crop).map(parse);
+    let sure = match &tesseract {
+        Ok(read) => read.3 >= MIN_CONFIDENCE && !matches!(read.1, parser::Overlay::Unreadable),
+        Err(_) => false,
+    };
+    if !fallback || sure {
+        return tesseract;
+    }
+
+    match (glyphs::read(crop).map(parse), tesseract) {
+        (Ok(read), _) if !matches!(read.1, parser::Overlay::Unreadable) => Ok(read),
+        (_, Ok(read)) => Ok(read),
+        (glyphs, Err(_)) => glyphs,
+    }
+}
+
+/// Mean word confidence (0-100) under which Tesseract is cross-checked
+const MIN_CONFIDENCE: i32 = 60;
+
+type Read = anyhow::Result<(String, i32)>;
+
+/// OCR run on a thread of its own and given up on a frame it is still reading
+/// after `--ocr-timeout`: that thread is left to finish alone and the next
+/// frame is read by a new one, so a frame Tesseract spins on for minutes holds
+/// up no worker.
+struct Watchdog {
+    timeout: Duration,
+    /// Read a frame that timed out again, simplified, see [`preprocess::simplify`]
+    retry: bool,
+    warm_up: Arc<dyn Fn() + Send + Sync>,
+    read: Arc<dyn Fn(&Path) -> Read + Send + Sync>,
+    /// Crops sent to the thread reading them and what it read
+    reader: Option<(Sender<PathBuf>, Receiver<Read>)>,
+}
+
+impl Watchdog {
+    fn new(
+        timeout: Duration,
+        retry: bool,
+        warm_up: impl Fn() + Send + Sync + 'static,
+        read: impl Fn(&Path) -> Read + Send + Sync + 'static,
+    ) -> Self {
+        let mut watchdog = Self {
+            timeout,
+            retry,
+            warm_up: Arc::new(warm_up),
+            read: Arc::new(read),
+            reader: None,
+        };
+        watchdog.start();
+        watchdog
+    }
+
+    /// Start a thread reading crops, once it has warmed up
+    fn start(&mut self) {
+        let (crops, crop_receiver) = unbounded::<PathBuf>();
+        let (read_sender, reads) = unbounded();
+        let (warmed_up, ready) = unbounded::<()>();
+        let (warm_up, read) = (self.warm_up.clone(), self.read.clone());
+        std::thread::spawn(move || {
+            warm_up();
+            drop(warmed_up);
+            for crop in crop_receiver {
+                if read_sender.send(read(&crop)).is_err() {
+                    break;
+                }
+            }
+        });
+        // loading a model is not timed
+        _ = ready.recv();
+        self.reader = Some((crops, reads));
+    }
+
+    /// What was read of `crop`, `None` when it took too long
+    fn attempt(&mut self, crop: &Path) -> Option<Read> {
+        if self.reader.is_none() {
+            self.start();
+        }
+        let (crops, reads) = self.reader.as_ref()?;
+        if crops.send(crop.to_path_buf()).is_err() {
+            self.reader = None;
+            return Some(Err(anyhow::anyhow!("OCR thread stopped")));
+        }
+        match reads.recv_timeout(self.timeout) {
+            Ok(read) => Some(read),
+            Err(_) => {
+                // its result is no longer waited for, the thread stops once done
+                self.reader = None;
+                None
+            }
+        }
+    }
+
+    /// Text and mean word confidence of `crop`
+    fn read(&mut self, crop: &Path) -> Read {
+        if let Some(read) = self.attempt(crop) {
+            return read;
+        }
+        let timed_out = format!("OCR timed out after {}s", self.timeout.as_secs_f64());
+        eprintln!("Warning: {} on {}", timed_out, crop.display());
+        if !self.retry {
+            anyhow::bail!(timed_out);
+        }
+
+        let simple = crop.with_extension("simple.png");
+        preprocess::simplify(crop, &simple).context(timed_out.clone())?;
+        let read = self.attempt(&simple);
+        _ = std::fs::remove_file(&simple);
+        read.unwrap_or_else(|| Err(anyhow::anyhow!("{}, simplified too", timed_out)))
+    }
+}
+
+#[cfg(feature = "tesseract")]
+thread_local! {
+    /// Engine of this OCR worker and the model and variables it was loaded
+    /// with, kept between frames as loading the model takes most of the time
+    static ENGINE: std::cell::RefCell<Option<(String, Tesseract)>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "tesseract")]
+fn engine_key(data: &Traineddata, vars: &[(String, String)]) -> String {
+    format!("{}/{}{:?}", data.dir, data.lang, vars)
+}
+
+/// Load the engine of this thread ahead of its first frame.
+#[cfg(feature = "tesseract")]
+fn warm_up_ocr(data: &Traineddata, vars: &[(String, String)]) -> anyhow::Result<()> {
+    let key = engine_key(data, vars);
+    ENGINE.with(|engine| {
+        let mut engine = engine.borrow_mut();
+        if engine.as_ref().is_none_or(|(loaded, _)| *loaded != key) {
+            *engine = Some((key, ocr_engine(data, vars)?));
+        }
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "tesseract"))]
+fn warm_up_ocr(_: &Traineddata, _: &[(String, String)]) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Text and mean word confidence read by Tesseract
+#[cfg(feature = "tesseract")]
+fn ocr(
+    crop: &Path,
+    data: &Traineddata,
+    vars: &[(String, String)],
+) -> anyhow::Result<(String, i32)> {
+    warm_up_ocr(data, vars)?;
+    ENGINE.with(|engine| {
+        let mut engine = engine.borrow_mut();
+        let (key, tess) = engine.take().expect("engine loaded");
+        // an engine failing on an image is dropped, the next frame loads another
+        let mut tess = tess
+            .set_image(&crop.to_string_lossy())
+            .context("set image")?;
+        let text = tess.get_text()?;
+        let confidence = tess.mean_text_conf();
+        *engine = Some((key, tess));
+
+        Ok((text, confidence))
+    })
+}
+
+#[cfg(not(feature = "tesseract"))]
+fn ocr(_: &Path, _: &Traineddata, _: &[(String, String)]) -> anyhow::Result<(String, i32)> {
+    anyhow::bail!("built without Tesseract")
+}
+
+/// Tesseract with the default variables and the `--ocr-var` overrides.
+#[cfg(feature = "tesseract")]
+fn ocr_engine(data: &Traineddata, vars: &[(String, String)]) -> anyhow::Result<Tesseract> {
+    let mut tess = Tesseract::new(Some(&data.dir), Some(&data.lang))?
+        .set_variable("user_defined_dpi", "96")?;
+    for (name, value) in vars {
+        tess = tess
+            .set_variable(name, value)
+            .with_context(|| format!("set tesseract variable {}={}", name, value))?;
+    }
+
+    Ok(tess)
+}
+
+/// `name=value` of `--ocr-var` and `--meta`
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=value, got `{}`", s))?;
+
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    pub fn new() -> anyhow::Result<Self> {
+        // root
+        let path =
+            std::env::temp_dir().join(format!("dash2gps-workspace-{}", Utc::now().timestamp()));
+        std::fs::create_dir(path.clone()).context("create temp folder")?;
+
+        Ok(Self { path })
+    }
+
+    /// Named after `input` rather than the time, so the paths of its frames
+    /// are the same every run
+    pub fn for_input(input: &Path) -> anyhow::Result<Self> {
+        let input = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+        let hash = Sha256::digest(input.to_string_lossy().as_bytes());
+        let name = hash[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let path = std::env::temp_dir().join(format!("dash2gps-workspace-{}", name));
+        std::fs::create_dir(path.clone()).with_context(|| {
+            format!(
+                "create {}, left by a run of the video that is still going or was killed?",
+                path.display()
+            )
+        })?;
+
+        Ok(Self { path })
+    }
+
+    pub fn new_folder(&self, name: impl Into<String>) -> anyhow::Result<PathBuf> {
+        let path = self.path.join(name.into());
+        std::fs::create_dir(path.clone())?;
+
+        Ok(path)
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_model_is_an_error() {
+        // unless a model is next to the test binary or in the current folder
+        if local_data_dir().unwrap().is_none() {
+            let error = find_data_dir().unwrap_err();
+            assert!(error.to_string().contains("eng.traineddata"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn stuck_ocr_given_up() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-watchdog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (clear, stuck) = (dir.join("clear.png"), dir.join("stuck.png"));
+        for crop in [&clear, &stuck] {
+            image::GrayImage::new(8, 8).save(crop).unwrap();
+        }
+        // spins on the stuck crop, not once it is simplified
+        let read = |crop: &Path| {
+            if crop.ends_with("stuck.png") {
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            Ok((crop.display().to_string(), 90))
+        };
+        let timeout = Duration::from_millis(100);
+
+        let mut watchdog = Watchdog::new(timeout, false, || (), read);
+        let error = watchdog.read(&stuck).unwrap_err();
+        assert_eq!(error.to_string(), "OCR timed out after 0.1s");
+        // read by a new thread
+        assert_eq!(
+            watchdog.read(&clear).unwrap().0,
+            clear.display().to_string()
+        );
+
+        let mut watchdog = Watchdog::new(timeout, true, || (), read);
+        let (text, _) = watchdog.read(&stuck).unwrap();
+        assert!(text.ends_with("stuck.simple.png"), "{}", text);
+        assert!(!dir.join("stuck.simple.png").exists());
+        _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn deterministic_runs() {
+        let opts = PipelineConfig::from_args(["--deterministic", "--threads", "8"]).unwrap();
+        let concurrency = Concurrency::new(&opts);
+        assert_eq!((concurrency.preprocess, concurrency.ocr), (1, 1));
+
+        let input = Path::new("dash2gps-deterministic.mp4");
+        let workspace = Workspace::for_input(input).unwrap();
+        let path = workspace.path.clone();
+        assert!(Workspace::for_input(input).is_err());
+        drop(workspace);
+        assert_eq!(Workspace::for_input(input).unwrap().path, path);
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config = PipelineConfig::default();
+        assert_eq!(config.interval, 10);
+        assert_eq!(config.ocr_engine, OcrEngine::Auto);
+        assert_eq!(config.prepare.color_tolerance, 60);
+
+        let config = PipelineConfig::from_args(["--interval", "5", "--country", "GB"]).unwrap();
+        assert_eq!(config.interval, 5);
+        assert_eq!(config.country.unwrap().code, "GB");
+        assert!(PipelineConfig::from_args(["--format", "gpx"]).is_err());
+    }
+
+    #[test]
+    fn samples_of_gps_log() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-pipeline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("clip.nmea"),
+            "$GPRMC,124229.00,A,5125.8000,N,00019.3333,E,44.3,90.0,060621,,,A*5A\n\
+             $GPRMC,124230.00,A,5125.7990,N,00019.3500,E,44.3,90.0,060621,,,A*5B\n",
+        )
+        .unwrap();
+
+        #[derive(Default)]
+        struct Seen(std::sync::Mutex<Vec<String>>);
+        impl Observer for Arc<Seen> {
+            fn on_stage_change(&self, stage: PipelineStage) {
+                self.0.lock().unwrap().push(format!("{:?}", stage));
+            }
+
+            fn on_point(&self, sample: &GpsSample) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("point {}", sample.frame_no));
+            }
+        }
+        let seen = Arc::new(Seen::default());
+
+        let pipeline = Pipeline::new(PipelineConfig {
+            interval: 1,
+            ..Default::default()
+        })
+        .with_observer(seen.clone());
+        let mut samples = pipeline.process(&dir.join("clip.mov")).unwrap();
+        let first = samples.next().unwrap();
+        assert_eq!(first.frame_no, 1);
+        assert!((first.lat - 51.43).abs() < 1e-4, "{}", first.lat);
+        assert_eq!(samples.by_ref().count(), 1);
+        assert_eq!(samples.finish().unwrap().points.len(), 2);
+        assert_eq!(
+            *seen.0.lock().unwrap(),
+            ["ReadingLog", "point 1", "point 2"]
+        );
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,820 @@
+//! Crop of the overlay at the bottom of a frame, made black text on white for
+//! OCR: grayscale, inverted, contrast pushed and brightened. Colored text is
+//! made gray from one channel or by its distance to the overlay color instead.
+//!
+//! After the grayscale conversion every step maps a byte to a byte, so they
+//! are folded into one table and the whole crop is done in a single pass. The
+//! grayscale loop is written to be vectorized (NEON on arm64, AVX2 when the
+//! CPU has it on x86_64); the result is identical to the `image` operations,
+//! which `--simd off` uses instead.
+//!
+//! Extra `--preprocess` filters run on the grayscale crop, before the table.
+//! `--binarize otsu` or `adaptive` replace the contrast steps with a
+//! threshold, for footage the fixed contrast does not suit.
+
+use std::{fmt, fs::File, io::BufReader, path::Path, str::FromStr};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use image::{
+    codecs::jpeg::JpegDecoder, imageops, ColorType, DynamicImage, GrayImage, ImageDecoder, Luma,
+    Rgb, RgbImage,
+};
+use serde::{Serialize, Serializer};
+
+/// Size ffmpeg scales the frames to
+pub const FRAME_WIDTH: u32 = 1280;
+pub const FRAME_HEIGHT: u32 = 720;
+/// Height of the overlay strip at the bottom of the frame
+pub const OVERLAY_HEIGHT: u32 = 50;
+const CONTRAST: f32 = -500.0;
+const BRIGHTEN: i32 = 50;
+/// Width in pixels of the line drawn around the crop
+const OUTLINE: u32 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Simd {
+    /// Single pass, vectorized where the CPU supports it
+    #[default]
+    Auto,
+    /// Step by step with the `image` crate, to rule out the fast path
+    Off,
+}
+
+/// How the overlay crops are prepared for OCR
+#[derive(Clone, Debug, Default)]
+pub struct Prepare {
+    /// Where the overlay is, the strip at the bottom of the frame by default
+    pub crop: Option<Crop>,
+    pub simd: Simd,
+    pub gray: Gray,
+    /// `--preprocess`
+    pub filters: Vec<Filter>,
+    pub binarize: Binarize,
+}
+
+/// How the gray crop is made black text on white
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Binarize {
+    /// Invert and push the contrast, leaving a few gray levels at the edges of the text
+    #[default]
+    Contrast,
+    /// One threshold for the whole crop, the one separating its gray levels best (Otsu)
+    Otsu,
+    /// A threshold for each pixel from the mean of those around it, for overlays over a
+    /// background that is bright in places, eg. the sky or headlights
+    Adaptive,
+}
+
+/// Side of the square of pixels `--binarize adaptive` averages, about the
+/// height of the overlay text
+const ADAPTIVE_WINDOW: u32 = 15;
+/// How much brighter than the mean around it a pixel is to be text
+const ADAPTIVE_OFFSET: u32 = 10;
+
+impl Binarize {
+    /// Make text, the brightest, black and the rest white. The contrast steps
+    /// are done by the table or the `image` operations instead.
+    fn apply(&self, image: &mut GrayImage) {
+        match self {
+            Binarize::Contrast => {}
+            Binarize::Otsu => {
+                let threshold = otsu_threshold(image);
+                for p in image.pixels_mut() {
+                    p[0] = if p[0] > threshold { 0 } else { 255 };
+                }
+            }
+            Binarize::Adaptive => {
+                let means = local_means(image, ADAPTIVE_WINDOW);
+                for (p, mean) in image.pixels_mut().zip(means) {
+                    p[0] = if p[0] as u32 > mean + ADAPTIVE_OFFSET {
+                        0
+                    } else {
+                        255
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Copy of a prepared crop in plain black and white, split at the gray level
+/// [`otsu_threshold`] finds, for another try at a frame OCR timed out on:
+/// without the gray levels at the edges of the text Tesseract has fewer
+/// shapes to try.
+pub fn simplify(crop: &Path, out: &Path) -> anyhow::Result<()> {
+    let mut image = image::open(crop).context("open crop")?.into_luma8();
+    let threshold = otsu_threshold(&image);
+    for p in image.pixels_mut() {
+        p[0] = if p[0] > threshold { 255 } else { 0 };
+    }
+
+    image.save(out).context("save simplified crop")
+}
+
+/// Gray level splitting the pixels in the two classes with the largest
+/// variance between them
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for p in image.pixels() {
+        histogram[p[0] as usize] += 1;
+    }
+    let total = image.pixels().len() as f64;
+    let sum = (0..256)
+        .map(|l| l as f64 * histogram[l] as f64)
+        .sum::<f64>();
+
+    let (mut best, mut best_variance) = (0, 0.0);
+    let (mut weight, mut weighted) = (0.0, 0.0);
+    for (l, &count) in histogram.iter().enumerate() {
+        weight += count as f64;
+        weighted += l as f64 * count as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        let mean_below = weighted / weight;
+        let mean_above = (sum - weighted) / (total - weight);
+        let variance = weight * (total - weight) * (mean_below - mean_above).powi(2);
+        if variance > best_variance {
+            (best, best_variance) = (l as u8, variance);
+        }
+    }
+
+    best
+}
+
+/// Mean of the `window` by `window` pixels around each pixel, fewer at the
+/// edges, from a summed-area table
+fn local_means(image: &GrayImage, window: u32) -> Vec<u32> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut sums = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = 0;
+        for x in 0..width {
+            row += image.as_raw()[y * width + x] as u64;
+            sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+        }
+    }
+
+    let half = window as usize / 2;
+    let mut means = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (top, bottom) = (y.saturating_sub(half), (y + half + 1).min(height));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(half), (x + half + 1).min(width));
+            let at = |x: usize, y: usize| sums[y * (width + 1) + x];
+            let sum = at(right, bottom) + at(left, top) - at(left, bottom) - at(right, top);
+            means.push((sum / ((right - left) * (bottom - top)) as u64) as u32);
+        }
+    }
+
+    means
+}
+
+/// How the crop is made gray, the overlay text brightest
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Gray {
+    /// Brightness, for white text
+    #[default]
+    Luma,
+    /// A single channel, eg. red or green for yellow text
+    Channel(Channel),
+    /// White within `tolerance` of the color of the text, fading to black at
+    /// twice that distance
+    Key { color: Color, tolerance: u16 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Gray {
+    fn of(&self, p: &[u8]) -> u8 {
+        match *self {
+            Gray::Luma => {
+                let mut l = [0];
+                luma_portable(p, &mut l);
+                l[0]
+            }
+            Gray::Channel(channel) => p[channel as usize],
+            Gray::Key { color, tolerance } => {
+                let distance = p
+                    .iter()
+                    .zip(color.0)
+                    .map(|(&a, b)| (a as f32 - b as f32).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                let tolerance = tolerance.max(1) as f32;
+                let fade = ((distance - tolerance) / tolerance).clamp(0.0, 1.0);
+                ((1.0 - fade) * 255.0).round() as u8
+            }
+        }
+    }
+
+    /// Gray level of every pixel of a row of RGB pixels
+    fn row(&self, rgb: &[u8], out: &mut [u8]) {
+        match self {
+            Gray::Luma => luma(rgb, out),
+            _ => {
+                for (o, p) in out.iter_mut().zip(rgb.chunks_exact(3)) {
+                    *o = self.of(p);
+                }
+            }
+        }
+    }
+}
+
+/// Color of the overlay text, as `RRGGBB` (eg. `ffff00`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color(pub [u8; 3]);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        let invalid = || format!("expected RRGGBB, eg. ffff00, got `{}`", s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Self([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Edge of the frame a full-width overlay strip is printed along
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayPosition {
+    Top,
+    Bottom,
+}
+
+/// Rectangle of the frame the overlay is printed in, as `WIDTHxHEIGHT+X+Y`
+/// (eg. `1280x50+0+670`) or `X,Y,WIDTH,HEIGHT` (eg. `0,670,1280,50`) in the
+/// 1280x720 frames read from the video.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Crop {
+    /// The strip at the bottom of the frame, where most cameras print it
+    pub fn bottom(width: u32, height: u32) -> Self {
+        let y = height.saturating_sub(OVERLAY_HEIGHT);
+        Self {
+            x: 0,
+            y,
+            width,
+            height: height - y,
+        }
+    }
+
+    /// Full-width strip `height` pixels tall along an edge of the 1280x720 frames
+    pub fn strip(position: OverlayPosition, height: u32) -> Self {
+        let height = height.clamp(1, FRAME_HEIGHT);
+        Self {
+            x: 0,
+            y: match position {
+                OverlayPosition::Top => 0,
+                OverlayPosition::Bottom => FRAME_HEIGHT - height,
+            },
+            width: FRAME_WIDTH,
+            height,
+        }
+    }
+
+    /// What ffmpeg writes of each frame instead of all of it: `crop`, or the
+    /// strip at the bottom, widened to even offsets and sizes as chroma
+    /// subsampling needs. Returned with where `crop` lies in it.
+    pub fn extracted(crop: Option<Crop>) -> (Crop, Crop) {
+        let crop = crop
+            .unwrap_or_else(|| Crop::bottom(FRAME_WIDTH, FRAME_HEIGHT))
+            .within(FRAME_WIDTH, FRAME_HEIGHT);
+        let (x, y) = (crop.x & !1, crop.y & !1);
+        let region = Crop {
+            x,
+            y,
+            width: (crop.x - x + crop.width).next_multiple_of(2),
+            height: (crop.y - y + crop.height).next_multiple_of(2),
+        };
+        let inner = Crop {
+            x: crop.x - x,
+            y: crop.y - y,
+            ..crop
+        };
+
+        (region, inner)
+    }
+
+    /// The part of the rectangle inside a `width` by `height` image, at least
+    /// one pixel.
+    fn within(&self, width: u32, height: u32) -> Self {
+        let x = self.x.min(width.saturating_sub(1));
+        let y = self.y.min(height.saturating_sub(1));
+        Self {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+}
+
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected WIDTHxHEIGHT+X+Y or X,Y,WIDTH,HEIGHT, eg. 1280x50+0+670 or 0,670,1280,50, got `{}`",
+                s
+            )
+        };
+        let number = |n: &str| n.trim().parse::<u32>().map_err(|_| invalid());
+        let crop = match s.split(',').collect::<Vec<_>>()[..] {
+            [x, y, width, height] => Self {
+                x: number(x)?,
+                y: number(y)?,
+                width: number(width)?,
+                height: number(height)?,
+            },
+            _ => {
+                let (size, position) = s.split_once('+').ok_or_else(invalid)?;
+                let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+                let (x, y) = position.split_once('+').ok_or_else(invalid)?;
+                Self {
+                    x: number(x)?,
+                    y: number(y)?,
+                    width: number(width)?,
+                    height: number(height)?,
+                }
+            }
+        };
+        if crop.width == 0 || crop.height == 0 {
+            return Err(invalid());
+        }
+        Ok(crop)
+    }
+}
+
+impl Serialize for Crop {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Extra step preparing the grayscale overlay crop, as `NAME[:VALUE]`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Unsharp mask: add the difference to a blurred copy this many times, to
+    /// recover text blurred by vibration, eg. `unsharp:1.5`
+    Unsharp(f32),
+}
+
+/// Radius of the blur an unsharp mask subtracts, about one stroke of the overlay font
+const UNSHARP_SIGMA: f32 = 1.0;
+
+impl Filter {
+    fn apply(&self, image: &mut GrayImage) {
+        match *self {
+            Filter::Unsharp(amount) => {
+                let blurred = imageops::blur(image, UNSHARP_SIGMA);
+                for (p, b) in image.pixels_mut().zip(blurred.pixels()) {
+                    let (l, b) = (p[0] as f32, b[0] as f32);
+                    *p = Luma([(l + (l - b) * amount).round().clamp(0.0, 255.0) as u8]);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Unsharp(amount) => write!(f, "unsharp:{}", amount),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.trim().split_once(':').unwrap_or((s.trim(), ""));
+        match name {
+            "unsharp" => {
+                let amount = match value {
+                    "" => 1.0,
+                    v => v
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|a| a.is_finite() && *a > 0.0)
+                        .ok_or_else(|| format!("expected a positive amount, got `{}`", v))?,
+                };
+                Ok(Filter::Unsharp(amount))
+            }
+            _ => Err(format!(
+                "unknown filter `{}`, expected `unsharp[:AMOUNT]`",
+                name
+            )),
+        }
+    }
+}
+
+impl Serialize for Filter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Copy of the frame with the overlay crop, `crop` or the strip at the bottom
+/// of the frame, outlined in red.
+pub fn outline(image: &DynamicImage, crop: Option<Crop>) -> RgbImage {
+    let mut out = image.to_rgb8();
+    let crop = crop
+        .unwrap_or_else(|| Crop::bottom(out.width(), out.height()))
+        .within(out.width(), out.height());
+    for y in crop.y..crop.y + crop.height {
+        for x in crop.x..crop.x + crop.width {
+            let edge = x - crop.x < OUTLINE
+                || y - crop.y < OUTLINE
+                || crop.x + crop.width - x <= OUTLINE
+                || crop.y + crop.height - y <= OUTLINE;
+            if edge {
+                out.put_pixel(x, y, Rgb([255, 0, 0]));
+            }
+        }
+    }
+
+    out
+}
+
+/// Crop the overlay, `crop` or the strip at the bottom of the frame.
+pub fn overlay_crop(image: &DynamicImage, prepare: &Prepare) -> DynamicImage {
+    let crop = prepare
+        .crop
+        .unwrap_or_else(|| Crop::bottom(image.width(), image.height()))
+        .within(image.width(), image.height());
+    match (prepare.simd, image) {
+        (Simd::Auto, DynamicImage::ImageRgb8(rgb)) => {
+            let mut out = GrayImage::new(crop.width, crop.height);
+            let row = crop.width as usize;
+            for (y, line) in out.chunks_exact_mut(row).enumerate() {
+                let start = ((crop.y as usize + y) * rgb.width() as usize + crop.x as usize) * 3;
+                prepare
+                    .gray
+                    .row(&rgb.as_raw()[start..start + row * 3], line);
+            }
+            for filter in &prepare.filters {
+                filter.apply(&mut out);
+            }
+            if prepare.binarize == Binarize::Contrast {
+                let table = table();
+                for p in out.iter_mut() {
+                    *p = table[*p as usize];
+                }
+            }
+            prepare.binarize.apply(&mut out);
+            DynamicImage::ImageLuma8(out)
+        }
+        (_, image) => {
+            let cropped = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+            let mut gray = match prepare.gray {
+                Gray::Luma => cropped.into_luma8(),
+                other => {
+                    let rgb = cropped.into_rgb8();
+                    GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+                        Luma([other.of(&rgb.get_pixel(x, y).0)])
+                    })
+                }
+            };
+            for filter in &prepare.filters {
+                filter.apply(&mut gray);
+            }
+            if prepare.binarize != Binarize::Contrast {
+                prepare.binarize.apply(&mut gray);
+                return DynamicImage::ImageLuma8(gray);
+            }
+            let mut i = DynamicImage::ImageLuma8(gray);
+            i.invert();
+            i.adjust_contrast(CONTRAST).brighten(BRIGHTEN)
+        }
+    }
+}
+
+/// Decodes the frames of a worker into the buffer of the previous frame,
+/// rather than allocating one for each.
+#[derive(Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// [`overlay_crop`] of an extracted frame
+    pub fn overlay_crop(&mut self, path: &Path, prepare: &Prepare) -> anyhow::Result<DynamicImage> {
+        let file = File::open(path).context("open image")?;
+        let decoder = JpegDecoder::new(BufReader::new(file)).context("open image")?;
+        let (width, height) = decoder.dimensions();
+        let color = decoder.color_type();
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.resize(decoder.total_bytes() as usize, 0);
+        decoder.read_image(&mut buffer).context("decode image")?;
+        let image = match color {
+            ColorType::Rgb8 => RgbImage::from_raw(width, height, buffer).map(DynamicImage::from),
+            ColorType::L8 => GrayImage::from_raw(width, height, buffer).map(DynamicImage::from),
+            other => anyhow::bail!("unexpected {:?} frame", other),
+        }
+        .context("decode image")?;
+
+        let crop = overlay_crop(&image, prepare);
+        self.buffer = image.into_bytes();
+        Ok(crop)
+    }
+}
+
+/// What the invert, contrast and brighten steps make of each gray level,
+/// with the same arithmetic as the `image` crate.
+fn table() -> [u8; 256] {
+    let percent = ((100.0 + CONTRAST) / 100.0).powi(2);
+    let mut table = [0; 256];
+    for (l, t) in table.iter_mut().enumerate() {
+        let inverted = 255.0 - l as f32;
+        let contrasted = (((inverted / 255.0 - 0.5) * percent + 0.5) * 255.0).clamp(0.0, 255.0);
+        *t = (contrasted as u8 as i32 + BRIGHTEN).clamp(0, 255) as u8;
+    }
+    table
+}
+
+/// Instructions `--simd auto` prepares the crops with on this CPU
+pub fn simd_path() -> &'static str {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        return "avx2";
+    }
+    if cfg!(target_arch = "aarch64") {
+        return "neon";
+    }
+    "portable"
+}
+
+fn luma(rgb: &[u8], out: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2
+        return unsafe { luma_avx2(rgb, out) };
+    }
+    luma_portable(rgb, out)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn luma_avx2(rgb: &[u8], out: &mut [u8]) {
+    luma_portable(rgb, out)
+}
+
+/// sRGB luma in fixed point, as `image` computes it
+#[inline(always)]
+fn luma_portable(rgb: &[u8], out: &mut [u8]) {
+    for (o, p) in out.iter_mut().zip(rgb.chunks_exact(3)) {
+        let l = 2126 * p[0] as u32 + 7152 * p[1] as u32 + 722 * p[2] as u32;
+        *o = (l / 10_000) as u8;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The fast path and the `image` operations give the same crop
+    fn same_both_ways(image: &DynamicImage, prepare: Prepare) {
+        let off = Prepare {
+            simd: Simd::Off,
+            ..prepare.clone()
+        };
+        assert_eq!(overlay_crop(image, &prepare), overlay_crop(image, &off));
+    }
+
+    #[test]
+    fn same_as_image_ops() {
+        let frame = image::open("sample-frame.jpg").unwrap();
+        assert!(matches!(frame, DynamicImage::ImageRgb8(_)));
+        same_both_ways(&frame, Prepare::default());
+        let crop = "700x60+450+1010".parse().ok();
+        same_both_ways(
+            &frame,
+            Prepare {
+                crop,
+                ..Default::default()
+            },
+        );
+
+        // every channel value
+        let gradient = image::RgbImage::from_fn(256, 60, |x, y| {
+            image::Rgb([x as u8, (x + y * 7) as u8, (255 - x + y) as u8])
+        });
+        let gradient = DynamicImage::ImageRgb8(gradient);
+        same_both_ways(&gradient, Prepare::default());
+        for gray in [
+            Gray::Channel(Channel::Green),
+            Gray::Key {
+                color: Color([255, 255, 0]),
+                tolerance: 60,
+            },
+        ] {
+            same_both_ways(
+                &gradient,
+                Prepare {
+                    gray,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn simplified_crop() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-simplify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (crop, out) = (dir.join("crop.png"), dir.join("simple.png"));
+        // dark text, gray at its edges, on white
+        GrayImage::from_fn(40, 10, |x, _| Luma([[20, 120, 250, 255][x as usize % 4]]))
+            .save(&crop)
+            .unwrap();
+
+        simplify(&crop, &out).unwrap();
+        let simple = image::open(&out).unwrap().into_luma8();
+        assert_eq!(
+            simple.pixels().take(4).map(|p| p[0]).collect::<Vec<_>>(),
+            [0, 0, 255, 255]
+        );
+        _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn thresholds() {
+        // bright text on a background getting brighter to the right
+        let image = GrayImage::from_fn(60, 20, |x, y| {
+            let text = (5..15).contains(&y) && x % 6 < 2;
+            Luma([if text { 120 + x as u8 * 2 } else { x as u8 * 2 }])
+        });
+        for binarize in [Binarize::Otsu, Binarize::Adaptive] {
+            let mut out = image.clone();
+            binarize.apply(&mut out);
+            assert!(out.pixels().all(|p| p[0] == 0 || p[0] == 255));
+            assert_eq!(out.get_pixel(0, 10)[0], 0, "{:?}", binarize);
+            assert_eq!(out.get_pixel(3, 10)[0], 255, "{:?}", binarize);
+            assert_eq!(out.get_pixel(0, 1)[0], 255, "{:?}", binarize);
+        }
+        // the background at the right is as bright as the text at the left
+        let mut adaptive = image.clone();
+        Binarize::Adaptive.apply(&mut adaptive);
+        assert_eq!(adaptive.get_pixel(56, 1)[0], 255);
+        assert_eq!(adaptive.get_pixel(54, 10)[0], 0);
+
+        let two_levels = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 3 { 200 } else { 30 }]));
+        assert!((30..200).contains(&otsu_threshold(&two_levels)));
+
+        let frame = image::open("sample-frame.jpg").unwrap();
+        for binarize in [Binarize::Otsu, Binarize::Adaptive] {
+            same_both_ways(
+                &frame,
+                Prepare {
+                    binarize,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn colored_text() {
+        let yellow = Gray::Key {
+            color: "ffff00".parse().unwrap(),
+            tolerance: 60,
+        };
+        assert_eq!(yellow.of(&[240, 230, 20]), 255);
+        assert_eq!(yellow.of(&[200, 200, 200]), 0);
+        assert!((1..255).contains(&yellow.of(&[255, 255, 80])));
+        // a white sky is as bright as the text, but not yellow
+        assert!(Gray::Luma.of(&[255, 255, 255]) - Gray::Luma.of(&[240, 230, 20]) < 40);
+        assert_eq!(yellow.of(&[255, 255, 255]), 0);
+        assert_eq!(Gray::Channel(Channel::Blue).of(&[1, 2, 3]), 3);
+
+        assert_eq!(Color([255, 255, 0]).to_string(), "ffff00");
+        assert_eq!("#FFFF00".parse(), Ok(Color([255, 255, 0])));
+        assert!("fff".parse::<Color>().is_err());
+        assert!("ffff0g".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn unsharp_steepens_edges() {
+        let edge = GrayImage::from_fn(20, 5, |x, _| Luma([if x < 10 { 40 } else { 200 }]));
+        let mut sharp = edge.clone();
+        Filter::Unsharp(1.5).apply(&mut sharp);
+        assert!(sharp.get_pixel(9, 2)[0] < 40 && sharp.get_pixel(10, 2)[0] > 200);
+        // flat areas stay as they are
+        assert_eq!(sharp.get_pixel(2, 2), edge.get_pixel(2, 2));
+
+        let frame = image::open("sample-frame.jpg").unwrap();
+        same_both_ways(
+            &frame,
+            Prepare {
+                filters: vec![Filter::Unsharp(1.5)],
+                ..Default::default()
+            },
+        );
+
+        assert_eq!("unsharp:1.5".parse(), Ok(Filter::Unsharp(1.5)));
+        assert_eq!("unsharp".parse(), Ok(Filter::Unsharp(1.0)));
+        assert_eq!(Filter::Unsharp(1.5).to_string(), "unsharp:1.5");
+        assert!("unsharp:-1".parse::<Filter>().is_err());
+        assert!("blur".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn crop_geometry() {
+        let crop = "1280x50+0+670".parse::<Crop>().unwrap();
+        assert_eq!(crop, Crop::bottom(1280, 720));
+        assert_eq!(crop.to_string(), "1280x50+0+670");
+        assert!("1280x50".parse::<Crop>().is_err());
+        assert!("0x50+0+0".parse::<Crop>().is_err());
+        assert_eq!("0, 670, 1280, 50".parse::<Crop>().unwrap(), crop);
+        assert!("0,670,1280".parse::<Crop>().is_err());
+
+        assert_eq!(Crop::strip(OverlayPosition::Bottom, 50), crop);
+        assert_eq!(
+            Crop::strip(OverlayPosition::Top, 80).to_string(),
+            "1280x80+0+0"
+        );
+
+        // past the edge of the frame
+        let crop = "500x100+1000+700".parse::<Crop>().unwrap();
+        assert_eq!(crop.within(1280, 720).to_string(), "280x20+1000+700");
+
+        let (region, inner) = Crop::extracted(None);
+        assert_eq!(region, Crop::bottom(1280, 720));
+        assert_eq!(inner.to_string(), "1280x50+0+0");
+        let (region, inner) = Crop::extracted("699x59+451+1".parse().ok());
+        assert_eq!(region.to_string(), "700x60+450+0");
+        assert_eq!(inner.to_string(), "699x59+1+1");
+    }
+
+    #[test]
+    fn decoded_like_image_open() {
+        let mut decoder = FrameDecoder::default();
+        let prepare = Prepare {
+            crop: "700x60+450+660".parse().ok(),
+            ..Default::default()
+        };
+        let frame = image::open("sample-frame.jpg").unwrap();
+        for _ in 0..2 {
+            let decoded = decoder
+                .overlay_crop(Path::new("sample-frame.jpg"), &prepare)
+                .unwrap();
+            assert_eq!(decoded, overlay_crop(&frame, &prepare));
+        }
+        assert_eq!(
+            decoder.buffer.len(),
+            (frame.width() * frame.height() * 3) as usize
+        );
+    }
+
+    #[test]
+    fn outlined() {
+        let frame = DynamicImage::ImageRgb8(RgbImage::new(64, 36));
+        let crop = "20x10+8+4".parse().ok();
+        let out = outline(&frame, crop);
+        let red = |x, y| *out.get_pixel(x, y) == Rgb([255, 0, 0]);
+        assert!(red(8, 4) && red(27, 13) && red(9, 5) && red(17, 12));
+        assert!(!red(10, 6) && !red(7, 4) && !red(28, 13) && !red(17, 8));
+    }
+}
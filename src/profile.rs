@@ -0,0 +1,229 @@
+//! `--profile`: the options suiting a camera model, built in or from
+//! `profiles.json` in the user's config folder, so settings for another camera
+//! can be shared without a new release.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Command, ValueEnum};
+use serde::Deserialize;
+
+use crate::{
+    config::{self, Config},
+    parser::CoordinateFormat,
+    preprocess::Crop,
+    timeline::FileNameTime,
+};
+
+pub const BUILT_IN: [&str; 5] = ["nextbase", "viofo", "blackvue", "garmin", "generic"];
+
+/// Options a profile sets, each as it is given on the command line. Any left
+/// out keeps its usual default.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// `--file-name-time`
+    pub file_name_time: Option<String>,
+    /// `--crop`
+    pub crop: Option<String>,
+    /// `--coordinate-format`
+    pub coordinate_format: Option<String>,
+    /// Characters the overlay is made of, the letters of its no-fix message
+    /// included, Tesseract reads no other
+    pub ocr_whitelist: Option<String>,
+}
+
+impl Profile {
+    /// Built-in profile, `None` for an unknown name
+    pub fn built_in(name: &str) -> Option<Self> {
+        let profile = match name {
+            // 210606_124229_001_FH.MP4, `N51°25 48” W0°19 20”`
+            "nextbase" => Self {
+                file_name_time: Some(
+                    r"(?P<year>\d{2})(?P<month>\d{2})(?P<day>\d{2})_(?P<hour>\d{2})(?P<minute>\d{2})(?P<second>\d{2})"
+                        .to_string(),
+                ),
+                coordinate_format: Some("dms".to_string()),
+                ..Self::default()
+            },
+            // overlay across the top of the frame
+            "viofo" => Self {
+                crop: Some("1280x50+0+0".to_string()),
+                coordinate_format: Some("decimal".to_string()),
+                ..Self::default()
+            },
+            // 20210606_124229_NF.mp4, read by the default pattern
+            "blackvue" => Self {
+                coordinate_format: Some("decimal".to_string()),
+                ..Self::default()
+            },
+            // GRMN0001.MP4 carries no time, the container's is used,
+            // `N51 25.80 E000 19.33`
+            "garmin" => Self {
+                coordinate_format: Some("ddm".to_string()),
+                ..Self::default()
+            },
+            "generic" => Self::default(),
+            _ => return None,
+        };
+        Some(profile)
+    }
+
+    /// Check every option, naming the one that is wrong
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(names) = &self.file_name_time {
+            names
+                .parse::<FileNameTime>()
+                .map_err(|e| anyhow::anyhow!("file_name_time: {}", e))?;
+        }
+        if let Some(crop) = &self.crop {
+            crop.parse::<Crop>()
+                .map_err(|e| anyhow::anyhow!("crop: {}", e))?;
+        }
+        if let Some(format) = &self.coordinate_format {
+            CoordinateFormat::from_str(format, true)
+                .map_err(|e| anyhow::anyhow!("coordinate_format: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Id of the options the profile sets and their value
+    fn defaults(&self) -> Vec<(String, Vec<String>)> {
+        [
+            ("file_name_time", self.file_name_time.clone()),
+            ("crop", self.crop.clone()),
+            ("coordinate_format", self.coordinate_format.clone()),
+            (
+                "ocr_var",
+                self.ocr_whitelist
+                    .as_ref()
+                    .map(|w| format!("tessedit_char_whitelist={}", w)),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(id, value)| Some((id.to_string(), vec![value?])))
+        .collect()
+    }
+}
+
+/// `profiles.json` in the [`config::dir`]
+pub fn config_file() -> PathBuf {
+    config::dir().join("profiles.json")
+}
+
+/// Profile of the `--config` file, of `profiles.json` or the built-in one of
+/// that name
+pub fn load(name: &str, config: Option<&Config>) -> anyhow::Result<Profile> {
+    if let Some(profile) = config.and_then(|c| c.profiles.get(name)) {
+        return Ok(profile.clone());
+    }
+    let file = config_file();
+    let mut profiles = match std::fs::read_to_string(&file) {
+        Ok(json) => parse(&json).with_context(|| format!("read {}", file.display()))?,
+        Err(_) => BTreeMap::new(),
+    };
+    match profiles.remove(name).or_else(|| Profile::built_in(name)) {
+        Some(profile) => Ok(profile),
+        None => anyhow::bail!(
+            "unknown profile `{}`, expected {} or a profile of {}",
+            name,
+            BUILT_IN.join(", "),
+            file.display()
+        ),
+    }
+}
+
+/// Profiles by name, every one checked
+fn parse(json: &str) -> anyhow::Result<BTreeMap<String, Profile>> {
+    let profiles = serde_json::from_str::<BTreeMap<String, Profile>>(json)?;
+    for (name, profile) in &profiles {
+        profile
+            .validate()
+            .with_context(|| format!("profile `{}`", name))?;
+    }
+    Ok(profiles)
+}
+
+/// Make the options of the profile the defaults of `command` and of its
+/// subcommands, so the command line and the environment still win.
+pub fn apply(command: Command, name: &str, config: Option<&Config>) -> anyhow::Result<Command> {
+    let profile = load(name, config)?;
+    profile
+        .validate()
+        .with_context(|| format!("profile `{}`", name))?;
+
+    config::set_defaults(command, &profile.defaults())
+        .with_context(|| format!("profile `{}`", name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{self, Overlay},
+        pipeline::PipelineConfig,
+    };
+
+    #[test]
+    fn built_in_profiles_are_valid() {
+        for name in BUILT_IN {
+            let profile = Profile::built_in(name).unwrap();
+            profile.validate().unwrap();
+            // the letters of `NO GPS` or of a number plate tell no-fix and
+            // other pages from misreads
+            assert_eq!(profile.ocr_whitelist, None, "{}", name);
+        }
+        assert_eq!(Profile::built_in("dashy"), None);
+    }
+
+    #[test]
+    fn profiles_file() {
+        let profiles = parse(
+            r#"{"dashy": {"crop": "0,0,640,40", "coordinate_format": "ddm", "ocr_whitelist": "0123456789NSEW.°'"}}"#,
+        )
+        .unwrap();
+        assert_eq!(profiles["dashy"].crop.as_deref(), Some("0,0,640,40"));
+        assert!(profiles["dashy"].defaults().contains(&(
+            "ocr_var".to_string(),
+            vec!["tessedit_char_whitelist=0123456789NSEW.°'".to_string()]
+        )));
+
+        let error = parse(r#"{"dashy": {"crop": "top"}}"#).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("profile `dashy`: crop"),
+            "{:#}",
+            error
+        );
+        assert!(parse(r#"{"dashy": {"region": "top"}}"#).is_err());
+    }
+
+    #[test]
+    fn options_win_over_profile() {
+        let config = |args: &[&str]| PipelineConfig::from_args(args).unwrap();
+
+        let viofo = config(&["--profile", "viofo"]);
+        assert_eq!(viofo.prepare.crop(), "1280x50+0+0".parse().ok());
+        assert_eq!(viofo.coordinate_format, CoordinateFormat::Decimal);
+
+        let pinned = config(&["--profile", "viofo", "--coordinate-format", "dms"]);
+        assert_eq!(pinned.coordinate_format, CoordinateFormat::Dms);
+        let bottom = config(&["--profile", "viofo", "--overlay-position", "bottom"]);
+        assert_eq!(bottom.prepare.crop(), "1280x50+0+670".parse().ok());
+
+        assert!(PipelineConfig::from_args(["--profile", "dashy"]).is_err());
+    }
+
+    #[test]
+    fn garmin_overlay() {
+        let garmin = PipelineConfig::from_args(["--profile", "garmin"]).unwrap();
+        let overlay = parser::parse_overlay_as(
+            "06/06/2021 12:42:29 N51 25.80 E000 19.33 72KM/H",
+            garmin.parser_strictness,
+            garmin.coordinate_format,
+        );
+        match overlay {
+            Overlay::Fix(c) => assert_eq!(c.to_decimal(), "51.43, 0.32216665"),
+            _ => panic!("Garmin overlay not read"),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Status line printed every few seconds while a video is read, so a long
+//! stretch without fixes (eg. a tunnel) can be told apart from a stalled
+//! pipeline.
+
+use std::{
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use serde::Serialize;
+
+use crate::track;
+
+/// Counters updated by the workers
+pub struct Progress {
+    started: Instant,
+    /// Frames written by ffmpeg and queued for the workers
+    queued: AtomicUsize,
+    /// Frames through OCR
+    read: AtomicUsize,
+    /// Frames read with a position or a no-fix marker
+    parsed: AtomicUsize,
+    /// Furthest frame read so far
+    frame_no: AtomicU32,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            queued: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            parsed: AtomicUsize::new(0),
+            frame_no: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Progress {
+    pub fn queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn read(&self, frame_no: u32, parsed: bool) {
+        self.read.fetch_add(1, Ordering::Relaxed);
+        if parsed {
+            self.parsed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.frame_no.fetch_max(frame_no, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, interval_sec: u32, duration_sec: Option<f64>) -> Snapshot {
+        let at = self.frame_no.load(Ordering::Relaxed).saturating_sub(1) * interval_sec;
+        Snapshot {
+            queued: self.queued.load(Ordering::Relaxed),
+            read: self.read.load(Ordering::Relaxed),
+            parsed: self.parsed.load(Ordering::Relaxed),
+            at_sec: at as f64,
+            duration_sec,
+            elapsed_sec: self.started.elapsed().as_secs_f64(),
+        }
+    }
+
+    /// eg. `Status: 120 frames queued, 96 read (3.2/s), 81% parsed, at 00:15:50`
+    pub fn line(&self, interval_sec: u32) -> String {
+        let s = self.snapshot(interval_sec, None);
+
+        format!(
+            "Status: {} frames queued, {} read ({:.1}/s), {}% parsed, at {}",
+            s.queued,
+            s.read,
+            s.read as f64 / s.elapsed_sec.max(1.0),
+            (s.parsed * 100).checked_div(s.read).unwrap_or(0),
+            track::format_offset(s.at_sec)
+        )
+    }
+}
+
+/// Counters at one point in time, see [`Progress::snapshot`]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Snapshot {
+    /// Frames written by ffmpeg so far
+    pub queued: usize,
+    /// Frames through OCR
+    pub read: usize,
+    /// Frames read with a position or a no-fix marker
+    pub parsed: usize,
+    /// Position in the video of the furthest frame read
+    pub at_sec: f64,
+    /// Length of the video, when known
+    pub duration_sec: Option<f64>,
+    pub elapsed_sec: f64,
+}
+
+/// Prints a line to stderr every `every` until dropped.
+pub struct Heartbeat {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    pub fn start(every: Duration, line: impl Fn() -> String + Send + 'static) -> Self {
+        let (stop, stopped) = bounded::<()>(0);
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(every) {
+                eprintln!("{}", line());
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        // disconnects the channel, which ends the wait right away
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_line() {
+        let progress = Progress::default();
+        assert!(progress.line(10).ends_with("0% parsed, at 00:00:00"));
+
+        for frame_no in 1..=4 {
+            progress.queued();
+            progress.read(frame_no, frame_no != 2);
+        }
+        progress.queued();
+        let line = progress.line(10);
+        assert!(
+            line.starts_with("Status: 5 frames queued, 4 read ("),
+            "{}",
+            line
+        );
+        assert!(line.ends_with("75% parsed, at 00:00:30"), "{}", line);
+    }
+}
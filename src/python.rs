@@ -0,0 +1,189 @@
+//! Python module (`--features python`), for scripts and notebooks. Build and
+//! install it with `maturin develop --release`, see `pyproject.toml`.
+//!
+//! ```python
+//! import dash2gps
+//! track = dash2gps.convert("footage.mov", interval=5, coordinate_format="dms")
+//! track.points[0]  # {'timestamp': ..., 'lat': 51.43, 'lon': 0.32, ...}
+//! track.write("footage.gpx")
+//! ```
+
+use std::path::PathBuf;
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList, PyTuple},
+};
+
+use crate::{
+    export::{self, record::Record},
+    Pipeline, PipelineConfig,
+};
+
+#[pymodule]
+fn dash2gps(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(convert, m)?)?;
+    m.add_class::<Track>()?;
+    Ok(())
+}
+
+/// Track of the video at `path`. The options are those of the command line,
+/// eg. `interval=5` for `--interval 5`, `ocr_ensemble=True` for
+/// `--ocr-ensemble`, a list for an option that can be repeated and a dict for
+/// `ocr_var`.
+#[pyfunction]
+#[pyo3(signature = (path, **options))]
+fn convert(py: Python<'_>, path: PathBuf, options: Option<&Bound<'_, PyDict>>) -> PyResult<Track> {
+    let args = match options {
+        Some(options) => command_line(options)?,
+        None => Vec::new(),
+    };
+    let config =
+        PipelineConfig::from_args(&args).map_err(|e| PyValueError::new_err(format!("{:#}", e)))?;
+
+    let track = py
+        .allow_threads(|| crate::convert_with(&Pipeline::new(config), &path))
+        .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))?;
+    Ok(Track { track, path })
+}
+
+/// Command line arguments of keyword arguments
+fn command_line(options: &Bound<'_, PyDict>) -> PyResult<Vec<String>> {
+    let mut args = Vec::new();
+    for (name, value) in options.iter() {
+        let flag = format!("--{}", name.extract::<String>()?.replace('_', "-"));
+        if value.is_none() {
+            continue;
+        }
+        if let Ok(set) = value.extract::<bool>() {
+            if set {
+                args.push(flag);
+            }
+            continue;
+        }
+        if let Ok(vars) = value.downcast::<PyDict>() {
+            for (k, v) in vars.iter() {
+                args.extend([flag.clone(), format!("{}={}", k.str()?, v.str()?)]);
+            }
+            continue;
+        }
+        if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+            for v in value.try_iter()? {
+                args.extend([flag.clone(), v?.str()?.to_string()]);
+            }
+            continue;
+        }
+        args.extend([flag, value.str()?.to_string()]);
+    }
+    Ok(args)
+}
+
+/// Track of a video
+#[pyclass(module = "dash2gps")]
+struct Track {
+    track: crate::Track,
+    path: PathBuf,
+}
+
+#[pymethods]
+impl Track {
+    /// Every point as a dict with the fields of `--format jsonl`
+    #[getter]
+    fn points<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let records = self
+            .track
+            .points
+            .iter()
+            .map(|p| Record::new(p, self.track.start))
+            .collect::<Vec<_>>();
+        let json = serde_json::to_string(&records).map_err(to_runtime_error)?;
+        py.import("json")?.call_method1("loads", (json,))
+    }
+
+    fn __len__(&self) -> usize {
+        self.track.points.len()
+    }
+
+    fn gpx(&self) -> PyResult<String> {
+        self.document("gpx")
+    }
+
+    fn json(&self) -> PyResult<String> {
+        self.document("json")
+    }
+
+    fn geojson(&self) -> PyResult<String> {
+        self.document("geojson")
+    }
+
+    /// Write the track to `path`, as `format` or the format of its extension
+    #[pyo3(signature = (path, format = None))]
+    fn write(&self, path: PathBuf, format: Option<&str>) -> PyResult<()> {
+        let extension = path.extension().and_then(|e| e.to_str());
+        let Some(format) = format.or(extension) else {
+            return Err(PyValueError::new_err("pass a format, gpx, json or geojson"));
+        };
+        let document = self.document(format)?;
+        std::fs::write(&path, document).map_err(to_runtime_error)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<dash2gps.Track of {} with {} points>",
+            self.path.display(),
+            self.track.points.len()
+        )
+    }
+}
+
+impl Track {
+    fn document(&self, format: &str) -> PyResult<String> {
+        if !export::DOCUMENT_FORMATS.contains(&format) {
+            return Err(PyValueError::new_err(format!(
+                "unknown format `{}`, expected gpx, json or geojson",
+                format
+            )));
+        }
+        let source = self.path.to_string_lossy();
+        export::document(&self.track, &source, format).map_err(to_runtime_error)
+    }
+}
+
+fn to_runtime_error(e: impl std::fmt::Display) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn options_as_command_line() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let options = PyDict::new(py);
+            options.set_item("interval", 5).unwrap();
+            options.set_item("ocr_ensemble", true).unwrap();
+            options.set_item("reuse_frames", false).unwrap();
+            options.set_item("country", py.None()).unwrap();
+            options.set_item("preprocess", vec!["unsharp"]).unwrap();
+            let vars = PyDict::new(py);
+            vars.set_item("classify_bln_numeric_mode", 1).unwrap();
+            options.set_item("ocr_var", vars).unwrap();
+
+            assert_eq!(
+                command_line(&options).unwrap(),
+                [
+                    "--interval",
+                    "5",
+                    "--ocr-ensemble",
+                    "--preprocess",
+                    "unsharp",
+                    "--ocr-var",
+                    "classify_bln_numeric_mode=1"
+                ]
+            );
+        });
+    }
+}
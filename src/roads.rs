@@ -0,0 +1,330 @@
+//! Distance and time driven on each type of road, from the roads OpenStreetMap
+//! has along the track (queried with the Overpass API).
+
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sink::network::NetworkSink,
+    track::{self, Track, TrackPoint},
+};
+
+pub const OVERPASS_URL: &str = "https://overpass-api.de/api/interpreter";
+/// Farthest a point is matched to a road, GPS error included
+const MAX_DISTANCE_M: f64 = 30.0;
+/// Points of the track per Overpass query
+const CHUNK: usize = 100;
+const KMH_PER_MPH: f64 = 1.609_344;
+/// Roads a car drives on
+const HIGHWAYS: &str = "^(motorway|trunk|primary|secondary|tertiary|unclassified|residential|living_street|service|road)(_link)?$";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoadType {
+    Motorway,
+    Urban,
+    Rural,
+}
+
+/// The road a point is matched to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Road {
+    pub road_type: RoadType,
+    /// Its `maxspeed`, when mapped as a number
+    pub limit_kmh: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct RoadTypes {
+    pub motorway: Driven,
+    pub urban: Driven,
+    pub rural: Driven,
+    /// Not matched to a road, eg. off-road or a car park OpenStreetMap does not map
+    pub unknown: Driven,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Driven {
+    pub distance_m: f64,
+    pub duration_sec: f64,
+}
+
+impl RoadTypes {
+    /// Sum up each pair of consecutive points under the road type of the first.
+    pub fn new(track: &Track, roads: &[Option<Road>]) -> Self {
+        let by_frame = track
+            .points
+            .iter()
+            .zip(roads)
+            .map(|(p, r)| (p.frame_no, r.map(|r| r.road_type)))
+            .collect::<BTreeMap<_, _>>();
+        let mut roads = Self::default();
+        for segment in track.segments() {
+            for w in segment.windows(2) {
+                let driven = match by_frame.get(&w[0].frame_no).copied().flatten() {
+                    Some(RoadType::Motorway) => &mut roads.motorway,
+                    Some(RoadType::Urban) => &mut roads.urban,
+                    Some(RoadType::Rural) => &mut roads.rural,
+                    None => &mut roads.unknown,
+                };
+                driven.distance_m +=
+                    track::distance_m(w[0].coordinate.lat_lon(), w[1].coordinate.lat_lon());
+                driven.duration_sec += w[0].seconds_to(&w[1]).max(0.0);
+            }
+        }
+
+        roads
+    }
+
+    pub fn summary(&self) -> String {
+        [
+            ("motorway", &self.motorway),
+            ("urban", &self.urban),
+            ("rural", &self.rural),
+            ("unknown", &self.unknown),
+        ]
+        .iter()
+        .filter(|(_, d)| d.distance_m > 0.0)
+        .map(|(name, d)| {
+            format!(
+                "{:.1}km {} ({})",
+                d.distance_m / 1000.0,
+                name,
+                track::format_offset(d.duration_sec)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    elements: Vec<Way>,
+}
+
+#[derive(Deserialize)]
+struct Way {
+    id: u64,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    geometry: Vec<Node>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    lat: f64,
+    lon: f64,
+}
+
+/// The road each point of the track is on, `None` when no road is near it.
+pub fn lookup(track: &Track, overpass_url: &str) -> anyhow::Result<Vec<Option<Road>>> {
+    // the public Overpass servers allow about a query a second
+    let network = NetworkSink::default().with_rate_limit(Duration::from_secs(1));
+    let mut ways = BTreeMap::new();
+    for chunk in track.points.chunks(CHUNK) {
+        let around = chunk
+            .iter()
+            .map(|p| {
+                let (lat, lon) = p.coordinate.lat_lon();
+                format!("{},{}", lat, lon)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let query = format!(
+            "[out:json][timeout:60];way[highway~\"{}\"](around:{},{});out tags geom;",
+            HIGHWAYS, MAX_DISTANCE_M, around
+        );
+        let response: Response = network
+            .call(|| Ok(ureq::post(overpass_url).send_form(&[("data", &query)])?))
+            .context("query Overpass API")?
+            .into_json()
+            .context("parse Overpass response")?;
+        ways.extend(response.elements.into_iter().map(|w| (w.id, w)));
+    }
+
+    let ways = ways.into_values().collect::<Vec<_>>();
+    Ok(track
+        .points
+        .iter()
+        .map(|p| nearest_road(p, &ways))
+        .collect())
+}
+
+/// The nearest road within [`MAX_DISTANCE_M`].
+fn nearest_road(p: &TrackPoint, ways: &[Way]) -> Option<Road> {
+    let (lat, lon) = p.coordinate.lat_lon();
+    let (lat, lon) = (lat as f64, lon as f64);
+    // meters on a plane around the point, plenty at this distance
+    let scale = 111_320.0 * lat.to_radians().cos();
+    let xy = |n: &Node| ((n.lon - lon) * scale, (n.lat - lat) * 111_320.0);
+
+    ways.iter()
+        .filter_map(|w| {
+            let nearest = w
+                .geometry
+                .windows(2)
+                .map(|s| distance_to_segment(xy(&s[0]), xy(&s[1])))
+                .fold(f64::INFINITY, f64::min);
+            (nearest <= MAX_DISTANCE_M).then_some((nearest, w))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .and_then(|(_, w)| {
+            Some(Road {
+                road_type: classify(&w.tags)?,
+                limit_kmh: speed_limit(&w.tags),
+            })
+        })
+}
+
+/// `maxspeed` in km/h, eg. `50` or `30 mph`. Zones such as `GB:nsl_single`
+/// are not turned into a number, their limit depends on the vehicle.
+fn speed_limit(tags: &BTreeMap<String, String>) -> Option<f64> {
+    let limit = tags.get("maxspeed")?;
+    match limit.split_once(' ') {
+        Some((mph, "mph")) => Some(mph.parse::<f64>().ok()? * KMH_PER_MPH),
+        _ => limit.parse().ok(),
+    }
+}
+
+/// Distance from the origin to the segment `a`-`b`
+fn distance_to_segment(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (-(a.0 * dx + a.1 * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx).hypot(a.1 + t * dy)
+}
+
+/// Motorways by their `highway` tag. Other roads are urban when they are
+/// residential, lit or limited to 50 km/h (30 mph) or less, rural otherwise.
+fn classify(tags: &BTreeMap<String, String>) -> Option<RoadType> {
+    let highway = tags.get("highway")?;
+    if highway.starts_with("motorway") {
+        return Some(RoadType::Motorway);
+    }
+    if matches!(
+        highway.as_str(),
+        "residential" | "living_street" | "service"
+    ) {
+        return Some(RoadType::Urban);
+    }
+
+    let urban = match tags.get("maxspeed").map(|s| s.as_str()) {
+        Some(zone) if zone.ends_with(":urban") || zone.ends_with(":living_street") => true,
+        Some(zone) if zone.ends_with(":rural") || zone.ends_with(":trunk") => false,
+        Some(_) => speed_limit(tags).is_some_and(|v| v <= 50.0),
+        None => tags.get("lit").is_some_and(|l| l == "yes"),
+    };
+
+    Some(if urban {
+        RoadType::Urban
+    } else {
+        RoadType::Rural
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Coordinate;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn classified() {
+        let t = |pairs: &[(&str, &str)]| classify(&tags(pairs));
+        assert_eq!(t(&[("highway", "motorway_link")]), Some(RoadType::Motorway));
+        assert_eq!(t(&[("highway", "residential")]), Some(RoadType::Urban));
+        assert_eq!(
+            t(&[("highway", "primary"), ("maxspeed", "30 mph")]),
+            Some(RoadType::Urban)
+        );
+        assert_eq!(
+            t(&[("highway", "primary"), ("maxspeed", "GB:nsl_single")]),
+            Some(RoadType::Rural)
+        );
+        assert_eq!(
+            t(&[("highway", "secondary"), ("maxspeed", "DE:urban")]),
+            Some(RoadType::Urban)
+        );
+        assert_eq!(
+            t(&[("highway", "trunk"), ("maxspeed", "100")]),
+            Some(RoadType::Rural)
+        );
+        assert_eq!(
+            t(&[("highway", "tertiary"), ("lit", "yes")]),
+            Some(RoadType::Urban)
+        );
+        assert_eq!(t(&[("name", "High Street")]), None);
+
+        let limit = |v| speed_limit(&tags(&[("maxspeed", v)]));
+        assert_eq!(limit("70"), Some(70.0));
+        assert!((limit("30 mph").unwrap() - 48.28).abs() < 0.01);
+        assert_eq!(limit("GB:nsl_single"), None);
+        assert_eq!(limit("none"), None);
+    }
+
+    #[test]
+    fn matched_and_summed() {
+        // north along a motorway at lon 0, then onto a residential street
+        // 20m east of it
+        let response: Response = serde_json::from_str(
+            r#"{"elements": [
+                {"type": "way", "id": 1, "tags": {"highway": "motorway"},
+                 "geometry": [{"lat": 51.0, "lon": 0.0}, {"lat": 51.01, "lon": 0.0}]},
+                {"type": "way", "id": 2, "tags": {"highway": "residential"},
+                 "geometry": [{"lat": 51.01, "lon": 0.00029}, {"lat": 51.02, "lon": 0.00029}]}
+            ]}"#,
+        )
+        .unwrap();
+        let points = [51.0, 51.005, 51.015, 51.02, 51.03]
+            .iter()
+            .enumerate()
+            .map(|(i, &lat)| TrackPoint {
+                frame_no: i as u32 + 1,
+                offset_sec: i as f64 * 10.0,
+                coordinate: Coordinate::Decimal { lat, lon: 0.0 },
+                accel_peak: None,
+                confidence: None,
+                readings: Default::default(),
+            })
+            .collect::<Vec<_>>();
+        let roads = points
+            .iter()
+            .map(|p| nearest_road(p, &response.elements))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roads
+                .iter()
+                .map(|r| r.map(|r| r.road_type))
+                .collect::<Vec<_>>(),
+            [
+                Some(RoadType::Motorway),
+                Some(RoadType::Motorway),
+                Some(RoadType::Urban),
+                Some(RoadType::Urban),
+                None
+            ]
+        );
+
+        let mut track = Track::from_frames(Vec::new(), 10);
+        track.points = points;
+        let roads = RoadTypes::new(&track, &roads);
+        assert!((roads.motorway.distance_m - 1668.0).abs() < 5.0);
+        assert_eq!(roads.motorway.duration_sec, 20.0);
+        assert_eq!(roads.urban.duration_sec, 20.0);
+        assert_eq!(roads.rural, Driven::default());
+        assert!(roads.summary().starts_with("1.7km motorway (00:00:20)"));
+    }
+}
@@ -0,0 +1,50 @@
+/// One reading of a BlackVue `.3gf` G-sensor log.
+pub struct Reading {
+    /// Milliseconds since the start of the recording
+    pub offset_ms: u32,
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+impl Reading {
+    /// Magnitude of the acceleration vector, in raw sensor units
+    pub fn magnitude(&self) -> f32 {
+        ((self.x as f32).powi(2) + (self.y as f32).powi(2) + (self.z as f32).powi(2)).sqrt()
+    }
+}
+
+/// Parse a `.3gf` file: 10 byte big-endian records of a `u32` millisecond
+/// offset followed by `i16` x, y and z axes. A truncated last record is ignored.
+pub fn parse(bytes: &[u8]) -> Vec<Reading> {
+    bytes
+        .chunks_exact(10)
+        .map(|r| Reading {
+            offset_ms: u32::from_be_bytes([r[0], r[1], r[2], r[3]]),
+            x: i16::from_be_bytes([r[4], r[5]]),
+            y: i16::from_be_bytes([r[6], r[7]]),
+            z: i16::from_be_bytes([r[8], r[9]]),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records() {
+        let bytes = [
+            0, 0, 0, 100, 0, 3, 0, 4, 0, 0, //
+            0, 0, 1, 0, 0xff, 0xfd, 0, 0, 0, 4, //
+            0, 0, // truncated
+        ];
+        let readings = parse(&bytes);
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].offset_ms, 100);
+        assert_eq!(readings[0].magnitude(), 5.0);
+        assert_eq!(readings[1].offset_ms, 256);
+        assert_eq!(readings[1].x, -3);
+    }
+}
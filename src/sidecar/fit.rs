@@ -0,0 +1,225 @@
+//! Minimal reader for the `record` messages of Garmin FIT activity files.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+
+/// Seconds between the unix epoch and the FIT epoch (1989-12-31T00:00:00Z)
+const FIT_EPOCH: i64 = 631_065_600;
+
+const RECORD_MESSAGE: u16 = 20;
+const FIELD_TIMESTAMP: u8 = 253;
+const FIELD_LAT: u8 = 0;
+const FIELD_LON: u8 = 1;
+
+/// A `record` message
+pub struct Record {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    /// `None` when the device had no fix
+    pub lat_lon: Option<(f32, f32)>,
+}
+
+struct Definition {
+    big_endian: bool,
+    global: u16,
+    /// field number and size in bytes
+    fields: Vec<(u8, usize)>,
+    developer_size: usize,
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<Vec<Record>> {
+    let header_size = *bytes.first().ok_or_else(|| anyhow!("empty FIT file"))? as usize;
+    if bytes.len() < 12 || bytes.get(8..12) != Some(b".FIT".as_slice()) {
+        bail!("not a FIT file");
+    }
+    let data_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let end = (header_size + data_size).min(bytes.len());
+
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    let mut records = Vec::new();
+    let mut last_timestamp: u32 = 0;
+    let mut pos = header_size;
+
+    while pos < end {
+        let header = bytes[pos];
+        pos += 1;
+
+        if header & 0x40 != 0 && header & 0x80 == 0 {
+            let (definition, size) = parse_definition(&bytes[pos..end], header & 0x20 != 0)?;
+            definitions.insert(header & 0x0f, definition);
+            pos += size;
+            continue;
+        }
+
+        // compressed timestamp headers carry a 5 bit offset from the last timestamp
+        let (local, compressed) = if header & 0x80 != 0 {
+            let offset = (header & 0x1f) as u32;
+            let mut ts = (last_timestamp & !0x1f) + offset;
+            if offset < last_timestamp & 0x1f {
+                ts += 0x20;
+            }
+            ((header >> 5) & 0x03, Some(ts))
+        } else {
+            (header & 0x0f, None)
+        };
+        let definition = definitions
+            .get(&local)
+            .ok_or_else(|| anyhow!("data message without definition at byte {}", pos))?;
+
+        let mut values = HashMap::new();
+        for &(field, size) in &definition.fields {
+            let raw = bytes
+                .get(pos..pos + size)
+                .ok_or_else(|| anyhow!("truncated FIT message"))?;
+            if size == 4 {
+                let raw = [raw[0], raw[1], raw[2], raw[3]];
+                values.insert(
+                    field,
+                    if definition.big_endian {
+                        u32::from_be_bytes(raw)
+                    } else {
+                        u32::from_le_bytes(raw)
+                    },
+                );
+            }
+            pos += size;
+        }
+        pos += definition.developer_size;
+
+        let timestamp = values.get(&FIELD_TIMESTAMP).copied().or(compressed);
+        if let Some(ts) = timestamp {
+            last_timestamp = ts;
+        }
+        if definition.global != RECORD_MESSAGE {
+            continue;
+        }
+
+        let lat_lon = match (values.get(&FIELD_LAT), values.get(&FIELD_LON)) {
+            (Some(&lat), Some(&lon)) if lat != 0x7fff_ffff && lon != 0x7fff_ffff => {
+                Some((semicircles(lat), semicircles(lon)))
+            }
+            _ => None,
+        };
+        if let Some(ts) = timestamp {
+            records.push(Record {
+                timestamp: ts as i64 + FIT_EPOCH,
+                lat_lon,
+            });
+        }
+    }
+
+    Ok(records)
+}
+
+fn parse_definition(
+    bytes: &[u8],
+    has_developer_fields: bool,
+) -> anyhow::Result<(Definition, usize)> {
+    let truncated = || anyhow!("truncated FIT definition");
+    let big_endian = *bytes.get(1).ok_or_else(truncated)? == 1;
+    let global = bytes.get(2..4).ok_or_else(truncated)?;
+    let global = if big_endian {
+        u16::from_be_bytes([global[0], global[1]])
+    } else {
+        u16::from_le_bytes([global[0], global[1]])
+    };
+    let count = *bytes.get(4).ok_or_else(truncated)? as usize;
+
+    let mut size = 5;
+    let mut fields = Vec::with_capacity(count);
+    for _ in 0..count {
+        let f = bytes.get(size..size + 3).ok_or_else(truncated)?;
+        fields.push((f[0], f[1] as usize));
+        size += 3;
+    }
+
+    let mut developer_size = 0;
+    if has_developer_fields {
+        let count = *bytes.get(size).ok_or_else(truncated)? as usize;
+        size += 1;
+        for _ in 0..count {
+            developer_size += *bytes.get(size + 1).ok_or_else(truncated)? as usize;
+            size += 3;
+        }
+    }
+
+    Ok((
+        Definition {
+            big_endian,
+            global,
+            fields,
+            developer_size,
+        },
+        size,
+    ))
+}
+
+fn semicircles(v: u32) -> f32 {
+    (v as i32 as f64 * 180.0 / 2f64.powi(31)) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn degrees(v: f64) -> [u8; 4] {
+        ((v * 2f64.powi(31) / 180.0) as i32).to_le_bytes()
+    }
+
+    fn fit_file() -> Vec<u8> {
+        let mut data = vec![
+            // definition, local 0: record with timestamp, lat, lon, speed
+            0x40, 0, 0, 20, 0, 4, //
+            253, 4, 0x86, //
+            0, 4, 0x85, //
+            1, 4, 0x85, //
+            6, 2, 0x84,
+        ];
+        let start = 1_000_000_000u32;
+        data.push(0x00);
+        data.extend(start.to_le_bytes());
+        data.extend(degrees(51.43));
+        data.extend(degrees(0.3222));
+        data.extend([0, 0]);
+        // no fix
+        data.push(0x00);
+        data.extend((start + 1).to_le_bytes());
+        data.extend(0x7fff_ffffu32.to_le_bytes());
+        data.extend(0x7fff_ffffu32.to_le_bytes());
+        data.extend([0, 0]);
+        // definition, local 1: record without timestamp
+        data.extend([0x41, 0, 0, 20, 0, 3, 0, 4, 0x85, 1, 4, 0x85, 6, 2, 0x84]);
+        // compressed timestamp on local 1, 2 seconds after the last one
+        data.push(0x80 | 0x20 | ((start + 3) & 0x1f) as u8);
+        data.extend(degrees(-33.8651));
+        data.extend(degrees(151.2099));
+        data.extend([0, 0]);
+
+        let mut file = vec![14, 0x10, 0, 0];
+        file.extend((data.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend([0, 0]);
+        file.extend(data);
+        file.extend([0, 0]);
+        file
+    }
+
+    #[test]
+    fn records() {
+        let records = parse(&fit_file()).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].timestamp, 1_000_000_000 + FIT_EPOCH);
+        let (lat, lon) = records[0].lat_lon.unwrap();
+        assert!((lat - 51.43).abs() < 1e-5 && (lon - 0.3222).abs() < 1e-5);
+        assert!(records[1].lat_lon.is_none());
+        assert_eq!(records[2].timestamp, 1_000_000_003 + FIT_EPOCH);
+        assert!(records[2].lat_lon.unwrap().1 > 151.0);
+    }
+
+    #[test]
+    fn not_fit() {
+        assert!(parse(b"$GPRMC,...").is_err());
+    }
+}
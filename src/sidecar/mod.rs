@@ -0,0 +1,135 @@
+//! GPS logs some cameras write next to the video. When present they are used
+//! instead of OCR, producing the same [`Track`] for the exporters.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    ffmpeg,
+    parser::{Coordinate, Overlay},
+    track::{FrameResult, Track},
+};
+
+pub mod accel;
+pub mod fit;
+pub mod nmea;
+
+/// Where locations are read from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Use a GPS log next to the video when there is one, OCR otherwise
+    Auto,
+    /// Always OCR the video overlay
+    Ocr,
+    /// Only use a GPS log next to the video, fail when there is none
+    Sidecar,
+}
+
+/// A position from a GPS log.
+pub struct Fix {
+    /// Seconds since the start of the video
+    pub offset_sec: f64,
+    /// `None` when the receiver reported it had no fix
+    pub lat_lon: Option<(f32, f32)>,
+}
+
+/// Find a GPS log next to the video, eg. `2021_0606_124229_001.NMEA` for
+/// `2021_0606_124229_001.MP4`, BlackVue's `20210606_124229_NF.gps` or a
+/// Garmin `GRMN0001.FIT`.
+pub fn find(video: &Path) -> Option<PathBuf> {
+    find_with_extension(video, &["nmea", "NMEA", "gps", "GPS", "fit", "FIT"])
+}
+
+fn find_with_extension(video: &Path, extensions: &[&str]) -> Option<PathBuf> {
+    extensions
+        .iter()
+        .map(|ext| video.with_extension(ext))
+        .find(|p| p.is_file())
+}
+
+/// Read a GPS log found by [`find`], merging in the BlackVue `.3gf` G-sensor
+/// log when there is one.
+pub fn read(path: &Path, video: &Path, interval_sec: u32) -> anyhow::Result<Track> {
+    let is_fit = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("fit"));
+    let fixes = if is_fit {
+        let records = fit::parse(&std::fs::read(path).context("read GPS sidecar")?)?;
+        fit_to_fixes(records, ffmpeg::probe_creation_time(video))
+    } else {
+        nmea::parse(&std::fs::read_to_string(path).context("read GPS sidecar")?)
+    };
+    if fixes.is_empty() {
+        anyhow::bail!("no GPS records found in {}", path.display());
+    }
+
+    let mut track = to_track(fixes, interval_sec);
+    if let Some(g_sensor) = find_with_extension(path, &["3gf", "3GF"]) {
+        let bytes = std::fs::read(&g_sensor).context("read G-sensor log")?;
+        merge_acceleration(&mut track, &accel::parse(&bytes), interval_sec);
+    }
+
+    Ok(track)
+}
+
+/// FIT files can span more than one clip, so records are correlated with the
+/// video by its creation time. Without one the first record is taken as the
+/// start of the video.
+fn fit_to_fixes(records: Vec<fit::Record>, video_start: Option<i64>) -> Vec<Fix> {
+    let Some(start) = video_start.or_else(|| records.first().map(|r| r.timestamp)) else {
+        return Vec::new();
+    };
+
+    records
+        .into_iter()
+        .filter(|r| r.timestamp >= start)
+        .map(|r| Fix {
+            offset_sec: (r.timestamp - start) as f64,
+            lat_lon: r.lat_lon,
+        })
+        .collect()
+}
+
+/// Attach the strongest acceleration of each sampled interval to its point.
+pub fn merge_acceleration(track: &mut Track, readings: &[accel::Reading], interval_sec: u32) {
+    for p in &mut track.points {
+        let start_ms = (p.offset_sec * 1000.0) as u64;
+        let end_ms = start_ms + interval_sec as u64 * 1000;
+
+        p.accel_peak = readings
+            .iter()
+            .filter(|r| (start_ms..end_ms).contains(&(r.offset_ms as u64)))
+            .map(accel::Reading::magnitude)
+            .reduce(f32::max);
+    }
+}
+
+/// Keep the first fix of every `interval_sec` so the track matches what OCR
+/// would have sampled.
+pub fn to_track(fixes: Vec<Fix>, interval_sec: u32) -> Track {
+    let mut frames: Vec<FrameResult> = Vec::new();
+    for fix in fixes {
+        let frame_no = (fix.offset_sec.max(0.0) / interval_sec as f64) as u32 + 1;
+        if frames.iter().any(|f| f.frame_no == frame_no) {
+            continue;
+        }
+
+        frames.push(FrameResult {
+            frame_no,
+            pts_sec: Some(fix.offset_sec),
+            overlay: match fix.lat_lon {
+                Some((lat, lon)) => Overlay::Fix(Coordinate::Decimal { lat, lon }),
+                None => Overlay::NoFix,
+            },
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        });
+    }
+
+    Track::from_frames(frames, interval_sec)
+}
@@ -0,0 +1,140 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use super::Fix;
+
+/// Parse the `$GPRMC`/`$GNRMC` sentences of an NMEA 0183 log.
+///
+/// Offsets are relative to the first sentence with a timestamp. Sentences with
+/// a bad checksum are skipped.
+///
+/// BlackVue `.gps` files prefix every line with the camera's unix time in
+/// milliseconds (`[1622983349000]$GPRMC,...`), including lines written before
+/// the receiver has a fix. When present, that clock is used instead so the
+/// offsets line up with the start of the recording.
+pub fn parse(content: &str) -> Vec<Fix> {
+    let mut start: Option<NaiveDateTime> = None;
+    let mut start_ms: Option<u64> = None;
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let (prefix_ms, sentence) = split_timestamp_prefix(line);
+            if let Some(ms) = prefix_ms {
+                start_ms.get_or_insert(ms);
+            }
+
+            let (time, lat_lon) = parse_rmc(sentence)?;
+            let start = *start.get_or_insert(time);
+            let offset_sec = match (prefix_ms, start_ms) {
+                (Some(ms), Some(start_ms)) => ms.saturating_sub(start_ms) as f64 / 1000.0,
+                _ => (time - start).num_milliseconds() as f64 / 1000.0,
+            };
+
+            Some(Fix {
+                offset_sec,
+                lat_lon,
+            })
+        })
+        .collect()
+}
+
+fn split_timestamp_prefix(line: &str) -> (Option<u64>, &str) {
+    let line = line.trim();
+    line.strip_prefix('[')
+        .and_then(|l| l.split_once(']'))
+        .and_then(|(ms, rest)| Some((Some(ms.parse().ok()?), rest)))
+        .unwrap_or((None, line))
+}
+
+/// Timestamp and position of a RMC sentence, position is `None` without a fix.
+pub fn parse_rmc(line: &str) -> Option<(NaiveDateTime, Option<(f32, f32)>)> {
+    let sentence = line.trim().strip_prefix('$')?;
+    let sentence = verify_checksum(sentence)?;
+    let fields = sentence.split(',').collect::<Vec<_>>();
+    if fields.len() < 10 || !fields[0].ends_with("RMC") {
+        return None;
+    }
+
+    let time = NaiveTime::parse_from_str(fields[1], "%H%M%S%.f").ok()?;
+    let date = NaiveDate::parse_from_str(fields[9], "%d%m%y").ok()?;
+    let lat_lon = match fields[2] {
+        "A" => Some((
+            to_degrees(fields[3], 2, fields[4] == "S")?,
+            to_degrees(fields[5], 3, fields[6] == "W")?,
+        )),
+        _ => None,
+    };
+
+    Some((date.and_time(time), lat_lon))
+}
+
+/// `ddmm.mmmm` / `dddmm.mmmm` to signed decimal degrees
+fn to_degrees(value: &str, degree_digits: usize, negative: bool) -> Option<f32> {
+    let degrees = value.get(..degree_digits)?.parse::<f64>().ok()?;
+    let minutes = value.get(degree_digits..)?.parse::<f64>().ok()?;
+    let v = (degrees + minutes / 60.0) as f32;
+
+    Some(if negative { -v } else { v })
+}
+
+/// Strip and check the `*hh` checksum, sentences without one are accepted.
+fn verify_checksum(sentence: &str) -> Option<&str> {
+    let Some((body, checksum)) = sentence.split_once('*') else {
+        return Some(sentence);
+    };
+    let expected = u8::from_str_radix(checksum.trim(), 16).ok()?;
+
+    (body.bytes().fold(0, |acc, b| acc ^ b) == expected).then_some(body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOG: &str = "$GPGGA,124229.00,5125.8000,N,00019.3333,E,1,08,0.9,20.1,M,47.0,M,,*50
+$GPRMC,124229.00,A,5125.8000,N,00019.3333,E,44.3,90.0,060621,,,A*5A
+$GPRMC,124230.00,A,5125.7990,N,00019.3500,E,44.3,90.0,060621,,,A*5B
+$GPRMC,124231.00,V,,,,,,,060621,,,N*79
+$GPRMC,124232.00,A,5125.7990,N,00019.3500,E,44.3,90.0,060621,,,A*00
+";
+
+    #[test]
+    fn rmc_sentences() {
+        let fixes = parse(LOG);
+
+        // the last sentence has a bad checksum
+        assert_eq!(fixes.len(), 3);
+        assert_eq!(fixes[1].offset_sec, 1.0);
+        let (lat, lon) = fixes[0].lat_lon.unwrap();
+        assert!((lat - 51.43).abs() < 1e-5, "{}", lat);
+        assert!((lon - 0.322_222).abs() < 1e-5, "{}", lon);
+        assert!(fixes[2].lat_lon.is_none());
+    }
+
+    #[test]
+    fn blackvue_timestamp_prefix() {
+        let log = "[1622983347000]
+[1622983348000]$GPGSA,A,1,,,,,,,,,,,,,,,*1E
+[1622983349000]$GPRMC,124229.00,A,5125.8000,N,00019.3333,E,44.3,90.0,060621,,,A*5A
+[1622983350000]$GPRMC,124230.00,A,5125.7990,N,00019.3500,E,44.3,90.0,060621,,,A*5B
+";
+        let fixes = parse(log);
+
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(fixes[0].offset_sec, 2.0);
+        assert_eq!(fixes[1].offset_sec, 3.0);
+    }
+
+    #[test]
+    fn southern_western_hemisphere() {
+        let (_, lat_lon) =
+            parse_rmc("$GPRMC,000000,A,3351.9060,S,15112.5940,E,0.0,0.0,010124,,").unwrap();
+        let (lat, lon) = lat_lon.unwrap();
+        assert!((lat + 33.8651).abs() < 1e-4);
+        assert!((lon - 151.2099).abs() < 1e-4);
+
+        let (_, lat_lon) =
+            parse_rmc("$GNRMC,000000,A,4042.6000,N,07400.3600,W,0.0,0.0,010124,,").unwrap();
+        assert!(lat_lon.unwrap().1 < -74.0);
+    }
+}
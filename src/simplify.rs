@@ -0,0 +1,91 @@
+//! Fewer points with the same shape, for `--max-points`: each point is ranked
+//! by the Douglas-Peucker tolerance at which it would be dropped, so keeping
+//! the highest ranked points keeps the corners and drops the points along
+//! straight roads first.
+
+/// Indices of the `max` points of `points` (`(lat, lon)`) that best keep its
+/// shape, in order. Points in `keep` are kept first.
+pub fn keep(points: &[(f32, f32)], max: usize, keep: &[usize]) -> Vec<usize> {
+    let mut rank = ranks(points);
+    for &i in keep {
+        rank[i] = f64::INFINITY;
+    }
+    let mut order = (0..points.len()).collect::<Vec<_>>();
+    // stable, so of equally ranked points the first ones are kept
+    order.sort_by(|&a, &b| rank[b].total_cmp(&rank[a]));
+    order.truncate(max);
+    order.sort_unstable();
+
+    order
+}
+
+/// Distance from the line between its neighbours at which each point is
+/// dropped, never more than that of the point which split its range, so any
+/// number of the highest ranked points is a Douglas-Peucker simplification.
+fn ranks(points: &[(f32, f32)]) -> Vec<f64> {
+    let mut rank = vec![0.0; points.len()];
+    let Some(last) = points.len().checked_sub(1) else {
+        return rank;
+    };
+    rank[0] = f64::INFINITY;
+    rank[last] = f64::INFINITY;
+
+    // local plane in degrees of latitude, good enough to compare distances
+    let lat = points.iter().map(|p| p.0 as f64).sum::<f64>() / points.len() as f64;
+    let scale = lat.to_radians().cos();
+    let xy = |i: usize| (points[i].1 as f64 * scale, points[i].0 as f64);
+
+    let mut ranges = vec![(0, last, f64::INFINITY)];
+    while let Some((a, b, parent)) = ranges.pop() {
+        let Some((i, distance)) = (a + 1..b)
+            .map(|i| (i, distance(xy(i), xy(a), xy(b))))
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+        else {
+            continue;
+        };
+        let distance = distance.min(parent);
+        rank[i] = distance;
+        ranges.push((a, i, distance));
+        ranges.push((i, b, distance));
+    }
+
+    rank
+}
+
+/// Distance from `p` to the segment `a`-`b`
+fn distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = match length > 0.0 {
+        true => (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_corners() {
+        // east along a road, a turn north at index 4, a small wiggle at 6
+        let points = [
+            (51.0, 0.0),
+            (51.0, 0.001),
+            (51.0, 0.002),
+            (51.0, 0.003),
+            (51.0, 0.004),
+            (51.001, 0.004),
+            (51.002, 0.00401),
+            (51.003, 0.004),
+        ];
+
+        assert_eq!(keep(&points, 3, &[]), [0, 4, 7]);
+        assert_eq!(keep(&points, 4, &[]), [0, 4, 6, 7]);
+        assert_eq!(keep(&points, 4, &[2]), [0, 2, 4, 7]);
+        assert_eq!(keep(&points, 20, &[]).len(), points.len());
+        assert!(keep(&[], 3, &[]).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+//! Post recovered points to Home Assistant's `device_tracker.see` service, so
+//! the trip shows on the map of the vehicle's device tracker.
+//!
+//! The service has no time for a point: Home Assistant records each at the
+//! time it is received. The history of a past trip is not back-filled, its
+//! points are posted in a burst and the last one stays the current position.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde_json::json;
+
+use super::network::{NetworkSink, Outbox, Pushed};
+use crate::track::{Track, TrackPoint};
+
+pub struct HomeAssistant {
+    /// eg. `http://homeassistant.local:8123`
+    url: String,
+    /// Long-lived access token
+    token: String,
+    /// Device tracker entity, `device_tracker.<device>`
+    device: String,
+}
+
+impl HomeAssistant {
+    pub fn new(url: &str, token: &str, device: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            device: device.to_string(),
+        }
+    }
+
+    /// Post every point in order, after those an earlier run could not.
+    /// Home Assistant records each at the time it is received; the time in the
+    /// video is kept in the `offset_sec` attribute. When it is out of reach the
+    /// points left are kept for the next run.
+    pub fn send(&self, source: &str, track: &Track) -> anyhow::Result<Pushed> {
+        let endpoint = format!("{}/api/services/device_tracker/see", self.url);
+        let payloads = track
+            .points
+            .iter()
+            .map(|p| payload(&self.device, source, &track.meta, p))
+            .collect();
+
+        NetworkSink::default()
+            .with_outbox(Outbox::new(&format!("home-assistant-{}", self.device)))
+            .push(payloads, |payload| {
+                ureq::post(&endpoint)
+                    .set("Authorization", &format!("Bearer {}", self.token))
+                    .send_json(payload)?;
+                Ok(())
+            })
+            .context("post points to Home Assistant")
+    }
+}
+
+fn payload(
+    device: &str,
+    source: &str,
+    meta: &BTreeMap<String, String>,
+    p: &TrackPoint,
+) -> serde_json::Value {
+    let (lat, lon) = p.coordinate.lat_lon();
+    let mut attributes = json!({
+        "source": source,
+        "offset_sec": p.offset_sec,
+    });
+    if let Some(speed) = p.speed_kmh() {
+        attributes["speed_kmh"] = json!(speed);
+    }
+    for (key, value) in meta {
+        if attributes.get(key).is_none() {
+            attributes[key] = json!(value);
+        }
+    }
+
+    json!({
+        "dev_id": device,
+        "gps": [lat, lon],
+        "source_type": "gps",
+        "attributes": attributes,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{Coordinate, Readings},
+        speed::SpeedUnit,
+    };
+
+    #[test]
+    fn see_payload() {
+        let p = TrackPoint {
+            frame_no: 3,
+            offset_sec: 20.0,
+            coordinate: Coordinate::Decimal {
+                lat: 51.43,
+                lon: 0.32,
+            },
+            accel_peak: None,
+            confidence: None,
+            readings: Readings {
+                speed: Some(50.0),
+                speed_unit: Some(SpeedUnit::Kmh),
+                ..Default::default()
+            },
+        };
+
+        let meta = BTreeMap::from([("driver".to_string(), "alice".to_string())]);
+        let payload = payload("car", "clip.mp4", &meta, &p);
+        assert_eq!(payload["dev_id"], "car");
+        assert_eq!(payload["gps"][0].as_f64().unwrap() as f32, 51.43);
+        assert_eq!(payload["attributes"]["offset_sec"], 20.0);
+        assert_eq!(payload["attributes"]["speed_kmh"], 50.0);
+        assert_eq!(payload["attributes"]["driver"], "alice");
+    }
+}
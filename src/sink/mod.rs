@@ -0,0 +1,8 @@
+//! Places recovered tracks are pushed to besides stdout, so an edge box can
+//! send them straight to cloud storage or a home automation server.
+
+#[cfg(feature = "home-assistant")]
+pub mod home_assistant;
+pub mod network;
+pub mod notify;
+pub mod rclone;
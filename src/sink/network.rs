@@ -0,0 +1,412 @@
+//! Calls to servers, shared by the sinks and lookups that make them (Home
+//! Assistant, the notification webhook, Overpass, what3words). Failed calls
+//! are retried after a growing delay and calls are spaced to respect rate
+//! limits. What a sink could not push is kept in an [`Outbox`] on disk and
+//! sent first by the next run, so a vehicle losing its connection for a while
+//! doesn't lose points.
+
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::{tessdata, versioned};
+
+/// Why a call failed
+#[derive(Debug)]
+pub enum Failure {
+    /// Might succeed later, eg. no connection or the server is busy
+    Transient(anyhow::Error),
+    /// Won't succeed until the configuration is fixed, eg. the token was
+    /// refused or the URL is wrong
+    Permanent(anyhow::Error),
+    /// The server refused this body itself, eg. a `400`
+    Rejected(anyhow::Error),
+}
+
+impl Failure {
+    fn into_error(self) -> anyhow::Error {
+        match self {
+            Failure::Transient(e) | Failure::Permanent(e) | Failure::Rejected(e) => e,
+        }
+    }
+}
+
+/// Timeouts, refused connections, `408`, `429` and `5xx` are transient, `400`
+/// and `422` reject the body
+#[cfg(any(
+    feature = "what3words",
+    feature = "home-assistant",
+    feature = "osm",
+    feature = "webhook"
+))]
+impl From<ureq::Error> for Failure {
+    fn from(e: ureq::Error) -> Self {
+        match &e {
+            ureq::Error::Status(408 | 429 | 500..=599, _) | ureq::Error::Transport(_) => {
+                Failure::Transient(e.into())
+            }
+            ureq::Error::Status(400 | 422, _) => Failure::Rejected(e.into()),
+            ureq::Error::Status(..) => Failure::Permanent(e.into()),
+        }
+    }
+}
+
+/// How often a transient failure is retried
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// Calls made in all, the first included
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for each of the next
+    pub delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Retry {
+    /// Wait before retry number `retry`, from 0
+    fn delay(&self, retry: u32) -> Duration {
+        self.delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Result of `call`, made again after a transient failure
+    pub fn run<T>(&self, mut call: impl FnMut() -> Result<T, Failure>) -> Result<T, Failure> {
+        let mut retry = 0;
+        loop {
+            match call() {
+                Err(Failure::Transient(_)) if retry + 1 < self.attempts => {
+                    std::thread::sleep(self.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Spaces calls by at least `interval`
+pub struct RateLimit {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Wait for the next call to be allowed
+    pub fn wait(&self) {
+        let mut last = self.last.lock().unwrap();
+        if let Some(wait) = last.and_then(|l| self.interval.checked_sub(l.elapsed())) {
+            std::thread::sleep(wait);
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+/// Bodies a sink could not push yet, one JSON object per line
+pub struct Outbox {
+    pub path: PathBuf,
+}
+
+/// Version of the layout of the lines of an outbox
+const VERSION: u32 = 1;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Entry {
+    body: Value,
+}
+
+impl Outbox {
+    /// Outbox of the sink `name` in the cache folder, eg. `home-assistant-dashcam`
+    pub fn new(name: &str) -> Self {
+        Self {
+            path: tessdata::cache_dir()
+                .join("outbox")
+                .join(format!("{}.jsonl", name)),
+        }
+    }
+
+    /// Bodies kept, oldest first. Lines that no longer read, eg. written by a
+    /// newer release, are dropped.
+    pub fn pending(&self) -> Vec<Value> {
+        let Ok(lines) = std::fs::read(&self.path) else {
+            return Vec::new();
+        };
+        lines
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| versioned::from_slice::<Entry>(line, VERSION, |_, v| Some(v)))
+            .map(|e| e.body)
+            .collect()
+    }
+
+    /// Keep `bodies` and nothing else
+    fn replace(&self, bodies: &[Value]) -> anyhow::Result<()> {
+        if bodies.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("empty {}", self.path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context("create outbox folder")?;
+        }
+        let mut lines = Vec::new();
+        for body in bodies {
+            lines.extend(versioned::to_vec(VERSION, &Entry { body: body.clone() })?);
+            lines.push(b'\n');
+        }
+        // written aside then renamed, so a crash leaves the old outbox whole
+        let part = self.path.with_extension("jsonl.part");
+        std::fs::File::create(&part)
+            .and_then(|mut f| f.write_all(&lines))
+            .and_then(|_| std::fs::rename(&part, &self.path))
+            .with_context(|| format!("write {}", self.path.display()))
+    }
+}
+
+/// What [`NetworkSink::push`] did
+#[derive(Debug, Default, PartialEq)]
+pub struct Pushed {
+    pub sent: usize,
+    /// Kept in the outbox for the next run
+    pub kept: usize,
+}
+
+/// Retries, rate limit and outbox of a server
+#[derive(Default)]
+pub struct NetworkSink {
+    pub retry: Retry,
+    rate_limit: Option<RateLimit>,
+    outbox: Option<Outbox>,
+}
+
+impl NetworkSink {
+    pub fn with_rate_limit(self, interval: Duration) -> Self {
+        Self {
+            rate_limit: Some(RateLimit::new(interval)),
+            ..self
+        }
+    }
+
+    pub fn with_outbox(self, outbox: Outbox) -> Self {
+        Self {
+            outbox: Some(outbox),
+            ..self
+        }
+    }
+
+    /// Result of `call`, retried and rate limited
+    pub fn call<T>(&self, call: impl FnMut() -> Result<T, Failure>) -> anyhow::Result<T> {
+        self.attempt(call).map_err(Failure::into_error)
+    }
+
+    fn attempt<T>(&self, mut call: impl FnMut() -> Result<T, Failure>) -> Result<T, Failure> {
+        self.retry.run(|| {
+            if let Some(rate_limit) = &self.rate_limit {
+                rate_limit.wait();
+            }
+            call()
+        })
+    }
+
+    /// Send each body in order, those kept by earlier runs first. When the
+    /// server stays out of reach the bodies left are kept in the outbox, or
+    /// it is an error without one. A permanent failure is returned with every
+    /// body left kept. A body the server rejects is dropped and the rejection
+    /// returned, the bodies after it are kept.
+    pub fn push(
+        &self,
+        bodies: Vec<Value>,
+        mut send: impl FnMut(&Value) -> Result<(), Failure>,
+    ) -> anyhow::Result<Pushed> {
+        let mut queue = self
+            .outbox
+            .as_ref()
+            .map(Outbox::pending)
+            .unwrap_or_default();
+        queue.extend(bodies);
+
+        let mut sent = 0;
+        let mut failure = None;
+        for body in &queue {
+            match self.attempt(|| send(body)) {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        let rest = &queue[sent..];
+
+        match (&self.outbox, failure) {
+            (Some(outbox), Some(Failure::Rejected(e))) => {
+                outbox.replace(&rest[1..])?;
+                Err(e)
+            }
+            (Some(outbox), Some(Failure::Permanent(e))) => {
+                outbox.replace(rest)?;
+                Err(e)
+            }
+            (Some(outbox), _) => {
+                outbox.replace(rest)?;
+                Ok(Pushed {
+                    sent,
+                    kept: rest.len(),
+                })
+            }
+            (None, Some(failure)) => Err(failure.into_error()),
+            (None, None) => Ok(Pushed { sent, kept: 0 }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use serde_json::json;
+
+    use super::*;
+
+    const NOW: Retry = Retry {
+        attempts: 3,
+        delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    };
+
+    fn offline() -> Failure {
+        Failure::Transient(anyhow::anyhow!("no connection"))
+    }
+
+    #[test]
+    fn retried_when_transient() {
+        let retry = Retry::default();
+        assert_eq!(retry.delay(0), Duration::from_secs(1));
+        assert_eq!(retry.delay(3), Duration::from_secs(8));
+        assert_eq!(retry.delay(40), Duration::from_secs(30));
+
+        let calls = Cell::new(0);
+        let flaky = || {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                3 => Ok(calls.get()),
+                _ => Err(offline()),
+            }
+        };
+        assert_eq!(NOW.run(flaky).unwrap(), 3);
+
+        calls.set(0);
+        let refused = || {
+            calls.set(calls.get() + 1);
+            Err::<(), _>(Failure::Permanent(anyhow::anyhow!("401")))
+        };
+        assert!(matches!(NOW.run(refused), Err(Failure::Permanent(_))));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn rate_limited() {
+        let rate_limit = RateLimit::new(Duration::from_millis(20));
+        let start = Instant::now();
+        for _ in 0..3 {
+            rate_limit.wait();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn kept_while_offline() {
+        let path =
+            std::env::temp_dir().join(format!("dash2gps-outbox-{}.jsonl", std::process::id()));
+        let sink = || {
+            NetworkSink {
+                retry: NOW,
+                ..NetworkSink::default()
+            }
+            .with_outbox(Outbox { path: path.clone() })
+        };
+        let points = |from: i32| (from..from + 3).map(|n| json!({ "n": n })).collect();
+
+        // the connection drops after the first point
+        let sent = Cell::new(0);
+        let pushed = sink()
+            .push(points(0), |_| match sent.get() {
+                0 => {
+                    sent.set(1);
+                    Ok(())
+                }
+                _ => Err(offline()),
+            })
+            .unwrap();
+        assert_eq!(pushed, Pushed { sent: 1, kept: 2 });
+
+        // the points kept go first
+        let mut received = Vec::new();
+        let pushed = sink()
+            .push(points(3), |body| {
+                received.push(body["n"].as_i64().unwrap());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(pushed, Pushed { sent: 5, kept: 0 });
+        assert_eq!(received, [1, 2, 3, 4, 5]);
+        assert!(!path.exists());
+
+        // a refused token keeps every point
+        let error = sink()
+            .push(points(0), |_| {
+                Err(Failure::Permanent(anyhow::anyhow!("401")))
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "401");
+        assert_eq!(
+            sink().outbox.unwrap().pending(),
+            [json!({"n": 0}), json!({"n": 1}), json!({"n": 2})]
+        );
+
+        // a rejected point is dropped, the others kept
+        let error = sink()
+            .push(vec![], |_| Err(Failure::Rejected(anyhow::anyhow!("400"))))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "400");
+        assert_eq!(
+            sink().outbox.unwrap().pending(),
+            [json!({"n": 1}), json!({"n": 2})]
+        );
+
+        // without an outbox nothing is kept
+        let error = NetworkSink {
+            retry: NOW,
+            ..NetworkSink::default()
+        }
+        .push(points(0), |_| Err(offline()))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "no connection");
+        _ = std::fs::remove_file(path);
+    }
+}
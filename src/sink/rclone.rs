@@ -0,0 +1,84 @@
+//! Copy the output with `rclone`. Any configured rclone remote works
+//! (`backup:dashcam/clip.json`), and `s3://bucket/key` or `gs://bucket/key`
+//! use rclone's S3/GCS backends with credentials from the environment.
+
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+/// Upload `content` to `destination`.
+pub fn upload(content: &[u8], destination: &str) -> anyhow::Result<()> {
+    let staged = std::env::temp_dir().join(format!("dash2gps-upload-{}", std::process::id()));
+    std::fs::write(&staged, content).context("stage output for upload")?;
+
+    let result = Command::new("rclone")
+        .arg("copyto")
+        .arg(&staged)
+        .arg(rclone_path(destination))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    _ = std::fs::remove_file(&staged);
+
+    let output = result.context("start rclone to upload the output, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "unable to upload to {}:\n{}",
+            destination,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Destination of the file at the relative `path` in the `folder` destination,
+/// eg. `backup:dashcam/2021/clip.gpx`
+pub fn join(folder: &str, path: &str) -> String {
+    let path = path.replace('\\', "/");
+    match folder.ends_with(['/', ':']) {
+        true => format!("{}{}", folder, path),
+        false => format!("{}/{}", folder, path),
+    }
+}
+
+/// rclone path of a destination: object store URIs become rclone's
+/// on-the-fly remotes, anything else is passed as is.
+fn rclone_path(destination: &str) -> String {
+    let backends = [("s3://", "s3"), ("gs://", "gcs")];
+    for (scheme, backend) in backends {
+        if let Some(path) = destination.strip_prefix(scheme) {
+            return format!(":{},env_auth=true:{}", backend, path);
+        }
+    }
+
+    destination.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn destinations() {
+        assert_eq!(
+            rclone_path("s3://dashcam/2021/clip.json"),
+            ":s3,env_auth=true:dashcam/2021/clip.json"
+        );
+        assert_eq!(
+            rclone_path("gs://dashcam/clip.gpx"),
+            ":gcs,env_auth=true:dashcam/clip.gpx"
+        );
+        assert_eq!(
+            rclone_path("backup:dashcam/clip.json"),
+            "backup:dashcam/clip.json"
+        );
+
+        assert_eq!(
+            join("backup:dashcam", "2021/clip.gpx"),
+            "backup:dashcam/2021/clip.gpx"
+        );
+        assert_eq!(join("backup:", "clip.gpx"), "backup:clip.gpx");
+        assert_eq!(join("s3://dashcam/", "clip.gpx"), "s3://dashcam/clip.gpx");
+    }
+}
@@ -0,0 +1,200 @@
+//! Speed printed on the overlay, and a check of its unit against the speed
+//! found from the distance between points.
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::track::{self, Track};
+
+const KMH_PER_MPH: f32 = 1.609_344;
+/// Slower than this the printed speed is too coarse to compare
+const MIN_SPEED_KMH: f64 = 15.0;
+/// Fewest point pairs to decide on
+const MIN_SAMPLES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedUnit {
+    Kmh,
+    Mph,
+}
+
+impl SpeedUnit {
+    pub fn to_kmh(self, speed: f32) -> f32 {
+        match self {
+            SpeedUnit::Kmh => speed,
+            SpeedUnit::Mph => speed * KMH_PER_MPH,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SpeedUnit::Kmh => "km/h",
+            SpeedUnit::Mph => "mph",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            SpeedUnit::Kmh => SpeedUnit::Mph,
+            SpeedUnit::Mph => SpeedUnit::Kmh,
+        }
+    }
+}
+
+/// Speed in km/h of `200kmh`, `200km/h`, `120mph` or `200`, km/h without a unit
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    let s = s.trim().to_lowercase();
+    let (number, unit) = match (
+        s.strip_suffix("mph"),
+        s.strip_suffix("kmh"),
+        s.strip_suffix("km/h"),
+    ) {
+        (Some(n), ..) => (n, SpeedUnit::Mph),
+        (_, Some(n), _) | (.., Some(n)) => (n, SpeedUnit::Kmh),
+        _ => (s.as_str(), SpeedUnit::Kmh),
+    };
+    match number.trim().parse::<f32>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(unit.to_kmh(n) as f64),
+        _ => Err(format!(
+            "expected a speed like `200kmh` or `120mph`, got `{}`",
+            s
+        )),
+    }
+}
+
+/// Give every printed speed a unit: `unit` when set, otherwise the one printed
+/// next to it or `default`. When the speeds then consistently disagree with the
+/// distance travelled by the mph/km/h factor the unit is switched, with a warning.
+pub fn resolve_units(track: &mut Track, unit: Option<SpeedUnit>, default: SpeedUnit) {
+    for p in &mut track.points {
+        if p.readings.speed.is_some() {
+            p.readings.speed_unit = unit.or(p.readings.speed_unit).or(Some(default));
+        }
+    }
+
+    let Some(ratio) = measured_ratio(track) else {
+        return;
+    };
+    let expected = KMH_PER_MPH as f64;
+    let wrong = if (ratio - expected).abs() < 0.2 {
+        // travelling faster than printed, the speeds are mph read as km/h
+        SpeedUnit::Kmh
+    } else if (ratio - 1.0 / expected).abs() < 0.08 {
+        SpeedUnit::Mph
+    } else {
+        return;
+    };
+
+    let mut corrected = 0;
+    for p in &mut track.points {
+        if p.readings.speed_unit == Some(wrong) {
+            p.readings.speed_unit = Some(wrong.other());
+            corrected += 1;
+        }
+    }
+    if corrected > 0 {
+        eprintln!(
+            "Warning: the overlay speed read as {} is {:.2}x off the speed between points, using {} instead",
+            wrong.label(),
+            1.0 / ratio,
+            wrong.other().label()
+        );
+    }
+}
+
+/// Median of the speed between consecutive points over the printed speed.
+fn measured_ratio(track: &Track) -> Option<f64> {
+    let mut ratios = track
+        .points
+        .windows(2)
+        .filter_map(|w| {
+            let dt = w[0].seconds_to(&w[1]);
+            let printed = (w[0].speed_kmh()? as f64 + w[1].speed_kmh()? as f64) / 2.0;
+            if dt <= 0.0 || printed < MIN_SPEED_KMH {
+                return None;
+            }
+            let d = track::distance_m(w[0].coordinate.lat_lon(), w[1].coordinate.lat_lon());
+
+            Some(d / dt * 3.6 / printed)
+        })
+        .collect::<Vec<_>>();
+    if ratios.len() < MIN_SAMPLES {
+        return None;
+    }
+
+    ratios.sort_by(f64::total_cmp);
+    Some(ratios[ratios.len() / 2])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{Coordinate, Overlay, Readings},
+        track::FrameResult,
+    };
+
+    /// Driving east at 100 km/h, one point every 10s
+    fn track(speed: f32, speed_unit: Option<SpeedUnit>) -> Track {
+        // 277.8m every 10s along the 51.43 parallel
+        let step = 277.78 / (111_320.0 * 51.43f32.to_radians().cos());
+        let frames = (0..8)
+            .map(|i| FrameResult {
+                frame_no: i + 1,
+                pts_sec: None,
+                overlay: Overlay::Fix(Coordinate::Decimal {
+                    lat: 51.43,
+                    lon: 0.32 + i as f32 * step,
+                }),
+                readings: Readings {
+                    speed: Some(speed),
+                    speed_unit,
+                    ..Default::default()
+                },
+                error: None,
+                confidence: None,
+            })
+            .collect();
+
+        Track::from_frames(frames, 10)
+    }
+
+    #[test]
+    fn consistent_units_are_kept() {
+        let mut t = track(62.1, Some(SpeedUnit::Mph));
+        resolve_units(&mut t, None, SpeedUnit::Kmh);
+        assert_eq!(t.points[0].readings.speed_unit, Some(SpeedUnit::Mph));
+
+        let mut t = track(100.0, None);
+        resolve_units(&mut t, None, SpeedUnit::Kmh);
+        assert_eq!(t.points[0].readings.speed_unit, Some(SpeedUnit::Kmh));
+
+        // the default of the country
+        let mut t = track(62.1, None);
+        resolve_units(&mut t, None, SpeedUnit::Mph);
+        assert_eq!(t.points[0].readings.speed_unit, Some(SpeedUnit::Mph));
+    }
+
+    #[test]
+    fn speeds() {
+        assert_eq!(parse_speed("200kmh"), Ok(200.0));
+        assert_eq!(parse_speed("200 km/h"), Ok(200.0));
+        assert_eq!(parse_speed("200"), Ok(200.0));
+        assert!((parse_speed("100MPH").unwrap() - 160.9344).abs() < 1e-3);
+        assert!(parse_speed("0kmh").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn wrong_unit_is_corrected() {
+        let mut t = track(62.1, None);
+        resolve_units(&mut t, Some(SpeedUnit::Kmh), SpeedUnit::Kmh);
+        assert_eq!(t.points[0].readings.speed_unit, Some(SpeedUnit::Mph));
+        assert!((t.points[0].speed_kmh().unwrap() - 100.0).abs() < 0.5);
+
+        let mut t = track(100.0, None);
+        resolve_units(&mut t, Some(SpeedUnit::Mph), SpeedUnit::Kmh);
+        assert_eq!(t.points[3].readings.speed_unit, Some(SpeedUnit::Kmh));
+    }
+}
@@ -0,0 +1,187 @@
+//! Where the recovered speed was over the limit OpenStreetMap has for the
+//! road.
+//!
+//! A rough check, not evidence: the speed is the one printed on the overlay as
+//! read by OCR, or worked out from the distance to the next point; points are
+//! matched to the nearest road, which can be a parallel one; and mapped limits
+//! can be missing or out of date.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    roads::Road,
+    track::{self, Track, TrackPoint},
+};
+
+/// Shown with every report of the events
+pub const CAVEAT: &str = "speeds are read from the overlay by OCR or estimated from the distance between points, and limits come from OpenStreetMap: check each event against the video";
+/// Over the limit by more than this, covering the rounding of printed speeds
+/// and the error of speeds from the distance between points
+const TOLERANCE_KMH: f64 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedSource {
+    /// Printed on the overlay
+    Overlay,
+    /// Distance to the next point over the time between them
+    Distance,
+}
+
+/// Consecutive points over the limit, described by the one most over it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Event {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    pub frame_no: u32,
+    pub lat: f32,
+    pub lon: f32,
+    pub limit_kmh: f64,
+    pub speed_kmh: f64,
+    pub excess_kmh: f64,
+    pub speed_source: SpeedSource,
+}
+
+pub fn events(track: &Track, roads: &[Option<Road>]) -> Vec<Event> {
+    let limits = track
+        .points
+        .iter()
+        .zip(roads)
+        .filter_map(|(p, r)| Some((p.frame_no, r.as_ref()?.limit_kmh?)))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut events = Vec::new();
+    for segment in track.segments() {
+        let mut current: Option<Event> = None;
+        for (i, p) in segment.iter().enumerate() {
+            let over = limits.get(&p.frame_no).and_then(|&limit| {
+                let (speed_kmh, speed_source) = speed(segment, i)?;
+                (speed_kmh > limit + TOLERANCE_KMH).then(|| {
+                    let (lat, lon) = p.coordinate.lat_lon();
+                    Event {
+                        start_sec: p.offset_sec,
+                        end_sec: p.offset_sec,
+                        frame_no: p.frame_no,
+                        lat,
+                        lon,
+                        limit_kmh: limit,
+                        speed_kmh,
+                        excess_kmh: speed_kmh - limit,
+                        speed_source,
+                    }
+                })
+            });
+            current = match (current, over) {
+                (Some(event), Some(over)) => Some(Event {
+                    start_sec: event.start_sec,
+                    ..if over.excess_kmh > event.excess_kmh {
+                        over
+                    } else {
+                        Event {
+                            end_sec: over.end_sec,
+                            ..event
+                        }
+                    }
+                }),
+                (Some(event), None) => {
+                    events.push(event);
+                    None
+                }
+                (None, over) => over,
+            };
+        }
+        events.extend(current);
+    }
+
+    events
+}
+
+/// Speed at the `i`th point of a segment: as printed, or from the distance to
+/// the next point (the previous one for the last).
+fn speed(segment: &[TrackPoint], i: usize) -> Option<(f64, SpeedSource)> {
+    if let Some(printed) = segment[i].speed_kmh() {
+        return Some((printed as f64, SpeedSource::Overlay));
+    }
+    let (a, b) = match (segment.get(i + 1), i.checked_sub(1)) {
+        (Some(next), _) => (&segment[i], next),
+        (None, Some(prev)) => (&segment[prev], &segment[i]),
+        (None, None) => return None,
+    };
+    let dt = a.seconds_to(b);
+    (dt > 0.0).then(|| {
+        let d = track::distance_m(a.coordinate.lat_lon(), b.coordinate.lat_lon());
+        (d / dt * 3.6, SpeedSource::Distance)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        parser::{Coordinate, Readings},
+        roads::RoadType,
+        speed::SpeedUnit,
+    };
+
+    #[test]
+    fn over_the_limit() {
+        // printed speeds, then one point without
+        let speeds = [
+            Some(60.0),
+            Some(80.0),
+            Some(95.0),
+            Some(72.0),
+            None,
+            Some(50.0),
+        ];
+        let mut track = Track::from_frames(Vec::new(), 10);
+        track.points = speeds
+            .iter()
+            .enumerate()
+            .map(|(i, speed)| TrackPoint {
+                frame_no: i as u32 + 1,
+                offset_sec: i as f64 * 10.0,
+                // 250m every 10s, 90 km/h
+                coordinate: Coordinate::Decimal {
+                    lat: 51.0 + i as f32 * 0.002_248,
+                    lon: 0.0,
+                },
+                accel_peak: None,
+                confidence: None,
+                readings: Readings {
+                    speed: *speed,
+                    speed_unit: Some(SpeedUnit::Kmh),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        let road = |limit_kmh| {
+            Some(Road {
+                road_type: RoadType::Rural,
+                limit_kmh,
+            })
+        };
+        let roads = [
+            road(Some(70.0)),
+            road(Some(70.0)),
+            road(Some(70.0)),
+            road(Some(70.0)),
+            road(Some(70.0)),
+            road(None),
+        ];
+
+        let events = events(&track, &roads);
+        assert_eq!(events.len(), 2, "{:?}", events);
+        // within the tolerance at 72, over it at 80 and 95
+        assert_eq!((events[0].start_sec, events[0].end_sec), (10.0, 20.0));
+        assert_eq!(events[0].frame_no, 3);
+        assert_eq!(events[0].excess_kmh, 25.0);
+        assert_eq!(events[0].speed_source, SpeedSource::Overlay);
+        // from the distance to the next point, no limit for the last one
+        assert_eq!(events[1].frame_no, 5);
+        assert_eq!(events[1].speed_source, SpeedSource::Distance);
+        assert!((events[1].speed_kmh - 90.0).abs() < 1.0);
+    }
+}
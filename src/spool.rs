@@ -0,0 +1,70 @@
+//! Videos given as bytes rather than a file, eg. piped to stdin or an upload
+//! held in memory, written to a temporary file removed once done. ffmpeg reads
+//! that file, as most containers can't be read from a pipe: an MP4 from a
+//! dashcam has its index at the end.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+pub struct SpooledVideo {
+    path: PathBuf,
+}
+
+impl SpooledVideo {
+    /// Copy a video from a reader, eg. stdin or a `&[u8]` buffer.
+    pub fn from_reader(mut reader: impl Read) -> anyhow::Result<Self> {
+        let name = format!(
+            "dash2gps-input-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        // removes what was written when the copy fails
+        let video = Self {
+            path: std::env::temp_dir().join(name),
+        };
+        let mut file = std::fs::File::create(&video.path).context("create temporary file")?;
+        let size = std::io::copy(&mut reader, &mut file).context("copy the video")?;
+        if size == 0 {
+            anyhow::bail!("no video given, the input is empty");
+        }
+
+        Ok(video)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledVideo {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn removed_once_done() {
+        let bytes = b"\0\0\0\x18ftypmp42";
+        let video = SpooledVideo::from_reader(&bytes[..]).unwrap();
+        let path = video.path().to_path_buf();
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+        let other = SpooledVideo::from_reader(&bytes[..]).unwrap();
+        assert_ne!(other.path(), path);
+        drop(video);
+        assert!(!path.exists());
+
+        assert!(SpooledVideo::from_reader(std::io::empty()).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Points written to stdout as they are found: streaming formats, and
+//! `--incremental` documents.
+
+use std::{collections::BTreeMap, io, sync::Mutex};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    cancel::CancellationToken,
+    export::TrackWriter,
+    parser::{Overlay, Readings},
+    track::{self, Track, TrackPoint},
+};
+
+/// Line ending of the text outputs
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Newline {
+    /// `\r\n` on Windows, `\n` elsewhere
+    Native,
+    Lf,
+    Crlf,
+}
+
+impl Newline {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Native if cfg!(windows) => "\r\n",
+            Newline::Native | Newline::Lf => "\n",
+            Newline::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Track written to stdout while the OCR workers read it. Frames finish out of
+/// order across workers, so points are held back until every earlier frame is
+/// read, and written out every `--flush-every` points so a pipe sees them
+/// without waiting for the end. Once a write fails nothing more is written,
+/// the run is cancelled and [`Live::finish`] returns the error.
+pub struct Live {
+    interval_sec: u32,
+    /// `--sample-offset`
+    offset_sec: f64,
+    flush_every: usize,
+    /// Cancelled when a write fails, eg. the reader of stdout has gone
+    cancel: Option<CancellationToken>,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    writer: Box<dyn TrackWriter + Send>,
+    /// Frame to write next
+    next: u32,
+    pending: BTreeMap<u32, (Overlay, Readings, Option<i32>)>,
+    unflushed: usize,
+    /// First write that failed
+    error: Option<io::Error>,
+}
+
+impl Live {
+    pub fn new(
+        writer: Box<dyn TrackWriter + Send>,
+        interval_sec: u32,
+        offset_sec: f64,
+        flush_every: usize,
+    ) -> Self {
+        Self {
+            interval_sec,
+            offset_sec,
+            flush_every: flush_every.max(1),
+            cancel: None,
+            inner: Mutex::new(Inner {
+                writer,
+                next: 1,
+                pending: BTreeMap::new(),
+                unflushed: 0,
+                error: None,
+            }),
+        }
+    }
+
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// A frame has been read, write it and the frames after it already read.
+    pub fn frame(
+        &self,
+        frame_no: u32,
+        overlay: &Overlay,
+        readings: &Readings,
+        confidence: Option<i32>,
+    ) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .pending
+            .insert(frame_no, (overlay.clone(), *readings, confidence));
+        loop {
+            let frame_no = inner.next;
+            let Some(read) = inner.pending.remove(&frame_no) else {
+                break;
+            };
+            inner.next += 1;
+            self.write(&mut inner, frame_no, read);
+        }
+    }
+
+    /// Write the frames still held back, in order, and close the document.
+    /// Returns the error of the first write that failed, if any.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        while let Some((frame_no, read)) = inner.pending.pop_first() {
+            self.write(&mut inner, frame_no, read);
+        }
+        if inner.error.is_none() {
+            let result = inner.writer.finish();
+            self.failed(&mut inner, result);
+        }
+
+        match &inner.error {
+            Some(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn write(
+        &self,
+        inner: &mut Inner,
+        frame_no: u32,
+        (overlay, readings, confidence): (Overlay, Readings, Option<i32>),
+    ) {
+        if inner.error.is_some() {
+            return;
+        }
+        let result = match overlay {
+            Overlay::Fix(coordinate) => {
+                inner.unflushed += 1;
+                let flush = inner.unflushed >= self.flush_every;
+                if flush {
+                    inner.unflushed = 0;
+                }
+                inner
+                    .writer
+                    .point(&TrackPoint {
+                        frame_no,
+                        offset_sec: track::nominal_offset(
+                            frame_no,
+                            self.interval_sec,
+                            self.offset_sec,
+                        ),
+                        coordinate,
+                        accel_peak: None,
+                        confidence,
+                        readings,
+                    })
+                    .and_then(|_| match flush {
+                        true => inner.writer.flush(),
+                        false => Ok(()),
+                    })
+            }
+            Overlay::NoFix => inner.writer.gap(),
+            Overlay::OtherPage | Overlay::Unreadable => Ok(()),
+        };
+        self.failed(inner, result);
+    }
+
+    /// Keep the error of a write and stop the run
+    fn failed(&self, inner: &mut Inner, result: io::Result<()>) {
+        if let Err(e) = result {
+            inner.error = Some(e);
+            if let Some(cancel) = &self.cancel {
+                cancel.cancel();
+            }
+        }
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        _ = self.finish();
+    }
+}
+
+/// Write whole trips, for streaming formats when they were read all at once.
+pub fn write_all(mut writer: impl TrackWriter, trips: &[Track]) -> io::Result<()> {
+    let segments = trips.iter().flat_map(|t| t.segments());
+    for (i, segment) in segments.enumerate() {
+        if i > 0 {
+            writer.gap()?;
+        }
+        for p in segment {
+            writer.point(p)?;
+        }
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse_overlay;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Record(Arc<Mutex<Vec<Option<u32>>>>);
+
+    impl TrackWriter for Record {
+        fn point(&mut self, p: &TrackPoint) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(Some(p.frame_no));
+            Ok(())
+        }
+        fn gap(&mut self) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(None);
+            Ok(())
+        }
+        fn finish(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn written_in_frame_order() {
+        let fix = parse_overlay("N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021");
+        assert!(matches!(fix, Overlay::Fix(_)));
+        let record = Record::default();
+        let live = Live::new(Box::new(record.clone()), 10, 0.0, 1);
+        let readings = Readings::default();
+
+        live.frame(2, &Overlay::NoFix, &readings, None);
+        live.frame(3, &fix, &readings, None);
+        assert!(record.0.lock().unwrap().is_empty());
+        live.frame(1, &fix, &readings, None);
+        assert_eq!(*record.0.lock().unwrap(), [Some(1), None, Some(3)]);
+
+        // frame 4 never read
+        live.frame(5, &fix, &readings, None);
+        live.finish().unwrap();
+        assert_eq!(*record.0.lock().unwrap(), [Some(1), None, Some(3), Some(5)]);
+    }
+
+    /// Stdout redirected to a full disk
+    struct Full;
+
+    impl TrackWriter for Full {
+        fn point(&mut self, _: &TrackPoint) -> std::io::Result<()> {
+            Err(io::Error::other("no space left on device"))
+        }
+        fn gap(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn finish(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_error_returned() {
+        let fix = parse_overlay("N51°25 48” E0°19 20” 51MPH 12:42:29 06/06/2021");
+        let cancel = CancellationToken::new();
+        let live = Live::new(Box::new(Full), 10, 0.0, 1).with_cancel(cancel.clone());
+        let readings = Readings::default();
+
+        live.frame(1, &fix, &readings, None);
+        live.frame(2, &fix, &readings, None);
+        assert!(cancel.is_cancelled());
+        let error = live.finish().unwrap_err();
+        assert_eq!(error.to_string(), "no space left on device");
+    }
+}
@@ -0,0 +1,123 @@
+//! Tesseract models published by the tesseract-ocr project, downloaded on
+//! first use into the user's cache folder.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrModel {
+    /// tessdata_fast: much faster, slightly less accurate
+    Fast,
+    /// tessdata_best: the most accurate
+    Best,
+}
+
+impl OcrModel {
+    fn repository(self) -> &'static str {
+        match self {
+            OcrModel::Fast => "tessdata_fast",
+            OcrModel::Best => "tessdata_best",
+        }
+    }
+
+    fn url(self) -> String {
+        format!(
+            "https://raw.githubusercontent.com/tesseract-ocr/{}/main/eng.traineddata",
+            self.repository()
+        )
+    }
+}
+
+/// A Tesseract model: `<dir>/<lang>.traineddata`
+#[derive(Clone)]
+pub struct Traineddata {
+    pub dir: String,
+    pub lang: String,
+}
+
+impl Traineddata {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        if !path.is_file() {
+            anyhow::bail!("OCR model {} not found", path.display());
+        }
+        let lang = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|_| path.extension().is_some_and(|e| e == "traineddata"))
+            .ok_or_else(|| anyhow::anyhow!("expected a .traineddata file"))?;
+        let dir = path
+            .canonicalize()
+            .context("resolve OCR model path")?
+            .parent()
+            .map(|d| d.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(Self {
+            dir,
+            lang: lang.to_string(),
+        })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        Path::new(&self.dir).join(format!("{}.traineddata", self.lang))
+    }
+}
+
+/// Folder holding `eng.traineddata` of `model`, downloading it with `curl`
+/// when it is not cached yet.
+pub fn model_dir(model: OcrModel) -> anyhow::Result<PathBuf> {
+    let dir = cache_dir().join(model.repository());
+    let file = dir.join("eng.traineddata");
+    if is_cached(model) {
+        return Ok(dir);
+    }
+
+    std::fs::create_dir_all(&dir).context("create model cache folder")?;
+    eprintln!("Downloading {} to {}", model.url(), file.display());
+    download(&model.url(), &file)?;
+
+    Ok(dir)
+}
+
+/// Whether `model` was downloaded already
+pub fn is_cached(model: OcrModel) -> bool {
+    cache_dir()
+        .join(model.repository())
+        .join("eng.traineddata")
+        .is_file()
+}
+
+/// `dash2gps` in the user's cache folder
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dash2gps")
+}
+
+fn download(url: &str, dest: &Path) -> anyhow::Result<()> {
+    // download next to the destination so an interrupted transfer is never taken for the model
+    let partial = dest.with_extension("part");
+    let status = Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error"])
+        .args(["--retry", "3"])
+        .arg("--output")
+        .arg(&partial)
+        .arg(url)
+        .status()
+        .context("start curl to download the OCR model")?;
+    if !status.success() {
+        _ = std::fs::remove_file(&partial);
+        anyhow::bail!("unable to download {}", url);
+    }
+
+    std::fs::rename(&partial, dest).context("save OCR model")
+}
@@ -0,0 +1,384 @@
+//! Daily report of the trips found across all the clips a camera recorded,
+//! cameras split a drive into short files so consecutive clips are joined.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    ffmpeg,
+    track::{Quality, Track},
+};
+
+/// Clips starting less than this after the previous one ended belong to the same trip
+const TRIP_GAP_SEC: f64 = 300.0;
+
+const VIDEO_EXTENSIONS: [&str; 5] = ["mp4", "mov", "avi", "mkv", "ts"];
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    /// Self-contained page with a map and the trips table
+    Html,
+    Json,
+}
+
+/// A video and the track recovered from it
+pub struct Clip {
+    pub path: PathBuf,
+    /// Recording start, on the camera's clock
+    pub start: NaiveDateTime,
+    pub duration_sec: f64,
+    pub track: Track,
+}
+
+#[derive(Serialize)]
+pub struct Timeline {
+    pub date: NaiveDate,
+    pub trips: Vec<Trip>,
+    /// Total driving time
+    pub duration_sec: f64,
+    pub distance_m: f64,
+}
+
+#[derive(Serialize)]
+pub struct Trip {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub duration_sec: f64,
+    pub distance_m: f64,
+    pub clips: Vec<PathBuf>,
+    /// `[lat, lon]` of every point, in order
+    pub points: Vec<(f32, f32)>,
+    /// How well each stretch of the trip was read, in order
+    pub strip: Vec<Stretch>,
+}
+
+/// A stretch of the quality strip of a trip, to its next one
+#[derive(Serialize)]
+pub struct Stretch {
+    /// Seconds since the start of the trip
+    pub start_sec: f64,
+    pub quality: Quality,
+    /// Index of the clip in `clips` and seconds into it, to open the video there
+    pub clip: usize,
+    pub clip_offset_sec: f64,
+    /// `[lat, lon]` of the point read
+    pub point: Option<(f32, f32)>,
+}
+
+impl Timeline {
+    pub fn new(date: NaiveDate, mut clips: Vec<Clip>) -> Self {
+        clips.sort_by_key(|c| c.start);
+
+        let mut trips: Vec<Trip> = Vec::new();
+        for clip in clips {
+            let end = clip.start + Duration::milliseconds((clip.duration_sec * 1000.0) as i64);
+            let points = clip
+                .track
+                .points
+                .iter()
+                .map(|p| p.coordinate.lat_lon())
+                .collect::<Vec<_>>();
+
+            let trip = match trips.last_mut() {
+                Some(trip) if gap_sec(trip.end, clip.start) < TRIP_GAP_SEC => {
+                    trip.end = trip.end.max(end);
+                    trip.clips.push(clip.path);
+                    trip.points.extend(points);
+                    trip
+                }
+                _ => {
+                    trips.push(Trip {
+                        start: clip.start,
+                        end,
+                        duration_sec: 0.0,
+                        distance_m: 0.0,
+                        clips: vec![clip.path],
+                        points,
+                        strip: Vec::new(),
+                    });
+                    trips.last_mut().expect("just pushed")
+                }
+            };
+            let clip_start_sec = gap_sec(trip.start, clip.start);
+            let clip_index = trip.clips.len() - 1;
+            trip.strip
+                .extend(clip.track.stretches().into_iter().map(|s| Stretch {
+                    start_sec: clip_start_sec + s.offset_sec,
+                    quality: s.quality,
+                    clip: clip_index,
+                    clip_offset_sec: s.offset_sec,
+                    point: s.point,
+                }));
+        }
+
+        for trip in &mut trips {
+            trip.duration_sec = gap_sec(trip.start, trip.end);
+            trip.distance_m = trip
+                .points
+                .windows(2)
+                .fold(0.0, |sum, w| sum + crate::track::distance_m(w[0], w[1]));
+        }
+
+        Self {
+            date,
+            duration_sec: trips.iter().fold(0.0, |sum, t| sum + t.duration_sec),
+            distance_m: trips.iter().fold(0.0, |sum, t| sum + t.distance_m),
+            trips,
+        }
+    }
+}
+
+fn gap_sec(from: NaiveDateTime, to: NaiveDateTime) -> f64 {
+    (to - from).num_milliseconds() as f64 / 1000.0
+}
+
+/// Videos in `dir` recorded on `date`, with their start time.
+pub fn find_clips(
+    dir: &Path,
+    date: NaiveDate,
+    names: Option<&FileNameTime>,
+) -> anyhow::Result<Vec<(PathBuf, NaiveDateTime)>> {
+    let mut clips = find_videos(dir, names)?;
+    clips.retain(|(_, start)| start.date() == date);
+
+    Ok(clips)
+}
+
+/// Whether an input is a `*` or `?` pattern of clips, eg. `clips/2021_0606_*.MP4`
+pub fn is_pattern(input: &str) -> bool {
+    Path::new(input)
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().contains(['*', '?']))
+}
+
+/// Videos in a folder, or matching a pattern in the name of its files, with
+/// their start time, in order.
+pub fn find_videos(
+    path: &Path,
+    names: Option<&FileNameTime>,
+) -> anyhow::Result<Vec<(PathBuf, NaiveDateTime)>> {
+    let (dir, pattern) = match path.is_dir() {
+        true => (path, None),
+        false => (
+            path.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new(".")),
+            path.file_name().map(|n| n.to_string_lossy()),
+        ),
+    };
+    let mut clips = Vec::new();
+    for entry in dir
+        .read_dir()
+        .with_context(|| format!("read {}", dir.display()))?
+        .flatten()
+    {
+        let path = entry.path();
+        let matches = |pattern: &str| {
+            let name = entry.file_name();
+            wildcard_match(pattern.as_bytes(), name.to_string_lossy().as_bytes())
+        };
+        if !is_video(&path) || !pattern.as_deref().is_none_or(matches) {
+            continue;
+        }
+
+        match clip_start(&path, names) {
+            Some(start) => clips.push((path, start)),
+            None => eprintln!(
+                "Warning: unable to tell when {} was recorded, skipped",
+                path.display()
+            ),
+        }
+    }
+    clips.sort_by_key(|(_, start)| *start);
+
+    Ok(clips)
+}
+
+/// Whether a file is a video, from its extension
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// `*` matches any run of characters and `?` any one
+fn wildcard_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, _) => name.is_empty(),
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Recording start from the file name most cameras use (`2021_0606_124229_001.MP4`,
+/// BlackVue's `20210606_124229_NF.mp4`), or `names`, or from the container otherwise.
+pub fn clip_start(path: &Path, names: Option<&FileNameTime>) -> Option<NaiveDateTime> {
+    let name = path.file_name()?.to_string_lossy();
+
+    names.unwrap_or(&DEFAULT_NAMES).parse(&name).or_else(|| {
+        let ts = ffmpeg::probe_creation_time(path)?;
+        Utc.timestamp_opt(ts, 0).single().map(|t| t.naive_utc())
+    })
+}
+
+/// `--file-name-time`: where the recording start is in the file names, as a
+/// regex with `year`, `month`, `day`, `hour`, `minute` and `second` groups.
+/// A two-digit year is in the 2000s.
+#[derive(Clone, Debug)]
+pub struct FileNameTime(Regex);
+
+static DEFAULT_NAMES: Lazy<FileNameTime> = Lazy::new(|| {
+    r"(?P<year>\d{4})_?(?P<month>\d{2})_?(?P<day>\d{2})_(?P<hour>\d{2})(?P<minute>\d{2})(?P<second>\d{2})"
+        .parse()
+        .unwrap()
+});
+
+impl FileNameTime {
+    const GROUPS: [&'static str; 6] = ["year", "month", "day", "hour", "minute", "second"];
+
+    fn parse(&self, name: &str) -> Option<NaiveDateTime> {
+        let cap = self.0.captures(name)?;
+        let n = |group: &str| cap[group].parse::<u32>().ok();
+        let year = match n("year")? {
+            y if y < 100 => 2000 + y,
+            y => y,
+        };
+
+        NaiveDate::from_ymd_opt(year as i32, n("month")?, n("day")?)?.and_hms_opt(
+            n("hour")?,
+            n("minute")?,
+            n("second")?,
+        )
+    }
+}
+
+impl FromStr for FileNameTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = Regex::new(s).map_err(|e| e.to_string())?;
+        let missing = Self::GROUPS
+            .iter()
+            .filter(|g| !regex.capture_names().any(|n| n == Some(**g)))
+            .copied()
+            .collect::<Vec<_>>();
+        match missing.is_empty() {
+            true => Ok(Self(regex)),
+            false => Err(format!("missing the groups {}", missing.join(", "))),
+        }
+    }
+}
+
+impl Serialize for FileNameTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse_overlay;
+    use crate::track::FrameResult;
+
+    fn clip(name: &str, duration_sec: f64, overlays: &[&str]) -> Clip {
+        let frames = overlays
+            .iter()
+            .enumerate()
+            .map(|(i, text)| FrameResult {
+                frame_no: i as u32 + 1,
+                pts_sec: None,
+                overlay: parse_overlay(*text),
+                readings: Default::default(),
+                error: None,
+                confidence: None,
+            })
+            .collect();
+
+        Clip {
+            path: PathBuf::from(name),
+            start: DEFAULT_NAMES.parse(name).unwrap(),
+            duration_sec,
+            track: Track::from_frames(frames, 60),
+        }
+    }
+
+    #[test]
+    fn file_names() {
+        let expected = NaiveDate::from_ymd_opt(2021, 6, 6)
+            .unwrap()
+            .and_hms_opt(12, 42, 29)
+            .unwrap();
+
+        let parse = |name| DEFAULT_NAMES.parse(name);
+        assert_eq!(parse("2021_0606_124229_001.MP4"), Some(expected));
+        assert_eq!(parse("20210606_124229_NF.mp4"), Some(expected));
+        assert_eq!(parse("GRMN0001.MP4"), None);
+
+        let nextbase = r"(?P<year>\d{2})(?P<month>\d{2})(?P<day>\d{2})_(?P<hour>\d{2})(?P<minute>\d{2})(?P<second>\d{2})"
+            .parse::<FileNameTime>()
+            .unwrap();
+        assert_eq!(nextbase.parse("210606_124229_001_FH.MP4"), Some(expected));
+        let error = r"(?P<year>\d{4})".parse::<FileNameTime>().unwrap_err();
+        assert_eq!(error, "missing the groups month, day, hour, minute, second");
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(is_pattern("clips/2021_0606_*.MP4"));
+        assert!(!is_pattern("clips/2021_0606_124229_001.MP4"));
+        let matches = |p: &str, n: &str| wildcard_match(p.as_bytes(), n.as_bytes());
+        assert!(matches("2021_0606_*.MP4", "2021_0606_124229_001.MP4"));
+        assert!(matches("*_00?.MP4", "2021_0606_124229_001.MP4"));
+        assert!(!matches("*.MOV", "2021_0606_124229_001.MP4"));
+        assert!(!matches("2021_0606_*.MP4", "2021_0607_124229_001.MP4"));
+    }
+
+    #[test]
+    fn consecutive_clips_form_trips() {
+        let date = NaiveDate::from_ymd_opt(2021, 6, 6).unwrap();
+        let timeline = Timeline::new(
+            date,
+            vec![
+                clip(
+                    "2021_0606_080300_002.MP4",
+                    180.0,
+                    &["N51°25 48” E0°19 50” 30MPH"],
+                ),
+                clip(
+                    "2021_0606_080000_001.MP4",
+                    180.0,
+                    &["N51°25 48” E0°19 20” 30MPH"],
+                ),
+                clip("2021_0606_173000_003.MP4", 120.0, &["GPS: searching"]),
+            ],
+        );
+
+        assert_eq!(timeline.trips.len(), 2);
+        assert_eq!(timeline.trips[0].clips.len(), 2);
+        assert_eq!(timeline.trips[0].duration_sec, 360.0);
+        assert!((timeline.trips[0].distance_m - 579.0).abs() < 5.0);
+        assert_eq!(timeline.duration_sec, 480.0);
+        assert!(timeline.trips[1].points.is_empty());
+
+        let strip = &timeline.trips[0].strip;
+        assert_eq!(strip.len(), 2);
+        assert_eq!((strip[1].start_sec, strip[1].clip), (180.0, 1));
+        assert_eq!(strip[1].quality, Quality::Good);
+        assert_eq!(timeline.trips[1].strip[0].quality, Quality::Fair);
+    }
+}
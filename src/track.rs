@@ -0,0 +1,1073 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    direction::Facing,
+    parser::{Coordinate, FormatDetection, Overlay, Readings},
+    simplify,
+};
+
+/// Below this speed the vehicle is taken as parked, for `--trip-gap`
+const STOPPED_KMH: f64 = 3.0;
+
+/// OCR confidence (0-100) from which a point is trusted in the quality strip
+const GOOD_CONFIDENCE: i32 = 80;
+
+/// Rejected points in a row, agreeing with each other, from which
+/// [`Track::drop_outliers`] takes them as the track rather than misreads when
+/// fewer points were kept before them
+const REANCHOR_AFTER: usize = 3;
+
+/// OCR outcome of a single extracted frame.
+pub struct FrameResult {
+    /// 1-based index of the frame as written by ffmpeg
+    pub frame_no: u32,
+    /// Presentation time of the frame in the video, when known
+    pub pts_sec: Option<f64>,
+    pub overlay: Overlay,
+    pub readings: Readings,
+    /// Why an unreadable frame could not be read, `None` when its text had no coordinates
+    pub error: Option<FrameError>,
+    /// OCR confidence (0-100), `None` when not read by OCR
+    pub confidence: Option<i32>,
+}
+
+/// Stage a frame was lost at
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    ImageDecode,
+    Ocr,
+    /// Read without coordinates or a no-fix marker
+    Parse,
+}
+
+pub struct FrameError {
+    pub stage: Stage,
+    pub message: String,
+}
+
+/// A sampled frame no location could be read from, kept to read it again
+/// with `--retry-failures`
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FailedFrame {
+    pub frame_no: u32,
+    pub offset_sec: f64,
+    pub stage: Stage,
+}
+
+/// Frames lost at each stage and points dropped afterwards
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Errors {
+    /// Frames that could not be opened or cropped
+    pub image_decode: usize,
+    /// Frames the OCR engine failed on
+    pub ocr: usize,
+    /// Frames read without coordinates or a no-fix marker
+    pub parse: usize,
+    /// Points dropped as implausible jumps
+    pub outliers: usize,
+    pub frames: Vec<FailedFrame>,
+    /// First failure message, to tell what went wrong
+    #[serde(skip)]
+    pub first: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct TrackPoint {
+    pub frame_no: u32,
+    /// Seconds since the start of the video
+    pub offset_sec: f64,
+    pub coordinate: Coordinate,
+    /// Strongest G-sensor reading around this point, in raw sensor units
+    pub accel_peak: Option<f32>,
+    /// OCR confidence (0-100) of the frame it was read from, `None` when not read by OCR
+    pub confidence: Option<i32>,
+    pub readings: Readings,
+}
+
+/// How well a stretch of the video was read
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    /// A point read with a confident OCR, or from a GPS log
+    Good,
+    /// A point read with a low OCR confidence, or the camera had no GPS fix
+    Fair,
+    /// No location could be read
+    Poor,
+}
+
+/// Stretch of the video from a sampled frame to the next, or of an interval
+/// without GPS fix
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stretch {
+    pub offset_sec: f64,
+    pub quality: Quality,
+    /// `(lat, lon)` of the point read
+    pub point: Option<(f32, f32)>,
+}
+
+/// Interval of the video where the camera reported it had no GPS fix.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Gap {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    pub frames: usize,
+}
+
+pub struct Track {
+    pub points: Vec<TrackPoint>,
+    pub no_fix: Vec<Gap>,
+    pub frames: usize,
+    pub unreadable: usize,
+    /// Frames showing another page of the overlay, not failures
+    pub other_pages: usize,
+    pub errors: Errors,
+    /// Which way the camera faces, when it could be told
+    pub facing: Option<Facing>,
+    /// Format the positions were read from the overlay in
+    pub coordinate_format: Option<FormatDetection>,
+    /// `--meta` tags, copied into every output
+    pub meta: BTreeMap<String, String>,
+    /// Recording start of the video on the camera's clock, from its file name or container
+    pub start: Option<NaiveDateTime>,
+    /// Distance and time by road type, with `--road-types`
+    #[cfg(feature = "osm")]
+    pub roads: Option<crate::roads::RoadTypes>,
+    /// Where the speed was over the limit, with `--speed-limits`
+    #[cfg(feature = "osm")]
+    pub speeding: Option<Vec<crate::speeding::Event>>,
+}
+
+impl TrackPoint {
+    /// Speed printed on the overlay, once its unit is known
+    pub fn speed_kmh(&self) -> Option<f32> {
+        Some(self.readings.speed_unit?.to_kmh(self.readings.speed?))
+    }
+
+    /// Wall-clock time of the point: `start` of the video plus its offset, the
+    /// clock printed on the overlay when the start is not known.
+    pub fn time(&self, start: Option<NaiveDateTime>) -> Option<NaiveDateTime> {
+        match start {
+            Some(start) => {
+                start.checked_add_signed(Duration::milliseconds((self.offset_sec * 1000.0) as i64))
+            }
+            None => self.readings.time,
+        }
+    }
+
+    /// Seconds from this point to `later`. By the clock printed on the overlay
+    /// when both show it, which holds when frames in between were skipped or
+    /// sampled away from their nominal position, by their position in the
+    /// video otherwise.
+    pub fn seconds_to(&self, later: &TrackPoint) -> f64 {
+        match (self.readings.time, later.readings.time) {
+            (Some(a), Some(b)) if b > a => (b - a).num_seconds() as f64,
+            _ => later.offset_sec - self.offset_sec,
+        }
+    }
+}
+
+impl Track {
+    pub fn from_frames(mut results: Vec<FrameResult>, interval_sec: u32) -> Self {
+        results.sort_by_key(|r| r.frame_no);
+
+        // the frame's own timestamp when ffmpeg reported it, its nominal position otherwise
+        let offset = |r: &FrameResult| {
+            r.pts_sec
+                .unwrap_or_else(|| nominal_offset(r.frame_no, interval_sec, 0.0))
+        };
+        let mut track = Track {
+            points: Vec::new(),
+            no_fix: Vec::new(),
+            frames: results.len(),
+            unreadable: 0,
+            other_pages: 0,
+            errors: Errors::default(),
+            facing: None,
+            coordinate_format: None,
+            meta: BTreeMap::new(),
+            start: None,
+            #[cfg(feature = "osm")]
+            roads: None,
+            #[cfg(feature = "osm")]
+            speeding: None,
+        };
+        let mut gap: Option<Gap> = None;
+
+        for r in results {
+            let offset_sec = offset(&r);
+            match r.overlay {
+                Overlay::Fix(coordinate) => {
+                    track.no_fix.extend(gap.take());
+                    track.points.push(TrackPoint {
+                        frame_no: r.frame_no,
+                        offset_sec,
+                        coordinate,
+                        accel_peak: None,
+                        confidence: r.confidence,
+                        readings: r.readings,
+                    });
+                }
+                Overlay::NoFix => {
+                    let end_sec = offset_sec + interval_sec as f64;
+                    let g = gap.get_or_insert(Gap {
+                        start_sec: offset_sec,
+                        end_sec,
+                        frames: 0,
+                    });
+                    g.end_sec = end_sec;
+                    g.frames += 1;
+                }
+                // neither open nor close a no-fix interval
+                Overlay::OtherPage => track.other_pages += 1,
+                Overlay::Unreadable => {
+                    track.unreadable += 1;
+                    track.errors.add(r.frame_no, offset_sec, r.error);
+                }
+            }
+        }
+        track.no_fix.extend(gap);
+
+        track
+    }
+
+    /// How well each stretch of the video was read, in order: its points, the
+    /// intervals without fix and the frames that could not be read.
+    pub fn stretches(&self) -> Vec<Stretch> {
+        let points = self.points.iter().map(|p| Stretch {
+            offset_sec: p.offset_sec,
+            quality: match p.confidence {
+                Some(c) if c < GOOD_CONFIDENCE => Quality::Fair,
+                _ => Quality::Good,
+            },
+            point: Some(p.coordinate.lat_lon()),
+        });
+        let no_fix = self.no_fix.iter().map(|g| Stretch {
+            offset_sec: g.start_sec,
+            quality: Quality::Fair,
+            point: None,
+        });
+        let failed = self.errors.frames.iter().map(|f| Stretch {
+            offset_sec: f.offset_sec,
+            quality: Quality::Poor,
+            point: None,
+        });
+        let mut stretches = points.chain(no_fix).chain(failed).collect::<Vec<_>>();
+        stretches.sort_by(|a, b| a.offset_sec.total_cmp(&b.offset_sec));
+
+        stretches
+    }
+
+    /// Runs of points not interrupted by an interval without GPS fix
+    pub fn segments(&self) -> Vec<&[TrackPoint]> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for (i, pair) in self.points.windows(2).enumerate() {
+            let (prev, p) = (&pair[0], &pair[1]);
+            if self
+                .no_fix
+                .iter()
+                .any(|g| g.start_sec > prev.offset_sec && g.start_sec < p.offset_sec)
+            {
+                segments.push(&self.points[start..=i]);
+                start = i + 1;
+            }
+        }
+        if start < self.points.len() {
+            segments.push(&self.points[start..]);
+        }
+
+        segments
+    }
+
+    /// Keep the `max` points that best keep the shape of the track, the ends
+    /// of each segment first, returns how many were removed.
+    pub fn simplify(&mut self, max: usize) -> usize {
+        if self.points.len() <= max {
+            return 0;
+        }
+        let mut ends = Vec::new();
+        let mut start = 0;
+        for segment in self.segments() {
+            ends.extend([start, start + segment.len() - 1]);
+            start += segment.len();
+        }
+        let positions = self
+            .points
+            .iter()
+            .map(|p| p.coordinate.lat_lon())
+            .collect::<Vec<_>>();
+        let kept = simplify::keep(&positions, max, &ends);
+
+        let before = self.points.len();
+        let mut kept = kept.into_iter().peekable();
+        let mut i = 0;
+        self.points.retain(|_| {
+            let keep = kept.next_if_eq(&i).is_some();
+            i += 1;
+            keep
+        });
+
+        before - self.points.len()
+    }
+
+    /// Remove the points within `meters` of where the track starts and ends,
+    /// returns how many were removed.
+    pub fn trim_ends(&mut self, meters: f64) -> usize {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0;
+        };
+        let (start, end) = (first.coordinate.lat_lon(), last.coordinate.lat_lon());
+        let before = self.points.len();
+        self.points.retain(|p| {
+            let at = p.coordinate.lat_lon();
+            distance_m(start, at) >= meters && distance_m(end, at) >= meters
+        });
+
+        before - self.points.len()
+    }
+
+    /// Split the track where the vehicle stays parked for at least `gap_sec`,
+    /// as phones segment location history. The points of the stop are left
+    /// out but its first and last, which end a trip and start the next. A stop
+    /// at the start or end of the video stays in its trip.
+    pub fn split_at_stops(&self, gap_sec: f64) -> Vec<Track> {
+        let points = &self.points;
+        // the overlay speed, or the speed since the previous point without one
+        let stopped = |i: usize| match points[i].speed_kmh() {
+            Some(v) => (v as f64) < STOPPED_KMH,
+            None => {
+                i > 0 && {
+                    let (prev, p) = (&points[i - 1], &points[i]);
+                    let dt = prev.seconds_to(p);
+                    dt > 0.0
+                        && distance_m(prev.coordinate.lat_lon(), p.coordinate.lat_lon()) / dt * 3.6
+                            < STOPPED_KMH
+                }
+            }
+        };
+
+        let mut trips = Vec::new();
+        let (mut start, mut moved, mut i) = (0, false, 0);
+        while i < points.len() {
+            if !stopped(i) {
+                moved = true;
+                i += 1;
+                continue;
+            }
+            let first = i;
+            while i + 1 < points.len() && stopped(i + 1) {
+                i += 1;
+            }
+            let parked_sec = points[first].seconds_to(&points[i]);
+            if moved && i + 1 < points.len() && parked_sec >= gap_sec {
+                trips.push(self.trip(&points[start..=first]));
+                (start, moved) = (i, false);
+            }
+            i += 1;
+        }
+        trips.push(self.trip(&points[start.min(points.len())..]));
+
+        trips
+    }
+
+    /// Part of the track with these points, and the no-fix intervals, failed
+    /// frames and speeding events between them.
+    fn trip(&self, points: &[TrackPoint]) -> Track {
+        let ends = points.first().zip(points.last());
+        let within = |sec: f64| {
+            ends.is_none_or(|(first, last)| (first.offset_sec..=last.offset_sec).contains(&sec))
+        };
+        let mut errors = Errors::default();
+        for f in self.errors.frames.iter().filter(|f| within(f.offset_sec)) {
+            errors.count(f.stage);
+            errors.frames.push(f.clone());
+        }
+
+        Track {
+            points: points.to_vec(),
+            no_fix: self
+                .no_fix
+                .iter()
+                .filter(|g| within(g.start_sec))
+                .cloned()
+                .collect(),
+            frames: ends.map_or(self.frames, |(first, last)| {
+                (last.frame_no - first.frame_no + 1) as usize
+            }),
+            unreadable: errors.frames.len(),
+            other_pages: 0,
+            errors,
+            facing: self.facing,
+            coordinate_format: self.coordinate_format,
+            meta: self.meta.clone(),
+            start: self.start,
+            #[cfg(feature = "osm")]
+            roads: None,
+            #[cfg(feature = "osm")]
+            speeding: self.speeding.as_ref().map(|events| {
+                events
+                    .iter()
+                    .filter(|e| within(e.start_sec))
+                    .cloned()
+                    .collect()
+            }),
+        }
+    }
+
+    /// Drop the points reached faster than `max_speed_kmh` from the last point
+    /// kept, typically a misread digit. A first point the next two agree is
+    /// too far off is dropped too, so a misread doesn't anchor the others.
+    /// When [`REANCHOR_AFTER`] or more rejected points in a row agree with
+    /// each other and outnumber the points kept before them, eg. a misread at
+    /// the start of a clip, the track goes on from them and those kept are
+    /// dropped instead. A misread repeated over a few frames of a longer track
+    /// stays rejected.
+    pub fn drop_outliers(&mut self, max_speed_kmh: f64) {
+        let too_fast = |a: &TrackPoint, b: &TrackPoint| {
+            let dt = a.seconds_to(b).abs().max(1.0);
+            distance_m(a.coordinate.lat_lon(), b.coordinate.lat_lon()) / dt * 3.6 > max_speed_kmh
+        };
+
+        let points = std::mem::take(&mut self.points);
+        let first = match points.as_slice() {
+            [a, b, c, ..] if too_fast(a, b) && too_fast(a, c) && !too_fast(b, c) => 1,
+            _ => 0,
+        };
+        self.errors.outliers += first;
+        let mut rejected: Vec<TrackPoint> = Vec::new();
+        for p in points.into_iter().skip(first) {
+            match self.points.last() {
+                Some(last) if too_fast(last, &p) => {
+                    self.errors.outliers += 1;
+                    if rejected.last().is_some_and(|r| too_fast(r, &p)) {
+                        rejected.clear();
+                    }
+                    rejected.push(p);
+                    if rejected.len() >= REANCHOR_AFTER && self.points.len() < rejected.len() {
+                        self.errors.outliers -= rejected.len();
+                        self.errors.outliers += self.points.len();
+                        self.points.clear();
+                        self.points.append(&mut rejected);
+                    }
+                }
+                _ => {
+                    rejected.clear();
+                    self.points.push(p);
+                }
+            }
+        }
+    }
+
+    /// Take in the failed frames of this track read again (`--retry-failures`):
+    /// recovered points and no-fix frames replace their failures, frames still
+    /// failing keep the stage of the retry.
+    pub fn merge_retried(&mut self, retried: Track) {
+        let in_gap = |f: &FailedFrame| {
+            retried
+                .no_fix
+                .iter()
+                .any(|g| (g.start_sec..g.end_sec).contains(&f.offset_sec))
+        };
+        let mut failed = std::mem::take(&mut self.errors.frames);
+        failed.retain(|f| !retried.points.iter().any(|p| p.frame_no == f.frame_no) && !in_gap(f));
+        for f in &mut failed {
+            if let Some(r) = retried
+                .errors
+                .frames
+                .iter()
+                .find(|r| r.frame_no == f.frame_no)
+            {
+                f.stage = r.stage;
+            }
+        }
+
+        self.points.extend(retried.points);
+        self.points.sort_by_key(|p| p.frame_no);
+        self.no_fix.extend(retried.no_fix);
+        self.no_fix
+            .sort_by(|a, b| a.start_sec.total_cmp(&b.start_sec));
+        self.no_fix.dedup_by(|next, g| {
+            // join intervals the retry made adjacent
+            let joined = next.start_sec <= g.end_sec;
+            if joined {
+                g.end_sec = g.end_sec.max(next.end_sec);
+                g.frames += next.frames;
+            }
+            joined
+        });
+
+        self.errors = Errors {
+            outliers: self.errors.outliers + retried.errors.outliers,
+            first: retried.errors.first,
+            ..Default::default()
+        };
+        for f in &failed {
+            self.errors.count(f.stage);
+        }
+        self.unreadable = failed.len();
+        self.errors.frames = failed;
+    }
+
+    /// Join the tracks of clips a recording was split into, in order of their
+    /// start: the offsets are from the start of the first and the frame
+    /// numbers continue across clips. `None` without clips.
+    pub fn stitch(mut clips: Vec<(NaiveDateTime, Track)>) -> Option<Track> {
+        clips.sort_by_key(|(start, _)| *start);
+        let mut clips = clips.into_iter();
+        let (first, mut track) = clips.next()?;
+        track.start = Some(first);
+        for (start, clip) in clips {
+            let shift_sec = (start - first).num_milliseconds() as f64 / 1000.0;
+            let shift_frames = track.frames as u32;
+
+            track.points.extend(clip.points.into_iter().map(|mut p| {
+                p.frame_no += shift_frames;
+                p.offset_sec += shift_sec;
+                p
+            }));
+            track.no_fix.extend(clip.no_fix.into_iter().map(|mut g| {
+                g.start_sec += shift_sec;
+                g.end_sec += shift_sec;
+                g
+            }));
+            let errors = &mut track.errors;
+            errors.image_decode += clip.errors.image_decode;
+            errors.ocr += clip.errors.ocr;
+            errors.parse += clip.errors.parse;
+            errors.outliers += clip.errors.outliers;
+            errors.first = errors.first.take().or(clip.errors.first);
+            errors
+                .frames
+                .extend(clip.errors.frames.into_iter().map(|mut f| {
+                    f.frame_no += shift_frames;
+                    f.offset_sec += shift_sec;
+                    f
+                }));
+
+            track.frames += clip.frames;
+            track.unreadable += clip.unreadable;
+            track.other_pages += clip.other_pages;
+            track.facing = track.facing.or(clip.facing);
+            track.coordinate_format = track.coordinate_format.or(clip.coordinate_format);
+            track.meta.extend(clip.meta);
+        }
+
+        Some(track)
+    }
+
+    pub fn no_fix_frames(&self) -> usize {
+        self.no_fix.iter().map(|g| g.frames).sum()
+    }
+
+    pub fn stats(&self) -> Stats {
+        let distance_m = self.points.windows(2).fold(0.0, |sum, w| {
+            sum + distance_m(w[0].coordinate.lat_lon(), w[1].coordinate.lat_lon())
+        });
+
+        Stats {
+            frames: self.frames,
+            points: self.points.len(),
+            no_fix_frames: self.no_fix_frames(),
+            unreadable_frames: self.unreadable,
+            distance_m,
+            duration_sec: match (self.points.first(), self.points.last()) {
+                (Some(first), Some(last)) => last.offset_sec - first.offset_sec,
+                _ => 0.0,
+            },
+        }
+    }
+}
+
+impl Errors {
+    fn add(&mut self, frame_no: u32, offset_sec: f64, error: Option<FrameError>) {
+        let stage = error.as_ref().map_or(Stage::Parse, |e| e.stage);
+        self.frames.push(FailedFrame {
+            frame_no,
+            offset_sec,
+            stage,
+        });
+        self.count(stage);
+        if let Some(error) = error {
+            self.first.get_or_insert(error.message);
+        }
+    }
+
+    fn count(&mut self, stage: Stage) {
+        match stage {
+            Stage::ImageDecode => self.image_decode += 1,
+            Stage::Ocr => self.ocr += 1,
+            Stage::Parse => self.parse += 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image_decode + self.ocr + self.parse + self.outliers == 0
+    }
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub frames: usize,
+    pub points: usize,
+    pub no_fix_frames: usize,
+    pub unreadable_frames: usize,
+    /// Length of the track in meters
+    pub distance_m: f64,
+    /// Seconds between the first and last point
+    pub duration_sec: f64,
+}
+
+/// [`Track::simplify`] trips to `max` points in all, shared by their number
+/// of points but at least two each. Returns how many were removed.
+pub fn simplify_trips(trips: &mut [Track], max: usize) -> usize {
+    let total = trips.iter().map(|t| t.points.len()).sum::<usize>();
+    if total <= max {
+        return 0;
+    }
+
+    trips
+        .iter_mut()
+        .map(|t| {
+            let share = (max * t.points.len() / total).max(2);
+            t.simplify(share)
+        })
+        .sum()
+}
+
+/// Great-circle distance in meters between two `(lat, lon)` pairs
+pub fn distance_m(a: (f32, f32), b: (f32, f32)) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+    let (lat1, lon1) = ((a.0 as f64).to_radians(), (a.1 as f64).to_radians());
+    let (lat2, lon2) = ((b.0 as f64).to_radians(), (b.1 as f64).to_radians());
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Format seconds as `HH:MM:SS`
+pub fn format_offset(sec: f64) -> String {
+    let sec = sec.max(0.0) as u64;
+    format!("{:02}:{:02}:{:02}", sec / 3600, sec / 60 % 60, sec % 60)
+}
+
+/// Seconds from `HH:MM:SS`, `MM:SS` or plain seconds, eg. `00:01:30` or `90.5`
+pub fn parse_offset(s: &str) -> Result<f64, String> {
+    let invalid = || format!("expected a time like `00:01:30` or `90`, got `{}`", s);
+    let parts = s.trim().split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let (seconds, whole) = parts.split_last().ok_or_else(invalid)?;
+    let seconds = seconds.parse::<f64>().map_err(|_| invalid())?;
+    let minutes = whole.iter().try_fold(0u64, |total, part| {
+        let n = part.parse::<u64>().map_err(|_| invalid())?;
+        Ok::<_, String>(total * 60 + n)
+    })?;
+    if !seconds.is_finite() || seconds < 0.0 || (!whole.is_empty() && seconds >= 60.0) {
+        return Err(invalid());
+    }
+
+    Ok(minutes as f64 * 60.0 + seconds)
+}
+
+/// Seconds into the video of a sampled frame by its number, for frames
+/// ffmpeg reported no timestamp of
+pub fn nominal_offset(frame_no: u32, interval_sec: u32, sample_offset_sec: f64) -> f64 {
+    frame_no.saturating_sub(1) as f64 * interval_sec as f64 + sample_offset_sec
+}
+
+/// Seconds from `90s`, `10m` or `1h`, or a time accepted by [`parse_offset`]
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let unit = [('s', 1.0), ('m', 60.0), ('h', 3600.0)]
+        .into_iter()
+        .find_map(|(suffix, scale)| Some((s.strip_suffix(suffix)?, scale)));
+    match unit {
+        Some((number, scale)) => match number.parse::<f64>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => Ok(n * scale),
+            _ => Err(format!(
+                "expected a duration like `10m` or `00:10:00`, got `{}`",
+                s
+            )),
+        },
+        None => parse_offset(s),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{parse_overlay, parse_readings};
+
+    fn frame(frame_no: u32, text: &str) -> FrameResult {
+        FrameResult {
+            frame_no,
+            pts_sec: None,
+            overlay: parse_overlay(text),
+            readings: Default::default(),
+            error: None,
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn no_fix_intervals() {
+        let track = Track::from_frames(
+            vec![
+                frame(4, "N51°25 48” E0°19 20” 51MPH"),
+                frame(1, "GPS: searching"),
+                frame(2, "garbage"),
+                frame(3, "GPS: searching"),
+                frame(5, "N0°0'0\" E0°0'0\" 0MPH"),
+            ],
+            10,
+        );
+
+        assert_eq!(track.points.len(), 1);
+        assert_eq!(track.points[0].offset_sec, 30.0);
+        assert_eq!(track.unreadable, 1);
+        assert_eq!(track.errors.parse, 1);
+        assert_eq!(
+            track.no_fix,
+            vec![
+                Gap {
+                    start_sec: 0.0,
+                    end_sec: 30.0,
+                    frames: 2
+                },
+                Gap {
+                    start_sec: 40.0,
+                    end_sec: 50.0,
+                    frames: 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_stitched() {
+        let at = |min| {
+            chrono::NaiveDate::from_ymd_opt(2021, 6, 6)
+                .unwrap()
+                .and_hms_opt(12, min, 0)
+                .unwrap()
+        };
+        let clip = |text| Track::from_frames(vec![frame(1, text), frame(2, "garbage")], 30);
+        let track = Track::stitch(vec![
+            (at(1), clip("N51°25 50” E0°19 30”")),
+            (at(0), clip("N51°25 48” E0°19 20”")),
+        ])
+        .unwrap();
+
+        assert_eq!(track.start, Some(at(0)));
+        assert_eq!(track.frames, 4);
+        let points = track
+            .points
+            .iter()
+            .map(|p| (p.frame_no, p.offset_sec))
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(1, 0.0), (3, 60.0)]);
+        assert_eq!(track.errors.parse, 2);
+        assert_eq!(track.errors.frames[1].frame_no, 4);
+        assert_eq!(track.errors.frames[1].offset_sec, 90.0);
+        assert!(Track::stitch(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn quality_of_stretches() {
+        let unsure = FrameResult {
+            confidence: Some(40),
+            ..frame(3, "N51°25 50” E0°19 30”")
+        };
+        let track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "garbage"),
+                unsure,
+                frame(4, "GPS: searching"),
+                frame(5, "GPS: searching"),
+            ],
+            10,
+        );
+
+        let stretches = track.stretches();
+        let quality = stretches
+            .iter()
+            .map(|s| (s.offset_sec, s.quality))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            quality,
+            vec![
+                (0.0, Quality::Good),
+                (10.0, Quality::Poor),
+                (20.0, Quality::Fair),
+                (30.0, Quality::Fair)
+            ]
+        );
+        assert!(stretches[0].point.is_some() && stretches[3].point.is_none());
+    }
+
+    #[test]
+    fn outliers() {
+        let mut track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "N51°25 58” E0°19 20”"),
+                // `51` misread as `57`
+                frame(3, "N57°26 08” E0°19 20”"),
+                frame(4, "N51°26 18” E0°19 20”"),
+                frame(5, "N51°26 28” E0°19 20”"),
+            ],
+            10,
+        );
+        track.drop_outliers(300.0);
+
+        assert_eq!(track.points.len(), 4);
+        assert_eq!(track.points[2].frame_no, 4);
+        assert_eq!(track.errors.outliers, 1);
+
+        // about 110 km/h, too fast for a bike
+        track.drop_outliers(60.0);
+        assert_eq!(track.points.len(), 1);
+        assert_eq!(track.errors.outliers, 4);
+
+        let frame_nos = |t: &Track| t.points.iter().map(|p| p.frame_no).collect::<Vec<_>>();
+        let mut track = Track::from_frames(
+            vec![
+                // misread first point
+                frame(1, "N57°25 48” E0°19 20”"),
+                frame(2, "N51°25 58” E0°19 20”"),
+                frame(3, "N51°26 08” E0°19 20”"),
+                // two misreads in a row
+                frame(4, "N51°26 18” E8°19 20”"),
+                frame(5, "N51°26 28” E8°19 20”"),
+                frame(6, "N51°26 38” E0°19 20”"),
+            ],
+            10,
+        );
+        track.drop_outliers(300.0);
+        assert_eq!(frame_nos(&track), [2, 3, 6]);
+        assert_eq!(track.errors.outliers, 3);
+
+        let mut track = Track::from_frames(
+            vec![
+                // two misreads that agree with each other
+                frame(1, "N57°25 48” E0°19 20”"),
+                frame(2, "N57°25 58” E0°19 20”"),
+                frame(3, "N51°26 08” E0°19 20”"),
+                frame(4, "N51°26 18” E0°19 20”"),
+                frame(5, "N51°26 28” E0°19 20”"),
+                frame(6, "N51°26 38” E0°19 20”"),
+            ],
+            10,
+        );
+        track.drop_outliers(300.0);
+        assert_eq!(frame_nos(&track), [3, 4, 5, 6]);
+        assert_eq!(track.errors.outliers, 2);
+
+        // the same misread over three frames in the middle of a trip
+        let frames = (1..=12)
+            .map(|n| {
+                let lat = if (6..=8).contains(&n) {
+                    "N57°25"
+                } else {
+                    "N51°25"
+                };
+                frame(n, &format!("{} {}” E0°19 20”", lat, 4 * n))
+            })
+            .collect();
+        let mut track = Track::from_frames(frames, 10);
+        track.drop_outliers(300.0);
+        assert_eq!(frame_nos(&track), [1, 2, 3, 4, 5, 9, 10, 11, 12]);
+        assert_eq!(track.errors.outliers, 3);
+    }
+
+    #[test]
+    fn split_at_long_stops() {
+        // a frame a minute, parked from frame 3 to 14
+        let mut frames = vec![
+            frame(1, "N51°25 48” E0°19 20”"),
+            frame(2, "N51°25 58” E0°19 20”"),
+        ];
+        frames.extend((3..=14).map(|n| frame(n, "N51°26 08” E0°19 20”")));
+        frames.push(frame(15, "N51°26 18” E0°19 20”"));
+        frames.push(frame(16, "N51°26 28” E0°19 20”"));
+        let track = Track::from_frames(frames, 60);
+
+        let trips = track.split_at_stops(600.0);
+        assert_eq!(trips.len(), 2);
+        let frame_nos = |t: &Track| t.points.iter().map(|p| p.frame_no).collect::<Vec<_>>();
+        assert_eq!(frame_nos(&trips[0]), [1, 2, 3, 4]);
+        assert_eq!(frame_nos(&trips[1]), [14, 15, 16]);
+        assert_eq!(trips[1].frames, 3);
+
+        assert_eq!(track.split_at_stops(900.0).len(), 1);
+    }
+
+    #[test]
+    fn simplified_keeps_shape() {
+        // north, then a turn east at frame 3
+        let mut track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "N51°25 58” E0°19 20”"),
+                frame(3, "N51°26 08” E0°19 20”"),
+                frame(4, "N51°26 08” E0°19 30”"),
+                frame(5, "N51°26 08” E0°19 40”"),
+            ],
+            10,
+        );
+
+        assert_eq!(track.simplify(5), 0);
+        assert_eq!(track.simplify(3), 2);
+        let frame_nos = track.points.iter().map(|p| p.frame_no).collect::<Vec<_>>();
+        assert_eq!(frame_nos, [1, 3, 5]);
+    }
+
+    #[test]
+    fn other_pages_are_no_failures() {
+        let track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "FW V2.1.3"),
+                frame(3, "N51°25 58” E0°19 20”"),
+                frame(4, "AB12 CDE"),
+                frame(5, "garbage"),
+            ],
+            10,
+        );
+
+        assert_eq!(track.points.len(), 2);
+        assert_eq!(track.other_pages, 2);
+        assert_eq!(track.unreadable, 1);
+        assert_eq!(track.errors.parse, 1);
+        assert!(track.no_fix.is_empty());
+    }
+
+    #[test]
+    fn trimmed_ends() {
+        // 10" of latitude apart, about 309m
+        let mut track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "N51°25 58” E0°19 20”"),
+                frame(3, "N51°26 08” E0°19 20”"),
+                frame(4, "N51°26 18” E0°19 20”"),
+                frame(5, "N51°26 28” E0°19 20”"),
+            ],
+            10,
+        );
+
+        assert_eq!(track.trim_ends(400.0), 4);
+        assert_eq!(track.points.len(), 1);
+        assert_eq!(track.points[0].frame_no, 3);
+    }
+
+    #[test]
+    fn retried_frames() {
+        let mut track = Track::from_frames(
+            vec![
+                frame(1, "N51°25 48” E0°19 20”"),
+                frame(2, "garbage"),
+                frame(3, "GPS: searching"),
+                frame(4, "garbage"),
+                frame(5, "garbage"),
+            ],
+            10,
+        );
+        assert_eq!(track.errors.parse, 3);
+
+        let retried = Track::from_frames(
+            vec![
+                frame(2, "N51°25 58” E0°19 20”"),
+                frame(4, "GPS: searching"),
+                frame(5, "still garbage"),
+            ],
+            10,
+        );
+        track.merge_retried(retried);
+
+        assert_eq!(track.points.len(), 2);
+        assert_eq!(track.points[1].frame_no, 2);
+        assert_eq!(
+            track.no_fix,
+            vec![Gap {
+                start_sec: 20.0,
+                end_sec: 40.0,
+                frames: 2
+            }]
+        );
+        assert_eq!(track.unreadable, 1);
+        assert_eq!(track.errors.parse, 1);
+        assert_eq!(track.errors.frames[0].frame_no, 5);
+    }
+
+    #[test]
+    fn overlay_clock() {
+        let text = |clock| format!("N51°25 48” E0°19 20” 51MPH {} 06/06/2021", clock);
+        let mut track = Track::from_frames(
+            vec![
+                FrameResult {
+                    readings: parse_readings(&text("12:42:29")),
+                    ..frame(1, &text("12:42:29"))
+                },
+                FrameResult {
+                    readings: parse_readings(&text("12:43:29")),
+                    ..frame(2, &text("12:43:29"))
+                },
+                frame(3, &text("12:43:39")),
+            ],
+            10,
+        );
+        let p = &track.points;
+        assert_eq!(p[0].seconds_to(&p[1]), 60.0);
+        // by the position in the video without the clock
+        assert_eq!(p[1].seconds_to(&p[2]), 10.0);
+
+        // a misread clock going back is not trusted
+        track.points[1].readings.time = track.points[0].readings.time;
+        let p = &track.points;
+        assert_eq!(p[0].seconds_to(&p[1]), 10.0);
+    }
+
+    #[test]
+    fn haversine() {
+        // London -> Paris
+        let d = distance_m((51.5074, -0.1278), (48.8566, 2.3522));
+        assert!((d - 343_500.0).abs() < 1_000.0, "{}", d);
+    }
+
+    #[test]
+    fn offset_format() {
+        assert_eq!(format_offset(3725.4), "01:02:05");
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("00:01:00"), Ok(60.0));
+        assert_eq!(parse_offset("1:30"), Ok(90.0));
+        assert_eq!(parse_offset("90.5"), Ok(90.5));
+        assert_eq!(parse_offset("01:02:03.5"), Ok(3723.5));
+        assert_eq!(parse_offset(&format_offset(3723.0)), Ok(3723.0));
+        assert_eq!(nominal_offset(1, 10, 0.0), 0.0);
+        assert_eq!(nominal_offset(3, 10, 0.5), 20.5);
+        assert!(parse_offset("1:60").is_err());
+        assert!(parse_offset("-5").is_err());
+        assert!(parse_offset("1:2:3:4").is_err());
+        assert!(parse_offset("one").is_err());
+
+        assert_eq!(parse_duration("10m"), Ok(600.0));
+        assert_eq!(parse_duration("1.5h"), Ok(5400.0));
+        assert_eq!(parse_duration("45s"), Ok(45.0));
+        assert_eq!(parse_duration("00:10:00"), Ok(600.0));
+        assert!(parse_duration("-1m").is_err());
+        assert!(parse_duration("tenm").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Files one release writes and a later one reads back, eg. the frame cache.
+//! Each JSON object is stamped with the version of its layout. An older one is
+//! migrated a version at a time. One written by a newer release, or that no
+//! longer reads, is left alone: the caller rebuilds it rather than misread it.
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Version of files written before they were stamped
+const UNSTAMPED: u32 = 1;
+
+/// `data` as JSON with a `version` field
+pub fn to_vec<T: Serialize>(version: u32, data: &T) -> anyhow::Result<Vec<u8>> {
+    let mut value = serde_json::to_value(data)?;
+    let Some(fields) = value.as_object_mut() else {
+        anyhow::bail!("only objects are stamped with a version");
+    };
+    fields.insert("version".to_string(), version.into());
+
+    Ok(serde_json::to_vec(&value)?)
+}
+
+/// Data of a file written at `version` or before. `migrate` turns the fields
+/// of a version into those of the next, `None` when that can't be done.
+pub fn from_slice<T: DeserializeOwned>(
+    json: &[u8],
+    version: u32,
+    migrate: impl Fn(u32, Value) -> Option<Value>,
+) -> Option<T> {
+    let mut value = serde_json::from_slice::<Value>(json).ok()?;
+    let found = match value.as_object_mut()?.remove("version") {
+        Some(v) => u32::try_from(v.as_u64()?).ok()?,
+        None => UNSTAMPED,
+    };
+    if found > version {
+        return None;
+    }
+    for from in found..version {
+        value = migrate(from, value)?;
+    }
+
+    serde_json::from_value(value).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Clip {
+        frames: usize,
+        interval_sec: u32,
+    }
+
+    /// version 2 added `interval_sec`, 10s before
+    fn migrate(from: u32, mut value: Value) -> Option<Value> {
+        match from {
+            1 => {
+                value
+                    .as_object_mut()?
+                    .insert("interval_sec".into(), 10.into());
+                Some(value)
+            }
+            _ => Some(value),
+        }
+    }
+
+    #[test]
+    fn migrated_or_ignored() {
+        let clip = Clip {
+            frames: 3,
+            interval_sec: 5,
+        };
+        let json = to_vec(2, &clip).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&json),
+            r#"{"frames":3,"interval_sec":5,"version":2}"#
+        );
+        assert_eq!(from_slice(&json, 2, migrate), Some(clip));
+
+        let unstamped = br#"{"frames":3}"#;
+        assert_eq!(
+            from_slice(unstamped, 2, migrate),
+            Some(Clip {
+                frames: 3,
+                interval_sec: 10
+            })
+        );
+        // from a newer release, or not migrated
+        let newer = br#"{"frames":3,"interval_sec":5,"version":3}"#;
+        assert_eq!(from_slice::<Clip>(newer, 2, migrate), None);
+        assert_eq!(from_slice::<Clip>(unstamped, 2, |_, _| None), None);
+        assert_eq!(from_slice::<Clip>(b"{\"frames\":", 2, migrate), None);
+    }
+}
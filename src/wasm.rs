@@ -0,0 +1,111 @@
+//! WebAssembly API (`--features wasm`), for a browser tool reading the text
+//! of overlays, or overlay strips cut out of the frames, without a server.
+//! Build with `cargo rustc --release --lib --target wasm32-unknown-unknown
+//! --no-default-features --features wasm --crate-type cdylib`, then run
+//! `wasm-bindgen --target web` on the `.wasm`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    export::{self, record::Record},
+    glyphs, parser,
+    pipeline::{self, PipelineConfig},
+    preprocess::{self, Crop, Prepare},
+    track::{FrameResult, Track},
+};
+
+/// Point of the text of one overlay as a JSON record (the fields of
+/// `--format jsonl`), `null` when it has no position.
+#[wasm_bindgen(js_name = parseOverlay)]
+pub fn parse_overlay(text: &str) -> Result<String, JsError> {
+    point(text).map_err(|e| JsError::new(&format!("{:#}", e)))
+}
+
+/// Text of an overlay strip, a PNG or JPEG image of the overlay alone, read
+/// with the built-in recognizer of common dashcam fonts.
+#[wasm_bindgen(js_name = readStrip)]
+pub fn read_strip(image: &[u8]) -> Result<String, JsError> {
+    strip_text(image).map_err(|e| JsError::new(&format!("{:#}", e)))
+}
+
+/// Track of the texts of overlays sampled every `--interval` seconds, as a
+/// `gpx`, `json` or `geojson` document. `args` are options of the command
+/// line, eg. `["--interval", "5", "--coordinate-format", "dms"]`.
+#[wasm_bindgen]
+pub fn convert(texts: Vec<String>, args: Vec<String>, format: &str) -> Result<String, JsError> {
+    document(&texts, &args, format).map_err(|e| JsError::new(&format!("{:#}", e)))
+}
+
+fn point(text: &str) -> anyhow::Result<String> {
+    let track = Track::from_frames(vec![frame(1, text, &PipelineConfig::default())], 1);
+    let record = track.points.first().map(|p| Record::new(p, None));
+
+    Ok(serde_json::to_string(&record)?)
+}
+
+fn strip_text(image: &[u8]) -> anyhow::Result<String> {
+    let image = image::load_from_memory(image)?;
+    let prepare = Prepare {
+        crop: Some(Crop {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        }),
+        ..Prepare::default()
+    };
+    let crop = preprocess::overlay_crop(&image, &prepare).into_luma8();
+
+    Ok(glyphs::recognize(&crop).0)
+}
+
+fn document(texts: &[String], args: &[String], format: &str) -> anyhow::Result<String> {
+    if !export::DOCUMENT_FORMATS.contains(&format) {
+        anyhow::bail!("unknown format `{}`, expected gpx, json or geojson", format);
+    }
+    let opts = PipelineConfig::from_args(args)?;
+    let frames = texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| frame(i as u32 + 1, text, &opts))
+        .collect();
+    let mut track = pipeline::build_track(frames, &opts);
+    pipeline::localize(&mut track, &opts);
+
+    export::document(&track, "overlay", format)
+}
+
+fn frame(frame_no: u32, text: &str, opts: &PipelineConfig) -> FrameResult {
+    FrameResult {
+        frame_no,
+        pts_sec: None,
+        overlay: parser::parse_overlay_as(text, opts.parser_strictness, opts.coordinate_format),
+        readings: parser::parse_readings(text),
+        error: None,
+        confidence: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overlay_text() {
+        let record = point("N51°25 48” E0°19 20” 44KM/H").unwrap();
+        assert!(
+            record.starts_with(r#"{"timestamp":null,"lat":51.43,"lon":0.32"#),
+            "{}",
+            record
+        );
+        assert_eq!(point("NO GPS").unwrap(), "null");
+    }
+
+    #[test]
+    fn document_of_texts() {
+        let texts = ["N51°25 48” E0°19 20”", "N51°25 49” E0°19 21”"].map(String::from);
+        let gpx = document(&texts, &["--interval".into(), "5".into()], "gpx").unwrap();
+        assert_eq!(gpx.matches("<trkpt").count(), 2, "{}", gpx);
+        assert!(document(&texts, &[], "kml").is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 use crossbeam_channel::Sender;
@@ -7,43 +12,220 @@ use notify::{
     EventKind, RecommendedWatcher, Watcher,
 };
 
-pub struct FsWatcher(RecommendedWatcher, PathBuf);
+/// Sends every file written to a folder once it is complete.
+///
+/// On Linux a file is complete when it is closed after writing. Other
+/// platforms report no close, there a file is complete once ffmpeg creates the
+/// next one, and [`FsWatcher::finish`] sends the last.
+pub struct FsWatcher {
+    watcher: RecommendedWatcher,
+    path: PathBuf,
+    sent: Arc<Mutex<Sent>>,
+}
+
+struct Sent {
+    change: Sender<PathBuf>,
+    paths: HashSet<PathBuf>,
+    /// Created and possibly still being written
+    writing: Option<PathBuf>,
+    /// Send every change rather than complete files once, see [`FsWatcher::changes`]
+    every_change: bool,
+}
+
+impl Sent {
+    fn send(&mut self, path: PathBuf) {
+        if self.every_change || self.paths.insert(path.clone()) {
+            _ = self.change.try_send(path);
+        }
+    }
+}
+
 impl FsWatcher {
     pub fn new(path: PathBuf, change: Sender<PathBuf>) -> anyhow::Result<Self> {
-        let watch = RecommendedWatcher::new(
+        Self::with_mode(path, change, false)
+    }
+
+    /// Sends every file created, written to or renamed in a folder, each time.
+    /// Files copied by another program, eg. rsync, can't be told complete by
+    /// their events, see [`Debounce`].
+    pub fn changes(path: PathBuf, change: Sender<PathBuf>) -> anyhow::Result<Self> {
+        Self::with_mode(path, change, true)
+    }
+
+    fn with_mode(
+        path: PathBuf,
+        change: Sender<PathBuf>,
+        every_change: bool,
+    ) -> anyhow::Result<Self> {
+        let sent = Arc::new(Mutex::new(Sent {
+            change,
+            paths: HashSet::new(),
+            writing: None,
+            every_change,
+        }));
+        let events = sent.clone();
+        let watcher = RecommendedWatcher::new(
             move |res: Result<notify::Event, _>| {
-                if let Ok(e) = res {
-                    if let EventKind::Access(AccessKind::Close(AccessMode::Write)) = e.kind {
-                        if let Some(path) = e.paths.first() {
-                            _ = change.try_send(path.clone());
+                let Ok(e) = res else {
+                    return;
+                };
+                let Some(path) = e.paths.first() else {
+                    return;
+                };
+                let mut sent = events.lock().unwrap_or_else(|e| e.into_inner());
+                match e.kind {
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Access(_)
+                        if sent.every_change =>
+                    {
+                        // both the old and new name of a rename
+                        for path in e.paths {
+                            sent.send(path);
+                        }
+                    }
+                    EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                        sent.send(path.clone())
+                    }
+                    EventKind::Create(_)
+                        if !cfg!(target_os = "linux") && sent.writing.as_ref() != Some(path) =>
+                    {
+                        if let Some(previous) = sent.writing.replace(path.clone()) {
+                            sent.send(previous);
                         }
                     }
+                    _ => {}
                 }
             },
             notify::Config::default(),
         )
         .context("init file monitor")?;
 
-        Ok(Self(watch, path))
+        Ok(Self {
+            watcher,
+            path,
+            sent,
+        })
     }
 
     pub fn start(&mut self) -> anyhow::Result<()> {
-        self.0
-            .watch(&self.1, notify::RecursiveMode::Recursive)
+        self.watcher
+            .watch(&self.path, notify::RecursiveMode::Recursive)
             .context("start file monitor")?;
 
         Ok(())
     }
 
+    /// Send the files not seen complete yet, once nothing writes to the folder
+    /// anymore.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let mut files = self
+            .path
+            .read_dir()
+            .context("read watched folder")?
+            .flatten()
+            .map(|f| f.path())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        for file in files {
+            sent.send(file);
+        }
+
+        Ok(())
+    }
+
     pub fn stop(&mut self) -> anyhow::Result<()> {
-        self.0.unwatch(&self.1).context("start file monitor")?;
+        self.watcher
+            .unwatch(&self.path)
+            .context("start file monitor")?;
 
         Ok(())
     }
 }
 
+/// Files changed lately, ready once their size stayed the same for a while:
+/// a copy in progress grows, a finished one stops.
+pub struct Debounce {
+    quiet: Duration,
+    /// Size of each file and since when
+    files: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl Debounce {
+    pub fn new(quiet: Duration) -> Self {
+        Self {
+            quiet,
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn changed(&mut self, path: PathBuf, now: Instant) {
+        let size = file_size(&path);
+        self.files.insert(path, (size, now));
+    }
+
+    /// Files whose size did not change for the quiet time, no longer followed.
+    pub fn ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut ready = Vec::new();
+        self.files.retain(|path, (size, since)| {
+            if !path.is_file() {
+                return false;
+            }
+            let current = file_size(path);
+            if current != *size {
+                *size = current;
+                *since = now;
+            } else if current > 0 && now.duration_since(*since) >= self.quiet {
+                ready.push(path.clone());
+                return false;
+            }
+            true
+        });
+        ready.sort();
+        ready
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    path.metadata().map_or(0, |m| m.len())
+}
+
 impl Drop for FsWatcher {
     fn drop(&mut self) {
         _ = self.stop();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ready_once_size_settles() {
+        let dir = std::env::temp_dir().join(format!("dash2gps-debounce-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let clip = dir.join("clip.MOV");
+        std::fs::write(&clip, b"moov").unwrap();
+
+        let start = Instant::now();
+        let later = |sec| start + Duration::from_secs(sec);
+        let mut debounce = Debounce::new(Duration::from_secs(5));
+        debounce.changed(clip.clone(), start);
+        assert!(debounce.ready(later(1)).is_empty());
+
+        // still being copied
+        std::fs::write(&clip, b"moov and more").unwrap();
+        assert!(debounce.ready(later(6)).is_empty());
+        assert!(debounce.ready(later(10)).is_empty());
+        assert_eq!(debounce.ready(later(11)), vec![clip.clone()]);
+        assert!(debounce.ready(later(20)).is_empty());
+
+        // renamed away before it settled
+        debounce.changed(clip.clone(), later(12));
+        std::fs::remove_file(&clip).unwrap();
+        assert!(debounce.ready(later(20)).is_empty());
+        std::fs::write(&clip, b"moov").unwrap();
+        assert!(debounce.ready(later(30)).is_empty());
+        _ = std::fs::remove_dir_all(&dir);
+    }
+}